@@ -0,0 +1,44 @@
+//! Markdown coupon parser throughput: `cargo bench --bench parser`
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+// The crate is a binary, so the std-only reader is built here directly
+#[path = "../src/coupons/markdown.rs"]
+mod markdown;
+
+use markdown::{FieldLabels, MarkdownCoupons};
+
+/// A my-coupons style listing with `count` coupons, every field filled in
+fn listing(count: usize) -> String {
+    let mut text = format!("# 我的优惠券\n\n共 {} 张\n\n", count);
+    for i in 0..count {
+        text.push_str(&format!(
+            "## 麦辣鸡腿堡套餐 {}\n- **优惠**: ¥{}.9\n- **有效期**: 2026-10-01 至 2026-12-31\n- **领取时间**: 2026-10-01 10:00:00\n- **标签**: 午餐、套餐\n<img src=\"https://img.mcd.cn/coupon/{}.png\" width=\"120\">\n\n",
+            i, i % 50, i
+        ));
+    }
+    text
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("markdown");
+    for count in [10, 100, 1000] {
+        let text = listing(count);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::new("borrowed", count), &text, |b, text| {
+            b.iter(|| MarkdownCoupons::new(black_box(text), FieldLabels::standard()).count())
+        });
+        // Plus copying every field out, as building a `Coupon` does
+        group.bench_with_input(BenchmarkId::new("owned", count), &text, |b, text| {
+            b.iter(|| {
+                MarkdownCoupons::new(black_box(text), FieldLabels::standard())
+                    .map(|c| [c.title, c.price, c.expiry, c.receive_time, c.tags, c.image_url, c.code].map(str::to_string))
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
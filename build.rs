@@ -0,0 +1,9 @@
+fn main() {
+    // The gRPC stubs are only generated for `--features grpc`; protoc ships with the build dependency
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc for this platform");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/coupon.proto").expect("compile proto/coupon.proto");
+    }
+}
@@ -0,0 +1,85 @@
+use std::{io::{self, IsTerminal, Write}, process::Stdio, time::Duration};
+use tokio::process::Command;
+
+use crate::config::AlertMode;
+
+/// Bells rung for one alert, spaced so they are heard as separate beeps
+const BELLS: usize = 3;
+const BELL_GAP: Duration = Duration::from_millis(400);
+
+/// Announce coupon titles matching the alert keywords. Sounds go to stderr, so JSON output on stdout stays clean.
+pub async fn announce(mode: AlertMode, titles: &[String]) {
+    if titles.is_empty() {
+        return;
+    }
+    match mode {
+        AlertMode::Off => {},
+        AlertMode::Bell => ring().await,
+        AlertMode::Speech => {
+            let text = format!("发现 {} 张值得领取的优惠券：{}", titles.len(), titles.join("，"));
+            if !speak(&text).await {
+                ring().await;
+            }
+        },
+    }
+}
+
+/// Ring the terminal bell a few times, louder than the single bell of an ordinary event
+async fn ring() {
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() {
+        return;
+    }
+    for i in 0..BELLS {
+        if i > 0 {
+            tokio::time::sleep(BELL_GAP).await;
+        }
+        let _ = stderr.write_all(b"\x07");
+        let _ = stderr.flush();
+    }
+}
+
+/// Environment variable carrying the text to the PowerShell speech script
+const SPEAK_TEXT_ENV: &str = "MCD_SPEAK_TEXT";
+
+/// Read `text` aloud with the speech tool of the platform. Returns false when none could be run.
+async fn speak(text: &str) -> bool {
+    for (program, args) in speech_commands(text) {
+        let status = Command::new(program)
+            .args(&args)
+            .env(SPEAK_TEXT_ENV, text)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+        match status {
+            Ok(status) if status.success() => return true,
+            Ok(status) => tracing::debug!("语音提醒 {} 退出: {}", program, status),
+            // Not installed, try the next one
+            Err(e) => tracing::debug!("无法运行语音提醒 {}: {}", program, e),
+        }
+    }
+    tracing::warn!("未找到可用的语音工具，改用终端响铃提醒");
+    false
+}
+
+/// Candidate speech tools, in order of preference
+fn speech_commands(text: &str) -> Vec<(&'static str, Vec<String>)> {
+    if cfg!(target_os = "macos") {
+        vec![("say", vec![text.to_string()])]
+    } else if cfg!(windows) {
+        // The text comes from upstream, so it is read from the environment and never parsed as script
+        let script = format!(
+            "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak($env:{})",
+            SPEAK_TEXT_ENV
+        );
+        vec![("powershell", vec!["-NoProfile".to_string(), "-Command".to_string(), script])]
+    } else {
+        vec![
+            ("spd-say", vec!["-w".to_string(), "-l".to_string(), "zh".to_string(), text.to_string()]),
+            ("espeak-ng", vec!["-v".to_string(), "cmn".to_string(), text.to_string()]),
+            ("espeak", vec!["-v".to_string(), "zh".to_string(), text.to_string()]),
+        ]
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Result};
+#[cfg(feature = "server")]
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::{IpAddr, Ipv4Addr};
+#[cfg(feature = "server")]
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::config::Config;
+
+/// A network such as "192.168.1.0/24", or a single address
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (addr_text, prefix_text) = text.split_once('/').map_or((text, None), |(a, p)| (a, Some(p)));
+        let addr: IpAddr = addr_text.parse().map_err(|_| anyhow!("无法识别的 IP 地址: {}", text))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix_text {
+            Some(p) => p.parse::<u8>().ok().filter(|p| *p <= max)
+                .ok_or_else(|| anyhow!("无效的网段前缀长度: {}", text))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    #[cfg(feature = "server")]
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Client addresses allowed to reach the web and MCP servers, from `allowed_ips` in the config.
+/// An empty list allows everyone; loopback is always allowed.
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    networks: Vec<Network>,
+}
+
+impl Allowlist {
+    /// Parse the entries of the `key` setting, rejecting malformed addresses and prefixes
+    pub fn from_config(entries: &[String], key: &str) -> Result<Self> {
+        let networks = entries.iter()
+            .filter(|e| !e.trim().is_empty())
+            .map(|e| Network::parse(e).map_err(|err| anyhow!("{} 配置无效: {}", key, err)))
+            .collect::<Result<_>>()?;
+        Ok(Self { networks })
+    }
+
+    #[cfg(feature = "server")]
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        self.networks.is_empty() || self.lists(ip)
+    }
+
+    /// Whether `ip` is loopback or in one of the networks. Unlike `allows`, an empty list
+    /// admits loopback only.
+    #[cfg(feature = "server")]
+    pub fn lists(&self, ip: IpAddr) -> bool {
+        // IPv4 clients of a dual-stack listener show up as ::ffff:a.b.c.d
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            v4 => v4,
+        };
+        ip.is_loopback() || self.networks.iter().any(|n| n.contains(ip))
+    }
+}
+
+/// Address a server listens on, from the `key` setting; loopback when unset
+pub fn bind_address(setting: Option<&str>, key: &str) -> Result<IpAddr> {
+    match setting.map(str::trim).filter(|s| !s.is_empty()) {
+        None => Ok(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        Some(text) => text.parse().map_err(|_| anyhow!("{} 配置无效，应为 IP 地址，如 127.0.0.1 或 0.0.0.0: {}", key, text)),
+    }
+}
+
+/// Whether a server may listen on `addr`. The servers claim and spend with the saved token,
+/// so one reachable from other machines needs `allowed_ips`, or `allow_public_bind` to
+/// accept the risk; the warning to show in that case is returned.
+pub fn check_bind(config: &Config, addr: IpAddr, key: &str) -> Result<Option<String>> {
+    if addr.is_loopback() || config.allowed_ips.iter().any(|e| !e.trim().is_empty()) {
+        return Ok(None);
+    }
+    if !config.allow_public_bind {
+        return Err(anyhow!(
+            "{} 为 {}，其他设备也能访问，且未配置 allowed_ips，同一网络中的任何人都能用你的账号领取优惠券。\
+             请改回 127.0.0.1、在 allowed_ips 中列出允许的地址，或确认风险后设置 \"allow_public_bind\": true",
+            key,
+            addr
+        ));
+    }
+    Ok(Some(format!("警告: 正在监听 {}，且未配置 allowed_ips，同一网络中的任何人都能用你的账号领取优惠券", addr)))
+}
+
+/// Reject requests from clients outside the allowlist with 403
+#[cfg(feature = "server")]
+pub async fn enforce(
+    State(allowlist): State<Arc<Allowlist>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if allowlist.allows(peer.ip()) {
+        return next.run(request).await;
+    }
+    tracing::warn!("已拒绝不在 allowed_ips 中的客户端: {}", peer.ip());
+    (StatusCode::FORBIDDEN, "403 Forbidden: 该地址不在允许访问的范围内").into_response()
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use fs2::FileExt;
+use std::{fs::{self, File, OpenOptions}, io::Write};
+
+use crate::{config::{Config, ConfigService}, coupons::{parse_coupons_from_markdown, ClaimVerification}, error::McdError, mcp::describe_error, utils::format_current_time};
+
+/// Append-only trail of every mutating action, one JSON object per line
+pub const AUDIT_FILE: &str = "audit.jsonl";
+/// Audit log of destructive MCP tool calls, written by the MCP server
+pub const MCP_AUDIT_FILE: &str = "mcp-audit.jsonl";
+/// Lock file taken around every write to the audit logs
+const LOCK_FILE: &str = "audit.lock";
+
+/// Kind of mutating action
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Claim,
+    TokenSet,
+    TokenReset,
+    ConfigChange,
+    AccountAdd,
+    AccountRemove,
+    AccountSwitch,
+    MarkUsed,
+    /// Scheduled claim fired at a release time
+    ScheduleTrigger,
+    SyncImport,
+    BackupRestore,
+    /// Old data dropped by the retention policy
+    Prune,
+    /// A family member asked for coupons to be claimed for them
+    RequestSubmit,
+    RequestApprove,
+    RequestReject,
+}
+
+impl Action {
+    /// Every action, in the order offered by `audit --action`
+    pub const ALL: [Action; 15] = [
+        Action::Claim, Action::TokenSet, Action::TokenReset, Action::ConfigChange, Action::AccountAdd,
+        Action::AccountRemove, Action::AccountSwitch, Action::MarkUsed, Action::ScheduleTrigger,
+        Action::SyncImport, Action::BackupRestore, Action::Prune, Action::RequestSubmit,
+        Action::RequestApprove, Action::RequestReject,
+    ];
+
+    /// Names of `ALL`, as written in the audit log
+    pub const NAMES: [&'static str; 15] = {
+        let mut names = [""; 15];
+        let mut i = 0;
+        while i < names.len() {
+            names[i] = Self::ALL[i].name();
+            i += 1;
+        }
+        names
+    };
+
+    /// Name written in the audit log, matching the serde representation
+    pub const fn name(self) -> &'static str {
+        match self {
+            Action::Claim => "claim",
+            Action::TokenSet => "token_set",
+            Action::TokenReset => "token_reset",
+            Action::ConfigChange => "config_change",
+            Action::AccountAdd => "account_add",
+            Action::AccountRemove => "account_remove",
+            Action::AccountSwitch => "account_switch",
+            Action::MarkUsed => "mark_used",
+            Action::ScheduleTrigger => "schedule_trigger",
+            Action::SyncImport => "sync_import",
+            Action::BackupRestore => "backup_restore",
+            Action::Prune => "prune",
+            Action::RequestSubmit => "request_submit",
+            Action::RequestApprove => "request_approve",
+            Action::RequestReject => "request_reject",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Claim => "领取",
+            Action::TokenSet => "设置Token",
+            Action::TokenReset => "重置Token",
+            Action::ConfigChange => "修改配置",
+            Action::AccountAdd => "添加账号",
+            Action::AccountRemove => "删除账号",
+            Action::AccountSwitch => "切换账号",
+            Action::MarkUsed => "标记使用",
+            Action::ScheduleTrigger => "定时触发",
+            Action::SyncImport => "导入同步包",
+            Action::BackupRestore => "恢复备份",
+            Action::Prune => "清理数据",
+            Action::RequestSubmit => "提交代领请求",
+            Action::RequestApprove => "批准代领请求",
+            Action::RequestReject => "拒绝代领请求",
+        }
+    }
+}
+
+/// One audited action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct AuditEntry {
+    /// Local time, "YYYY-MM-DD HH:MM:SS"
+    pub at: String,
+    /// Who did it: tui, cli, watch, scheduler, web:<client IP> or mcp:<session>
+    pub actor: String,
+    pub action: Action,
+    pub success: bool,
+    /// What was changed, or why it failed
+    pub detail: String,
+    /// Active account at the time, if accounts are configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+}
+
+/// Append an action to the trail. Failures are only logged so auditing never blocks the action.
+pub fn record(actor: &str, action: Action, success: bool, detail: impl Into<String>) {
+    let entry = AuditEntry {
+        at: format_current_time(),
+        actor: actor.to_string(),
+        action,
+        success,
+        detail: detail.into(),
+        account: ConfigService::global().current().active_account.clone(),
+    };
+    if let Err(e) = append(&entry) {
+        tracing::warn!("写入审计日志失败: {:#}", e);
+    }
+}
+
+/// Record the outcome of an auto-bind call
+pub fn record_claim(actor: &str, result: &std::result::Result<String, McdError>, verification: Option<&ClaimVerification>) {
+    let detail = match result {
+        Ok(_) if verification.is_some_and(ClaimVerification::nothing_bound) => ClaimVerification::message(verification),
+        Ok(text) => format!("领取成功！领取到 {} 张", parse_coupons_from_markdown(text).len()),
+        Err(e) => format!("领取失败: {}", describe_error(e)),
+    };
+    record(actor, Action::Claim, ClaimVerification::succeeded(result, verification), detail);
+}
+
+/// Actor name of a web request from `ip`
+#[cfg(feature = "web")]
+pub fn web_actor(ip: std::net::IpAddr) -> String {
+    format!("web:{}", ip)
+}
+
+/// Exclusive lock on the audit logs, released when the returned file is dropped. Appends and
+/// the retention prune, which rewrites the logs, hold it so no line is lost between them, also
+/// across processes such as a running server and the `prune` command.
+pub fn lock() -> Result<File> {
+    let path = Config::data_path(LOCK_FILE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context(format!("无法创建目录: {}", dir.display()))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .context(format!("无法打开文件: {}", path.display()))?;
+    file.lock_exclusive().context(format!("无法锁定文件: {}", path.display()))?;
+    Ok(file)
+}
+
+fn append(entry: &AuditEntry) -> Result<()> {
+    let path = Config::data_path(AUDIT_FILE);
+    let _lock = lock()?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context(format!("无法打开文件: {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+        .context(format!("无法写入文件: {}", path.display()))
+}
+
+/// Entries newest first, keeping those whose actor starts with `actor` and whose action matches.
+/// Lines that do not parse are skipped rather than failing the whole read.
+pub fn query(actor: Option<&str>, action: Option<Action>) -> Result<Vec<AuditEntry>> {
+    let path = Config::data_path(AUDIT_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(&path).context(format!("无法读取文件: {}", path.display()))?;
+    Ok(text.lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+        .filter(|e| actor.is_none_or(|actor| e.actor.starts_with(actor)))
+        .filter(|e| action.is_none_or(|action| e.action == action))
+        .collect())
+}
+
+/// Parse an action name as written in the log, e.g. "token_set"
+pub fn parse_action(name: &str) -> Option<Action> {
+    Action::ALL.into_iter().find(|action| action.name() == name)
+}
@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::{self, File}, io::{Read, Write}, path::Path};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{audit, config::{Config, ConfigService}, coupons::{HISTORY_FILE, USAGE_FILE}, images, utils::format_current_time};
+
+/// Marker in the `format` field of the manifest
+const ARCHIVE_FORMAT: &str = "mcd-coupon-backup";
+/// Manifest inside the archive describing what it holds
+const MANIFEST: &str = "manifest.json";
+/// Name of the config file inside the archive
+const CONFIG_ENTRY: &str = "config.json";
+/// Folder of cached coupon images inside the archive
+const IMAGE_DIR: &str = "image-cache/";
+/// Files in the data directory that go into an archive
+const DATA_FILES: &[&str] = &[HISTORY_FILE, USAGE_FILE, audit::AUDIT_FILE, audit::MCP_AUDIT_FILE];
+
+/// Largest entry read from an archive, so a crafted one cannot exhaust memory on restore
+const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+/// Largest total of all entries read from an archive
+const MAX_ARCHIVE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Upgrades the entries of an archive by one layout version
+type Migration = fn(&mut BTreeMap<String, Vec<u8>>) -> Result<()>;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` archive to version `n + 2`.
+/// Append a step here whenever the archive or a data file layout changes incompatibly.
+const MIGRATIONS: &[Migration] = &[];
+
+/// Current archive layout version
+const ARCHIVE_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Versions and contents of an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    /// Version of the program that wrote the archive
+    pub app_version: String,
+    pub created_at: String,
+    pub files: Vec<String>,
+}
+
+/// What a create or restore covered
+#[derive(Debug, Serialize)]
+pub struct ArchiveSummary {
+    #[serde(flatten)]
+    pub manifest: Manifest,
+    /// Layout version the archive was upgraded from on restore
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrated_from: Option<u32>,
+}
+
+/// Write the config, local data and image cache to a zip archive at `path`
+pub fn create(path: &Path) -> Result<ArchiveSummary> {
+    let mut entries: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+
+    let config_path = Config::get_config_path();
+    let config = match fs::read(&config_path) {
+        Ok(bytes) => bytes,
+        // Nothing saved yet: store the defaults so the archive always restores a config
+        Err(_) => serde_json::to_vec_pretty(&*ConfigService::global().current())?,
+    };
+    entries.insert(CONFIG_ENTRY.to_string(), config);
+
+    for name in DATA_FILES {
+        let file_path = Config::data_path(name);
+        if file_path.exists() {
+            let bytes = fs::read(&file_path).context(format!("无法读取文件: {}", file_path.display()))?;
+            entries.insert(name.to_string(), bytes);
+        }
+    }
+    if let Ok(dir) = fs::read_dir(images::cache_dir()) {
+        for entry in dir.filter_map(|e| e.ok()).filter(|e| e.path().is_file()) {
+            let bytes = fs::read(entry.path()).context(format!("无法读取文件: {}", entry.path().display()))?;
+            entries.insert(format!("{}{}", IMAGE_DIR, entry.file_name().to_string_lossy()), bytes);
+        }
+    }
+
+    let manifest = Manifest {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: format_current_time(),
+        files: entries.keys().cloned().collect(),
+    };
+
+    let file = File::create(path).context(format!("无法创建文件: {}", path.display()))?;
+    let mut writer = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    writer.start_file(MANIFEST, options)?;
+    writer.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    for (name, bytes) in &entries {
+        writer.start_file(name.as_str(), options)?;
+        writer.write_all(bytes)?;
+    }
+    writer.finish().context(format!("无法写入文件: {}", path.display()))?;
+
+    Ok(ArchiveSummary { manifest, migrated_from: None })
+}
+
+/// Restore the archive at `path`, upgrading archives written by older versions first.
+/// Nothing is overwritten unless the whole archive reads and its config parses.
+pub fn restore(path: &Path) -> Result<ArchiveSummary> {
+    let file = File::open(path).context(format!("无法读取文件: {}", path.display()))?;
+    let mut archive = ZipArchive::new(file).context("不是有效的备份文件")?;
+
+    let mut manifest: Manifest = {
+        let mut text = String::new();
+        archive.by_name(MANIFEST)
+            .context("备份文件缺少 manifest.json")?
+            .take(MAX_ENTRY_BYTES)
+            .read_to_string(&mut text)?;
+        serde_json::from_str(&text).context("无法解析备份文件的 manifest.json")?
+    };
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(anyhow!("不是本工具生成的备份文件"));
+    }
+    if manifest.version > ARCHIVE_VERSION {
+        return Err(anyhow!(
+            "备份文件由较新的版本 ({}) 生成，请先升级本工具后再恢复",
+            manifest.app_version
+        ));
+    }
+
+    let mut entries = BTreeMap::new();
+    let mut total = 0;
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        if entry.is_dir() || entry.name() == MANIFEST {
+            continue;
+        }
+        let name = entry.name().to_string();
+        if entry.size() > MAX_ENTRY_BYTES {
+            return Err(anyhow!("备份中的 {} 超过 {} 字节上限", name, MAX_ENTRY_BYTES));
+        }
+        // The declared size may lie, so reading stops one byte past the limit to notice
+        let mut bytes = Vec::new();
+        entry.take(MAX_ENTRY_BYTES + 1)
+            .read_to_end(&mut bytes)
+            .context(format!("无法读取备份中的 {}", name))?;
+        if bytes.len() as u64 > MAX_ENTRY_BYTES {
+            return Err(anyhow!("备份中的 {} 超过 {} 字节上限", name, MAX_ENTRY_BYTES));
+        }
+        total += bytes.len() as u64;
+        if total > MAX_ARCHIVE_BYTES {
+            return Err(anyhow!("备份文件解压后超过 {} 字节上限", MAX_ARCHIVE_BYTES));
+        }
+        entries.insert(name, bytes);
+    }
+
+    let migrated_from = (manifest.version < ARCHIVE_VERSION).then_some(manifest.version);
+    for migration in &MIGRATIONS[(manifest.version.max(1) - 1) as usize..] {
+        migration(&mut entries)?;
+    }
+    manifest.version = ARCHIVE_VERSION;
+
+    let config = entries.get(CONFIG_ENTRY).ok_or_else(|| anyhow!("备份文件缺少 config.json"))?;
+    serde_json::from_slice::<Config>(config).context("备份中的配置无法解析")?;
+
+    let mut restored = Vec::new();
+    for (name, bytes) in &entries {
+        let target = if name == CONFIG_ENTRY {
+            Config::get_config_path()
+        } else if DATA_FILES.contains(&name.as_str()) {
+            Config::data_path(name)
+        } else if let Some(image) = name.strip_prefix(IMAGE_DIR).filter(|n| is_plain_name(n)) {
+            images::cache_dir().join(image)
+        } else {
+            // Entries this version does not know about are left alone
+            continue;
+        };
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir).context(format!("无法创建目录: {}", dir.display()))?;
+        }
+        fs::write(&target, bytes).context(format!("无法写入文件: {}", target.display()))?;
+        restored.push(name.clone());
+    }
+    manifest.files = restored;
+
+    Ok(ArchiveSummary { manifest, migrated_from })
+}
+
+/// A bare file name, so archive entries cannot write outside the cache folder
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && name != "." && name != ".."
+}
@@ -0,0 +1,18 @@
+// Local zip archives are always built, since data migrations back up to them;
+// encrypted WebDAV backups need the `storage` feature
+pub mod archive;
+#[cfg(feature = "storage")]
+mod webdav;
+
+#[cfg(feature = "storage")]
+pub use webdav::{restore, upload};
+#[cfg(all(feature = "storage", feature = "server"))]
+pub use webdav::spawn_scheduler;
+
+/// Without `storage` a configured backup schedule is reported instead of run
+#[cfg(all(feature = "server", not(feature = "storage")))]
+pub fn spawn_scheduler(config: &crate::config::Config) {
+    if config.backup.as_ref().and_then(|b| b.interval_hours).is_some_and(|h| h > 0) {
+        tracing::warn!("{}", crate::cli::not_compiled("WebDAV 定时备份", "storage"));
+    }
+}
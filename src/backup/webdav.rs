@@ -0,0 +1,98 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use std::time::Duration;
+
+use crate::{config::BackupConfig, sync::{self, SyncSummary}};
+// Only the long-running servers upload on a schedule
+#[cfg(feature = "server")]
+use crate::config::{Config, ConfigService};
+
+/// Name of the backup file inside the WebDAV folder
+const BACKUP_FILE: &str = "mcd-coupon-backup.json";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upload an encrypted bundle of config and local data, tokens included, to the WebDAV folder
+pub async fn upload(target: &BackupConfig) -> Result<SyncSummary> {
+    let (text, summary) = sync::seal(&target.passphrase, true)?;
+    let client = http_client()?;
+
+    // Servers answer 405 when the folder already exists, so the result is ignored
+    let mkcol = Method::from_bytes(b"MKCOL").expect("MKCOL is a valid method");
+    let _ = authorize(client.request(mkcol, folder_url(target)), target).send().await;
+
+    let response = authorize(client.put(file_url(target)), target)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(text)
+        .send()
+        .await
+        .context("上传备份失败")?;
+    if !response.status().is_success() {
+        return Err(anyhow!("上传备份失败: HTTP {}", response.status()));
+    }
+    Ok(summary)
+}
+
+/// Download the backup from the WebDAV folder and restore it
+pub async fn restore(target: &BackupConfig) -> Result<SyncSummary> {
+    let response = authorize(http_client()?.get(file_url(target)), target)
+        .send()
+        .await
+        .context("下载备份失败")?;
+    match response.status() {
+        StatusCode::NOT_FOUND => return Err(anyhow!("远端没有备份: {}", file_url(target))),
+        status if !status.is_success() => return Err(anyhow!("下载备份失败: HTTP {}", status)),
+        _ => {}
+    }
+    let text = response.text().await.context("下载备份失败")?;
+    sync::open(&text, &target.passphrase)
+}
+
+/// Upload periodically in the background when `backup.interval_hours` is set.
+/// Used by the long-running html and mcpserver modes.
+#[cfg(feature = "server")]
+pub fn spawn_scheduler(config: &Config) {
+    let Some(hours) = config.backup.as_ref().and_then(|b| b.interval_hours).filter(|h| *h > 0) else {
+        return;
+    };
+    tracing::info!("已启用 WebDAV 定时备份，每 {} 小时上传一次", hours);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(hours.saturating_mul(3600)));
+        // The first tick completes at once; the first upload waits a full interval instead of every startup
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            // Re-read the config so edits made while running are picked up
+            let Some(target) = ConfigService::global().current().backup.clone() else {
+                continue;
+            };
+            match upload(&target).await {
+                Ok(summary) => tracing::info!("WebDAV 备份已上传 ({})", summary.created_at),
+                Err(e) => tracing::warn!("WebDAV 备份失败: {:#}", e),
+            }
+        }
+    });
+}
+
+fn http_client() -> Result<Client> {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("无法创建 HTTP 客户端")
+}
+
+fn authorize(request: RequestBuilder, target: &BackupConfig) -> RequestBuilder {
+    if target.username.is_empty() {
+        request
+    } else {
+        request.basic_auth(&target.username, Some(&target.password))
+    }
+}
+
+fn folder_url(target: &BackupConfig) -> String {
+    format!("{}/", target.url.trim_end_matches('/'))
+}
+
+fn file_url(target: &BackupConfig) -> String {
+    format!("{}{}", folder_url(target), BACKUP_FILE)
+}
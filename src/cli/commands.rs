@@ -0,0 +1,1146 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use comfy_table::{presets::UTF8_FULL_CONDENSED, Cell, Color, ContentArrangement, Table};
+use serde::Serialize;
+use std::{collections::HashSet, io::{self, IsTerminal}, path::Path, time::{Duration, Instant}};
+
+use crate::{alert, audit::{self, Action, AuditEntry}, cli::{ColorChoice, GlobalOptions, OutputFormat, Verbosity, CONTROL_COMMANDS, DEBUG_ACTIONS, CONTROL_TARGETS, START_MODES}, config::{AlertMode, Config, ConfigService, StartMode}, control::{self, InstanceStatus}, coupons::{claim_verified, first_match, parse_coupons_from_markdown, ClaimHistory, ClaimPlan, ClaimStats, ClaimVerification, Coupon, RuleMatches, UsageStore, Verdict}, backup::archive::{self, ArchiveSummary}, daemon, mcp::{describe_error, McpClient}, mqtt, retention::{self, PruneReport}, utils::format_current_time};
+#[cfg(feature = "storage")]
+use crate::{backup, sync::{self, SyncSummary}};
+#[cfg(feature = "storage")]
+use std::io::{BufRead, Write};
+
+/// Environment variable holding the sync bundle passphrase, for non-interactive use
+#[cfg(feature = "storage")]
+const SYNC_PASSPHRASE_ENV: &str = "MCD_COUPON_SYNC_PASSPHRASE";
+/// Entries `audit` prints when `--limit` is not given
+const DEFAULT_AUDIT_LIMIT: usize = 50;
+
+/// Result of a one-shot command, printed as-is in JSON mode
+#[derive(Debug, Serialize)]
+struct CommandOutput {
+    success: bool,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+}
+
+/// Result of the `audit` command
+#[derive(Debug, Serialize)]
+struct AuditOutput {
+    success: bool,
+    message: String,
+    entries: Vec<AuditEntry>,
+}
+
+/// Result of the `stats` command
+#[derive(Debug, Serialize)]
+struct StatsOutput {
+    success: bool,
+    message: String,
+    /// "YYYY-MM" when `--month` was given, otherwise all history is counted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    month: Option<String>,
+    #[serde(flatten)]
+    stats: ClaimStats,
+    /// Value of the coupons marked used in the same period
+    estimated_savings: f64,
+    /// Used coupons counted in `estimated_savings`
+    redeemed: usize,
+}
+
+/// Result of `backup create` and restoring a local archive
+#[derive(Debug, Serialize)]
+struct ArchiveOutput {
+    success: bool,
+    message: String,
+    path: String,
+    #[serde(flatten)]
+    summary: ArchiveSummary,
+}
+
+/// Result of the `prune` command
+#[derive(Debug, Serialize)]
+struct PruneOutput {
+    success: bool,
+    message: String,
+    #[serde(flatten)]
+    report: PruneReport,
+}
+
+/// Result of the `validate` command
+#[derive(Debug, Serialize)]
+struct ValidateOutput {
+    valid: bool,
+    message: String,
+    config_path: String,
+}
+
+/// Result of the `plan` command
+#[derive(Debug, Serialize)]
+struct PlanOutput {
+    success: bool,
+    message: String,
+    plan: ClaimPlan,
+}
+
+/// Result of the `list` command
+#[derive(Debug, Serialize)]
+struct ListOutput {
+    success: bool,
+    message: String,
+    /// "available" or "mine"
+    source: &'static str,
+    coupons: Vec<Coupon>,
+}
+
+/// New coupons found by one `watch` poll
+#[derive(Debug, Serialize)]
+struct WatchEvent {
+    at: String,
+    message: String,
+    new_coupons: Vec<Coupon>,
+    /// Titles of new coupons allowed by `rules.claim_if`
+    matched: Vec<String>,
+    /// Titles of new coupons excluded by `rules.never_claim`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    blocked: Vec<String>,
+    /// Outcome of the automatic claim, when one was attempted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    claimed: Option<bool>,
+    /// Titles of new coupons matching `watch.alert_keywords`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alerted: Vec<String>,
+}
+
+/// Result of the `rules` command
+#[derive(Debug, Serialize)]
+struct RulesOutput {
+    success: bool,
+    message: String,
+    rules: Vec<RuleMatches>,
+    /// Verdict for every claimable coupon
+    coupons: Vec<CouponVerdict>,
+}
+
+#[derive(Debug, Serialize)]
+struct CouponVerdict {
+    title: String,
+    #[serde(flatten)]
+    verdict: Verdict,
+}
+
+/// Result of the `sync` command
+#[cfg(feature = "storage")]
+#[derive(Debug, Serialize)]
+struct SyncOutput {
+    success: bool,
+    message: String,
+    path: String,
+    #[serde(flatten)]
+    summary: SyncSummary,
+}
+
+/// Result of the `backup` command
+#[cfg(feature = "storage")]
+#[derive(Debug, Serialize)]
+struct BackupOutput {
+    success: bool,
+    message: String,
+    url: String,
+    #[serde(flatten)]
+    summary: SyncSummary,
+}
+
+/// Result of the `daemon` command
+#[derive(Debug, Serialize)]
+struct DaemonOutput {
+    success: bool,
+    message: String,
+    pid: Option<u32>,
+    log: String,
+    /// The web mode's own status, when it answers on its control socket
+    #[serde(skip_serializing_if = "Option::is_none")]
+    web: Option<InstanceStatus>,
+}
+
+#[derive(Debug, Serialize)]
+struct TimingsOutput {
+    success: bool,
+    message: String,
+    phases: Vec<Phase>,
+    /// Resident memory after the phases; None where the platform does not report it
+    memory: Option<MemoryStats>,
+    binary_bytes: Option<u64>,
+    /// Cargo features this binary was built with
+    features: Vec<&'static str>,
+}
+
+/// One timed step of starting up
+#[derive(Debug, Serialize)]
+struct Phase {
+    name: &'static str,
+    millis: f64,
+    /// Why the step failed or was skipped, or what it returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct MemoryStats {
+    rss_bytes: u64,
+    peak_rss_bytes: u64,
+}
+
+/// Build an MCP client from the saved token
+fn load_client() -> Result<McpClient> {
+    let config = ConfigService::global().current();
+    if !config.has_valid_token() {
+        return Err(anyhow!(
+            "未检测到已保存的 Token，请先运行 tui 或 html 模式设置 Token (配置文件: {})",
+            Config::get_config_path().display()
+        ));
+    }
+    Ok(McpClient::with_url(config.token.clone(), config.upstream_url())?)
+}
+
+/// Print a failed command result and report failure to the caller.
+/// Errors are written to stderr even in quiet mode.
+fn fail(options: &GlobalOptions, message: String) -> bool {
+    let output = CommandOutput {
+        success: false,
+        message,
+        details: None,
+    };
+    match options.output {
+        OutputFormat::Json => options.emit(&output, |_| {}),
+        OutputFormat::Text => eprintln!("{}", output.message),
+    }
+    false
+}
+
+/// `claim`: claim all available coupons once and exit. Returns whether it succeeded;
+/// an answer of success with nothing new held counts as a failure.
+pub async fn claim(options: &GlobalOptions) -> Result<bool> {
+    let client = match load_client() {
+        Ok(client) => client,
+        Err(e) => return Ok(fail(options, e.to_string())),
+    };
+
+    let (result, verification) = claim_verified(&client).await;
+    ClaimHistory::record("cli", &result, verification.as_ref());
+    audit::record_claim("cli", &result, verification.as_ref());
+    if let Some(published) = mqtt::publish_claim("cli", &result, verification.as_ref()) {
+        let _ = published.await;
+    }
+    let success = ClaimVerification::succeeded(&result, verification.as_ref());
+    match result {
+        Ok(result) => {
+            let output = CommandOutput {
+                success,
+                message: ClaimVerification::message(verification.as_ref()),
+                details: Some(result),
+            };
+            options.emit(&output, |o| {
+                println!("{}", o.message);
+                if let Some(details) = &o.details {
+                    println!("{}", details);
+                }
+            });
+            Ok(output.success)
+        }
+        Err(e) => Ok(fail(options, format!("领取失败: {}", describe_error(&e)))),
+    }
+}
+
+/// `validate`: check whether the saved token is accepted upstream. Returns whether it is valid.
+pub async fn validate(options: &GlobalOptions) -> Result<bool> {
+    let config_path = Config::get_config_path().display().to_string();
+    let client = match load_client() {
+        Ok(client) => client,
+        Err(e) => return Ok(fail(options, e.to_string())),
+    };
+
+    let (valid, message) = match client.validate_token().await {
+        Ok(true) => (true, "Token 有效".to_string()),
+        Ok(false) => (false, "Token 无效，请重新设置".to_string()),
+        Err(e) => (false, format!("验证失败: {}", e)),
+    };
+
+    if !valid && options.output == OutputFormat::Text {
+        return Ok(fail(options, message));
+    }
+
+    let output = ValidateOutput {
+        valid,
+        message,
+        config_path,
+    };
+    options.emit(&output, |o| println!("{}", o.message));
+    Ok(valid)
+}
+
+/// `plan`: show what a claim would add without claiming anything
+pub async fn plan(options: &GlobalOptions) -> Result<bool> {
+    let client = match load_client() {
+        Ok(client) => client,
+        Err(e) => return Ok(fail(options, e.to_string())),
+    };
+
+    let (available, held) = tokio::join!(client.get_available_coupons(), client.get_my_coupons());
+    let available = match available {
+        Ok(text) => parse_coupons_from_markdown(&text),
+        Err(e) => return Ok(fail(options, format!("可领取优惠券加载失败: {}", describe_error(&e)))),
+    };
+    let held = match held {
+        Ok(text) => parse_coupons_from_markdown(&text),
+        Err(e) => return Ok(fail(options, format!("已领取优惠券加载失败: {}", describe_error(&e)))),
+    };
+
+    let plan = ClaimPlan::build(&available, &held);
+    let output = PlanOutput {
+        success: true,
+        message: plan.summary(),
+        plan,
+    };
+    options.emit(&output, |o| {
+        println!("{}", o.message);
+        for coupon in &o.plan.would_add {
+            println!("  + {} ({})", coupon.title, coupon.expiry);
+        }
+        for coupon in &o.plan.duplicates {
+            println!("  = {} (已持有)", coupon.title);
+        }
+        for conflict in &o.plan.conflicts {
+            println!("  ! {} - {}", conflict.coupon.title, conflict.reason);
+        }
+    });
+    Ok(true)
+}
+
+/// `list [--available|--mine]`: print held (default) or claimable coupons as a table
+pub async fn list(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let available = args.iter().any(|a| a == "--available");
+    if available && args.iter().any(|a| a == "--mine") {
+        return Ok(fail(options, "--available 与 --mine 不能同时使用".to_string()));
+    }
+    let client = match load_client() {
+        Ok(client) => client,
+        Err(e) => return Ok(fail(options, e.to_string())),
+    };
+
+    let (source, label, result) = if available {
+        ("available", "可领取", client.get_available_coupons().await)
+    } else {
+        ("mine", "已领取", client.get_my_coupons().await)
+    };
+    let mut coupons = match result {
+        Ok(text) => parse_coupons_from_markdown(&text),
+        Err(e) => return Ok(fail(options, format!("{}优惠券加载失败: {}", label, describe_error(&e)))),
+    };
+    if !available {
+        UsageStore::load().unwrap_or_default().apply(&mut coupons);
+    }
+
+    let output = ListOutput {
+        success: true,
+        message: format!("共 {} 张{}优惠券", coupons.len(), label),
+        source,
+        coupons,
+    };
+    options.emit(&output, |o| {
+        if !o.coupons.is_empty() {
+            println!("{}", coupon_table(options, &o.coupons, o.source == "mine"));
+        }
+        println!("{}", o.message);
+    });
+    Ok(true)
+}
+
+/// `watch`: poll claimable coupons until interrupted, announcing new ones and claiming
+/// when one of them is allowed by the claim rules. Returns whether it exited cleanly.
+pub async fn watch(options: &GlobalOptions) -> Result<bool> {
+    let client = match load_client() {
+        Ok(client) => client,
+        Err(e) => return Ok(fail(options, e.to_string())),
+    };
+    let config = ConfigService::global().current();
+    let rules = config.rules.clone();
+    let watch = config.watch.clone();
+    let minutes = watch.interval_minutes.max(1);
+
+    if options.output == OutputFormat::Text && options.verbosity != Verbosity::Quiet {
+        println!("开始监控可领取的优惠券，每 {} 分钟检查一次，按 Ctrl+C 退出", minutes);
+        if !rules.claim_if.is_empty() {
+            println!("新优惠券包含以下关键词时自动领取: {}", rules.claim_if.join(", "));
+        }
+        if !rules.never_claim.is_empty() {
+            println!("包含以下关键词的新优惠券不会触发领取: {}", rules.never_claim.join(", "));
+        }
+        if watch.alert != AlertMode::Off && !watch.alert_keywords.is_empty() {
+            println!("新优惠券包含以下关键词时发出提醒: {}", watch.alert_keywords.join(", "));
+        }
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+    // Coupons seen so far; the first poll only records what is already there
+    let mut seen: Option<HashSet<String>> = None;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(true),
+        }
+
+        let coupons = match client.get_available_coupons().await {
+            Ok(text) => parse_coupons_from_markdown(&text),
+            Err(e) => {
+                fail(options, format!("[{}] 可领取优惠券加载失败: {}", format_current_time(), describe_error(&e)));
+                continue;
+            }
+        };
+        let titles: HashSet<String> = coupons.iter().map(|c| c.title.clone()).collect();
+        let Some(previous) = seen.replace(titles) else {
+            tracing::info!("当前可领取 {} 张优惠券", coupons.len());
+            continue;
+        };
+
+        let new_coupons: Vec<Coupon> = coupons.into_iter()
+            .filter(|c| !previous.contains(&c.title))
+            .collect();
+        if new_coupons.is_empty() {
+            tracing::debug!("没有新的可领取优惠券");
+            continue;
+        }
+
+        let mut matched = Vec::new();
+        let mut blocked = Vec::new();
+        for coupon in &new_coupons {
+            match rules.evaluate(coupon) {
+                Verdict::Claim(_) => matched.push(coupon.title.clone()),
+                Verdict::Never(_) => blocked.push(coupon.title.clone()),
+                Verdict::Unmatched => {}
+            }
+        }
+        let alerted: Vec<String> = new_coupons.iter()
+            .filter(|c| first_match(&watch.alert_keywords, c).is_some())
+            .map(|c| c.title.clone())
+            .collect();
+        let mut message = format!("发现 {} 张新的可领取优惠券", new_coupons.len());
+        if !alerted.is_empty() {
+            message.push_str(&format!("，其中 {} 张包含提醒关键词", alerted.len()));
+        }
+        let claimed = if matched.is_empty() {
+            None
+        } else {
+            let (result, verification) = claim_verified(&client).await;
+            ClaimHistory::record("watch", &result, verification.as_ref());
+            audit::record_claim("watch", &result, verification.as_ref());
+            mqtt::publish_claim("watch", &result, verification.as_ref());
+            match &result {
+                Ok(_) => message.push_str(&format!("，已自动领取: {}", ClaimVerification::message(verification.as_ref()))),
+                Err(e) => message.push_str(&format!("，自动领取失败: {}", describe_error(e))),
+            }
+            Some(ClaimVerification::succeeded(&result, verification.as_ref()))
+        };
+
+        let event = WatchEvent {
+            at: format_current_time(),
+            message,
+            new_coupons,
+            matched,
+            blocked,
+            claimed,
+            alerted,
+        };
+        options.emit(&event, |e| {
+            // Ring the terminal bell so the event is noticed in a background tab
+            println!("\x07[{}] {}", e.at, e.message);
+            for coupon in &e.new_coupons {
+                let mark = if e.matched.contains(&coupon.title) {
+                    "*"
+                } else if e.blocked.contains(&coupon.title) {
+                    "x"
+                } else if e.alerted.contains(&coupon.title) {
+                    "!"
+                } else {
+                    "+"
+                };
+                println!("  {} {} ({})", mark, coupon.title, coupon.expiry);
+            }
+        });
+        alert::announce(watch.alert, &event.alerted).await;
+    }
+}
+
+/// `rules`: dry-run the claim rules against the coupons claimable right now
+pub async fn rules(options: &GlobalOptions) -> Result<bool> {
+    let rules = ConfigService::global().current().rules.clone();
+    if rules.is_empty() {
+        return Ok(fail(
+            options,
+            format!("未配置自动领取规则，请在配置文件中添加 rules.claim_if / rules.never_claim ({})", Config::get_config_path().display()),
+        ));
+    }
+    let client = match load_client() {
+        Ok(client) => client,
+        Err(e) => return Ok(fail(options, e.to_string())),
+    };
+    let coupons = match client.get_available_coupons().await {
+        Ok(text) => parse_coupons_from_markdown(&text),
+        Err(e) => return Ok(fail(options, format!("可领取优惠券加载失败: {}", describe_error(&e)))),
+    };
+
+    let verdicts: Vec<CouponVerdict> = coupons.iter()
+        .map(|c| CouponVerdict {
+            title: c.title.clone(),
+            verdict: rules.evaluate(c),
+        })
+        .collect();
+    let allowed = verdicts.iter().filter(|v| matches!(v.verdict, Verdict::Claim(_))).count();
+    let output = RulesOutput {
+        success: true,
+        message: format!("当前 {} 张可领取优惠券中，{} 张会被规则自动领取（试运行，未实际领取）", coupons.len(), allowed),
+        rules: rules.matches(&coupons),
+        coupons: verdicts,
+    };
+    options.emit(&output, |o| {
+        for rule in &o.rules {
+            println!("{} \"{}\": 匹配 {} 张", rule.rule, rule.keyword, rule.coupons.len());
+            for title in &rule.coupons {
+                println!("  - {}", title);
+            }
+        }
+        println!();
+        for coupon in &o.coupons {
+            match &coupon.verdict {
+                Verdict::Claim(keyword) => println!("  * {} (领取: {})", coupon.title, keyword),
+                Verdict::Never(keyword) => println!("  x {} (排除: {})", coupon.title, keyword),
+                Verdict::Unmatched => println!("    {}", coupon.title),
+            }
+        }
+        println!("{}", o.message);
+    });
+    Ok(true)
+}
+
+/// Empty table in the shared style, honoring `--color always` when piped
+fn new_table(options: &GlobalOptions) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    if options.color == ColorChoice::Always {
+        table.enforce_styling();
+    }
+    table
+}
+
+/// `color` for a table cell, or no color when color output is off
+fn paint(options: &GlobalOptions, color: Color) -> Color {
+    if options.color.enabled(io::stdout().is_terminal()) { color } else { Color::Reset }
+}
+
+/// Column-aligned coupon table with an expiry countdown
+fn coupon_table(options: &GlobalOptions, coupons: &[Coupon], show_used: bool) -> Table {
+    let mut table = new_table(options);
+    let mut header = vec!["优惠券", "优惠", "有效期", "剩余", "标签"];
+    if show_used {
+        header.push("状态");
+    }
+    table.set_header(header);
+
+    for coupon in coupons {
+        let (countdown, color) = match coupon.days_left() {
+            Some(days) if days < 0 => ("已过期".to_string(), Color::DarkGrey),
+            Some(0) => ("今天到期".to_string(), Color::Red),
+            Some(days) if days <= 3 => (format!("{} 天", days), Color::Yellow),
+            Some(days) => (format!("{} 天", days), Color::Reset),
+            None => ("-".to_string(), Color::Reset),
+        };
+        let mut row = vec![
+            Cell::new(format!("{}{}", coupon.title, coupon.count_badge())),
+            Cell::new(&coupon.price),
+            Cell::new(&coupon.expiry),
+            Cell::new(countdown).fg(paint(options, color)),
+            Cell::new(&coupon.tags),
+        ];
+        if show_used {
+            row.push(Cell::new(if coupon.used { "已使用" } else { "未使用" }));
+        }
+        table.add_row(row);
+    }
+    table
+}
+
+/// Value of `--name <value>` or `--name=<value>` in a command's arguments
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == name {
+            return iter.next().map(String::as_str);
+        }
+        if let Some(value) = arg.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// `audit [--actor <actor>] [--action <action>] [--limit <n>]`: print the audit trail, newest first
+pub fn audit(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let action = match flag_value(args, "--action") {
+        Some(name) => match audit::parse_action(name) {
+            Some(action) => Some(action),
+            None => return Ok(fail(options, format!("未知的操作: {} (可选: {})", name, Action::NAMES.join(", ")))),
+        },
+        None => None,
+    };
+    let limit = match flag_value(args, "--limit").map(str::parse::<usize>) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => return Ok(fail(options, "--limit 应为正整数".to_string())),
+        None => DEFAULT_AUDIT_LIMIT,
+    };
+    let mut entries = match audit::query(flag_value(args, "--actor"), action) {
+        Ok(entries) => entries,
+        Err(e) => return Ok(fail(options, format!("读取审计日志失败: {:#}", e))),
+    };
+    let total = entries.len();
+    entries.truncate(limit);
+
+    let output = AuditOutput {
+        success: true,
+        message: if total > entries.len() {
+            format!("共 {} 条记录，显示最近 {} 条", total, entries.len())
+        } else {
+            format!("共 {} 条记录", total)
+        },
+        entries,
+    };
+    options.emit(&output, |o| {
+        if !o.entries.is_empty() {
+            let mut table = new_table(options);
+            table.set_header(vec!["时间", "执行者", "操作", "结果", "详情"]);
+            for entry in &o.entries {
+                let (result, color) = if entry.success { ("成功", Color::Reset) } else { ("失败", Color::Red) };
+                table.add_row(vec![
+                    Cell::new(&entry.at),
+                    Cell::new(&entry.actor),
+                    Cell::new(entry.action.label()),
+                    Cell::new(result).fg(paint(options, color)),
+                    Cell::new(&entry.detail),
+                ]);
+            }
+            println!("{}", table);
+        }
+        println!("{}", o.message);
+    });
+    Ok(true)
+}
+
+/// Summarize the claim history and the savings of used coupons, for all time or one
+/// `--month YYYY-MM`. Returns whether the history could be read.
+pub fn stats(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let month = match flag_value(args, "--month") {
+        Some(text) => match NaiveDate::parse_from_str(&format!("{}-01", text), "%Y-%m-%d") {
+            Ok(first) => Some(first),
+            Err(_) => return Ok(fail(options, format!("--month 应为 YYYY-MM 格式，如 2024-06: {}", text))),
+        },
+        None => None,
+    };
+    let history = match ClaimHistory::load() {
+        Ok(history) => history,
+        Err(e) => return Ok(fail(options, format!("读取领取记录失败: {:#}", e))),
+    };
+    let stats = history.stats(month);
+    let (estimated_savings, redeemed) = UsageStore::load().unwrap_or_default().savings_in(month);
+
+    let period = month.map(|m| m.format("%Y-%m").to_string());
+    let output = StatsOutput {
+        success: true,
+        message: match &period {
+            Some(period) => format!("{} 共领取 {} 次", period, stats.attempts),
+            None => format!("共领取 {} 次", stats.attempts),
+        },
+        month: period,
+        stats,
+        estimated_savings,
+        redeemed,
+    };
+    options.emit(&output, |o| {
+        let s = &o.stats;
+        println!("{}", match &o.month {
+            Some(month) => format!("{} 领取统计", month),
+            None => "全部领取统计".to_string(),
+        });
+        println!("  领取次数: {}（成功 {}，成功率 {}%）", s.attempts, s.succeeded, s.success_rate);
+        println!("  领到优惠券: {} 张，面值约 ¥{}", s.coupons_claimed, s.claimed_value);
+        if !s.top_categories.is_empty() {
+            let top: Vec<String> = s.top_categories.iter().map(|c| format!("{} {}", c.name, c.count)).collect();
+            println!("  常领类别: {}", top.join("、"));
+        }
+        println!("  预估节省: ¥{}（已使用 {} 张）", o.estimated_savings, o.redeemed);
+    });
+    Ok(true)
+}
+
+/// Create or restore a local archive. Returns whether it succeeded.
+fn backup_archive(options: &GlobalOptions, action: &str, path: &Path) -> bool {
+    let result = if action == "create" {
+        archive::create(path)
+    } else {
+        let result = archive::restore(path);
+        audit::record("cli", Action::BackupRestore, result.is_ok(), match &result {
+            Ok(_) => format!("已从 {} 恢复", path.display()),
+            Err(e) => format!("从 {} 恢复失败: {:#}", path.display(), e),
+        });
+        result
+    };
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(e) => return fail(options, format!("{:#}", e)),
+    };
+
+    let done = if action == "create" { "已创建备份" } else { "已从备份恢复" };
+    let output = ArchiveOutput {
+        success: true,
+        message: format!("{}: {}", done, path.display()),
+        path: path.display().to_string(),
+        summary,
+    };
+    options.emit(&output, |o| {
+        println!("{}", o.message);
+        println!("备份时间: {}（版本 {}）", o.summary.manifest.created_at, o.summary.manifest.app_version);
+        if let Some(version) = o.summary.migrated_from {
+            println!("已将第 {} 版格式的备份升级为当前格式", version);
+        }
+        println!("包含 {} 个文件", o.summary.manifest.files.len());
+    });
+    true
+}
+
+/// `prune [--dry-run]`: apply the retention policy now, or only report what it would remove
+pub fn prune(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    let retention = ConfigService::global().current().retention.clone();
+    let report = match retention::prune(&retention, dry_run) {
+        Ok(report) => report,
+        Err(e) => return Ok(fail(options, format!("清理失败: {:#}", e))),
+    };
+    if !dry_run && report.removed() > 0 {
+        audit::record("cli", Action::Prune, true, retention::summary(&report));
+    }
+
+    let output = PruneOutput {
+        success: true,
+        message: match (dry_run, report.removed()) {
+            (_, 0) => "没有需要清理的数据".to_string(),
+            (true, removed) => format!("将清理 {} 项，可释放 {}（未实际删除）", removed, format_bytes(report.bytes())),
+            (false, removed) => format!("已清理 {} 项，释放 {}", removed, format_bytes(report.bytes())),
+        },
+        report,
+    };
+    options.emit(&output, |o| {
+        if o.report.removed() > 0 {
+            let mut table = new_table(options);
+            table.set_header(vec!["数据", "清理条数", "空间"]);
+            for item in &o.report.items {
+                table.add_row(vec![item.target.to_string(), item.removed.to_string(), format_bytes(item.bytes)]);
+            }
+            println!("{}", table);
+        }
+        println!("{}", o.message);
+    });
+    Ok(true)
+}
+
+/// `debug timings`: time the steps of starting up again one by one, then report memory
+/// use and binary size. `started` is when `main` was entered. Returns whether it succeeded.
+pub async fn debug(options: &GlobalOptions, args: &[String], started: Instant) -> Result<bool> {
+    if args.first().map(String::as_str) != Some("timings") {
+        return Ok(fail(options, format!("用法: debug <{}>", DEBUG_ACTIONS.join("|"))));
+    }
+    let mut phases = vec![Phase {
+        name: "启动到执行命令",
+        millis: millis(started),
+        note: Some("参数解析、数据升级与读取配置".to_string()),
+    }];
+    let mut timed = |name, at: Instant, note: Option<String>| phases.push(Phase { name, millis: millis(at), note });
+
+    let at = Instant::now();
+    let config = Config::load();
+    timed("读取配置", at, config.as_ref().err().map(|e| format!("{:#}", e)));
+    let config = config.unwrap_or_default();
+
+    let at = Instant::now();
+    let history = ClaimHistory::load();
+    let usage = UsageStore::load();
+    timed("读取本地数据", at, Some(format!(
+        "领取记录 {} 条，已使用 {} 张",
+        history.map(|h| h.entries.len()).unwrap_or(0),
+        usage.map(|u| u.used.len()).unwrap_or(0),
+    )));
+
+    let at = Instant::now();
+    let client = if config.has_valid_token() {
+        McpClient::with_url(config.token.clone(), config.upstream_url()).map_err(|e| format!("{:#}", e))
+    } else {
+        Err("未设置 Token，跳过上游请求".to_string())
+    };
+    timed("初始化客户端", at, client.as_ref().err().cloned());
+
+    if let Ok(client) = client {
+        let at = Instant::now();
+        let note = match client.get_available_coupons().await {
+            Ok(markdown) => format!("可领取 {} 张", parse_coupons_from_markdown(&markdown).len()),
+            Err(e) => format!("失败: {}", describe_error(&e)),
+        };
+        timed("首次上游请求", at, Some(note));
+    }
+
+    let total = millis(started);
+    let output = TimingsOutput {
+        success: true,
+        message: format!("共耗时 {:.1} ms", total),
+        phases,
+        memory: memory_stats(),
+        binary_bytes: std::env::current_exe().and_then(std::fs::metadata).map(|m| m.len()).ok(),
+        features: [
+            ("tui", cfg!(feature = "tui")),
+            ("web", cfg!(feature = "web")),
+            ("mcp-server", cfg!(feature = "mcp-server")),
+            ("notifications", cfg!(feature = "notifications")),
+            ("storage", cfg!(feature = "storage")),
+            ("grpc", cfg!(feature = "grpc")),
+        ]
+        .into_iter()
+        .filter_map(|(name, on)| on.then_some(name))
+        .collect(),
+    };
+    options.emit(&output, |o| {
+        let mut table = new_table(options);
+        table.set_header(vec!["阶段", "耗时 (ms)", "说明"]);
+        for phase in &o.phases {
+            table.add_row(vec![
+                Cell::new(phase.name),
+                Cell::new(format!("{:.1}", phase.millis)),
+                Cell::new(phase.note.as_deref().unwrap_or("")),
+            ]);
+        }
+        println!("{}", table);
+        println!("{}", o.message);
+        match &o.memory {
+            Some(m) => println!("内存占用: {}（峰值 {}）", format_bytes(m.rss_bytes), format_bytes(m.peak_rss_bytes)),
+            None => println!("内存占用: 当前平台不支持"),
+        }
+        if let Some(bytes) = o.binary_bytes {
+            println!("程序大小: {}", format_bytes(bytes));
+        }
+        println!("启用的功能: {}", if o.features.is_empty() { "无".to_string() } else { o.features.join(", ") });
+    });
+    Ok(true)
+}
+
+fn millis(since: Instant) -> f64 {
+    (since.elapsed().as_secs_f64() * 10_000.0).round() / 10.0
+}
+
+/// Current and peak resident memory, from /proc on Linux
+fn memory_stats() -> Option<MemoryStats> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib = |key: &str| {
+        status.lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|rest| rest.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kib| kib * 1024)
+    };
+    Some(MemoryStats { rss_bytes: kib("VmRSS:")?, peak_rss_bytes: kib("VmHWM:")? })
+}
+
+/// Byte count for display, e.g. "1.5 MiB"
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// `sync export|import <file> [--no-token]`: move config and local data between machines
+/// through a passphrase-encrypted bundle. Returns whether it succeeded.
+#[cfg(feature = "storage")]
+pub fn sync(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let include_token = !args.iter().any(|a| a == "--no-token");
+    let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let (Some(action), Some(file)) = (positional.first(), positional.get(1)) else {
+        return Ok(fail(options, "用法: sync export|import <file> [--no-token]".to_string()));
+    };
+    let path = Path::new(file.as_str());
+
+    let passphrase = match read_passphrase(action.as_str()) {
+        Ok(passphrase) => passphrase,
+        Err(e) => return Ok(fail(options, e.to_string())),
+    };
+
+    let (result, done) = match action.as_str() {
+        "export" => (sync::export(path, &passphrase, include_token), "已导出同步包"),
+        "import" => {
+            let result = sync::import(path, &passphrase);
+            audit::record("cli", Action::SyncImport, result.is_ok(), match &result {
+                Ok(_) => format!("已导入 {}", file),
+                Err(e) => format!("导入 {} 失败: {}", file, e),
+            });
+            (result, "已导入同步包")
+        },
+        other => return Ok(fail(options, format!("未知的 sync 操作: {} (可选: export, import)", other))),
+    };
+
+    match result {
+        Ok(summary) => {
+            let output = SyncOutput {
+                success: true,
+                message: format!("{}: {}", done, file),
+                path: file.to_string(),
+                summary,
+            };
+            options.emit(&output, |o| {
+                println!("{}", o.message);
+                println!("创建时间: {}", o.summary.created_at);
+                println!("包含 Token: {}", if o.summary.includes_token { "是" } else { "否" });
+                if !o.summary.files.is_empty() {
+                    println!("数据文件: {}", o.summary.files.join(", "));
+                }
+            });
+            Ok(true)
+        }
+        Err(e) => Ok(fail(options, format!("同步失败: {}", e))),
+    }
+}
+
+#[cfg(not(feature = "storage"))]
+pub fn sync(options: &GlobalOptions, _args: &[String]) -> Result<bool> {
+    Ok(fail(options, super::not_compiled("同步功能", "storage")))
+}
+
+/// Passphrase from the environment, or prompted on the terminal
+#[cfg(feature = "storage")]
+fn read_passphrase(action: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(SYNC_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let prompt = if action == "export" { "请设置同步包密码: " } else { "请输入同步包密码: " };
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    let passphrase = line.trim_end_matches(['\r', '\n']).to_string();
+    if passphrase.is_empty() {
+        return Err(anyhow!("密码不能为空 (也可通过环境变量 {} 提供)", SYNC_PASSPHRASE_ENV));
+    }
+    Ok(passphrase)
+}
+
+/// `backup create|restore <file>`: write or restore a full local archive.
+/// `backup upload|restore`: push an encrypted bundle to the configured WebDAV folder,
+/// or restore from it. Returns whether it succeeded.
+pub async fn backup(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    if let [action, file, ..] = args {
+        if action == "create" || action == "restore" {
+            return Ok(backup_archive(options, action, Path::new(file)));
+        }
+    }
+    if args.first().map(String::as_str) == Some("create") {
+        return Ok(fail(options, "用法: backup create <file>".to_string()));
+    }
+    backup_remote(options, args).await
+}
+
+/// `backup upload|restore` against the WebDAV folder in the config
+#[cfg(feature = "storage")]
+async fn backup_remote(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let Some(target) = ConfigService::global().current().backup.clone() else {
+        return Ok(fail(
+            options,
+            format!("未配置 WebDAV 备份，请在配置文件中添加 backup 设置 ({})", Config::get_config_path().display()),
+        ));
+    };
+
+    let (result, done) = match args.first().map(String::as_str) {
+        Some("upload") => (backup::upload(&target).await, "备份已上传"),
+        Some("restore") => {
+            let result = backup::restore(&target).await;
+            audit::record("cli", Action::BackupRestore, result.is_ok(), match &result {
+                Ok(_) => format!("已从 {} 恢复", target.url),
+                Err(e) => format!("从 {} 恢复失败: {:#}", target.url, e),
+            });
+            (result, "已从备份恢复")
+        },
+        Some(other) => return Ok(fail(options, format!("未知的 backup 操作: {} (可选: create, restore, upload)", other))),
+        None => return Ok(fail(options, "用法: backup create|restore <file> 或 backup upload|restore".to_string())),
+    };
+
+    match result {
+        Ok(summary) => {
+            let output = BackupOutput {
+                success: true,
+                message: format!("{}: {}", done, target.url),
+                url: target.url.clone(),
+                summary,
+            };
+            options.emit(&output, |o| {
+                println!("{}", o.message);
+                println!("备份时间: {}", o.summary.created_at);
+                if !o.summary.files.is_empty() {
+                    println!("数据文件: {}", o.summary.files.join(", "));
+                }
+            });
+            Ok(true)
+        }
+        Err(e) => Ok(fail(options, format!("{:#}", e))),
+    }
+}
+
+#[cfg(not(feature = "storage"))]
+async fn backup_remote(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    match args.first().map(String::as_str) {
+        Some("upload" | "restore") => Ok(fail(options, super::not_compiled("WebDAV 备份", "storage"))),
+        Some(other) => Ok(fail(options, format!("未知的 backup 操作: {} (可选: create, restore)", other))),
+        None => Ok(fail(options, "用法: backup create|restore <file>".to_string())),
+    }
+}
+
+/// `ctl <command> [--target web|mcp]`: send one command to a running instance over its
+/// local control socket. Returns whether the instance reported success.
+pub async fn ctl(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let target = flag_value(args, "--target").unwrap_or("web");
+    if !CONTROL_TARGETS.contains(&target) {
+        return Ok(fail(options, format!("未知的目标实例: {} (可选: {})", target, CONTROL_TARGETS.join(", "))));
+    }
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--target" {
+            iter.next();
+        } else if !arg.starts_with("--") {
+            positional.push(arg.as_str());
+        }
+    }
+    let Some(command) = positional.first().copied().filter(|c| CONTROL_COMMANDS.contains(c)) else {
+        return Ok(fail(options, format!("用法: ctl <{}> [--target {}]", CONTROL_COMMANDS.join("|"), CONTROL_TARGETS.join("|"))));
+    };
+
+    let reply = match control::send(target, command).await {
+        Ok(reply) => reply,
+        Err(e) => return Ok(fail(options, format!("{:#}", e))),
+    };
+    if !reply.success {
+        return Ok(fail(options, reply.message));
+    }
+    options.emit(&reply, |r| {
+        println!("{}", r.message);
+        if let Some(status) = &r.status {
+            println!("模式: {} (PID {})", status.mode, status.pid);
+            println!("Token: {}", if status.has_token { "已设置" } else { "未设置" });
+            if let Some(account) = &status.account {
+                println!("当前账号: {}", account);
+            }
+            println!("已缓存优惠券: {} 张", status.cached_coupons);
+            println!("最近领取: {}", status.last_claim.as_deref().unwrap_or("无"));
+            println!("定时领取: {}", status.scheduler);
+        }
+    });
+    Ok(true)
+}
+
+/// `daemon start [--foreground]|stop|status`: keep the web mode and the scheduler running
+/// in the background, restarting the web mode when it exits. Returns whether it succeeded;
+/// `status` fails when the daemon is not running.
+pub async fn daemon(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let log = daemon::log_path().display().to_string();
+    let foreground = args.iter().any(|a| a == "--foreground");
+    let (pid, message, web) = match args.first().map(String::as_str) {
+        Some("start") if foreground => {
+            if let Err(e) = daemon::supervise().await {
+                return Ok(fail(options, format!("{:#}", e)));
+            }
+            return Ok(true);
+        },
+        Some("start") => {
+            if let Some(pid) = daemon::running() {
+                return Ok(fail(options, format!("守护进程已在运行 (PID {})", pid)));
+            }
+            match daemon::start_detached().await {
+                Ok(pid) => (Some(pid), format!("守护进程已启动 (PID {})", pid), None),
+                Err(e) => return Ok(fail(options, format!("{:#}", e))),
+            }
+        },
+        Some("stop") => match daemon::stop().await {
+            Ok(pid) => (None, format!("守护进程已停止 (PID {})", pid), None),
+            Err(e) => return Ok(fail(options, format!("{:#}", e))),
+        },
+        Some("status") => {
+            let Some(pid) = daemon::running() else {
+                return Ok(fail(options, "守护进程未在运行".to_string()));
+            };
+            let web = control::send("web", "status").await.ok().and_then(|reply| reply.status);
+            let message = match &web {
+                Some(_) => format!("守护进程运行中 (PID {})", pid),
+                None => format!("守护进程运行中 (PID {})，网页模式暂未响应，可能正在重启", pid),
+            };
+            (Some(pid), message, web)
+        },
+        Some(other) => return Ok(fail(options, format!("未知的 daemon 操作: {} (可选: start, stop, status)", other))),
+        None => return Ok(fail(options, "用法: daemon start|stop|status [--foreground]".to_string())),
+    };
+
+    let output = DaemonOutput {
+        success: true,
+        message,
+        pid,
+        log,
+        web,
+    };
+    options.emit(&output, |o| {
+        println!("{}", o.message);
+        if let Some(web) = &o.web {
+            println!("网页模式: PID {}，Token {}", web.pid, if web.has_token { "已设置" } else { "未设置" });
+            println!("最近领取: {}", web.last_claim.as_deref().unwrap_or("无"));
+            println!("定时领取: {}", web.scheduler);
+        }
+        println!("日志: {}", o.log);
+    });
+    Ok(true)
+}
+
+/// `set-default <mode>`: start `mode` when no command is given, or show the mode menu
+/// again for `menu`. Returns whether it was saved.
+pub fn set_default(options: &GlobalOptions, args: &[String]) -> Result<bool> {
+    let mode = match args.first().map(String::as_str) {
+        Some("menu") => None,
+        Some(name) => match StartMode::from_name(name) {
+            Some(mode) => Some(mode),
+            None => return Ok(fail(options, format!("未知的模式: {} (可选: {})", name, START_MODES.join(", ")))),
+        },
+        None => return Ok(fail(options, format!("用法: set-default <{}>", START_MODES.join("|")))),
+    };
+    if let Err(e) = save_default_mode(mode) {
+        return Ok(fail(options, format!("保存配置失败: {:#}", e)));
+    }
+
+    let output = CommandOutput {
+        success: true,
+        message: match mode {
+            Some(mode) => format!("已将默认模式设为{}，不带命令运行时将直接启动（加 --menu 显示选择菜单）", mode.label()),
+            None => "已清除默认模式，不带命令运行时将显示模式选择菜单".to_string(),
+        },
+        details: None,
+    };
+    options.emit(&output, |o| println!("{}", o.message));
+    Ok(true)
+}
+
+/// Save `default_mode`, from `set-default` or the mode menu
+pub fn save_default_mode(mode: Option<StartMode>) -> Result<()> {
+    ConfigService::global().update(|config| config.default_mode = mode)?;
+    audit::record("cli", Action::ConfigChange, true, format!("默认模式: {}", mode.map_or("选择菜单", StartMode::label)));
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Result};
+
+use crate::cli::{Arg, Command, BIN_NAME, COMMANDS, GLOBAL_FLAGS, SHELLS};
+
+/// Generate the completion script for the given shell
+pub fn generate(shell: &str) -> Result<String> {
+    match shell {
+        "bash" => Ok(bash()),
+        "zsh" => Ok(zsh()),
+        "fish" => Ok(fish()),
+        "powershell" | "pwsh" => Ok(powershell()),
+        _ => Err(anyhow!("不支持的 shell: {} (可选: {})", shell, SHELLS.join(", "))),
+    }
+}
+
+/// Words that may follow a subcommand: flag names and fixed positional values
+fn argument_words(command: &Command) -> Vec<&'static str> {
+    let mut words = Vec::new();
+    for arg in command.args {
+        if arg.name.starts_with('<') {
+            words.extend_from_slice(arg.values);
+        } else {
+            words.push(arg.name);
+        }
+    }
+    words.extend(global_flag_names());
+    words
+}
+
+fn global_flag_names() -> impl Iterator<Item = &'static str> {
+    GLOBAL_FLAGS.iter().map(|f| f.name)
+}
+
+/// All flags, global and per-command, that consume a value
+fn value_flags() -> impl Iterator<Item = &'static Arg> {
+    GLOBAL_FLAGS
+        .iter()
+        .chain(COMMANDS.iter().flat_map(|c| c.args.iter()))
+        .filter(|a| a.takes_value())
+}
+
+fn function_name() -> String {
+    format!("_{}", BIN_NAME.replace('-', "_"))
+}
+
+fn bash() -> String {
+    let mut first_words: Vec<&str> = COMMANDS.iter().map(|c| c.name).collect();
+    first_words.extend(global_flag_names());
+    let mut script = String::new();
+    script.push_str(&format!("{}() {{\n", function_name()));
+    script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    local prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str("    case \"$prev\" in\n");
+    for flag in value_flags() {
+        let words = if flag.takes_file() {
+            "-f".to_string()
+        } else if flag.free_value() {
+            script.push_str(&format!("        {}) return 0 ;;\n", flag.name));
+            continue;
+        } else {
+            format!("-W \"{}\"", flag.values.join(" "))
+        };
+        script.push_str(&format!(
+            "        {}) COMPREPLY=( $(compgen {} -- \"$cur\") ); return 0 ;;\n",
+            flag.name, words
+        ));
+    }
+    script.push_str("    esac\n");
+    script.push_str("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+    script.push_str(&format!(
+        "        COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )\n",
+        first_words.join(" ")
+    ));
+    script.push_str("        return 0\n");
+    script.push_str("    fi\n");
+    script.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+    for command in COMMANDS.iter().filter(|c| !c.args.is_empty()) {
+        script.push_str(&format!(
+            "        {}) COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") ) ;;\n",
+            command.name,
+            argument_words(command).join(" ")
+        ));
+    }
+    script.push_str(&format!(
+        "        *) COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") ) ;;\n",
+        global_flag_names().collect::<Vec<_>>().join(" ")
+    ));
+    script.push_str("    esac\n");
+    script.push_str("}\n");
+    script.push_str(&format!("complete -F {} {}\n", function_name(), BIN_NAME));
+    script
+}
+
+fn zsh() -> String {
+    let mut script = String::new();
+    script.push_str(&format!("#compdef {}\n\n", BIN_NAME));
+    script.push_str(&format!("{}() {{\n", function_name()));
+    script.push_str("    local -a commands args\n");
+    script.push_str("    commands=(\n");
+    for command in COMMANDS {
+        script.push_str(&format!("        '{}:{}'\n", command.name, command.about));
+    }
+    script.push_str("    )\n");
+    script.push_str("    if (( CURRENT == 2 )); then\n");
+    script.push_str("        _describe 'command' commands\n");
+    script.push_str("        return\n");
+    script.push_str("    fi\n");
+    script.push_str("    case $words[CURRENT-1] in\n");
+    for flag in value_flags() {
+        if flag.takes_file() {
+            script.push_str(&format!("        {}) _files; return ;;\n", flag.name));
+            continue;
+        }
+        if flag.free_value() {
+            script.push_str(&format!("        {}) _message '{}'; return ;;\n", flag.name, flag.about));
+            continue;
+        }
+        script.push_str(&format!(
+            "        {}) _values '{}' {}; return ;;\n",
+            flag.name,
+            flag.about,
+            flag.values.join(" ")
+        ));
+    }
+    script.push_str("    esac\n");
+    script.push_str("    args=(\n");
+    for flag in GLOBAL_FLAGS {
+        script.push_str(&format!("        '{}:{}'\n", flag.name, flag.about));
+    }
+    script.push_str("    )\n");
+    script.push_str("    case $words[2] in\n");
+    for command in COMMANDS.iter().filter(|c| !c.args.is_empty()) {
+        script.push_str(&format!("        {})\n", command.name));
+        script.push_str("            args+=(\n");
+        for arg in command.args {
+            if arg.name.starts_with('<') {
+                for value in arg.values {
+                    script.push_str(&format!("                '{}:{}'\n", value, arg.about));
+                }
+            } else {
+                script.push_str(&format!("                '{}:{}'\n", arg.name, arg.about));
+            }
+        }
+        script.push_str("            )\n");
+        script.push_str("            ;;\n");
+    }
+    script.push_str("    esac\n");
+    script.push_str("    _describe 'argument' args\n");
+    script.push_str("}\n\n");
+    script.push_str(&format!("{} \"$@\"\n", function_name()));
+    script
+}
+
+fn fish() -> String {
+    let mut script = String::new();
+    script.push_str(&format!("complete -c {} -f\n", BIN_NAME));
+    for flag in GLOBAL_FLAGS {
+        let long = flag.name.trim_start_matches("--");
+        if flag.takes_file() {
+            script.push_str(&format!("complete -c {} -l {} -rF -d '{}'\n", BIN_NAME, long, flag.about));
+        } else if flag.takes_value() {
+            script.push_str(&format!(
+                "complete -c {} -l {} -xa '{}' -d '{}'\n",
+                BIN_NAME, long, flag.values.join(" "), flag.about
+            ));
+        } else {
+            script.push_str(&format!("complete -c {} -l {} -d '{}'\n", BIN_NAME, long, flag.about));
+        }
+    }
+    for command in COMMANDS {
+        script.push_str(&format!(
+            "complete -c {} -n '__fish_use_subcommand' -a {} -d '{}'\n",
+            BIN_NAME, command.name, command.about
+        ));
+    }
+    for command in COMMANDS {
+        for arg in command.args {
+            let condition = format!("__fish_seen_subcommand_from {}", command.name);
+            if arg.name.starts_with('<') && arg.values.is_empty() {
+                script.push_str(&format!(
+                    "complete -c {} -n '{}' -F -d '{}'\n",
+                    BIN_NAME, condition, arg.about
+                ));
+            } else if arg.name.starts_with('<') {
+                script.push_str(&format!(
+                    "complete -c {} -n '{}' -a '{}' -d '{}'\n",
+                    BIN_NAME, condition, arg.values.join(" "), arg.about
+                ));
+            } else if let Some(long) = arg.name.strip_prefix("--") {
+                let values = if arg.takes_file() {
+                    " -rF".to_string()
+                } else if arg.free_value() {
+                    " -x".to_string()
+                } else if arg.takes_value() {
+                    format!(" -xa '{}'", arg.values.join(" "))
+                } else {
+                    String::new()
+                };
+                script.push_str(&format!(
+                    "complete -c {} -n '{}' -l {}{} -d '{}'\n",
+                    BIN_NAME, condition, long, values, arg.about
+                ));
+            }
+        }
+    }
+    script
+}
+
+fn powershell() -> String {
+    let mut script = String::new();
+    script.push_str(&format!(
+        "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{\n",
+        BIN_NAME
+    ));
+    script.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    script.push_str("    $elements = @($commandAst.CommandElements | ForEach-Object { $_.ToString() })\n");
+    script.push_str("    $commands = @{\n");
+    for command in COMMANDS {
+        let words: Vec<String> = argument_words(command)
+            .iter()
+            .map(|w| format!("'{}'", w))
+            .collect();
+        script.push_str(&format!("        '{}' = @({})\n", command.name, words.join(", ")));
+    }
+    script.push_str("    }\n");
+    let globals: Vec<String> = global_flag_names().map(|w| format!("'{}'", w)).collect();
+    script.push_str(&format!("    $globals = @({})\n", globals.join(", ")));
+    script.push_str("    if ($elements.Count -le 1 -or ($elements.Count -eq 2 -and $wordToComplete)) {\n");
+    script.push_str("        $candidates = @($commands.Keys) + $globals\n");
+    script.push_str("    } else {\n");
+    script.push_str("        $candidates = $commands[$elements[1]]\n");
+    script.push_str("    }\n");
+    script.push_str("    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n");
+    script.push_str("        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n");
+    script.push_str("    }\n");
+    script.push_str("}\n");
+    script
+}
@@ -0,0 +1,60 @@
+use crate::cli::{BIN_NAME, COMMANDS, GLOBAL_FLAGS};
+
+/// Render the man page in roff format
+pub fn generate() -> String {
+    let mut page = String::new();
+    page.push_str(&format!(
+        ".TH {} 1 \"\" \"{} {}\" \"User Commands\"\n",
+        BIN_NAME.to_uppercase(),
+        BIN_NAME,
+        env!("CARGO_PKG_VERSION")
+    ));
+    page.push_str(".SH NAME\n");
+    page.push_str(&format!("{} \\- 麦当劳优惠券自动领取工具\n", escape(BIN_NAME)));
+    page.push_str(".SH SYNOPSIS\n");
+    page.push_str(&format!("\\fB{}\\fR [\\fICOMMAND\\fR] [\\fIARGS\\fR]\n", escape(BIN_NAME)));
+    page.push_str(".SH DESCRIPTION\n");
+    page.push_str("支持网页界面、终端界面和MCP服务器三种模式的麦当劳优惠券自动领取工具。\n");
+    page.push_str("不带参数运行时显示交互式模式选择菜单。\n");
+    page.push_str(".SH COMMANDS\n");
+    for command in COMMANDS {
+        page.push_str(".TP\n");
+        page.push_str(&format!("\\fB{}\\fR\n", escape(&command.usage())));
+        page.push_str(&format!("{}\n", escape(command.about)));
+        if !command.aliases.is_empty() {
+            page.push_str(".br\n");
+            page.push_str(&format!("别名: {}\n", escape(&command.aliases.join(", "))));
+        }
+        for arg in command.args {
+            page.push_str(".RS\n.TP\n");
+            page.push_str(&format!("\\fI{}\\fR\n", escape(arg.name)));
+            if arg.values.is_empty() {
+                page.push_str(&format!("{}\n", escape(arg.about)));
+            } else {
+                page.push_str(&format!("{} ({})\n", escape(arg.about), escape(&arg.values.join(", "))));
+            }
+            page.push_str(".RE\n");
+        }
+    }
+    page.push_str(".SH OPTIONS\n");
+    for flag in GLOBAL_FLAGS {
+        page.push_str(".TP\n");
+        page.push_str(&format!("\\fB{}\\fR\n", escape(&flag.usage())));
+        page.push_str(&format!("{}\n", escape(flag.about)));
+    }
+    page.push_str(".SH FILES\n");
+    page.push_str(".TP\n");
+    page.push_str("\\fI~/.config/mcd\\-coupon\\-tui\\-rust/config.json\\fR\n");
+    page.push_str("配置文件，保存 Token 等设置\n");
+    page.push_str(".TP\n");
+    page.push_str("\\fI./mcd\\-coupon\\-config.json\\fR\n");
+    page.push_str("当前目录下的配置文件，存在时优先读取\n");
+    page.push_str(".SH SEE ALSO\n");
+    page.push_str("https://github.com/shijianzhong/mcd-coupon\n");
+    page
+}
+
+/// Escape characters that have special meaning in roff
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('-', "\\-")
+}
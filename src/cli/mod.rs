@@ -0,0 +1,581 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::{audit::Action, mcp::chaos::ChaosConfig};
+
+pub mod commands;
+pub mod completions;
+pub mod manpage;
+
+/// Name of the released binary, used in completion scripts and the man page
+pub const BIN_NAME: &str = "mcd-coupon";
+
+/// A subcommand accepted on the command line
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub about: &'static str,
+    pub args: &'static [Arg],
+}
+
+/// An argument of a subcommand: either a `--flag` or a `<value>` placeholder.
+/// A `--flag` with `values` takes one of those values as its parameter;
+/// a single `<placeholder>` value stands for a free-form one such as a file path.
+pub struct Arg {
+    pub name: &'static str,
+    pub about: &'static str,
+    /// Fixed set of accepted values, offered by shell completion
+    pub values: &'static [&'static str],
+}
+
+/// A `--flag` with more values than this shows a placeholder in the usage line,
+/// and its values are listed under the command description instead
+const MAX_INLINE_VALUES: usize = 5;
+
+/// Values per line when they are listed under the command description
+const VALUES_PER_LINE: usize = 6;
+
+/// Shells supported by the `completions` subcommand
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+/// Modes accepted by `set-default`; `menu` clears the saved mode
+pub const START_MODES: &[&str] = &["tui", "plain", "html", "mcpserver", "menu"];
+
+/// Commands accepted by `ctl`, as sent over the control socket
+pub const CONTROL_COMMANDS: &[&str] = &["claim", "status", "reload-config", "stop"];
+
+/// Running modes `ctl --target` can reach
+pub const CONTROL_TARGETS: &[&str] = &["web", "mcp"];
+
+/// Actions accepted by `debug`
+pub const DEBUG_ACTIONS: &[&str] = &["timings"];
+
+/// Flags accepted anywhere on the command line, before or after the subcommand
+pub const GLOBAL_FLAGS: &[Arg] = &[
+    Arg {
+        name: "--output",
+        about: "输出格式，json 便于脚本处理",
+        values: &["text", "json"],
+    },
+    Arg {
+        name: "--json",
+        about: "等同于 --output json",
+        values: &[],
+    },
+    Arg {
+        name: "--quiet",
+        about: "仅输出错误 (-q)",
+        values: &[],
+    },
+    Arg {
+        name: "--verbose",
+        about: "输出调试日志，-vv 输出请求详情 (-v)",
+        values: &[],
+    },
+    Arg {
+        name: "--color",
+        about: "何时使用颜色，auto 时遵循 NO_COLOR 环境变量",
+        values: &["auto", "always", "never"],
+    },
+    Arg {
+        name: "--menu",
+        about: "不带命令运行时显示模式选择菜单，忽略保存的默认模式",
+        values: &[],
+    },
+    Arg {
+        name: "--profile",
+        about: "叠加配置文件 profiles 中的一组设置，如在 NAS 上使用 server",
+        values: &["<name>"],
+    },
+    Arg {
+        name: "--record",
+        about: "将与麦当劳服务的请求和响应录制到文件 (不含 Token)",
+        values: &["<file>"],
+    },
+    Arg {
+        name: "--replay",
+        about: "从录制文件回放响应，无需 Token 和网络",
+        values: &["<file>"],
+    },
+    Arg {
+        name: "--chaos",
+        about: "开发用：向上游请求注入延迟、失败、限流和畸形响应，如 latency=200-1500,fail=0.1",
+        values: &["<spec>"],
+    },
+];
+
+/// All subcommands, in the order they are shown in help output
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "tui",
+        aliases: &["1"],
+        about: "终端界面模式",
+        args: &[Arg {
+            name: "--plain",
+            about: "纯文本逐行交互，不使用全屏界面和颜色，适合读屏软件和简易终端",
+            values: &[],
+        }],
+    },
+    Command {
+        name: "html",
+        aliases: &["web", "2"],
+        about: "网页界面模式",
+        args: &[Arg {
+            name: "--no-browser",
+            about: "不自动打开浏览器",
+            values: &[],
+        }],
+    },
+    Command {
+        name: "mcpserver",
+        aliases: &["mcp-server", "3"],
+        about: "MCP服务器模式",
+        args: &[],
+    },
+    Command {
+        name: "claim",
+        aliases: &[],
+        about: "一键领取所有优惠券后退出",
+        args: &[],
+    },
+    Command {
+        name: "plan",
+        aliases: &[],
+        about: "预览一键领取会新增、重复或冲突的优惠券",
+        args: &[],
+    },
+    Command {
+        name: "list",
+        aliases: &["ls"],
+        about: "以表格列出已领取或可领取的优惠券",
+        args: &[
+            Arg {
+                name: "--available",
+                about: "列出当前可领取的优惠券",
+                values: &[],
+            },
+            Arg {
+                name: "--mine",
+                about: "列出已领取的优惠券（默认）",
+                values: &[],
+            },
+        ],
+    },
+    Command {
+        name: "watch",
+        aliases: &[],
+        about: "持续监控可领取的优惠券，出现新券时提醒，并按规则自动领取",
+        args: &[],
+    },
+    Command {
+        name: "rules",
+        aliases: &[],
+        about: "试运行自动领取规则，显示每条规则匹配的当前优惠券",
+        args: &[],
+    },
+    Command {
+        name: "validate",
+        aliases: &[],
+        about: "检查已保存的 Token 是否有效",
+        args: &[],
+    },
+    Command {
+        name: "stats",
+        aliases: &[],
+        about: "统计领取次数、成功率、常领类别和预估节省",
+        args: &[Arg {
+            name: "--month",
+            about: "只统计该月，如 2024-06，默认统计全部记录",
+            values: &["<YYYY-MM>"],
+        }],
+    },
+    Command {
+        name: "audit",
+        aliases: &[],
+        about: "查看审计日志：领取、Token、配置等修改操作的执行者与结果",
+        args: &[
+            Arg {
+                name: "--actor",
+                about: "只显示该执行者的记录，如 tui、web、mcp、scheduler",
+                values: &["<actor>"],
+            },
+            Arg {
+                name: "--action",
+                about: "只显示该类操作",
+                values: &Action::NAMES,
+            },
+            Arg {
+                name: "--limit",
+                about: "最多显示的条数，默认 50",
+                values: &["<n>"],
+            },
+        ],
+    },
+    Command {
+        name: "prune",
+        aliases: &[],
+        about: "按保留策略清理过期的领取记录、审计日志、图片缓存和访问日志",
+        args: &[Arg {
+            name: "--dry-run",
+            about: "只列出将被清理的内容，不实际删除",
+            values: &[],
+        }],
+    },
+    Command {
+        name: "sync",
+        aliases: &[],
+        about: "导出或导入加密的配置与本地数据同步包，用于迁移到另一台设备",
+        args: &[
+            Arg {
+                name: "<action>",
+                about: "export 导出 / import 导入",
+                values: &["export", "import"],
+            },
+            Arg {
+                name: "<file>",
+                about: "同步包文件路径",
+                values: &[],
+            },
+            Arg {
+                name: "--no-token",
+                about: "导出时不包含 Token，导入时保留本机已保存的 Token",
+                values: &[],
+            },
+        ],
+    },
+    Command {
+        name: "backup",
+        aliases: &[],
+        about: "创建或恢复完整备份文件，或上传加密备份到配置的 WebDAV 目录、从中恢复",
+        args: &[
+            Arg {
+                name: "<action>",
+                about: "create 创建备份文件 / restore 恢复 / upload 上传到 WebDAV",
+                values: &["create", "restore", "upload"],
+            },
+            Arg {
+                name: "<file>",
+                about: "备份文件路径；restore 不指定时从 WebDAV 恢复",
+                values: &[],
+            },
+        ],
+    },
+    Command {
+        name: "daemon",
+        aliases: &[],
+        about: "在后台持续运行网页模式和定时领取，退出后自动重启",
+        args: &[
+            Arg {
+                name: "<action>",
+                about: "start 启动 / stop 停止 / status 查看状态",
+                values: &["start", "stop", "status"],
+            },
+            Arg {
+                name: "--foreground",
+                about: "start 时不转入后台，在当前终端运行",
+                values: &[],
+            },
+        ],
+    },
+    Command {
+        name: "ctl",
+        aliases: &[],
+        about: "通过本地控制接口向运行中的网页或 MCP 服务器实例发送命令",
+        args: &[
+            Arg {
+                name: "<command>",
+                about: "claim 领取 / status 状态 / reload-config 重新加载配置 / stop 停止",
+                values: CONTROL_COMMANDS,
+            },
+            Arg {
+                name: "--target",
+                about: "目标实例，默认 web",
+                values: CONTROL_TARGETS,
+            },
+        ],
+    },
+    Command {
+        name: "debug",
+        aliases: &[],
+        about: "诊断工具：打印启动各阶段耗时、内存占用和程序大小",
+        args: &[Arg {
+            name: "<action>",
+            about: "timings 启动阶段耗时与内存统计",
+            values: DEBUG_ACTIONS,
+        }],
+    },
+    Command {
+        name: "set-default",
+        aliases: &[],
+        about: "设置不带命令运行时直接启动的模式，menu 恢复为显示模式选择菜单",
+        args: &[Arg {
+            name: "<mode>",
+            about: "tui / plain 纯文本 / html / mcpserver / menu",
+            values: START_MODES,
+        }],
+    },
+    Command {
+        name: "completions",
+        aliases: &[],
+        about: "输出 shell 自动补全脚本",
+        args: &[Arg {
+            name: "<shell>",
+            about: "目标 shell",
+            values: SHELLS,
+        }],
+    },
+    Command {
+        name: "manpage",
+        aliases: &[],
+        about: "输出 man 手册页 (roff 格式)",
+        args: &[],
+    },
+    Command {
+        name: "help",
+        aliases: &["-h", "--help"],
+        about: "显示帮助信息",
+        args: &[],
+    },
+];
+
+impl Command {
+    /// Usage line shown in help and the man page, e.g. `completions <shell>`
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+        for arg in self.args {
+            usage.push(' ');
+            usage.push_str(&arg.usage());
+        }
+        usage
+    }
+}
+
+impl Arg {
+    /// Usage fragment, e.g. `<shell>`, `[--json]` or `[--output <text|json>]`
+    pub fn usage(&self) -> String {
+        if self.name.starts_with('<') {
+            self.name.to_string()
+        } else if self.values.is_empty() {
+            format!("[{}]", self.name)
+        } else if self.free_value() {
+            format!("[{} {}]", self.name, self.values[0])
+        } else if self.lists_values() {
+            format!("[{} <{}>]", self.name, self.name.trim_start_matches('-'))
+        } else {
+            format!("[{} <{}>]", self.name, self.values.join("|"))
+        }
+    }
+
+    /// Whether this is a `--flag` that consumes the following value
+    pub fn takes_value(&self) -> bool {
+        self.name.starts_with("--") && !self.values.is_empty()
+    }
+
+    /// Whether the values are too many for the usage line and are listed separately
+    pub fn lists_values(&self) -> bool {
+        self.name.starts_with("--") && self.values.len() > MAX_INLINE_VALUES
+    }
+
+    /// Whether the value is free-form, e.g. a file path, rather than one of `values`
+    pub fn free_value(&self) -> bool {
+        matches!(self.values, [value] if value.starts_with('<'))
+    }
+
+    /// Whether the free-form value is a file path, so shells complete file names
+    pub fn takes_file(&self) -> bool {
+        self.values == ["<file>"]
+    }
+}
+
+/// Output format selected with `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// When to use color, selected with `--color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color terminals, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output going to a stream that is or is not a terminal
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+/// Log verbosity selected with `-q` / `-v` / `-vv`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Errors only
+    Quiet,
+    #[default]
+    Normal,
+    /// Debug logs
+    Verbose,
+    /// Debug logs plus upstream request/response details
+    Trace,
+}
+
+impl Verbosity {
+    fn increase(self) -> Self {
+        match self {
+            Verbosity::Quiet | Verbosity::Normal => Verbosity::Verbose,
+            Verbosity::Verbose | Verbosity::Trace => Verbosity::Trace,
+        }
+    }
+}
+
+/// Options parsed from [`GLOBAL_FLAGS`]
+#[derive(Debug, Clone, Default)]
+pub struct GlobalOptions {
+    pub output: OutputFormat,
+    pub verbosity: Verbosity,
+    pub color: ColorChoice,
+    /// Show the mode menu even when a default mode is saved
+    pub menu: bool,
+    /// Config profile laid over the settings
+    pub profile: Option<String>,
+    /// File to record upstream exchanges to
+    pub record: Option<PathBuf>,
+    /// File to replay upstream exchanges from
+    pub replay: Option<PathBuf>,
+    /// Misbehaviour to inject into upstream calls
+    pub chaos: Option<ChaosConfig>,
+}
+
+impl GlobalOptions {
+    /// Print `value` as JSON in JSON mode, otherwise print it with `text`.
+    /// Text output is suppressed in quiet mode; JSON is always printed.
+    pub fn emit<T: Serialize>(&self, value: &T, text: impl FnOnce(&T)) {
+        match self.output {
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+            }
+            OutputFormat::Text if self.verbosity == Verbosity::Quiet => {}
+            OutputFormat::Text => text(value),
+        }
+    }
+}
+
+/// Split global flags out of the raw arguments (without the program name),
+/// returning them together with the remaining positional arguments
+pub fn parse_global_options(args: &[String]) -> Result<(GlobalOptions, Vec<String>)> {
+    let mut options = GlobalOptions::default();
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        match flag {
+            "--output" | "-o" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => iter.next().cloned().ok_or_else(|| anyhow!("--output 需要一个参数: text | json"))?,
+                };
+                options.output = match value.to_lowercase().as_str() {
+                    "text" => OutputFormat::Text,
+                    "json" => OutputFormat::Json,
+                    _ => return Err(anyhow!("未知的输出格式: {} (可选: text, json)", value)),
+                };
+            }
+            "--color" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => iter.next().cloned().ok_or_else(|| anyhow!("--color 需要一个参数: auto | always | never"))?,
+                };
+                options.color = match value.to_lowercase().as_str() {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    _ => return Err(anyhow!("未知的颜色选项: {} (可选: auto, always, never)", value)),
+                };
+            }
+            "--record" | "--replay" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => iter.next().cloned().ok_or_else(|| anyhow!("{} 需要一个文件路径", flag))?,
+                };
+                if flag == "--record" {
+                    options.record = Some(PathBuf::from(value));
+                } else {
+                    options.replay = Some(PathBuf::from(value));
+                }
+            }
+            "--profile" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => iter.next().cloned().ok_or_else(|| anyhow!("--profile 需要一个 profile 名称"))?,
+                };
+                options.profile = Some(value);
+            }
+            "--chaos" => {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => iter.next().cloned().ok_or_else(|| anyhow!("--chaos 需要一个参数，如 latency=200-1500,fail=0.1,limit=0.05,malformed=0.1"))?,
+                };
+                options.chaos = Some(value.parse().map_err(|e: String| anyhow!("--chaos 参数无效: {}", e))?);
+            }
+            "--json" => options.output = OutputFormat::Json,
+            "--menu" => options.menu = true,
+            "--quiet" | "-q" => options.verbosity = Verbosity::Quiet,
+            "--verbose" | "-v" => options.verbosity = options.verbosity.increase(),
+            "-vv" => options.verbosity = Verbosity::Trace,
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    if options.record.is_some() && options.replay.is_some() {
+        return Err(anyhow!("--record 和 --replay 不能同时使用"));
+    }
+    Ok((options, rest))
+}
+
+/// Message for a command or mode left out of this build by its Cargo feature
+pub fn not_compiled(what: &str, feature: &str) -> String {
+    format!("此版本未编译{}，请使用 cargo build --features {} 重新编译", what, feature)
+}
+
+/// Print the help text generated from the command table
+pub fn print_help() {
+    println!();
+    println!("麦当劳优惠券自动领取工具");
+    println!();
+    println!("用法:");
+    let width = COMMANDS.iter().map(|c| c.usage().len()).max().unwrap_or(0) + BIN_NAME.len() + 1;
+    println!("  {:<width$}  交互式选择模式，或启动 set-default 保存的模式", BIN_NAME, width = width);
+    for command in COMMANDS {
+        let usage = format!("{} {}", BIN_NAME, command.usage());
+        println!("  {:<width$}  {}", usage, command.about, width = width);
+        for arg in command.args.iter().filter(|a| a.lists_values()) {
+            println!("  {:<width$}  {} 可选:", "", arg.name, width = width);
+            for values in arg.values.chunks(VALUES_PER_LINE) {
+                println!("  {:<width$}    {}", "", values.join(", "), width = width);
+            }
+        }
+    }
+    println!();
+    println!("全局选项:");
+    let width = GLOBAL_FLAGS.iter().map(|f| f.usage().len()).max().unwrap_or(0);
+    for flag in GLOBAL_FLAGS {
+        println!("  {:<width$}  {}", flag.usage(), flag.about, width = width);
+    }
+    println!();
+}
@@ -1,23 +1,586 @@
 use serde::{Deserialize, Serialize};
-use std::fs;
-use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use crate::{coupons::ClaimRules, error::{McdError, Result}, mcp::session};
+#[cfg(any(feature = "tui", feature = "web"))]
+use crate::utils::format_current_time;
+
+mod service;
+
+pub use service::ConfigService;
+
+/// Number of recently validated tokens kept in `token_history`
+#[cfg(any(feature = "tui", feature = "web"))]
+pub const TOKEN_HISTORY_LIMIT: usize = 5;
+/// Name of the profile applied on load, set by `--profile` so processes started by `daemon` inherit it
+pub const PROFILE_ENV: &str = "MCD_COUPON_PROFILE";
+/// Directory holding `config.json`, instead of the platform's config directory
+pub const CONFIG_DIR_ENV: &str = "MCD_COUPON_CONFIG_DIR";
+/// Directory of the local data files, instead of the platform's data directory
+pub const DATA_DIR_ENV: &str = "MCD_COUPON_DATA_DIR";
+/// Directory of the image cache, instead of the platform's cache directory
+pub const CACHE_DIR_ENV: &str = "MCD_COUPON_CACHE_DIR";
+/// Name of the tool's folder in the platform directories
+const APP_DIR: &str = "mcd-coupon-tui-rust";
 
 /// Application configuration
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct Config {
+    /// Token of the active account
     pub token: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mcp_server_port: Option<u16>,
+    /// First port the web mode tries (default 8080); the following ones are tried when it is busy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_port: Option<u16>,
+    /// Port of the gRPC server the web mode also runs, only in builds with `--features grpc`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_port: Option<u16>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mcp_server_url: Option<String>,
+    /// Saved accounts for multi-account setups
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<Account>,
+    /// Name of the account whose token is currently in `token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_account: Option<String>,
+    /// Recently validated tokens, newest first, for quick re-selection
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_history: Vec<TokenHistoryEntry>,
+    /// Upper bound on upstream response bodies, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_response_bytes: Option<usize>,
+    /// `User-Agent` sent upstream, instead of the tool's name and version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every upstream request, e.g. {"Accept-Language": "zh-CN"}
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub upstream_headers: BTreeMap<String, String>,
+    /// Show coupons upstream lists more than once as separate entries, for debugging
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keep_duplicate_coupons: bool,
+    /// Extra upstream field labels and the field each fills (price, expiry, receive_time, tags
+    /// or code), e.g. {"使用期限": "expiry"} after upstream renames 有效期
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub field_labels: BTreeMap<String, String>,
+    /// Time zone of release times, expiry countdowns and timestamps: an IANA name, or "local"
+    /// for the system's. Defaults to Asia/Shanghai, where coupons are released.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Estimate the calories of coupons from the items named in their titles
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub show_calories: bool,
+    /// TUI key bindings
+    #[serde(default, skip_serializing_if = "KeyBindings::is_default")]
+    pub keybindings: KeyBindings,
+    /// TUI color palette; `--color never` and `NO_COLOR` turn color off regardless
+    #[serde(default, skip_serializing_if = "ThemePreset::is_default")]
+    pub theme: ThemePreset,
+    /// Mode started when no command is given, instead of showing the mode menu
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_mode: Option<StartMode>,
+    /// Minimum minutes between auto-bind-coupons calls from one MCP session (default 10, 0 disables)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_claim_interval_minutes: Option<u64>,
+    /// Skip asking MCP clients to confirm auto-bind-coupons with the user, for trusted setups
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mcp_auto_approve_claims: bool,
+    /// Addresses or CIDR networks allowed to reach the web and MCP servers; empty allows all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_ips: Vec<String>,
+    /// Addresses or CIDR networks of the account owner, who may approve or reject family claim
+    /// requests in the web mode; this machine always may, and nobody else when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub request_owner_ips: Vec<String>,
+    /// Address the web mode listens on (default 127.0.0.1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_bind: Option<String>,
+    /// Address the MCP server listens on (default 127.0.0.1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_server_bind: Option<String>,
+    /// Let the servers listen on a non-loopback address with an empty `allowed_ips`,
+    /// where anyone on the network can claim with the saved token
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_public_bind: bool,
+    /// Write one line per web / MCP server request to `access.log` in the data directory
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub access_log: bool,
+    /// Keyword rules for automatic claims
+    #[serde(default, skip_serializing_if = "ClaimRules::is_empty")]
+    pub rules: ClaimRules,
+    /// Settings of the `watch` command
+    #[serde(default, skip_serializing_if = "WatchConfig::is_default")]
+    pub watch: WatchConfig,
+    /// Soft monthly limit on claimed coupons or their value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget: Option<BudgetConfig>,
+    /// Daily claims at coupon release times, in html and mcpserver modes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleConfig>,
+    /// WebDAV backup target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<BackupConfig>,
+    /// How long local history, logs and caches are kept
+    #[serde(default, skip_serializing_if = "RetentionConfig::is_default")]
+    pub retention: RetentionConfig,
+    /// Share card images
+    #[serde(default, skip_serializing_if = "ShareConfig::is_default")]
+    pub share: ShareConfig,
+    /// Links opening a coupon in the McDonald's app, on web cards and in notifications
+    #[serde(default, skip_serializing_if = "DeepLinkConfig::is_default")]
+    pub deep_links: DeepLinkConfig,
+    /// MQTT broker for smart-home integration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+    /// Named sets of settings laid over the rest with `--profile`, e.g. "server" for a NAS deployment
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Map<String, Value>>,
+    /// What the active profile changed, so saving writes the file's own values back
+    #[serde(skip)]
+    overrides: Option<ProfileOverrides>,
+}
+
+/// Top-level settings a profile replaced on load
+#[derive(Debug, Clone, Default)]
+struct ProfileOverrides {
+    profile: String,
+    /// Key, its value in the file if any, and its value with the profile applied
+    keys: Vec<(String, Option<Value>, Value)>,
+}
+
+impl ProfileOverrides {
+    /// Lay profile `name` over the top-level settings in `root`. Objects such as `mqtt` are
+    /// merged key by key, so a profile only needs the fields it changes.
+    fn apply(root: &mut Map<String, Value>, name: &str) -> Option<Self> {
+        let Some(profile) = root.get("profiles").and_then(|p| p.get(name)).and_then(Value::as_object).cloned() else {
+            tracing::warn!("配置中没有名为 {} 的 profile，已忽略", name);
+            return None;
+        };
+        let mut keys = Vec::new();
+        for (key, overlay) in profile.into_iter().filter(|(key, _)| key != "profiles") {
+            let original = root.get(&key).cloned();
+            let value = root.entry(key.clone()).or_insert(Value::Null);
+            merge(value, overlay);
+            keys.push((key, original, value.clone()));
+        }
+        Some(Self { profile: name.to_string(), keys })
+    }
+
+    /// Put the file's own values back into `root` before saving. A setting changed while the
+    /// profile was active is kept in the profile, where it came from.
+    fn restore(&self, root: &mut Map<String, Value>) {
+        for (key, original, applied) in &self.keys {
+            let current = root.remove(key);
+            if let Some(current) = current.filter(|c| c != applied) {
+                if let Some(profile) = root.get_mut("profiles").and_then(|p| p.get_mut(&self.profile)).and_then(Value::as_object_mut) {
+                    profile.insert(key.clone(), current);
+                }
+            }
+            if let Some(original) = original {
+                root.insert(key.clone(), original.clone());
+            }
+        }
+    }
+}
+
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Profile selected with `--profile`, if any
+fn active_profile() -> Option<String> {
+    std::env::var(PROFILE_ENV).ok().filter(|name| !name.trim().is_empty())
+}
+
+/// The tool's folder for one kind of file: the directory named by `env` when set,
+/// else one in the platform directory, else one under `fallback` in the home directory
+fn app_dir(env: &str, platform: fn() -> Option<PathBuf>, fallback: &str) -> PathBuf {
+    if let Some(dir) = std::env::var_os(env).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    platform()
+        .unwrap_or_else(|| dirs::home_dir().expect("无法获取用户主目录").join(fallback))
+        .join(APP_DIR)
+}
+
+/// WebDAV folder that backups are uploaded to, e.g. a 坚果云 folder
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BackupConfig {
+    /// Folder URL, e.g. "https://dav.jianguoyun.com/dav/mcd-coupon"
+    pub url: String,
+    #[serde(default)]
+    pub username: String,
+    /// Password, or the app password for 坚果云
+    #[serde(default)]
+    pub password: String,
+    /// Passphrase that encrypts the uploaded bundle
+    pub passphrase: String,
+    /// Upload interval in html and mcpserver modes. No scheduled uploads when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_hours: Option<u64>,
+}
+
+/// MQTT broker that claim results and coupon counts are published to, e.g. for Home Assistant
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// Broker host name or address, e.g. "192.168.1.10"
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    /// Topic of every claim result
+    pub claim_topic: String,
+    /// Retained topic with the held coupon counts
+    pub coupons_topic: String,
+    /// Topic announcing each held coupon once when it starts to expire soon
+    pub expiring_topic: String,
+    /// Topic of every family claim request submitted, approved or rejected in the web mode
+    pub request_topic: String,
+    /// Minutes between coupon count updates in html and mcpserver modes; 0 publishes claim results only
+    pub interval_minutes: u64,
+    /// Coupons expiring within this many days count as expiring soon
+    pub expiring_days: i64,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 1883,
+            username: String::new(),
+            password: String::new(),
+            claim_topic: "mcd-coupon/claim".to_string(),
+            coupons_topic: "mcd-coupon/coupons".to_string(),
+            expiring_topic: "mcd-coupon/expiring".to_string(),
+            request_topic: "mcd-coupon/requests".to_string(),
+            interval_minutes: 30,
+            expiring_days: 3,
+        }
+    }
+}
+
+/// Claim times aligned to daily coupon releases
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Release times in server time (UTC+8), "HH:MM" or "HH:MM:SS"
+    pub release_times: Vec<String>,
+    /// Seconds after a release before the first claim
+    pub delay_seconds: u64,
+    /// Minutes after a release during which empty or failed claims are retried
+    pub retry_minutes: u64,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            release_times: Vec::new(),
+            delay_seconds: 3,
+            retry_minutes: 3,
+        }
+    }
+}
+
+/// Retention of local data; 0 keeps that data forever
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Days claim history entries are kept
+    pub history_days: u64,
+    /// Days audit log entries are kept
+    pub audit_days: u64,
+    /// Days a cached coupon image is kept after download
+    pub image_cache_days: u64,
+    /// Largest total size of the image cache, in MiB
+    pub image_cache_mb: u64,
+    /// Days the rotated access log is kept
+    pub log_days: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            history_days: 365,
+            audit_days: 365,
+            image_cache_days: 30,
+            image_cache_mb: 100,
+            log_days: 30,
+        }
+    }
+}
+
+impl RetentionConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether every limit is off, so there is nothing to prune
+    #[cfg(feature = "server")]
+    pub fn is_disabled(&self) -> bool {
+        [self.history_days, self.audit_days, self.image_cache_days, self.image_cache_mb, self.log_days]
+            .iter()
+            .all(|v| *v == 0)
+    }
+}
+
+/// Rendering of coupon share cards
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ShareConfig {
+    /// TTF/OTF/TTC font with Chinese glyphs; common system fonts are tried when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    /// Link in the QR code of a card
+    pub link: String,
+}
+
+impl Default for ShareConfig {
+    fn default() -> Self {
+        Self {
+            font: None,
+            link: "https://www.mcdonalds.com.cn/".to_string(),
+        }
+    }
+}
+
+impl ShareConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Templates of the links that open a coupon in the McDonald's app. `{title}`, `{raw_title}`
+/// and `{tags}` are replaced URL-encoded; an empty template leaves that link out.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct DeepLinkConfig {
+    /// Link in the app's own scheme, only opened where the app is installed
+    pub app: String,
+    /// https link the app registers as a universal link, opening the browser without the app
+    pub universal: String,
+}
+
+impl Default for DeepLinkConfig {
+    fn default() -> Self {
+        Self {
+            app: "mcdapp://coupon?title={title}".to_string(),
+            universal: String::new(),
+        }
+    }
+}
+
+impl DeepLinkConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Monthly claim limits; claims warn or are skipped once the month reaches one
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Most coupons claimed in a calendar month
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_coupons: Option<usize>,
+    /// Most face value claimed in a calendar month, in yuan
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<f64>,
+    /// What a claim does once a limit is reached
+    pub action: BudgetAction,
+}
+
+/// Reaction of claims to a reached `BudgetConfig` limit
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    /// Claim anyway, with a reminder in the result
+    #[default]
+    Warn,
+    /// Do not claim until the next month
+    Skip,
+}
+
+/// Polling settings of the `watch` command
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Minutes between checks for new coupons
+    pub interval_minutes: u64,
+    /// Keywords in the title or tags that make a new coupon worth an alert
+    pub alert_keywords: Vec<String>,
+    /// How such coupons are announced
+    pub alert: AlertMode,
+}
+
+/// Mode saved with `set-default` or from the mode menu
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StartMode {
+    Tui,
+    /// `tui --plain`
+    Plain,
+    Html,
+    McpServer,
+}
+
+impl StartMode {
+    /// Mode for a command name or alias, as accepted on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "tui" => Some(StartMode::Tui),
+            "plain" => Some(StartMode::Plain),
+            "html" | "web" => Some(StartMode::Html),
+            "mcpserver" | "mcp-server" => Some(StartMode::McpServer),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StartMode::Tui => "终端模式",
+            StartMode::Plain => "纯文本模式",
+            StartMode::Html => "网页模式",
+            StartMode::McpServer => "MCP服务器模式",
+        }
+    }
+}
+
+/// Color palette of the TUI
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    #[default]
+    Default,
+    /// Blue / orange instead of green / red, for red-green color blindness
+    ColorBlind,
+    /// No colors, only bold, dim and reversed text
+    Monochrome,
+}
+
+impl ThemePreset {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemePreset::Default => "默认",
+            ThemePreset::ColorBlind => "色盲友好",
+            ThemePreset::Monochrome => "黑白",
+        }
+    }
+
+    /// Preset after this one, for cycling through them in the TUI
+    #[cfg(feature = "tui")]
+    pub fn next(self) -> Self {
+        match self {
+            ThemePreset::Default => ThemePreset::ColorBlind,
+            ThemePreset::ColorBlind => ThemePreset::Monochrome,
+            ThemePreset::Monochrome => ThemePreset::Default,
+        }
+    }
+}
+
+/// Announcement of new coupons matching `alert_keywords`
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertMode {
+    /// Repeated terminal bell
+    Bell,
+    /// Read the coupon titles aloud with the system speech tool, falling back to the bell
+    Speech,
+    Off,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 15,
+            alert_keywords: vec!["免费".to_string()],
+            alert: AlertMode::Bell,
+        }
+    }
+}
+
+impl WatchConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+}
+
+/// Remappable TUI keys, written like "c", "ctrl+q" or "f1"
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub claim: String,
+    pub list: String,
+    pub quit: String,
+    pub help: String,
+    pub refresh: String,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            claim: "a".to_string(),
+            list: "c".to_string(),
+            quit: "ctrl+q".to_string(),
+            help: "?".to_string(),
+            refresh: "r".to_string(),
+        }
+    }
+}
+
+impl KeyBindings {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A saved McDonald's account
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct Account {
+    pub name: String,
+    pub token: String,
+    /// Upstream MCP URL for this account, overriding the global `mcp_server_url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_server_url: Option<String>,
+}
+
+/// A previously validated token
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TokenHistoryEntry {
+    pub token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// When the token last passed validation
+    pub last_valid: String,
+}
+
+impl TokenHistoryEntry {
+    /// Nickname, or "未命名" when none was given
+    #[cfg(feature = "tui")]
+    pub fn label(&self) -> &str {
+        self.nickname.as_deref().unwrap_or("未命名")
+    }
 }
 
 impl Config {
     /// Load configuration from file
     pub fn load() -> Result<Self> {
         // Try to load from current directory first
-        let fallback_path = std::env::current_dir()?
-            .join("mcd-coupon-config.json");
+        let fallback_path = Self::local_config_path()?;
         
         if fallback_path.exists() {
             match Self::load_from_path(&fallback_path) {
@@ -44,13 +607,68 @@ impl Config {
         Ok(Self::default())
     }
     
+    /// Like `load`, but a config file that cannot be parsed is an error instead of
+    /// being skipped, so a broken file is never taken for an empty config
+    pub fn load_checked() -> Result<Self> {
+        match Self::check_files()? {
+            Some(path) => Self::load_from_path(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parse every config file `load` looks at, surfacing the errors it silently skips.
+    /// Returns the file that would be used, or None when there is none yet.
+    pub fn check_files() -> Result<Option<std::path::PathBuf>> {
+        let candidates = [
+            Self::local_config_path()?,
+            Self::get_config_path(),
+        ];
+        let mut used = None;
+        for path in candidates.into_iter().filter(|p| p.exists()) {
+            Self::load_from_path(&path)?;
+            used.get_or_insert(path);
+        }
+        Ok(used)
+    }
+
     /// Helper method to load config from a specific path
     fn load_from_path(path: &std::path::Path) -> Result<Self> {
         let config_str = fs::read_to_string(path)
-            .context(format!("无法读取文件: {}", path.display()))?;
+            .map_err(|e| McdError::config(format!("无法读取文件: {}", path.display()), e))?;
         
-        serde_json::from_str(&config_str)
-            .context(format!("无法解析文件: {}", path.display()))
+        let parse_error = |e| McdError::config(format!("无法解析文件: {}", path.display()), e);
+        let config: Self = serde_json::from_str(&config_str).map_err(parse_error)?;
+        let Some(name) = active_profile() else {
+            return Ok(config);
+        };
+
+        // Parsed once more with the profile laid over it
+        let mut value: Value = serde_json::from_str(&config_str).map_err(parse_error)?;
+        let Some(root) = value.as_object_mut() else {
+            return Ok(config);
+        };
+        let overrides = ProfileOverrides::apply(root, &name);
+        let mut config: Self = serde_json::from_value(value)
+            .map_err(|e| McdError::config(format!("profile {} 中的设置无效: {}", name, path.display()), e))?;
+        config.overrides = overrides;
+        Ok(config)
+    }
+
+    /// Check that the profile selected with `--profile` exists, returning its name
+    pub fn check_profile() -> Result<Option<String>> {
+        let Some(name) = active_profile() else {
+            return Ok(None);
+        };
+        let config = Self::load()?;
+        if !config.profiles.contains_key(&name) {
+            let known: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+            return Err(McdError::Parse(if known.is_empty() {
+                format!("配置文件中没有 profile: {} (未定义任何 profiles)", name)
+            } else {
+                format!("配置文件中没有 profile: {} (可选: {})", name, known.join(", "))
+            }));
+        }
+        Ok(Some(name))
     }
     
     /// Save configuration to file
@@ -66,8 +684,7 @@ impl Config {
         }
         
         // Fall back to current directory
-        let fallback_path = std::env::current_dir()?
-            .join("mcd-coupon-config.json");
+        let fallback_path = Self::local_config_path()?;
         
         match self.save_to_path(&fallback_path) {
             Ok(_) => {
@@ -75,7 +692,7 @@ impl Config {
                 Ok(())
             },
             Err(e) => {
-                Err(anyhow::anyhow!("无法保存配置文件到任何位置: {}", e))
+                Err(McdError::config(format!("无法保存配置文件到任何位置: {}", e), e))
             }
         }
     }
@@ -86,31 +703,168 @@ impl Config {
         if let Some(dir) = path.parent() {
             if !dir.exists() {
                 fs::create_dir_all(dir)
-                    .context(format!("无法创建目录: {}", dir.display()))?;
+                    .map_err(|e| McdError::config(format!("无法创建目录: {}", dir.display()), e))?;
             }
         }
         
-        let config_str = serde_json::to_string_pretty(self)
-            .context("无法序列化配置")?;
+        // Profile values stay in the profile, never in the settings they were laid over
+        let base;
+        let config = match &self.overrides {
+            Some(overrides) => {
+                let mut value = serde_json::to_value(self).map_err(|e| McdError::config("无法序列化配置", e))?;
+                if let Some(root) = value.as_object_mut() {
+                    overrides.restore(root);
+                }
+                base = serde_json::from_value::<Self>(value).map_err(|e| McdError::config("无法序列化配置", e))?;
+                &base
+            },
+            None => self,
+        };
+        let config_str = serde_json::to_string_pretty(config)
+            .map_err(|e| McdError::config("无法序列化配置", e))?;
         
         fs::write(path, config_str)
-            .context(format!("无法写入文件: {}", path.display()))
+            .map_err(|e| McdError::config(format!("无法写入文件: {}", path.display()), e))
     }
     
+    /// Config file in the current directory, which takes precedence over the one in the config directory
+    fn local_config_path() -> Result<std::path::PathBuf> {
+        std::env::current_dir()
+            .map(|dir| dir.join("mcd-coupon-config.json"))
+            .map_err(|e| McdError::config("无法获取当前目录", e))
+    }
+
     /// Get the path to the configuration file
     pub fn get_config_path() -> std::path::PathBuf {
-        // Try to get system config directory first
-        if let Some(config_dir) = dirs::config_dir() {
-            return config_dir.join("mcd-coupon-tui-rust").join("config.json");
-        }
-        
-        // Fall back to home directory
-        let home_dir = dirs::home_dir().expect("无法获取用户主目录");
-        home_dir.join(".config").join("mcd-coupon-tui-rust").join("config.json")
+        app_dir(CONFIG_DIR_ENV, dirs::config_dir, ".config").join("config.json")
     }
-    
-    /// Check if a valid token exists
+
+    /// Path of a local data file, such as the claim history or the audit log
+    pub fn data_path(file_name: &str) -> std::path::PathBuf {
+        Self::data_dir().join(file_name)
+    }
+
+    /// Directory of the local data files
+    pub fn data_dir() -> std::path::PathBuf {
+        app_dir(DATA_DIR_ENV, dirs::data_dir, ".local/share")
+    }
+
+    /// Path in the cache directory, for files that are downloaded again when removed
+    pub fn cache_path(name: &str) -> std::path::PathBuf {
+        Self::cache_dir().join(name)
+    }
+
+    /// Directory of the image cache
+    pub fn cache_dir() -> std::path::PathBuf {
+        app_dir(CACHE_DIR_ENV, dirs::cache_dir, ".cache")
+    }
+
+    /// Check if a valid token exists. Replaying a recording needs no token.
     pub fn has_valid_token(&self) -> bool {
-        !self.token.trim().is_empty()
+        !self.token.trim().is_empty() || session::is_replaying()
+    }
+
+    /// Upstream MCP URL of the active account, falling back to the global `mcp_server_url`
+    pub fn upstream_url(&self) -> Option<&str> {
+        let active = self.active_account.as_deref()
+            .and_then(|name| self.accounts.iter().find(|a| a.name == name));
+        match active {
+            Some(account) => self.account_upstream_url(account),
+            None => self.mcp_server_url.as_deref(),
+        }
+    }
+
+    /// Upstream MCP URL of `account`, falling back to the global `mcp_server_url`
+    pub fn account_upstream_url<'a>(&'a self, account: &'a Account) -> Option<&'a str> {
+        account.mcp_server_url.as_deref().or(self.mcp_server_url.as_deref())
+    }
+
+    /// Check that the global and per-account upstream URLs are http(s) URLs
+    pub fn check_upstream_urls(&self) -> Result<()> {
+        let urls = self.mcp_server_url.iter()
+            .chain(self.accounts.iter().filter_map(|a| a.mcp_server_url.as_ref()));
+        for url in urls {
+            match reqwest::Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {},
+                Ok(_) => return Err(McdError::Parse(format!("mcp_server_url 只支持 http 或 https 地址: {}", url))),
+                Err(e) => return Err(McdError::config(format!("mcp_server_url 配置无效: {}", url), e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the active token, keeping the active account entry in sync
+    #[cfg(any(feature = "tui", feature = "web"))]
+    pub fn set_token(&mut self, token: String) {
+        if let Some(name) = self.active_account.clone() {
+            if let Some(account) = self.accounts.iter_mut().find(|a| a.name == name) {
+                account.token = token.clone();
+            }
+        }
+        self.token = token;
+    }
+
+    /// Move a token that just passed validation to the front of the history.
+    /// Without a nickname, the existing one or the active account name is kept.
+    #[cfg(any(feature = "tui", feature = "web"))]
+    pub fn remember_token(&mut self, token: &str, nickname: Option<String>) {
+        let previous = self.token_history.iter()
+            .position(|e| e.token == token)
+            .map(|i| self.token_history.remove(i));
+        let nickname = nickname
+            .filter(|n| !n.trim().is_empty())
+            .or_else(|| previous.and_then(|e| e.nickname))
+            .or_else(|| self.active_account.clone());
+        self.token_history.insert(0, TokenHistoryEntry {
+            token: token.to_string(),
+            nickname,
+            last_valid: format_current_time(),
+        });
+        self.token_history.truncate(TOKEN_HISTORY_LIMIT);
+    }
+
+    /// Turn a single-token config into a one-account list so it can be managed alongside new accounts
+    #[cfg(feature = "tui")]
+    pub fn ensure_default_account(&mut self) {
+        if self.accounts.is_empty() && self.has_valid_token() {
+            self.accounts.push(Account {
+                name: "默认".to_string(),
+                token: self.token.clone(),
+                mcp_server_url: None,
+            });
+            self.active_account = Some("默认".to_string());
+        }
+    }
+
+    /// Make the named account active. Returns false if no such account exists.
+    #[cfg(feature = "tui")]
+    pub fn switch_account(&mut self, name: &str) -> bool {
+        match self.accounts.iter().find(|a| a.name == name) {
+            Some(account) => {
+                self.token = account.token.clone();
+                self.active_account = Some(account.name.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add an account, replacing the token of an existing account with the same name
+    #[cfg(feature = "tui")]
+    pub fn add_account(&mut self, name: String, token: String) {
+        match self.accounts.iter_mut().find(|a| a.name == name) {
+            Some(account) => account.token = token,
+            None => self.accounts.push(Account { name, token, mcp_server_url: None }),
+        }
+    }
+
+    /// Remove the named account. Removing the active account also clears the active token.
+    #[cfg(feature = "tui")]
+    pub fn remove_account(&mut self, name: &str) {
+        self.accounts.retain(|a| a.name != name);
+        if self.active_account.as_deref() == Some(name) {
+            self.active_account = None;
+            self.token = String::new();
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,129 @@
+use std::{fs, sync::{Arc, Mutex, OnceLock}, time::SystemTime};
+use tokio::sync::watch;
+
+use super::Config;
+use crate::error::{McdError, Result};
+
+/// Modification times of the config files, to notice edits made outside this process
+type Stamp = [Option<SystemTime>; 2];
+
+/// The configuration shared by every mode of the process. It is read from disk once and
+/// again only when the file changes; all changes go through `update`, one at a time,
+/// so two screens or handlers saving at once cannot overwrite each other's changes.
+pub struct ConfigService {
+    sender: watch::Sender<Arc<Config>>,
+    /// Held while a change is applied and saved
+    writer: Mutex<()>,
+    files: Mutex<Files>,
+}
+
+/// What the service knows about the config files on disk
+struct Files {
+    /// Config files as of the last successful read or write
+    stamp: Stamp,
+    /// Files that failed to parse and the error, while they are broken
+    broken: Option<(Stamp, String)>,
+}
+
+impl ConfigService {
+    /// The process-wide service, loading the config on first use
+    pub fn global() -> &'static Self {
+        static SERVICE: OnceLock<ConfigService> = OnceLock::new();
+        SERVICE.get_or_init(|| {
+            let stamp = stamp();
+            let (config, broken) = match Config::load_checked() {
+                Ok(config) => (config, None),
+                Err(e) => {
+                    tracing::warn!("配置文件无法解析，暂用默认配置且不会保存修改: {}", e);
+                    (Config::default(), Some((stamp, e.to_string())))
+                },
+            };
+            Self {
+                sender: watch::Sender::new(Arc::new(config)),
+                writer: Mutex::new(()),
+                files: Mutex::new(Files { stamp, broken }),
+            }
+        })
+    }
+
+    /// Current configuration, re-read first when the file was edited by another process.
+    /// While the file cannot be parsed the last good configuration is kept.
+    pub fn current(&self) -> Arc<Config> {
+        self.refresh();
+        self.sender.borrow().clone()
+    }
+
+    /// Re-read the config file, e.g. for the `reload-config` control command
+    #[cfg(feature = "server")]
+    pub fn reload(&self) -> Result<Arc<Config>> {
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        let now = stamp();
+        match Config::load_checked() {
+            Ok(config) => {
+                *files = Files { stamp: now, broken: None };
+                let config = Arc::new(config);
+                self.sender.send_replace(config.clone());
+                Ok(config)
+            },
+            Err(e) => {
+                files.broken = Some((now, e.to_string()));
+                Err(e)
+            },
+        }
+    }
+
+    /// Read the files again when they changed since the last read, keeping the
+    /// current config and the old stamp when they do not parse, so a fix is picked up
+    fn refresh(&self) {
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        let now = stamp();
+        if files.stamp == now || files.broken.as_ref().is_some_and(|(stamp, _)| *stamp == now) {
+            return;
+        }
+        match Config::load_checked() {
+            Ok(config) => {
+                *files = Files { stamp: now, broken: None };
+                self.sender.send_replace(Arc::new(config));
+            },
+            Err(e) => {
+                tracing::warn!("配置文件无法解析，继续使用之前的配置: {}", e);
+                files.broken = Some((now, e.to_string()));
+            },
+        }
+    }
+
+    /// Apply `change` to the current config, save it and notify subscribers
+    pub fn update(&self, change: impl FnOnce(&mut Config)) -> Result<Arc<Config>> {
+        self.try_update(|config| {
+            change(config);
+            Ok::<_, McdError>(())
+        })
+    }
+
+    /// Like `update`, but nothing is saved when `change` fails or the config file is broken
+    pub fn try_update<E: From<McdError>>(&self, change: impl FnOnce(&mut Config) -> std::result::Result<(), E>) -> std::result::Result<Arc<Config>, E> {
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut config = Config::clone(&self.current());
+        // Saving now would replace the file being edited with a config it was not read from
+        if let Some((_, error)) = &self.files.lock().unwrap_or_else(|e| e.into_inner()).broken {
+            return Err(McdError::Parse(format!("配置文件无法解析，请先修正后再修改设置: {}", error)).into());
+        }
+        change(&mut config)?;
+        config.save()?;
+        let config = Arc::new(config);
+        *self.files.lock().unwrap_or_else(|e| e.into_inner()) = Files { stamp: stamp(), broken: None };
+        self.sender.send_replace(config.clone());
+        Ok(config)
+    }
+
+    /// Receiver woken after every change, whether saved here or read back from disk
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.sender.subscribe()
+    }
+}
+
+fn stamp() -> Stamp {
+    let modified = |path: std::path::PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+    [Config::local_config_path().ok().and_then(modified), modified(Config::get_config_path())]
+}
@@ -0,0 +1,345 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::config::Config;
+
+// The listening side runs in the web and MCP servers; `ctl` only needs `send`
+#[cfg(feature = "server")]
+use std::sync::OnceLock;
+#[cfg(feature = "server")]
+use tokio::{io::{AsyncRead, AsyncWrite}, sync::{mpsc, oneshot, watch}};
+#[cfg(feature = "server")]
+use crate::{config::ConfigService, coupons::ClaimHistory, scheduler};
+
+/// Audit actor of commands sent over the control socket
+#[cfg(feature = "server")]
+pub const ACTOR: &str = "control";
+/// Longest wait for a reply in `send`; a claim can take a while upstream
+const REPLY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// One request line, e.g. `{"command":"reload-config"}`
+#[cfg(feature = "server")]
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+enum Request {
+    Claim,
+    Status,
+    ReloadConfig,
+    Stop,
+}
+
+#[cfg(feature = "server")]
+impl Request {
+    /// The command for the running mode, or `None` for `stop`
+    fn command(self) -> Option<Command> {
+        match self {
+            Request::Claim => Some(Command::Claim),
+            Request::Status => Some(Command::Status),
+            Request::ReloadConfig => Some(Command::ReloadConfig),
+            Request::Stop => None,
+        }
+    }
+}
+
+/// Commands answered by the running mode; `stop` is handled here
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Copy)]
+pub enum Command {
+    Claim,
+    Status,
+    ReloadConfig,
+}
+
+/// One reply line
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reply {
+    pub success: bool,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<InstanceStatus>,
+}
+
+#[cfg(feature = "server")]
+impl Reply {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Self {
+            success: true,
+            message: message.into(),
+            status: None,
+        }
+    }
+
+    pub fn failed(message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            message: message.into(),
+            status: None,
+        }
+    }
+}
+
+/// Answer to `status`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanceStatus {
+    /// web or mcp
+    pub mode: String,
+    pub pid: u32,
+    pub has_token: bool,
+    pub account: Option<String>,
+    /// Coupons cached by the running instance
+    pub cached_coupons: usize,
+    /// Most recent entry of the claim history, from any source
+    pub last_claim: Option<String>,
+    pub scheduler: String,
+}
+
+#[cfg(feature = "server")]
+impl InstanceStatus {
+    pub fn reply(mode: &str, has_token: bool, cached_coupons: usize) -> Reply {
+        let config = ConfigService::global().current();
+        let last_claim = ClaimHistory::load().ok()
+            .and_then(|h| h.entries.last().cloned())
+            .map(|e| format!("{} [{}] {}", e.at, e.source, e.message));
+        let scheduler = scheduler::status();
+        let status = Self {
+            mode: mode.to_string(),
+            pid: std::process::id(),
+            has_token,
+            account: config.active_account.clone(),
+            cached_coupons,
+            last_claim,
+            scheduler: match scheduler.next_run {
+                Some(next) => format!("下次领取 {}", next),
+                None => scheduler.message,
+            },
+        };
+        Reply {
+            status: Some(status),
+            ..Reply::ok("运行中")
+        }
+    }
+}
+
+/// A command waiting for the running mode to answer it
+#[cfg(feature = "server")]
+pub struct Pending {
+    pub command: Command,
+    reply: oneshot::Sender<Reply>,
+}
+
+#[cfg(feature = "server")]
+impl Pending {
+    pub fn reply(self, reply: Reply) {
+        // The client may have hung up already
+        let _ = self.reply.send(reply);
+    }
+}
+
+#[cfg(feature = "server")]
+fn stop_signal() -> &'static watch::Sender<bool> {
+    static STOP: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    STOP.get_or_init(|| watch::channel(false).0)
+}
+
+/// Resolves once `stop` has been received; pass to the server's graceful shutdown
+#[cfg(feature = "server")]
+pub async fn stopped() {
+    let mut stop = stop_signal().subscribe();
+    let _ = stop.wait_for(|stopped| *stopped).await;
+}
+
+/// Listen on the control socket of `mode` in the background and hand the commands
+/// it receives to the returned channel. `None` when another instance of the mode
+/// already owns the socket or it cannot be created.
+#[cfg(feature = "server")]
+pub fn spawn(mode: &'static str) -> Option<mpsc::Receiver<Pending>> {
+    let (sender, receiver) = mpsc::channel(8);
+    match listen(mode, sender) {
+        Ok(()) => Some(receiver),
+        Err(e) => {
+            tracing::warn!("控制接口未启动: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Send one command to a running instance of `mode` and wait for its reply
+pub async fn send(mode: &str, command: &str) -> Result<Reply> {
+    let stream = connect(mode).await?;
+    let (read, mut write) = tokio::io::split(stream);
+    let line = serde_json::json!({ "command": command }).to_string();
+    write.write_all(format!("{}\n", line).as_bytes()).await?;
+    write.flush().await?;
+
+    let mut reply = String::new();
+    tokio::time::timeout(REPLY_TIMEOUT, BufReader::new(read).read_line(&mut reply))
+        .await
+        .map_err(|_| anyhow!("{} 秒内未收到回复", REPLY_TIMEOUT.as_secs()))??;
+    if reply.trim().is_empty() {
+        return Err(anyhow!("连接已关闭，未收到回复"));
+    }
+    serde_json::from_str(&reply).context("无法解析回复")
+}
+
+/// Answer every request line on one connection
+#[cfg(feature = "server")]
+async fn serve_connection<S>(stream: S, commands: mpsc::Sender<Pending>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (reply, stop) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => match request.command() {
+                Some(command) => (forward(command, &commands).await, false),
+                None => (Reply::ok("正在停止"), true),
+            },
+            Err(e) => (Reply::failed(format!("无法识别的命令: {}", e)), false),
+        };
+        let Ok(mut text) = serde_json::to_string(&reply) else {
+            break;
+        };
+        text.push('\n');
+        let written = write.write_all(text.as_bytes()).await;
+        let _ = write.flush().await;
+        if stop {
+            tracing::info!("已通过控制接口收到停止命令");
+            stop_signal().send_replace(true);
+            break;
+        }
+        if written.is_err() {
+            break;
+        }
+    }
+}
+
+/// Hand `command` to the running mode and wait for its answer
+#[cfg(feature = "server")]
+async fn forward(command: Command, commands: &mpsc::Sender<Pending>) -> Reply {
+    let (reply, answer) = oneshot::channel();
+    if commands.send(Pending { command, reply }).await.is_err() {
+        return Reply::failed("实例正在退出");
+    }
+    answer.await.unwrap_or_else(|_| Reply::failed("实例未回复"))
+}
+
+/// Socket file of `mode`, in the private `control` directory of the data directory
+#[cfg(unix)]
+fn socket_path(mode: &str) -> std::path::PathBuf {
+    Config::data_path(&format!("control/{}.sock", mode))
+}
+
+/// Removes the socket file when the listener goes away
+#[cfg(all(unix, feature = "server"))]
+struct SocketFile(std::path::PathBuf);
+
+#[cfg(all(unix, feature = "server"))]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(all(unix, feature = "server"))]
+fn listen(mode: &'static str, commands: mpsc::Sender<Pending>) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use tokio::net::UnixListener;
+
+    let path = socket_path(mode);
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            return Err(anyhow!("{} 已被另一个运行中的实例使用", path.display()));
+        }
+        // Left behind by an instance that did not exit cleanly
+        std::fs::remove_file(&path).context(format!("无法删除文件: {}", path.display()))?;
+    }
+    // Anyone who can connect can claim, so the socket is bound in a directory only the owner
+    // can enter: it is never reachable by others, not even before its own mode is set
+    if let Some(dir) = path.parent() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .context(format!("无法创建目录: {}", dir.display()))?;
+        // Created by someone else or loosened since
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+            .context(format!("无法设置目录权限: {}", dir.display()))?;
+    }
+    let listener = UnixListener::bind(&path).context(format!("无法监听 {}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    println!("控制接口已启动: {}", path.display());
+
+    let socket = SocketFile(path);
+    tokio::spawn(async move {
+        let _socket = socket;
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve_connection(stream, commands.clone()));
+                },
+                Err(e) => tracing::warn!("控制接口接受连接失败: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn connect(mode: &str) -> Result<tokio::net::UnixStream> {
+    let path = socket_path(mode);
+    tokio::net::UnixStream::connect(&path)
+        .await
+        .context(format!("无法连接 {}，{} 模式是否正在运行？", path.display(), mode))
+}
+
+/// Named pipe of `mode`
+#[cfg(windows)]
+fn pipe_name(mode: &str) -> String {
+    format!(r"\\.\pipe\mcd-coupon-tui-rust-{}", mode)
+}
+
+#[cfg(all(windows, feature = "server"))]
+fn listen(mode: &'static str, commands: mpsc::Sender<Pending>) -> Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = pipe_name(mode);
+    // Fails when another instance already created the pipe
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&name)
+        .context(format!("无法创建命名管道 {}，可能已有实例在运行", name))?;
+    println!("控制接口已启动: {}", name);
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = server.connect().await {
+                tracing::warn!("控制接口接受连接失败: {}", e);
+                continue;
+            }
+            let next = match ServerOptions::new().create(&name) {
+                Ok(next) => next,
+                Err(e) => {
+                    tracing::warn!("无法创建命名管道 {}: {}", name, e);
+                    return;
+                }
+            };
+            let connected = std::mem::replace(&mut server, next);
+            tokio::spawn(serve_connection(connected, commands.clone()));
+        }
+    });
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn connect(mode: &str) -> Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    let name = pipe_name(mode);
+    tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(&name)
+        .context(format!("无法连接 {}，{} 模式是否正在运行？", name, mode))
+}
@@ -0,0 +1,32 @@
+
+use crate::{config::{BudgetAction, BudgetConfig, ConfigService}, coupons::ClaimHistory, error::{McdError, Result}, utils::local_today};
+
+/// Which limit of `budget` the claims of this month have reached, if any
+fn reached(budget: &BudgetConfig) -> Option<String> {
+    let stats = ClaimHistory::load().unwrap_or_default().stats(Some(local_today()));
+    if let Some(max) = budget.max_coupons.filter(|max| stats.coupons_claimed >= *max) {
+        return Some(format!("本月已领取 {} 张优惠券，达到每月 {} 张的上限", stats.coupons_claimed, max));
+    }
+    budget.max_value
+        .filter(|max| stats.claimed_value >= *max)
+        .map(|max| format!("本月已领取面值约 ¥{} 的优惠券，达到每月 ¥{} 的上限", stats.claimed_value, max))
+}
+
+/// Check the configured monthly budget before a claim. Once a limit is reached, a `skip`
+/// budget fails with `McdError::OverBudget` and a `warn` budget returns the reminder to
+/// show with the result of the claim.
+pub fn check_budget() -> Result<Option<String>> {
+    let Some(budget) = ConfigService::global().current().budget.clone() else {
+        return Ok(None);
+    };
+    let Some(reason) = reached(&budget) else {
+        return Ok(None);
+    };
+    match budget.action {
+        BudgetAction::Warn => {
+            tracing::warn!("{}，仍继续领取", reason);
+            Ok(Some(reason))
+        },
+        BudgetAction::Skip => Err(McdError::OverBudget(format!("{}，已跳过领取（可在配置的 budget 中调整）", reason))),
+    }
+}
@@ -0,0 +1,86 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::{config::{Account, Config}, coupons::{parse_coupons_from_markdown, Coupon, UsageStore}, error::Result, mcp::McpClient};
+
+/// Coupons held by two accounts side by side, matched by title
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct AccountComparison {
+    pub a: String,
+    pub b: String,
+    /// Coupons only account `a` holds
+    pub only_a: Vec<Coupon>,
+    /// Coupons only account `b` holds
+    pub only_b: Vec<Coupon>,
+    /// Titles both accounts hold
+    pub both: Vec<String>,
+}
+
+impl AccountComparison {
+    pub fn build(a: &str, a_coupons: &[Coupon], b: &str, b_coupons: &[Coupon]) -> Self {
+        let titles = |coupons: &[Coupon]| coupons.iter().map(|c| c.title.clone()).collect::<HashSet<_>>();
+        let (a_titles, b_titles) = (titles(a_coupons), titles(b_coupons));
+        let only = |coupons: &[Coupon], other: &HashSet<String>| coupons.iter()
+            .filter(|c| !other.contains(&c.title))
+            .cloned()
+            .collect();
+        Self {
+            a: a.to_string(),
+            b: b.to_string(),
+            only_a: only(a_coupons, &b_titles),
+            only_b: only(b_coupons, &a_titles),
+            // Listing order of account a, which is the one the user picked first
+            both: a_coupons.iter()
+                .map(|c| c.title.clone())
+                .filter(|t| b_titles.contains(t))
+                .collect(),
+        }
+    }
+
+    /// Markdown summary for MCP clients
+    #[cfg(feature = "mcp-server")]
+    pub fn to_markdown(&self) -> String {
+        let mut text = format!("# 账号对比: {} / {}\n", self.a, self.b);
+        for (name, coupons) in [(&self.a, &self.only_a), (&self.b, &self.only_b)] {
+            text.push_str(&format!("\n## 仅「{}」持有 ({} 张)\n", name, coupons.len()));
+            for c in coupons {
+                let used = if c.used { " (已使用)" } else { "" };
+                text.push_str(&format!("- {} — {} · {}{}\n", c.title, c.price, c.expiry, used));
+            }
+        }
+        text.push_str(&format!("\n## 两个账号都有 ({} 张)\n", self.both.len()));
+        for title in &self.both {
+            text.push_str(&format!("- {}\n", title));
+        }
+        text
+    }
+}
+
+/// The two saved accounts to compare, or why they cannot be compared
+pub fn pick_accounts<'a>(config: &'a Config, a: &str, b: &str) -> std::result::Result<(&'a Account, &'a Account), String> {
+    if config.accounts.len() < 2 {
+        return Err("至少需要保存两个账号才能对比".to_string());
+    }
+    if a == b {
+        return Err("请选择两个不同的账号".to_string());
+    }
+    let find = |name: &str| config.accounts.iter()
+        .find(|account| account.name == name)
+        .ok_or_else(|| format!("未找到账号: {}", name));
+    Ok((find(a)?, find(b)?))
+}
+
+/// Coupons held by a saved account, with local used marks applied
+async fn account_coupons(config: &Config, account: &Account) -> Result<Vec<Coupon>> {
+    let client = McpClient::with_url(account.token.clone(), config.account_upstream_url(account))?;
+    let mut coupons = parse_coupons_from_markdown(&client.get_my_coupons().await?);
+    UsageStore::load().unwrap_or_default().apply(&mut coupons);
+    Ok(coupons)
+}
+
+/// Fetch both accounts at once and compare their coupons
+pub async fn compare_accounts(config: &Config, a: &Account, b: &Account) -> Result<AccountComparison> {
+    let (a_coupons, b_coupons) = tokio::try_join!(account_coupons(config, a), account_coupons(config, b))?;
+    Ok(AccountComparison::build(&a.name, &a_coupons, &b.name, &b_coupons))
+}
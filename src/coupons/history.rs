@@ -0,0 +1,246 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs};
+
+use crate::{config::{Config, ConfigService}, coupons::{parse_amount, parse_coupons_from_markdown, ClaimVerification, Coupon}, error::McdError, mcp::describe_error, utils::format_current_time};
+#[cfg(feature = "web")]
+use crate::coupons::UsageStore;
+
+/// File holding past claim results, in the data directory
+pub const HISTORY_FILE: &str = "claim-history.json";
+/// Oldest entries are dropped beyond this many
+const MAX_ENTRIES: usize = 1000;
+/// Categories listed in `ClaimStats::top_categories`
+const TOP_CATEGORIES: usize = 5;
+
+/// One claim attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema, async_graphql::SimpleObject))]
+pub struct ClaimEntry {
+    /// Local time, "YYYY-MM-DD HH:MM:SS"
+    pub at: String,
+    /// Active account at the time, if accounts are configured
+    pub account: Option<String>,
+    /// Where the claim was started: tui, web, cli, watch, schedule or mcp
+    pub source: String,
+    pub success: bool,
+    pub message: String,
+    /// Titles of the claimed coupons, when the upstream summary lists them
+    #[serde(default)]
+    pub coupons: Vec<String>,
+    /// Titles that actually appeared in the held coupons, when the claim was verified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified: Option<Vec<String>>,
+    /// Tags of the claimed coupons, repeated for each coupon carrying them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Summed face value of the claimed coupons whose price has a number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+}
+
+impl ClaimEntry {
+    fn date(&self) -> Option<NaiveDate> {
+        NaiveDateTime::parse_from_str(&self.at, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .map(|at| at.date())
+    }
+}
+
+/// How many coupons were claimed with a tag
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Claims, redemptions and savings of one tag, see `ClaimHistory::tag_stats`
+#[cfg(feature = "web")]
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema, async_graphql::SimpleObject))]
+pub struct TagStats {
+    pub tag: String,
+    /// Coupons with the tag claimed successfully
+    pub claimed: usize,
+    /// Coupons with the tag marked used
+    pub used: usize,
+    /// Face value of the used coupons with a parseable amount
+    pub savings: f64,
+}
+
+/// Totals over the claim history, see `ClaimHistory::stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaimStats {
+    /// Claim attempts, successful or not
+    pub attempts: usize,
+    pub succeeded: usize,
+    /// Percentage of attempts that succeeded, 0 without any
+    pub success_rate: f64,
+    /// Coupons listed by the successful claims
+    pub coupons_claimed: usize,
+    /// Most claimed tags, most first
+    pub top_categories: Vec<CategoryCount>,
+    /// Face value of the claimed coupons. Claims recorded before values were kept count as 0.
+    pub claimed_value: f64,
+}
+
+/// Locally persisted claim history, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClaimHistory {
+    #[serde(default)]
+    pub entries: Vec<ClaimEntry>,
+}
+
+impl ClaimHistory {
+    /// Load the history, starting empty if the file does not exist yet
+    pub fn load() -> Result<Self> {
+        let path = Config::data_path(HISTORY_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .context(format!("无法读取文件: {}", path.display()))?;
+        serde_json::from_str(&text)
+            .context(format!("无法解析文件: {}", path.display()))
+    }
+
+    /// Save the history in the data directory
+    pub fn save(&self) -> Result<()> {
+        let path = Config::data_path(HISTORY_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .context(format!("无法创建目录: {}", dir.display()))?;
+        }
+        let text = serde_json::to_string_pretty(self).context("无法序列化领取记录")?;
+        fs::write(&path, text)
+            .context(format!("无法写入文件: {}", path.display()))
+    }
+
+    /// Drop entries made before `cutoff`, returning how many were dropped.
+    /// Entries without a readable time are kept.
+    pub fn prune_before(&mut self, cutoff: NaiveDateTime) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|e| NaiveDateTime::parse_from_str(&e.at, "%Y-%m-%d %H:%M:%S").map_or(true, |at| at >= cutoff));
+        before - self.entries.len()
+    }
+
+    /// Append the outcome of an auto-bind call and what it was verified to add. Failing to persist it is only logged,
+    /// so a full disk never turns a successful claim into an error.
+    pub fn record(source: &str, result: &Result<String, McdError>, verification: Option<&ClaimVerification>) {
+        // Skipped for the monthly budget before anything reached upstream, so not an attempt
+        if matches!(result, Err(McdError::OverBudget(_))) {
+            return;
+        }
+        let claimed = match result {
+            Ok(text) => parse_coupons_from_markdown(text),
+            Err(_) => Vec::new(),
+        };
+        let entry = ClaimEntry {
+            at: format_current_time(),
+            account: ConfigService::global().current().active_account.clone(),
+            source: source.to_string(),
+            success: ClaimVerification::succeeded(result, verification),
+            message: match result {
+                Ok(_) => ClaimVerification::message(verification),
+                Err(e) => format!("领取失败: {}", describe_error(e)),
+            },
+            coupons: claimed.iter().map(|c| c.title.clone()).collect(),
+            verified: verification.and_then(ClaimVerification::titles),
+            tags: claimed.iter().flat_map(Coupon::tag_list).collect(),
+            value: claimed.iter()
+                .filter_map(|c| parse_amount(&c.price))
+                .reduce(|total, amount| total + amount)
+                .map(|total| (total * 100.0).round() / 100.0),
+        };
+
+        let saved = Self::load().and_then(|mut history| {
+            history.entries.push(entry);
+            let excess = history.entries.len().saturating_sub(MAX_ENTRIES);
+            history.entries.drain(..excess);
+            history.save()
+        });
+        if let Err(e) = saved {
+            tracing::warn!("保存领取记录失败: {:#}", e);
+        }
+    }
+
+    /// Entries within the inclusive date range and for the given account, newest first
+    #[cfg(any(feature = "tui", feature = "web", feature = "mcp-server"))]
+    pub fn query(&self, from: Option<NaiveDate>, to: Option<NaiveDate>, account: Option<&str>) -> Vec<ClaimEntry> {
+        self.entries.iter()
+            .rev()
+            .filter(|e| {
+                let date = e.date();
+                from.is_none_or(|from| date.is_some_and(|d| d >= from))
+                    && to.is_none_or(|to| date.is_some_and(|d| d <= to))
+            })
+            .filter(|e| account.is_none_or(|name| e.account.as_deref() == Some(name)))
+            .cloned()
+            .collect()
+    }
+
+    /// Claims and savings grouped by tag over the inclusive date range, most claimed first.
+    /// Savings come from `usage`, counted on the day a coupon was marked used.
+    #[cfg(feature = "web")]
+    pub fn tag_stats(&self, usage: &UsageStore, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Vec<TagStats> {
+        let in_range = |date: Option<NaiveDate>| {
+            from.is_none_or(|from| date.is_some_and(|d| d >= from)) && to.is_none_or(|to| date.is_some_and(|d| d <= to))
+        };
+        let mut by_tag: HashMap<String, TagStats> = HashMap::new();
+        let new = |tag: &String| TagStats { tag: tag.clone(), claimed: 0, used: 0, savings: 0.0 };
+        for tag in self.entries.iter().filter(|e| e.success && in_range(e.date())).flat_map(|e| &e.tags) {
+            by_tag.entry(tag.clone()).or_insert_with(|| new(tag)).claimed += 1;
+        }
+        for used in usage.used.iter().filter(|u| in_range(u.used_on())) {
+            let amount = parse_amount(&used.price).unwrap_or(0.0);
+            for tag in &used.tags {
+                let stats = by_tag.entry(tag.clone()).or_insert_with(|| new(tag));
+                stats.used += 1;
+                stats.savings += amount;
+            }
+        }
+
+        let mut tags: Vec<TagStats> = by_tag.into_values()
+            .map(|s| TagStats { savings: (s.savings * 100.0).round() / 100.0, ..s })
+            .collect();
+        // Ties by name, so the chart does not reorder between loads
+        tags.sort_by(|a, b| b.claimed.cmp(&a.claimed).then(b.used.cmp(&a.used)).then_with(|| a.tag.cmp(&b.tag)));
+        tags
+    }
+
+    /// Totals over all entries, or those made in the month of `month` when given
+    pub fn stats(&self, month: Option<NaiveDate>) -> ClaimStats {
+        let entries: Vec<&ClaimEntry> = self.entries.iter()
+            .filter(|e| month.is_none_or(|m| e.date().is_some_and(|d| d.year() == m.year() && d.month() == m.month())))
+            .collect();
+        let succeeded: Vec<&ClaimEntry> = entries.iter().copied().filter(|e| e.success).collect();
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for tag in succeeded.iter().flat_map(|e| &e.tags) {
+            *counts.entry(tag).or_default() += 1;
+        }
+        let mut top_categories: Vec<CategoryCount> = counts.into_iter()
+            .map(|(name, count)| CategoryCount { name: name.to_string(), count })
+            .collect();
+        // Ties by name, so the listing does not change between runs
+        top_categories.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        top_categories.truncate(TOP_CATEGORIES);
+
+        let success_rate = if entries.is_empty() {
+            0.0
+        } else {
+            (succeeded.len() as f64 * 1000.0 / entries.len() as f64).round() / 10.0
+        };
+        // Float `sum` of nothing is -0.0, see `UsageStore::savings_in`
+        let claimed_value = succeeded.iter().filter_map(|e| e.value).fold(0.0, |total, value| total + value);
+        ClaimStats {
+            attempts: entries.len(),
+            succeeded: succeeded.len(),
+            success_rate,
+            coupons_claimed: succeeded.iter().map(|e| e.coupons.len()).sum(),
+            top_categories,
+            claimed_value: (claimed_value * 100.0).round() / 100.0,
+        }
+    }
+}
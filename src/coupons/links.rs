@@ -0,0 +1,35 @@
+use std::sync::Mutex;
+
+use crate::config::DeepLinkConfig;
+
+static TEMPLATES: Mutex<Option<DeepLinkConfig>> = Mutex::new(None);
+
+/// Link templates of parsed coupons, from the `deep_links` config option
+pub fn set_deep_links(links: DeepLinkConfig) {
+    *TEMPLATES.lock().unwrap_or_else(|e| e.into_inner()) = Some(links);
+}
+
+/// App and universal links of a coupon, None where the template is empty
+pub(super) fn deep_links(title: &str, raw_title: &str, tags: &str) -> (Option<String>, Option<String>) {
+    let templates = TEMPLATES.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default();
+    let fill = |template: &str| {
+        let template = template.trim();
+        (!template.is_empty()).then(|| {
+            template
+                .replace("{title}", &encode(title))
+                .replace("{raw_title}", &encode(raw_title))
+                .replace("{tags}", &encode(tags))
+        })
+    };
+    (fill(&templates.app), fill(&templates.universal))
+}
+
+/// Percent-encode everything but the unreserved characters, so titles are safe in a query
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,111 @@
+//! Single-pass reader for the upstream markdown coupon listing. Only depends on std,
+//! so the benchmarks can build it on its own.
+
+use std::{str::Lines, sync::{Arc, OnceLock}};
+
+/// Fields of one coupon as slices of the upstream text, before any normalization
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RawCoupon<'a> {
+    pub title: &'a str,
+    pub price: &'a str,
+    pub expiry: &'a str,
+    pub receive_time: &'a str,
+    pub tags: &'a str,
+    pub image_url: &'a str,
+    /// Redemption code, when upstream lists one
+    pub code: &'a str,
+}
+
+/// Coupon field filled by a `- **label**: value` line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Price,
+    Expiry,
+    ReceiveTime,
+    Tags,
+    Code,
+}
+
+/// Which field each upstream label fills, first match wins
+#[derive(Debug, Clone)]
+pub struct FieldLabels(pub Vec<(String, Field)>);
+
+impl FieldLabels {
+    /// Labels upstream uses today
+    pub fn standard() -> Arc<Self> {
+        static STANDARD: OnceLock<Arc<FieldLabels>> = OnceLock::new();
+        STANDARD.get_or_init(|| {
+            let labels = [("优惠", Field::Price), ("有效期", Field::Expiry), ("领取时间", Field::ReceiveTime), ("标签", Field::Tags), ("券码", Field::Code)];
+            Arc::new(Self(labels.iter().map(|(label, field)| (label.to_string(), *field)).collect()))
+        }).clone()
+    }
+
+    fn field(&self, label: &str) -> Option<Field> {
+        self.0.iter().find(|(l, _)| l == label).map(|(_, field)| *field)
+    }
+}
+
+/// Label and value of a `- **label**: value` line, also with a full-width colon or the
+/// colon inside the bold
+fn field_line(line: &str) -> Option<(&str, &str)> {
+    let (label, value) = line.strip_prefix("- **")?.split_once("**")?;
+    let label = label.trim().trim_end_matches([':', '：']).trim_end();
+    let value = value.trim_start();
+    let value = value.strip_prefix([':', '：']).unwrap_or(value);
+    Some((label, value.trim()))
+}
+
+/// Coupons of a markdown listing, read one at a time as the iterator advances,
+/// so a very large response never needs all its coupons in memory at once
+pub struct MarkdownCoupons<'a> {
+    lines: Lines<'a>,
+    /// Coupon whose title was read and whose field lines are still coming
+    current: Option<RawCoupon<'a>>,
+    labels: Arc<FieldLabels>,
+}
+
+impl<'a> MarkdownCoupons<'a> {
+    /// Reader mapping field lines to coupon fields through `labels`
+    pub fn new(text: &'a str, labels: Arc<FieldLabels>) -> Self {
+        Self { lines: text.lines(), current: None, labels }
+    }
+}
+
+impl<'a> Iterator for MarkdownCoupons<'a> {
+    type Item = RawCoupon<'a>;
+
+    fn next(&mut self) -> Option<RawCoupon<'a>> {
+        for line in self.lines.by_ref() {
+            let line = line.trim();
+
+            // A new title (## 标题) ends the coupon before it
+            if let Some(title) = line.strip_prefix("## ") {
+                let next = RawCoupon { title: title.trim_start_matches("## "), ..Default::default() };
+                match self.current.replace(next) {
+                    Some(done) if !done.title.is_empty() => return Some(done),
+                    _ => continue,
+                }
+            }
+
+            let Some(coupon) = self.current.as_mut() else {
+                continue;
+            };
+            if let Some((label, value)) = field_line(line) {
+                match self.labels.field(label) {
+                    Some(Field::Price) => coupon.price = value,
+                    Some(Field::Expiry) => coupon.expiry = value,
+                    Some(Field::ReceiveTime) => coupon.receive_time = value,
+                    Some(Field::Tags) => coupon.tags = value,
+                    Some(Field::Code) => coupon.code = value,
+                    None => {},
+                }
+            } else if line.starts_with("<img") {
+                // src of <img src="..." ...>
+                if let Some(src) = line.split_once("src=\"").and_then(|(_, rest)| rest.split_once('"')) {
+                    coupon.image_url = src.0;
+                }
+            }
+        }
+        self.current.take().filter(|c| !c.title.is_empty())
+    }
+}
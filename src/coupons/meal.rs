@@ -0,0 +1,141 @@
+use serde::Serialize;
+
+use crate::{coupons::{match_key, parse_amount, Coupon}, utils::local_today};
+
+/// A coupon picked for the meal, with its parsed value
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct MealItem {
+    pub title: String,
+    pub price: String,
+    pub expiry: String,
+    /// Value parsed from the price text, if any
+    pub amount: Option<f64>,
+    /// Estimated kcal, when `show_calories` is on and the title names known items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calories: Option<u32>,
+}
+
+/// Checklist for a store visit built from the coupons the user picked
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct MealPlan {
+    pub items: Vec<MealItem>,
+    /// Sum of the parsed coupon prices
+    pub total: f64,
+    /// Items whose price text has no number
+    pub unpriced: usize,
+    /// Sum of the items' estimated kcal, when any item has an estimate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calories: Option<u32>,
+    /// Overlapping or conflicting picks
+    pub warnings: Vec<String>,
+    /// Markdown checklist to take to the store
+    pub checklist: String,
+}
+
+impl MealPlan {
+    /// Build the plan from the picked coupons, in the order they were picked
+    pub fn build(picked: &[Coupon]) -> Self {
+        let today = local_today();
+        let mut warnings = Vec::new();
+
+        for (i, coupon) in picked.iter().enumerate() {
+            if picked[..i].iter().any(|c| c.title == coupon.title) {
+                warnings.push(format!("「{}」选择了多次，同一订单通常只能使用一张", coupon.title));
+            }
+            if coupon.used {
+                warnings.push(format!("「{}」已标记为已使用", coupon.title));
+            }
+            if coupon.expiry_date().is_some_and(|d| d < today) {
+                warnings.push(format!("「{}」已过期 ({})", coupon.title, coupon.expiry));
+            }
+            if coupon.valid_from().is_some_and(|d| d > today) {
+                warnings.push(format!("「{}」尚未生效 ({})", coupon.title, coupon.expiry));
+            }
+        }
+
+        // Coupons whose validity windows do not overlap cannot be used on one visit
+        for (i, a) in picked.iter().enumerate() {
+            for b in &picked[i + 1..] {
+                let disjoint = match (a.valid_from(), a.expiry_date(), b.valid_from(), b.expiry_date()) {
+                    (_, Some(a_end), Some(b_start), _) if a_end < b_start => true,
+                    (Some(a_start), _, _, Some(b_end)) if b_end < a_start => true,
+                    _ => false,
+                };
+                if disjoint {
+                    warnings.push(format!("「{}」与「{}」的有效期不重叠，无法在同一次使用", a.title, b.title));
+                }
+            }
+        }
+
+        let items: Vec<MealItem> = picked.iter()
+            .map(|c| MealItem {
+                title: c.title.clone(),
+                price: c.price.clone(),
+                expiry: c.expiry.clone(),
+                amount: parse_amount(&c.price),
+                calories: c.calories,
+            })
+            .collect();
+        let total = items.iter().filter_map(|i| i.amount).fold(0.0, |total, amount| total + amount);
+        let total = (total * 100.0).round() / 100.0;
+        let unpriced = items.iter().filter(|i| i.amount.is_none()).count();
+        let calories = items.iter().filter_map(|i| i.calories).reduce(|total, calories| total + calories);
+        let checklist = checklist(&items, total, unpriced, calories, &warnings);
+
+        Self {
+            items,
+            total,
+            unpriced,
+            calories,
+            warnings,
+            checklist,
+        }
+    }
+}
+
+fn checklist(items: &[MealItem], total: f64, unpriced: usize, calories: Option<u32>, warnings: &[String]) -> String {
+    let mut text = format!("# 点餐清单 ({})\n\n", local_today().format("%Y-%m-%d"));
+    for item in items {
+        text.push_str(&format!("- [ ] {} — {}", item.title, item.price));
+        if let Some(calories) = item.calories {
+            text.push_str(&format!("，约 {} 千卡", calories));
+        }
+        if !item.expiry.is_empty() {
+            text.push_str(&format!(" (有效期: {})", item.expiry));
+        }
+        text.push('\n');
+    }
+
+    text.push_str(&format!("\n**合计**: ¥{:.2}", total));
+    if unpriced > 0 {
+        text.push_str(&format!("（另有 {} 张未能识别价格）", unpriced));
+    }
+    text.push('\n');
+    if let Some(calories) = calories {
+        text.push_str(&format!("**热量**: 约 {} 千卡（按标题中识别到的单品估算）\n", calories));
+    }
+
+    if !warnings.is_empty() {
+        text.push_str("\n**注意**:\n");
+        for warning in warnings {
+            text.push_str(&format!("- {}\n", warning));
+        }
+    }
+    text
+}
+
+/// Pick coupons by title, compared by `match_key`, in the order given. Titles that match nothing are returned separately.
+pub fn pick_by_title(coupons: &[Coupon], titles: &[String]) -> (Vec<Coupon>, Vec<String>) {
+    let mut picked = Vec::new();
+    let mut missing = Vec::new();
+    for title in titles {
+        let key = match_key(title);
+        match coupons.iter().find(|c| match_key(&c.title) == key) {
+            Some(coupon) => picked.push(coupon.clone()),
+            None => missing.push(title.clone()),
+        }
+    }
+    (picked, missing)
+}
@@ -0,0 +1,315 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{collections::{hash_map::Entry, BTreeMap, HashMap}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
+
+use crate::utils::local_today;
+
+mod budget;
+#[cfg(feature = "server")]
+mod compare;
+mod history;
+mod links;
+mod markdown;
+#[cfg(feature = "server")]
+mod meal;
+mod normalize;
+mod nutrition;
+mod plan;
+#[cfg(feature = "mcp-server")]
+mod recommend;
+mod rules;
+mod usage;
+mod verify;
+
+pub use budget::check_budget;
+#[cfg(feature = "server")]
+pub use compare::{compare_accounts, pick_accounts};
+#[cfg(feature = "web")]
+pub use compare::AccountComparison;
+pub use history::{ClaimHistory, ClaimStats, HISTORY_FILE};
+#[cfg(any(feature = "tui", feature = "web"))]
+pub use history::ClaimEntry;
+#[cfg(feature = "web")]
+pub use history::TagStats;
+pub use links::set_deep_links;
+#[cfg(feature = "server")]
+pub use meal::{pick_by_title, MealPlan};
+#[cfg(feature = "web")]
+pub use meal::MealItem;
+pub use normalize::{contains_key, match_key, normalize_text};
+pub use nutrition::estimate_calories;
+pub use plan::ClaimPlan;
+#[cfg(feature = "web")]
+pub use plan::PlanConflict;
+#[cfg(feature = "mcp-server")]
+pub use recommend::recommend;
+pub use rules::{first_match, ClaimRules, RuleMatches, Verdict};
+pub use usage::{UsageStore, USAGE_FILE};
+pub use verify::{claim_verified, ClaimVerification};
+#[cfg(any(feature = "tui", feature = "server"))]
+pub use verify::ClaimCheck;
+
+use markdown::{Field, FieldLabels, MarkdownCoupons, RawCoupon};
+
+/// Coupon parsed from the upstream markdown listing
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema, async_graphql::SimpleObject))]
+pub struct Coupon {
+    /// Title with full-width and traditional characters normalized, see `normalize_text`
+    pub title: String,
+    pub price: String,
+    pub expiry: String,
+    pub receive_time: String,
+    /// Tags, normalized like the title
+    pub tags: String,
+    pub image_url: String,
+    /// Title as upstream sent it
+    #[serde(default)]
+    pub raw_title: String,
+    /// Tags as upstream sent them
+    #[serde(default)]
+    pub raw_tags: String,
+    /// Marked as redeemed locally, see `UsageStore`
+    #[serde(default)]
+    pub used: bool,
+    /// Copies upstream listed for this coupon, merged into this entry
+    #[serde(default = "one")]
+    pub count: usize,
+    /// Estimated kcal of the items named in the title, when `show_calories` is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calories: Option<u32>,
+    /// Link opening the coupon in the McDonald's app, from `deep_links.app`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_link: Option<String>,
+    /// https link the app handles when installed, from `deep_links.universal`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub universal_link: Option<String>,
+    /// Redemption code upstream listed, shown as a QR code for the cashier to scan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+fn one() -> usize {
+    1
+}
+
+static KEEP_DUPLICATES: AtomicBool = AtomicBool::new(false);
+static SHOW_CALORIES: AtomicBool = AtomicBool::new(false);
+/// Standard labels plus those from `field_labels`; None until set
+static FIELD_LABELS: Mutex<Option<Arc<FieldLabels>>> = Mutex::new(None);
+
+/// Keep duplicate listings as separate coupons, from the `keep_duplicate_coupons` config option
+pub fn set_keep_duplicates(keep: bool) {
+    KEEP_DUPLICATES.store(keep, Ordering::Relaxed);
+}
+
+/// Estimate the calories of parsed coupons, from the `show_calories` config option
+pub fn set_show_calories(show: bool) {
+    SHOW_CALORIES.store(show, Ordering::Relaxed);
+}
+
+/// Map more upstream labels to coupon fields, from the `field_labels` config option, so a
+/// renamed label can be fixed in the config. They take precedence over the standard labels.
+/// Entries naming an unknown field are skipped and reported in the error.
+pub fn set_field_labels(config: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    let (mut labels, result) = field_labels(config);
+    labels.extend(FieldLabels::standard().0.iter().cloned());
+    *FIELD_LABELS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(FieldLabels(labels)));
+    result
+}
+
+/// Whether every entry of the `field_labels` config option names a known field
+pub fn check_field_labels(config: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    field_labels(config).1
+}
+
+fn field_labels(config: &BTreeMap<String, String>) -> (Vec<(String, Field)>, anyhow::Result<()>) {
+    let mut labels = Vec::new();
+    let mut unknown = Vec::new();
+    for (label, name) in config {
+        let field = match name.trim() {
+            "price" => Field::Price,
+            "expiry" => Field::Expiry,
+            "receive_time" => Field::ReceiveTime,
+            "tags" => Field::Tags,
+            "code" => Field::Code,
+            _ => {
+                unknown.push(format!("\"{}\": \"{}\"", label, name));
+                continue;
+            },
+        };
+        labels.push((label.trim().to_string(), field));
+    }
+    let result = if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("field_labels 中的 {} 无法识别，字段应为 price、expiry、receive_time、tags 或 code", unknown.join("、")))
+    };
+    (labels, result)
+}
+
+/// Coupon from upstream fields, normalizing the title and tags and keeping the raw text
+impl From<RawCoupon<'_>> for Coupon {
+    fn from(raw: RawCoupon<'_>) -> Self {
+        let title = normalize_text(raw.title);
+        let calories = SHOW_CALORIES.load(Ordering::Relaxed).then(|| estimate_calories(&title)).flatten();
+        let (app_link, universal_link) = links::deep_links(&title, raw.title, raw.tags);
+        Self {
+            title,
+            price: raw.price.to_string(),
+            expiry: raw.expiry.to_string(),
+            receive_time: raw.receive_time.to_string(),
+            tags: normalize_text(raw.tags),
+            image_url: raw.image_url.to_string(),
+            raw_title: raw.title.to_string(),
+            raw_tags: raw.tags.to_string(),
+            used: false,
+            count: 1,
+            calories,
+            app_link,
+            universal_link,
+            code: Some(raw.code.to_string()).filter(|c| !c.is_empty()),
+        }
+    }
+}
+
+impl Coupon {
+    /// " x2" style suffix for merged duplicates, empty for a single copy
+    pub fn count_badge(&self) -> String {
+        if self.count > 1 {
+            format!(" x{}", self.count)
+        } else {
+            String::new()
+        }
+    }
+
+    /// "约 510 千卡" for a coupon with a calorie estimate
+    #[cfg(feature = "tui")]
+    pub fn calories_label(&self) -> Option<String> {
+        self.calories.map(|calories| format!("约 {} 千卡", calories))
+    }
+
+    /// Link to redeem the coupon in the app: the app link, or the universal link without one
+    #[cfg(feature = "web")]
+    pub fn redeem_link(&self) -> Option<&str> {
+        self.app_link.as_deref().or(self.universal_link.as_deref())
+    }
+
+    /// Last date mentioned in the expiry text, taken as the end of the validity window
+    pub fn expiry_date(&self) -> Option<NaiveDate> {
+        find_dates(&self.expiry).into_iter().last()
+    }
+
+    /// First date in the expiry text when it gives a range, taken as the start of the validity window
+    #[cfg(feature = "server")]
+    pub fn valid_from(&self) -> Option<NaiveDate> {
+        let dates = find_dates(&self.expiry);
+        (dates.len() >= 2).then(|| dates[0])
+    }
+
+    /// Days until the coupon expires, negative if already expired
+    pub fn days_left(&self) -> Option<i64> {
+        self.expiry_date()
+            .map(|date| (date - local_today()).num_days())
+    }
+
+    /// Individual tags, split on the separators upstream uses
+    pub fn tag_list(&self) -> Vec<String> {
+        self.tags
+            .split([',', '，', '、', '/', '|', ' '])
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Parse markdown text to extract coupons
+pub fn parse_coupons_from_markdown(text: &str) -> Vec<Coupon> {
+    let coupons = coupons_from_markdown(text).collect();
+    if KEEP_DUPLICATES.load(Ordering::Relaxed) {
+        coupons
+    } else {
+        merge_duplicates(coupons)
+    }
+}
+
+/// Coupons of a markdown listing one at a time, in upstream order and without merging
+/// duplicates, for callers that do not need the whole list of a very large response
+pub fn coupons_from_markdown(text: &str) -> impl Iterator<Item = Coupon> + '_ {
+    let labels = FIELD_LABELS.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    MarkdownCoupons::new(text, labels.unwrap_or_else(FieldLabels::standard)).map(Coupon::from)
+}
+
+/// Merge coupons with the same normalized title and expiry, keeping the first and counting the copies
+pub fn merge_duplicates(coupons: Vec<Coupon>) -> Vec<Coupon> {
+    let mut merged: Vec<Coupon> = Vec::with_capacity(coupons.len());
+    let mut seen: HashMap<(String, String), usize> = HashMap::new();
+    for coupon in coupons {
+        match seen.entry((match_key(&coupon.title), match_key(&coupon.expiry))) {
+            Entry::Occupied(entry) => merged[*entry.get()].count += coupon.count,
+            Entry::Vacant(entry) => {
+                entry.insert(merged.len());
+                merged.push(coupon);
+            }
+        }
+    }
+    merged
+}
+
+/// Find all `YYYY-MM-DD` style dates (also `/`, `.` or 年月日 separated) in the text
+fn find_dates(text: &str) -> Vec<NaiveDate> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut dates = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+
+        let mut parts = Vec::new();
+        let mut j = i;
+        while parts.len() < 3 {
+            let start = j;
+            while j < chars.len() && chars[j].is_ascii_digit() {
+                j += 1;
+            }
+            if start == j {
+                break;
+            }
+            parts.push(chars[start..j].iter().collect::<String>());
+            if parts.len() < 3 && j < chars.len() && matches!(chars[j], '-' | '/' | '.' | '年' | '月') {
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        if parts.len() == 3 && parts[0].len() == 4 {
+            let date = match (parts[0].parse(), parts[1].parse(), parts[2].parse()) {
+                (Ok(year), Ok(month), Ok(day)) => NaiveDate::from_ymd_opt(year, month, day),
+                _ => None,
+            };
+            if let Some(date) = date {
+                dates.push(date);
+                i = j;
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    dates
+}
+
+/// Parse the first decimal number in a price text such as "¥9.9" or "立减5元"
+pub fn parse_amount(text: &str) -> Option<f64> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let number: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    number.trim_end_matches('.').parse().ok()
+}
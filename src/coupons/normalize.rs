@@ -0,0 +1,73 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+/// Traditional characters seen in coupon titles and tags, each followed in
+/// `SIMPLIFIED` by its simplified form at the same position
+const TRADITIONAL: &str = concat!(
+    "雞麥當勞條飲雙層捲塊優買賣贈費組點號個熱檸蘋魚豬麵麪燒圓週歲兒樂園會員專價錢減滿",
+    "張單獨選擇換現時間門區與鮮蝦醬濃湯飯穀類領於適並無檻額驗證碼營養長紅綠藍黃節慶禮",
+    "開關東來這邊對實體還進遠後從為麼們說話頭學發髮經過動機種樣總線級輕極盃蕃漢鹹麗燉",
+    "腸餅餃飽館鍋鐵銀網絲紙結給續約純細終統幣壽訂計記許試詳認讓請設謝讀變貨質購貴賞讚",
+    "贊車軟較運達醫鬆齊龍鳳鴨鵝雜難電靈韓順須預題風飛馬驚夠寶將幾廣廳應數歡氣淨溫灣燈",
+    "爭畫盤積競簡糧聯聲舊藝處裝見規視觀評護豐郵錄閃陽險隨雲頁項顧顯餘鹽漿薑蔥蘿蔔筍鱈",
+    "鮭燻滷烏煙團糰餡纖維鈣蘭傳備僅兌內兩凍別劃勁務勝協參啟問嘗嚐噴嚴圍國圖場壓夢夾獎",
+    "師帶幫庫態擔擴擺斷暫曆業構標樓檢權殺決況沒潔濕爐獲異盜眾確離稱稅簽籤紀納絕絡綜編",
+    "緩縮績織羅義聞職腦腳臉臨興莊華萬葉蓋藥虛衝製覽訪詞語誤課調談講譯議貝負財貼資賓賺",
+    "賽贏輔輸辦遞釀針銷鋪鋼鎖鎮閉閒閑閣隊階際靜響頂頓頻顆願顏飾餓騎鳥鴿齋臺檯裡裏",
+);
+const SIMPLIFIED: &str = concat!(
+    "鸡麦当劳条饮双层卷块优买卖赠费组点号个热柠苹鱼猪面面烧圆周岁儿乐园会员专价钱减满",
+    "张单独选择换现时间门区与鲜虾酱浓汤饭谷类领于适并无槛额验证码营养长红绿蓝黄节庆礼",
+    "开关东来这边对实体还进远后从为么们说话头学发发经过动机种样总线级轻极杯番汉咸丽炖",
+    "肠饼饺饱馆锅铁银网丝纸结给续约纯细终统币寿订计记许试详认让请设谢读变货质购贵赏赞",
+    "赞车软较运达医松齐龙凤鸭鹅杂难电灵韩顺须预题风飞马惊够宝将几广厅应数欢气净温湾灯",
+    "争画盘积竞简粮联声旧艺处装见规视观评护丰邮录闪阳险随云页项顾显余盐浆姜葱萝卜笋鳕",
+    "鲑熏卤乌烟团团馅纤维钙兰传备仅兑内两冻别划劲务胜协参启问尝尝喷严围国图场压梦夹奖",
+    "师带帮库态担扩摆断暂历业构标楼检权杀决况没洁湿炉获异盗众确离称税签签纪纳绝络综编",
+    "缓缩绩织罗义闻职脑脚脸临兴庄华万叶盖药虚冲制览访词语误课调谈讲译议贝负财贴资宾赚",
+    "赛赢辅输办递酿针销铺钢锁镇闭闲闲阁队阶际静响顶顿频颗愿颜饰饿骑鸟鸽斋台台里里",
+);
+
+/// Full-width punctuation kept as is in display text, since it is the usual form in Chinese
+const CJK_PUNCTUATION: [char; 7] = ['！', '（', '）', '，', '：', '；', '？'];
+
+fn simplified() -> &'static HashMap<char, char> {
+    static MAP: OnceLock<HashMap<char, char>> = OnceLock::new();
+    MAP.get_or_init(|| TRADITIONAL.chars().zip(SIMPLIFIED.chars()).collect())
+}
+
+/// Half-width form of a full-width ASCII character
+fn half_width(c: char) -> Option<char> {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0),
+        '\u{3000}' => Some(' '),
+        _ => None,
+    }
+}
+
+/// Display form of upstream text: full-width digits, letters and symbols become
+/// half-width (Chinese punctuation stays), traditional characters become simplified
+pub fn normalize_text(text: &str) -> String {
+    text.trim()
+        .chars()
+        .map(|c| match half_width(c) {
+            Some(half) if !CJK_PUNCTUATION.contains(&c) => half,
+            _ => simplified().get(&c).copied().unwrap_or(c),
+        })
+        .collect()
+}
+
+/// Comparison key: the display form without whitespace, with all punctuation
+/// half-width and letters lowercased
+pub fn match_key(text: &str) -> String {
+    normalize_text(text)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| half_width(c).unwrap_or(c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether `keyword` appears in `text`, compared by `match_key`
+pub fn contains_key(text: &str, keyword: &str) -> bool {
+    match_key(text).contains(&match_key(keyword))
+}
@@ -0,0 +1,59 @@
+use crate::coupons::match_key;
+
+/// Calories (kcal) of one standard serving of common menu items, from the published
+/// nutrition figures. Drinks are medium size; sizes named in the title take precedence.
+const CALORIES: &[(&str, u32)] = &[
+    ("巨无霸", 520),
+    ("麦辣鸡腿堡", 510),
+    ("板烧鸡腿堡", 440),
+    ("双层吉士汉堡", 460),
+    ("吉士汉堡", 300),
+    ("汉堡包", 250),
+    ("麦香鱼", 330),
+    ("麦香鸡", 390),
+    ("猪柳蛋麦满分", 440),
+    ("猪柳麦满分", 360),
+    ("麦辣鸡翅", 240),
+    ("麦乐鸡", 230),
+    ("麦脆鸡", 350),
+    ("大薯条", 450),
+    ("中薯条", 330),
+    ("小薯条", 220),
+    ("薯条", 330),
+    ("薯饼", 150),
+    ("大杯可乐", 280),
+    ("小杯可乐", 140),
+    ("可乐", 210),
+    ("雪碧", 200),
+    ("麦旋风", 340),
+    ("圆筒", 150),
+    ("甜筒", 150),
+    ("新地", 260),
+    ("圣代", 260),
+    ("菠萝派", 230),
+    ("香芋派", 240),
+    ("拿铁", 190),
+    ("美式咖啡", 10),
+    ("豆浆", 100),
+    ("油条", 230),
+    ("玉米杯", 80),
+];
+
+/// Estimated calories of the items named in a coupon title, summing each item once.
+/// Longer names win over the names inside them, so "中薯条" is not also counted as "薯条".
+/// None when the title names nothing in the table.
+pub fn estimate_calories(title: &str) -> Option<u32> {
+    let mut rest = match_key(title);
+    let mut names: Vec<&(&str, u32)> = CALORIES.iter().collect();
+    names.sort_by_key(|(name, _)| std::cmp::Reverse(name.chars().count()));
+
+    let mut total = None;
+    for (name, calories) in names {
+        if let Some(at) = rest.find(name) {
+            // Blanked out so shorter names cannot match inside it again
+            rest.replace_range(at..at + name.len(), "|");
+            total = Some(total.unwrap_or(0) + calories);
+        }
+    }
+    total
+}
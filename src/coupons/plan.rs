@@ -0,0 +1,63 @@
+use serde::Serialize;
+
+use crate::coupons::Coupon;
+
+/// What a claim would do, computed by diffing available coupons against held ones
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct ClaimPlan {
+    /// Coupons a claim would add to the account
+    pub would_add: Vec<Coupon>,
+    /// Available coupons already held with the same validity
+    pub duplicates: Vec<Coupon>,
+    /// Available coupons that a claim would not add as-is
+    pub conflicts: Vec<PlanConflict>,
+}
+
+/// An available coupon that clashes with a held coupon or cannot be claimed
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct PlanConflict {
+    pub coupon: Coupon,
+    pub reason: String,
+}
+
+impl ClaimPlan {
+    /// Diff the available coupon listing against the held coupons
+    pub fn build(available: &[Coupon], held: &[Coupon]) -> Self {
+        let mut plan = ClaimPlan::default();
+
+        for coupon in available {
+            if coupon.days_left().is_some_and(|days| days < 0) {
+                plan.conflicts.push(PlanConflict {
+                    coupon: coupon.clone(),
+                    reason: format!("已过期 ({})", coupon.expiry),
+                });
+                continue;
+            }
+
+            match held.iter().find(|h| h.title == coupon.title) {
+                None => plan.would_add.push(coupon.clone()),
+                Some(h) if h.expiry == coupon.expiry && h.price == coupon.price => {
+                    plan.duplicates.push(coupon.clone());
+                }
+                Some(h) => plan.conflicts.push(PlanConflict {
+                    coupon: coupon.clone(),
+                    reason: format!("已持有同名优惠券 (优惠: {}，有效期: {})", h.price, h.expiry),
+                }),
+            }
+        }
+
+        plan
+    }
+
+    /// One-line summary, e.g. "将新增 3 张，重复 2 张，冲突 1 张"
+    pub fn summary(&self) -> String {
+        format!(
+            "将新增 {} 张，重复 {} 张，冲突 {} 张",
+            self.would_add.len(),
+            self.duplicates.len(),
+            self.conflicts.len()
+        )
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::coupons::{match_key, ClaimHistory, Coupon, UsageStore};
+
+/// Smallest title similarity for a past coupon to be named as a reason
+const SIMILAR_TITLE: f64 = 0.3;
+/// A coupon marked used counts this many times a claim, as it was actually wanted
+const USE_WEIGHT: f64 = 2.0;
+
+/// Available coupon scored against what was claimed and used before
+#[derive(Debug, Clone, Serialize)]
+pub struct Recommendation {
+    pub title: String,
+    pub price: String,
+    pub expiry: String,
+    /// Cosine similarity to the history profile, 0-1
+    pub score: f64,
+    /// Why it was picked, e.g. "你领取过 5 次「板烧鸡腿堡」"
+    pub reasons: Vec<String>,
+}
+
+/// Weighted terms of a title or tag list
+type Terms = HashMap<String, f64>;
+
+/// A past coupon title with how often it was claimed and used
+struct Past {
+    title: String,
+    claimed: usize,
+    used: usize,
+    terms: Terms,
+}
+
+/// Character bigrams of the title, since Chinese titles have no spaces to split on,
+/// plus each tag as a whole term
+fn terms(title: &str, tags: &[String]) -> Terms {
+    let chars: Vec<char> = match_key(title).chars().collect();
+    let mut terms = Terms::new();
+    if chars.len() == 1 {
+        *terms.entry(format!("t:{}", chars[0])).or_default() += 1.0;
+    }
+    for pair in chars.windows(2) {
+        *terms.entry(format!("t:{}{}", pair[0], pair[1])).or_default() += 1.0;
+    }
+    for tag in tags {
+        *terms.entry(format!("g:{}", match_key(tag))).or_default() += 1.0;
+    }
+    terms
+}
+
+fn cosine(a: &Terms, b: &Terms) -> f64 {
+    let dot: f64 = a.iter().filter_map(|(term, x)| b.get(term).map(|y| x * y)).sum();
+    let norm = |v: &Terms| v.values().map(|x| x * x).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+/// Rank `available` by TF-IDF similarity of titles and tags to the coupons claimed
+/// (`history`) and marked used (`usage`) before, best first, keeping the `limit` best
+/// that share anything with the history
+pub fn recommend(available: &[Coupon], history: &ClaimHistory, usage: &UsageStore, limit: usize) -> Vec<Recommendation> {
+    // Past titles, merged by match key, in order of first appearance
+    let mut past: Vec<Past> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut count = |title: &str, used: bool| {
+        let key = match_key(title);
+        let i = *index.entry(key).or_insert_with(|| {
+            past.push(Past { title: title.to_string(), claimed: 0, used: 0, terms: terms(title, &[]) });
+            past.len() - 1
+        });
+        if used { past[i].used += 1 } else { past[i].claimed += 1 }
+    };
+    let successful = || history.entries.iter().filter(|e| e.success);
+    for title in successful().flat_map(|e| &e.coupons) {
+        count(title, false);
+    }
+    for used in &usage.used {
+        count(&used.title, true);
+    }
+    let mut tag_counts: HashMap<String, usize> = HashMap::new();
+    for tag in successful().flat_map(|e| &e.tags) {
+        *tag_counts.entry(tag.clone()).or_default() += 1;
+    }
+    if past.is_empty() && tag_counts.is_empty() {
+        return Vec::new();
+    }
+
+    let candidates: Vec<Terms> = available.iter().map(|c| terms(&c.title, &c.tag_list())).collect();
+    // Document frequency over the candidates and the distinct past titles
+    let mut df: HashMap<&str, f64> = HashMap::new();
+    for doc in candidates.iter().chain(past.iter().map(|p| &p.terms)) {
+        for term in doc.keys() {
+            *df.entry(term).or_default() += 1.0;
+        }
+    }
+    let docs = (candidates.len() + past.len()) as f64;
+    let idf = |term: &str| ((docs + 1.0) / (df.get(term).copied().unwrap_or(0.0) + 1.0)).ln() + 1.0;
+    let weigh = |doc: &Terms| -> Terms { doc.iter().map(|(term, tf)| (term.clone(), tf * idf(term))).collect() };
+
+    let mut profile = Terms::new();
+    for p in &past {
+        let weight = p.claimed as f64 + p.used as f64 * USE_WEIGHT;
+        for (term, value) in weigh(&p.terms) {
+            *profile.entry(term).or_default() += value * weight;
+        }
+    }
+    for (tag, n) in &tag_counts {
+        let term = format!("g:{}", match_key(tag));
+        let value = idf(&term) * *n as f64;
+        *profile.entry(term).or_default() += value;
+    }
+
+    let mut ranked: Vec<Recommendation> = available.iter()
+        .zip(&candidates)
+        .filter_map(|(coupon, doc)| {
+            let score = cosine(&profile, &weigh(doc));
+            (score > 0.0).then(|| Recommendation {
+                title: coupon.title.clone(),
+                price: coupon.price.clone(),
+                expiry: coupon.expiry.clone(),
+                score: (score * 1000.0).round() / 1000.0,
+                reasons: reasons(coupon, doc, &past, &tag_counts),
+            })
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked.truncate(limit);
+    ranked
+}
+
+/// The past coupon the title is most like, and the tags claimed before
+fn reasons(coupon: &Coupon, doc: &Terms, past: &[Past], tag_counts: &HashMap<String, usize>) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let title_terms: Terms = doc.iter().filter(|(term, _)| term.starts_with("t:")).map(|(t, v)| (t.clone(), *v)).collect();
+    let closest = past.iter()
+        .map(|p| (p, cosine(&title_terms, &p.terms)))
+        .filter(|(_, similarity)| *similarity >= SIMILAR_TITLE)
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((p, _)) = closest {
+        let mut times = Vec::new();
+        if p.claimed > 0 {
+            times.push(format!("领取过 {} 次", p.claimed));
+        }
+        if p.used > 0 {
+            times.push(format!("用过 {} 次", p.used));
+        }
+        if match_key(&p.title) == match_key(&coupon.title) {
+            reasons.push(format!("你{}「{}」", times.join("、"), p.title));
+        } else {
+            reasons.push(format!("与你{}的「{}」相似", times.join("、"), p.title));
+        }
+    }
+    let top_tag = coupon.tag_list().into_iter()
+        .filter_map(|tag| tag_counts.get(&tag).map(|n| (tag, *n)))
+        .max_by_key(|(_, n)| *n);
+    if let Some((tag, n)) = top_tag {
+        reasons.push(format!("你常领「{}」类优惠券（{} 张）", tag, n));
+    }
+    reasons
+}
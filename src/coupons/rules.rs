@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::coupons::{contains_key, Coupon};
+
+/// Keyword rules deciding which coupons may be claimed automatically.
+/// A keyword matches when it appears in the coupon title or tags; `never_claim` wins over `claim_if`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ClaimRules {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub claim_if: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub never_claim: Vec<String>,
+}
+
+/// How the rules treat one coupon, with the keyword that decided it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "verdict", content = "keyword", rename_all = "snake_case")]
+pub enum Verdict {
+    Claim(String),
+    Never(String),
+    Unmatched,
+}
+
+/// Coupons matched by one rule keyword, as shown by the `rules` tester
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleMatches {
+    /// "claim_if" or "never_claim"
+    pub rule: &'static str,
+    pub keyword: String,
+    pub coupons: Vec<String>,
+}
+
+impl ClaimRules {
+    pub fn is_empty(&self) -> bool {
+        self.claim_if.iter().chain(&self.never_claim).all(|k| k.trim().is_empty())
+    }
+
+    /// Verdict for a coupon
+    pub fn evaluate(&self, coupon: &Coupon) -> Verdict {
+        if let Some(keyword) = first_match(&self.never_claim, coupon) {
+            return Verdict::Never(keyword);
+        }
+        match first_match(&self.claim_if, coupon) {
+            Some(keyword) => Verdict::Claim(keyword),
+            None => Verdict::Unmatched,
+        }
+    }
+
+    /// Coupons matched by each keyword, `claim_if` first
+    pub fn matches(&self, coupons: &[Coupon]) -> Vec<RuleMatches> {
+        let rules = self.claim_if.iter().map(|k| ("claim_if", k))
+            .chain(self.never_claim.iter().map(|k| ("never_claim", k)));
+        rules
+            .filter(|(_, keyword)| !keyword.trim().is_empty())
+            .map(|(rule, keyword)| RuleMatches {
+                rule,
+                keyword: keyword.trim().to_string(),
+                coupons: coupons.iter()
+                    .filter(|c| contains(c, keyword.trim()))
+                    .map(|c| c.title.clone())
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// First non-empty keyword found in the coupon title or tags
+pub fn first_match(keywords: &[String], coupon: &Coupon) -> Option<String> {
+    keywords.iter()
+        .map(|k| k.trim())
+        .find(|k| !k.is_empty() && contains(coupon, k))
+        .map(str::to_string)
+}
+
+/// Substring match on the title and tags, ignoring case, width and traditional forms
+fn contains(coupon: &Coupon, keyword: &str) -> bool {
+    contains_key(&coupon.title, keyword) || contains_key(&coupon.tags, keyword)
+}
@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::{config::Config, coupons::{match_key, parse_amount, Coupon}};
+#[cfg(any(feature = "tui", feature = "web"))]
+use crate::utils::format_current_time;
+#[cfg(feature = "web")]
+use crate::utils::local_today;
+
+/// File holding the coupons marked as used, in the data directory
+pub const USAGE_FILE: &str = "used-coupons.json";
+
+/// A coupon the user marked as redeemed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsedCoupon {
+    pub title: String,
+    pub expiry: String,
+    pub price: String,
+    pub used_at: String,
+    /// Tags of the coupon, for savings by tag. Marks saved before tags were kept have none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl UsedCoupon {
+    fn is(&self, coupon: &Coupon) -> bool {
+        // Upstream spacing and letter case vary between listings
+        match_key(&self.title) == match_key(&coupon.title) && self.expiry == coupon.expiry
+    }
+
+    /// Day it was marked used
+    pub fn used_on(&self) -> Option<NaiveDate> {
+        NaiveDateTime::parse_from_str(&self.used_at, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .map(|at| at.date())
+    }
+
+    /// Whether it was marked used in the same month as `day`
+    fn used_in_month_of(&self, day: NaiveDate) -> bool {
+        self.used_on().is_some_and(|at| at.year() == day.year() && at.month() == day.month())
+    }
+}
+
+/// Locally persisted "used" flags. Upstream has no notion of redeemed coupons,
+/// so a coupon is identified by its title and expiry.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStore {
+    #[serde(default)]
+    pub used: Vec<UsedCoupon>,
+}
+
+impl UsageStore {
+    /// Load the store, starting empty if the file does not exist yet
+    pub fn load() -> Result<Self> {
+        let path = Config::data_path(USAGE_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .context(format!("无法读取文件: {}", path.display()))?;
+        serde_json::from_str(&text)
+            .context(format!("无法解析文件: {}", path.display()))
+    }
+
+    /// Save the store in the data directory
+    #[cfg(any(feature = "tui", feature = "web"))]
+    pub fn save(&self) -> Result<()> {
+        let path = Config::data_path(USAGE_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .context(format!("无法创建目录: {}", dir.display()))?;
+        }
+        let text = serde_json::to_string_pretty(self).context("无法序列化使用记录")?;
+        fs::write(&path, text)
+            .context(format!("无法写入文件: {}", path.display()))
+    }
+
+    pub fn is_used(&self, coupon: &Coupon) -> bool {
+        self.used.iter().any(|u| u.is(coupon))
+    }
+
+    /// Flip the used flag of a coupon. Returns the new state.
+    #[cfg(any(feature = "tui", feature = "web"))]
+    pub fn toggle(&mut self, coupon: &Coupon) -> bool {
+        if self.is_used(coupon) {
+            self.used.retain(|u| !u.is(coupon));
+            false
+        } else {
+            self.used.push(UsedCoupon {
+                title: coupon.title.clone(),
+                expiry: coupon.expiry.clone(),
+                price: coupon.price.clone(),
+                used_at: format_current_time(),
+                tags: coupon.tag_list(),
+            });
+            true
+        }
+    }
+
+    /// Set `used` on each coupon from the store
+    pub fn apply(&self, coupons: &mut [Coupon]) {
+        for coupon in coupons {
+            coupon.used = self.is_used(coupon);
+        }
+    }
+
+    /// Total value of the coupons redeemed this month
+    #[cfg(feature = "web")]
+    pub fn savings_this_month(&self) -> (f64, usize) {
+        self.savings_in(Some(local_today()))
+    }
+
+    /// Total value of the coupons redeemed in the month of `month`, or ever when not given,
+    /// with how many coupons it counts
+    pub fn savings_in(&self, month: Option<NaiveDate>) -> (f64, usize) {
+        let amounts: Vec<f64> = self.used.iter()
+            .filter(|u| month.is_none_or(|m| u.used_in_month_of(m)))
+            .filter_map(|u| parse_amount(&u.price))
+            .collect();
+        // Float `sum` of nothing is -0.0, which would show up as "¥-0"
+        let total = amounts.iter().fold(0.0, |total, amount| total + amount);
+        ((total * 100.0).round() / 100.0, amounts.len())
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::{coupons::{check_budget, match_key, parse_coupons_from_markdown, Coupon}, error::Result, mcp::McpClient};
+
+/// What a claim actually added, from the held coupons before and after it
+#[derive(Debug, Clone, Default)]
+pub struct ClaimVerification {
+    /// Held coupons that were not held before, or held in fewer copies
+    pub appeared: Vec<Coupon>,
+    /// Titles the upstream answer lists as claimed
+    pub claimed: Vec<String>,
+    /// Claimed titles that are not held at all afterwards
+    pub missing: Vec<String>,
+    /// Monthly budget limit that was already reached when the claim started, see `check_budget`
+    pub budget: Option<String>,
+    /// Whether the held coupons could be compared around the claim; the lists above are empty when not
+    pub verified: bool,
+}
+
+impl ClaimVerification {
+    /// Diff the held coupons around a claim, checking them against the claim answer
+    pub fn diff(response: &str, before: &[Coupon], after: &[Coupon]) -> Self {
+        let mut held = HashMap::new();
+        for coupon in before {
+            *held.entry(key(coupon)).or_insert(0) += coupon.count;
+        }
+        let mut appeared = Vec::new();
+        for coupon in after {
+            let left = held.entry(key(coupon)).or_insert(0);
+            if *left >= coupon.count {
+                *left -= coupon.count;
+            } else {
+                *left = 0;
+                appeared.push(coupon.clone());
+            }
+        }
+        let claimed: Vec<String> = parse_coupons_from_markdown(response).into_iter().map(|c| c.title).collect();
+        let missing = claimed.iter()
+            .filter(|title| !after.iter().any(|a| match_key(&a.title) == match_key(title)))
+            .cloned()
+            .collect();
+        Self { appeared, claimed, missing, budget: None, verified: true }
+    }
+
+    /// Upstream answered with claimed coupons, but nothing new is held
+    pub fn nothing_bound(&self) -> bool {
+        self.appeared.is_empty() && !self.claimed.is_empty()
+    }
+
+    /// Whether a claim counts as successful: upstream accepted it and it was not verified to
+    /// bind nothing. Every front end, the history, the audit log and MQTT go by this.
+    pub fn succeeded<T, E>(result: &std::result::Result<T, E>, verification: Option<&Self>) -> bool {
+        result.is_ok() && !verification.is_some_and(Self::nothing_bound)
+    }
+
+    /// Titles of the coupons that appeared; None when the claim could not be verified
+    pub fn titles(&self) -> Option<Vec<String>> {
+        self.verified.then(|| self.appeared.iter().map(|c| c.title.clone()).collect())
+    }
+
+    /// Message for a claim upstream accepted; without a verification the answer is taken at its word
+    pub fn message(verification: Option<&Self>) -> String {
+        let Some(verification) = verification else {
+            return "领取成功！".to_string();
+        };
+        let mut message = if !verification.verified {
+            "领取成功！".to_string()
+        } else if verification.nothing_bound() {
+            format!("上游返回领取成功（{}），但我的优惠券中没有新增，可能并未领取到", verification.claimed.join("、"))
+        } else if verification.appeared.is_empty() {
+            "领取成功！没有新增优惠券".to_string()
+        } else {
+            let mut message = format!("领取成功！实际新增 {} 张: {}", verification.appeared.len(), verification.appeared.iter().map(|c| c.title.as_str()).collect::<Vec<_>>().join("、"));
+            if !verification.missing.is_empty() {
+                message.push_str(&format!("；未到账: {}", verification.missing.join("、")));
+            }
+            message
+        };
+        if let Some(budget) = &verification.budget {
+            message.push_str(&format!("（提醒: {}）", budget));
+        }
+        message
+    }
+}
+
+fn key(coupon: &Coupon) -> (String, String) {
+    (match_key(&coupon.title), coupon.expiry.clone())
+}
+
+/// Budget reminder and held coupons taken around a claim, to verify what it added. Every claim
+/// path goes through this, so the budget reminder is kept even when the claim cannot be verified.
+pub struct ClaimCheck {
+    budget: Option<String>,
+    before: Option<Vec<Coupon>>,
+    after: Option<Vec<Coupon>>,
+}
+
+impl ClaimCheck {
+    /// Check the monthly budget before claiming; errors when it is set to skip
+    pub fn start() -> Result<Self> {
+        Ok(Self { budget: check_budget()?, before: None, after: None })
+    }
+
+    /// Held coupons before the claim; a failed fetch only leaves the claim unverified
+    pub fn held_before(&mut self, mine: &Result<String>) {
+        self.before = held(mine, "领取前");
+    }
+
+    /// Held coupons after the claim; a failed fetch only leaves the claim unverified
+    pub fn held_after(&mut self, mine: &Result<String>) {
+        self.after = held(mine, "领取后");
+    }
+
+    /// Fetch the held coupons after a claim upstream accepted, when there is something to
+    /// compare them with, and verify the claim
+    pub async fn verify(mut self, client: &McpClient, response: &str) -> Option<ClaimVerification> {
+        if self.before.is_some() {
+            self.held_after(&client.get_my_coupons().await);
+        }
+        self.finish(response)
+    }
+
+    /// Verification of a claim upstream accepted with `response`. Without both held lists it is
+    /// unverified, and only there at all to carry the budget reminder.
+    pub fn finish(self, response: &str) -> Option<ClaimVerification> {
+        match (self.before, self.after) {
+            (Some(before), Some(after)) => Some(ClaimVerification { budget: self.budget, ..ClaimVerification::diff(response, &before, &after) }),
+            _ => self.budget.map(|budget| ClaimVerification { budget: Some(budget), ..ClaimVerification::default() }),
+        }
+    }
+}
+
+fn held(mine: &Result<String>, when: &str) -> Option<Vec<Coupon>> {
+    match mine {
+        Ok(text) => Some(parse_coupons_from_markdown(text)),
+        Err(e) => {
+            tracing::warn!("{}获取优惠券失败，无法核对领取结果: {}", when, e);
+            None
+        },
+    }
+}
+
+/// Claim with `client`, fetching the held coupons before and after to verify what appeared.
+/// The verification is None when the claim failed, or when either fetch failed and there is no
+/// budget reminder to carry; that does not fail the claim.
+pub async fn claim_verified(client: &McpClient) -> (Result<String>, Option<ClaimVerification>) {
+    let mut check = match ClaimCheck::start() {
+        Ok(check) => check,
+        Err(e) => return (Err(e), None),
+    };
+    check.held_before(&client.get_my_coupons().await);
+    let result = client.auto_bind_coupons().await;
+    let verification = match &result {
+        Ok(response) => check.verify(client, response).await,
+        Err(_) => None,
+    };
+    (result, verification)
+}
@@ -0,0 +1,298 @@
+use anyhow::{anyhow, Context, Result};
+use fs2::FileExt;
+use std::{fs::{self, File}, io::{ErrorKind, Write}, path::PathBuf, process::Stdio, time::{Duration, Instant}};
+use tokio::process::{Child, Command};
+
+use crate::{config::Config, control};
+
+/// PID of the running supervisor, in the data directory
+pub const PID_FILE: &str = "daemon.pid";
+/// Locked by the supervisor for as long as it runs. Separate from the PID file, as a lock on
+/// Windows also keeps other processes from reading the file.
+pub const LOCK_FILE: &str = "daemon.lock";
+/// Output of the supervisor and the web mode it runs
+pub const LOG_FILE: &str = "daemon.log";
+/// The log moves to `daemon.log.1` once it grows past this size, see `rotate_log`
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Wait before restarting a web mode that exited
+const RESTART_DELAY: Duration = Duration::from_secs(10);
+/// A web mode that ran at least this long is considered healthy again
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+/// Longest wait for a stopped process to exit
+const STOP_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub fn pid_path() -> PathBuf {
+    Config::data_path(PID_FILE)
+}
+
+pub fn log_path() -> PathBuf {
+    Config::data_path(LOG_FILE)
+}
+
+/// Move the log to `daemon.log.1` once it is past [`MAX_LOG_BYTES`]. The supervisor and its web
+/// mode keep the log open for appending, so it is copied and truncated rather than renamed.
+pub fn rotate_log() -> Result<()> {
+    let log = log_path();
+    if fs::metadata(&log).map_or(true, |m| m.len() < MAX_LOG_BYTES) {
+        return Ok(());
+    }
+    let rotated = log.with_extension("log.1");
+    fs::copy(&log, &rotated).context(format!("无法写入文件: {}", rotated.display()))?;
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&log)
+        .and_then(|file| file.set_len(0))
+        .context(format!("无法写入文件: {}", log.display()))
+}
+
+/// PID of the running daemon. A PID file left by a daemon that is gone is removed, so a
+/// reused PID is never taken for the daemon.
+pub fn running() -> Option<u32> {
+    let path = pid_path();
+    if !supervisor_locked() {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+    let pid = fs::read_to_string(&path).ok()?.trim().parse::<u32>().ok()?;
+    (is_alive(pid) && is_daemon(pid)).then_some(pid)
+}
+
+/// Whether a supervisor holds the lock file
+fn supervisor_locked() -> bool {
+    let Ok(file) = fs::OpenOptions::new().write(true).open(Config::data_path(LOCK_FILE)) else {
+        return false;
+    };
+    // Released again straight away when the file is dropped
+    file.try_lock_exclusive().is_err()
+}
+
+/// Start the supervisor in the background, detached from the terminal, and return its PID
+pub async fn start_detached() -> Result<u32> {
+    let log = log_path();
+    if let Some(dir) = log.parent() {
+        fs::create_dir_all(dir).context(format!("无法创建目录: {}", dir.display()))?;
+    }
+    let output = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .context(format!("无法写入文件: {}", log.display()))?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(["daemon", "start", "--foreground"])
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(output.try_clone()?)
+        .stderr(output);
+    detach(&mut command);
+    let mut child = command.spawn().context("无法启动后台进程")?;
+
+    // Catch a supervisor that exits straight away, e.g. on a broken config
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    if let Some(status) = child.try_wait()? {
+        return Err(anyhow!("后台进程已退出 ({})，请查看日志: {}", status, log.display()));
+    }
+    Ok(child.id())
+}
+
+/// Run the web mode in a child process, restarting it whenever it exits, until the
+/// supervisor is told to stop. Holds the PID file for as long as it runs.
+pub async fn supervise() -> Result<()> {
+    if let Some(pid) = running() {
+        return Err(anyhow!("守护进程已在运行 (PID {})", pid));
+    }
+    let _pid_file = PidFile::create()?;
+    tracing::info!("守护进程已启动 (PID {})", std::process::id());
+
+    let mut failures = 0u32;
+    loop {
+        let started = Instant::now();
+        let mut child = spawn_web()?;
+        tokio::select! {
+            status = child.wait() => {
+                let status = status?;
+                // Back off further while it keeps failing right after start
+                failures = if started.elapsed() >= HEALTHY_AFTER { 1 } else { failures + 1 };
+                let delay = RESTART_DELAY * failures.min(6);
+                tracing::warn!("网页模式已退出 ({})，{} 秒后重启", status, delay.as_secs());
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {},
+                    _ = shutdown_signal() => break,
+                }
+            },
+            _ = shutdown_signal() => {
+                stop_web(&mut child).await;
+                break;
+            },
+        }
+    }
+    tracing::info!("守护进程已停止");
+    Ok(())
+}
+
+/// Ask the running daemon to stop and wait for it to exit. Returns its PID.
+pub async fn stop() -> Result<u32> {
+    let pid = running().ok_or_else(|| anyhow!("守护进程未在运行"))?;
+    // Checked again right before signalling, in case it exited meanwhile
+    if !is_daemon(pid) {
+        return Err(anyhow!("PID {} 不是本程序的守护进程，未发送停止信号", pid));
+    }
+    terminate(pid)?;
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while is_alive(pid) {
+        if Instant::now() > deadline {
+            return Err(anyhow!("守护进程 (PID {}) 在 {} 秒内未退出", pid, STOP_TIMEOUT.as_secs()));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    let _ = fs::remove_file(pid_path());
+    Ok(pid)
+}
+
+fn spawn_web() -> Result<Child> {
+    Command::new(std::env::current_exe()?)
+        .args(["web", "--no-browser"])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("无法启动网页模式")
+}
+
+/// Stop the web mode through its control socket, so it shuts down cleanly,
+/// and kill it if that does not work
+async fn stop_web(child: &mut Child) {
+    let stopped = control::send("web", "stop").await.is_ok_and(|reply| reply.success);
+    if stopped && tokio::time::timeout(STOP_TIMEOUT, child.wait()).await.is_ok() {
+        return;
+    }
+    let _ = child.kill().await;
+}
+
+/// Ctrl+C, or SIGTERM from `daemon stop`
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = terminate.recv() => {},
+                _ = tokio::signal::ctrl_c() => {},
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Holds the lock file and removes the PID file when the supervisor exits
+struct PidFile {
+    path: PathBuf,
+    _lock: File,
+}
+
+impl PidFile {
+    fn create() -> Result<Self> {
+        let lock_path = Config::data_path(LOCK_FILE);
+        if let Some(dir) = lock_path.parent() {
+            fs::create_dir_all(dir).context(format!("无法创建目录: {}", dir.display()))?;
+        }
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .context(format!("无法打开文件: {}", lock_path.display()))?;
+        lock.try_lock_exclusive().map_err(|_| anyhow!("守护进程已在运行"))?;
+
+        // Holding the lock, any PID file left is from a supervisor that is gone
+        let path = pid_path();
+        let mut file = match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                fs::remove_file(&path).context(format!("无法删除文件: {}", path.display()))?;
+                fs::OpenOptions::new().write(true).create_new(true).open(&path)
+            },
+            opened => opened,
+        }
+        .context(format!("无法写入文件: {}", path.display()))?;
+        writeln!(file, "{}", std::process::id()).context(format!("无法写入文件: {}", path.display()))?;
+        Ok(Self { path, _lock: lock })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Own process group, so closing the terminal or Ctrl+C there does not reach it
+#[cfg(unix)]
+fn detach(command: &mut std::process::Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+#[cfg(windows)]
+fn detach(command: &mut std::process::Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    // Signal 0 only checks that the process exists and may be signalled
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+/// Whether `pid` runs this program's supervisor, from its command line. The binary may have
+/// been replaced by an upgrade since, so the file name is compared rather than the file.
+#[cfg(target_os = "linux")]
+fn is_daemon(pid: u32) -> bool {
+    let Ok(cmdline) = fs::read(format!("/proc/{}/cmdline", pid)) else {
+        return false;
+    };
+    let args: Vec<String> = cmdline.split(|b| *b == 0).map(|a| String::from_utf8_lossy(a).into_owned()).collect();
+    let name = |path: &str| std::path::Path::new(path).file_name().map(|n| n.to_os_string());
+    let ours = std::env::current_exe().ok().and_then(|exe| exe.file_name().map(|n| n.to_os_string()));
+    ours.is_some() && args.first().and_then(|a| name(a)) == ours
+        && args.iter().any(|a| a == "daemon")
+        && args.iter().any(|a| a == "--foreground")
+}
+
+/// Without `/proc` the lock file is the check: only the supervisor holding it writes the PID
+#[cfg(not(target_os = "linux"))]
+fn is_daemon(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(anyhow!("无法停止守护进程 (PID {}): {}", pid, std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn is_alive(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+        .output()
+        .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).split_whitespace().any(|word| word == pid.to_string()))
+}
+
+/// Windows has no SIGTERM; ending the tree also takes the web mode down
+#[cfg(windows)]
+fn terminate(pid: u32) -> Result<()> {
+    let status = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .stdout(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("无法停止守护进程 (PID {})", pid));
+    }
+    Ok(())
+}
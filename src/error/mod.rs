@@ -0,0 +1,82 @@
+use thiserror::Error;
+
+use crate::mcp::UpstreamError;
+
+/// Failures of the config and upstream layers, typed so each frontend can react to the kind of error,
+/// e.g. send the user back to the token screen on `TokenInvalid`. Converted to anyhow only in main.
+#[derive(Debug, Error)]
+pub enum McdError {
+    /// A config file could not be read, parsed or written
+    #[error("{message}")]
+    Config {
+        message: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Upstream rejected the token
+    #[error("Token 无效或已过期，请重新设置")]
+    TokenInvalid,
+    /// Upstream answered with an HTTP error status
+    #[error("MCP Server error: {status} - {body}")]
+    UpstreamStatus { status: u16, body: String },
+    /// Upstream served a block page or an oversized body instead of an MCP response
+    #[error(transparent)]
+    Upstream(#[from] UpstreamError),
+    /// The JSON-RPC call itself failed
+    #[error("MCP error {code}: {message}")]
+    Rpc { code: i32, message: String },
+    /// The upstream tool ran and reported an error
+    #[error("MCP tool error: {0}")]
+    UpstreamTool(String),
+    /// An upstream response could not be understood
+    #[error("{0}")]
+    Parse(String),
+    /// The monthly budget was reached and is set to skip claims
+    #[error("{0}")]
+    OverBudget(String),
+    /// The request did not get a response
+    #[error("网络请求失败: {0}")]
+    Network(#[from] reqwest::Error),
+    /// An upstream call failed; `request_id` is what it sent in `X-Request-Id`
+    #[error("{error}（请求 ID: {request_id}）")]
+    Traced { request_id: String, error: Box<McdError> },
+}
+
+impl McdError {
+    /// Config error with the underlying cause kept as the source
+    pub fn config(message: impl Into<String>, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        McdError::Config {
+            message: message.into(),
+            source: Box::new(source),
+        }
+    }
+
+    /// Tag the error with the id of the upstream call that failed. A rejected token is
+    /// left as it is, as frontends match on it to ask for a new one.
+    pub fn traced(self, request_id: impl Into<String>) -> Self {
+        match self {
+            McdError::TokenInvalid | McdError::Traced { .. } => self,
+            error => McdError::Traced { request_id: request_id.into(), error: Box::new(error) },
+        }
+    }
+
+    /// The error without its request id
+    #[cfg(feature = "mcp-server")]
+    pub fn untraced(&self) -> &McdError {
+        match self {
+            McdError::Traced { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Id of the upstream call that failed, when known
+    #[cfg(feature = "mcp-server")]
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            McdError::Traced { request_id, .. } => Some(request_id),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T, E = McdError> = std::result::Result<T, E>;
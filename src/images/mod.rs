@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use crate::config::Config;
+
+// Only the interfaces that show coupon images download them; the cache folder is
+// still known to every build so backups and retention can find it
+#[cfg(any(feature = "tui", feature = "web"))]
+use anyhow::{anyhow, Context, Result};
+#[cfg(any(feature = "tui", feature = "web"))]
+use futures_util::{stream, StreamExt};
+#[cfg(any(feature = "tui", feature = "web"))]
+use reqwest::Client;
+#[cfg(any(feature = "tui", feature = "web"))]
+use std::{collections::{hash_map::DefaultHasher, HashSet}, fs, hash::{Hash, Hasher}, sync::{Mutex, OnceLock}, time::Duration};
+#[cfg(any(feature = "tui", feature = "web"))]
+use crate::coupons::Coupon;
+
+/// Folder in the cache directory holding downloaded coupon images
+const CACHE_DIR: &str = "image-cache";
+/// Images downloaded at the same time while prefetching
+#[cfg(any(feature = "tui", feature = "web"))]
+const CONCURRENCY: usize = 4;
+/// Larger downloads are dropped instead of cached
+#[cfg(any(feature = "tui", feature = "web"))]
+const MAX_IMAGE_BYTES: usize = 2 * 1024 * 1024;
+#[cfg(any(feature = "tui", feature = "web"))]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Most image URLs remembered from coupon lists; the set starts over once it is full
+#[cfg(any(feature = "tui", feature = "web"))]
+const MAX_KNOWN: usize = 2000;
+
+/// Image URLs seen in coupon lists; only these are fetched on behalf of the web page
+#[cfg(any(feature = "tui", feature = "web"))]
+static KNOWN: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Download the images of `coupons` into the disk cache in the background, a few at a time,
+/// so coupon cards render at once later even on a slow connection
+#[cfg(any(feature = "tui", feature = "web"))]
+pub fn prefetch(coupons: &[Coupon]) {
+    let urls: Vec<String> = {
+        let mut known = KNOWN.lock().unwrap_or_else(|e| e.into_inner());
+        let known = known.get_or_insert_with(HashSet::new);
+        // Cached images stay reachable through `is_known`, so forgetting the rest is harmless
+        if known.len() + coupons.len() > MAX_KNOWN {
+            known.clear();
+        }
+        coupons.iter()
+            .map(|c| c.image_url.clone())
+            .filter(|url| url.starts_with("http"))
+            .filter(|url| known.insert(url.clone()))
+            .collect()
+    };
+    let missing: Vec<String> = urls.into_iter().filter(|url| !cache_path(url).exists()).collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let total = missing.len();
+        let fetched = stream::iter(missing)
+            .map(|url| async move {
+                let result = download(&url).await;
+                if let Err(e) = &result {
+                    tracing::debug!("预取优惠券图片失败 {}: {:#}", url, e);
+                }
+                result.is_ok()
+            })
+            .buffer_unordered(CONCURRENCY)
+            .filter(|ok| std::future::ready(*ok))
+            .count()
+            .await;
+        tracing::debug!("已预取 {}/{} 张优惠券图片", fetched, total);
+    });
+}
+
+/// Whether `url` came from a coupon list or is already cached
+#[cfg(feature = "web")]
+pub fn is_known(url: &str) -> bool {
+    let known = KNOWN.lock().unwrap_or_else(|e| e.into_inner());
+    known.as_ref().is_some_and(|k| k.contains(url)) || cache_path(url).exists()
+}
+
+/// Image bytes and content type, from the disk cache or downloaded into it
+#[cfg(feature = "web")]
+pub async fn get(url: &str) -> Result<(Vec<u8>, &'static str)> {
+    let bytes = match fs::read(cache_path(url)) {
+        Ok(bytes) => bytes,
+        Err(_) => download(url).await?,
+    };
+    let content_type = content_type(&bytes);
+    Ok((bytes, content_type))
+}
+
+#[cfg(any(feature = "tui", feature = "web"))]
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let mut response = http_client()?.get(url).send().await.context("下载图片失败")?;
+    if !response.status().is_success() {
+        return Err(anyhow!("下载图片失败: HTTP {}", response.status()));
+    }
+    if response.content_length().is_some_and(|len| len > MAX_IMAGE_BYTES as u64) {
+        return Err(anyhow!("图片超过 {} 字节上限", MAX_IMAGE_BYTES));
+    }
+
+    // Read chunk by chunk so an oversized body without Content-Length is cut off early
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.context("下载图片失败")? {
+        if bytes.len() + chunk.len() > MAX_IMAGE_BYTES {
+            return Err(anyhow!("图片超过 {} 字节上限", MAX_IMAGE_BYTES));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let path = cache_path(url);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context(format!("无法创建目录: {}", dir.display()))?;
+    }
+    fs::write(&path, &bytes).context(format!("无法写入文件: {}", path.display()))?;
+    Ok(bytes)
+}
+
+/// Folder holding the cached images
+pub fn cache_dir() -> PathBuf {
+    Config::cache_path(CACHE_DIR)
+}
+
+#[cfg(any(feature = "tui", feature = "web"))]
+fn cache_path(url: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    cache_dir().join(format!("{:016x}", hasher.finish()))
+}
+
+/// Content type from the file signature, as cached files have no extension
+#[cfg(feature = "web")]
+fn content_type(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(any(feature = "tui", feature = "web"))]
+fn http_client() -> Result<Client> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("无法创建 HTTP 客户端")?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, fmt, hash::{DefaultHasher, Hash, Hasher}, sync::{atomic::{AtomicU64, Ordering}, Mutex, OnceLock}, time::{Duration, Instant}};
+use tokio::sync::watch;
+
+/// How long a finished operation answers retries carrying its idempotency key
+const IDEMPOTENCY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Operations that must not run twice at once for one account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Claim,
+    /// Reloading the held coupons
+    #[cfg_attr(not(feature = "web"), allow(dead_code))]
+    Refresh,
+}
+
+impl Operation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Operation::Claim => "claim",
+            Operation::Refresh => "refresh",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::Claim => "领取",
+            Operation::Refresh => "刷新",
+        }
+    }
+}
+
+/// How an operation ended, handed to the callers that joined or retried it
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub success: bool,
+    pub message: String,
+}
+
+struct Running {
+    id: String,
+    idempotency_key: Option<String>,
+    done: watch::Receiver<Option<Outcome>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    running: HashMap<(Operation, String), Running>,
+    /// Ids and outcomes by operation, account and idempotency key, with when they finished
+    finished: HashMap<(Operation, String, String), (Instant, String, Outcome)>,
+}
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Registry> {
+    registry().lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Account key of a token, so the registry does not keep tokens around
+pub fn account(token: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    token.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Right to run an operation; the others asking meanwhile get the outcome passed to `finish`
+pub struct Ticket {
+    id: String,
+    operation: Operation,
+    account: String,
+    idempotency_key: Option<String>,
+    done: watch::Sender<Option<Outcome>>,
+}
+
+impl Ticket {
+    #[cfg(feature = "web")]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn finish(self, outcome: Outcome) {
+        if let Some(key) = &self.idempotency_key {
+            lock().finished.insert((self.operation, self.account.clone(), key.clone()), (Instant::now(), self.id.clone(), outcome.clone()));
+        }
+        self.done.send_replace(Some(outcome));
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut registry = lock();
+        let key = (self.operation, self.account.clone());
+        if registry.running.get(&key).is_some_and(|r| r.id == self.id) {
+            registry.running.remove(&key);
+        }
+    }
+}
+
+/// An operation that is already running or already done
+pub enum Existing {
+    /// `same_key` when the caller retried with the idempotency key the operation started with
+    Running { operation: Operation, id: String, same_key: bool, done: watch::Receiver<Option<Outcome>> },
+    /// Retry of a finished operation, with its outcome
+    Done {
+        #[cfg_attr(not(feature = "web"), allow(dead_code))]
+        id: String,
+        outcome: Outcome,
+    },
+}
+
+/// Refusal to start an operation while another one runs
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub operation: Operation,
+    pub id: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "已有{}正在进行（操作 {}），请稍后查看结果", self.operation.label(), self.id)
+    }
+}
+
+impl Existing {
+    /// Id of the operation
+    #[cfg(feature = "web")]
+    pub fn id(&self) -> &str {
+        match self {
+            Existing::Running { id, .. } | Existing::Done { id, .. } => id,
+        }
+    }
+
+    /// Outcome to answer with: a retry waits for its operation to finish,
+    /// any other caller is refused with the running operation's id
+    pub async fn resolve(self) -> Result<Outcome, Conflict> {
+        match self {
+            Existing::Done { outcome, .. } => Ok(outcome),
+            Existing::Running { operation, id, same_key: false, .. } => Err(Conflict { operation, id }),
+            Existing::Running { operation, mut done, .. } => match done.wait_for(Option::is_some).await {
+                Ok(outcome) => Ok(outcome.clone().unwrap_or_else(|| interrupted(operation))),
+                // Dropped without finishing
+                Err(_) => Ok(interrupted(operation)),
+            },
+        }
+    }
+}
+
+fn interrupted(operation: Operation) -> Outcome {
+    Outcome { success: false, message: format!("{}已中断，请重试", operation.label()) }
+}
+
+/// Start `operation` for `account`, unless the same operation is running for it or
+/// `idempotency_key` names one that already finished
+pub fn begin(operation: Operation, account: &str, idempotency_key: Option<&str>) -> Result<Ticket, Existing> {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    let mut registry = lock();
+    if let Some(existing) = find(&mut registry, operation, Some(account), idempotency_key) {
+        return Err(existing);
+    }
+    let id = format!("{}-{}", operation.as_str(), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let (done, receiver) = watch::channel(None);
+    registry.running.insert((operation, account.to_string()), Running {
+        id: id.clone(),
+        idempotency_key: idempotency_key.map(str::to_string),
+        done: receiver,
+    });
+    Ok(Ticket {
+        id,
+        operation,
+        account: account.to_string(),
+        idempotency_key: idempotency_key.map(str::to_string),
+        done,
+    })
+}
+
+/// The running `operation` of any account, or the outcome `idempotency_key` names.
+/// For servers acting for one account, to answer before waiting on their own state.
+pub fn existing(operation: Operation, idempotency_key: Option<&str>) -> Option<Existing> {
+    find(&mut lock(), operation, None, idempotency_key)
+}
+
+fn find(registry: &mut Registry, operation: Operation, account: Option<&str>, idempotency_key: Option<&str>) -> Option<Existing> {
+    registry.finished.retain(|_, (at, _, _)| at.elapsed() < IDEMPOTENCY_TTL);
+    if let Some(key) = idempotency_key {
+        let done = registry.finished.iter()
+            .find(|((op, a, k), _)| *op == operation && k == key && account.is_none_or(|account| a == account))
+            .map(|(_, (_, id, outcome))| Existing::Done { id: id.clone(), outcome: outcome.clone() });
+        if done.is_some() {
+            return done;
+        }
+    }
+    registry.running.iter()
+        .find(|((op, a), _)| *op == operation && account.is_none_or(|account| a == account))
+        .map(|(_, running)| Existing::Running {
+            operation,
+            id: running.id.clone(),
+            same_key: idempotency_key.is_some() && running.idempotency_key.as_deref() == idempotency_key,
+            done: running.done.clone(),
+        })
+}
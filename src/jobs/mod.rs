@@ -0,0 +1,105 @@
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Mutex};
+
+use crate::{coupons::Coupon, progress::{self, Progress}, utils::format_current_time};
+
+/// Finished jobs kept for polling; the oldest are dropped first
+const MAX_JOBS: usize = 50;
+
+/// What a background job does
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Claim all available coupons
+    Claim,
+    /// Reload the held coupons
+    Refresh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// An operation run in the background, polled by id instead of holding the request open
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct Job {
+    /// Same as the operation id in `Operation-Id` headers, e.g. "claim-3"
+    pub id: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    /// Stage of a running claim
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>,
+    /// Result message, once finished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Held coupons after the job, when it succeeded and they are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coupons: Option<Vec<Coupon>>,
+    pub started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+}
+
+static JOBS: Mutex<VecDeque<Job>> = Mutex::new(VecDeque::new());
+
+fn jobs() -> std::sync::MutexGuard<'static, VecDeque<Job>> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record a job that has just started, returning it as first reported
+pub fn start(id: &str, kind: JobKind) -> Job {
+    let job = Job {
+        id: id.to_string(),
+        kind,
+        status: JobStatus::Running,
+        progress: None,
+        message: None,
+        coupons: None,
+        started_at: format_current_time(),
+        finished_at: None,
+    };
+    let mut jobs = jobs();
+    // Make room by dropping the oldest finished job; running ones are never dropped
+    if jobs.len() >= MAX_JOBS {
+        if let Some(oldest) = jobs.iter().position(|j| j.status != JobStatus::Running) {
+            jobs.remove(oldest);
+        }
+    }
+    jobs.push_back(job.clone());
+    job
+}
+
+/// Settle job `id` with its result
+pub fn finish(id: &str, success: bool, message: String, coupons: Option<Vec<Coupon>>) {
+    if let Some(job) = jobs().iter_mut().find(|j| j.id == id) {
+        job.status = if success { JobStatus::Succeeded } else { JobStatus::Failed };
+        job.message = Some(message);
+        job.coupons = coupons.filter(|_| success);
+        job.finished_at = Some(format_current_time());
+    }
+}
+
+/// Job `id` as it stands; a running claim carries the latest progress
+pub fn get(id: &str) -> Option<Job> {
+    jobs().iter().find(|j| j.id == id).cloned().map(with_progress)
+}
+
+/// Jobs still kept, newest first
+pub fn list() -> Vec<Job> {
+    jobs().iter().rev().cloned().map(with_progress).collect()
+}
+
+fn with_progress(mut job: Job) -> Job {
+    if job.status == JobStatus::Running && job.kind == JobKind::Claim {
+        job.progress = progress::latest();
+    }
+    job
+}
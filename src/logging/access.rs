@@ -0,0 +1,141 @@
+#[cfg(feature = "server")]
+use axum::{extract::ConnectInfo, http::{Request, Response}};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+#[cfg(feature = "server")]
+use std::{net::SocketAddr, time::Duration};
+#[cfg(feature = "server")]
+use tower_http::{
+    classify::{ServerErrorsAsFailures, SharedClassifier},
+    trace::{DefaultOnBodyChunk, DefaultOnEos, MakeSpan, OnResponse, TraceLayer},
+};
+#[cfg(feature = "server")]
+use tracing::Span;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::Config;
+
+/// Tracing target of access log events, routed to the access log file
+pub const TARGET: &str = "access";
+/// File the access log is written to, in the data directory
+pub const ACCESS_LOG_FILE: &str = "access.log";
+/// The log moves to `access.log.1` once it grows past this size
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Access log layer for the web and MCP routers: one line per request with method, path,
+/// status, latency and client IP. Bodies and query strings are never logged.
+#[cfg(feature = "server")]
+pub type AccessLogLayer = TraceLayer<
+    SharedClassifier<ServerErrorsAsFailures>,
+    AccessSpan,
+    (),
+    AccessResponse,
+    DefaultOnBodyChunk,
+    DefaultOnEos,
+    (),
+>;
+
+#[cfg(feature = "server")]
+pub fn layer() -> AccessLogLayer {
+    TraceLayer::new_for_http()
+        .make_span_with(AccessSpan)
+        .on_request(())
+        .on_response(AccessResponse)
+        .on_failure(())
+}
+
+/// Span carrying the request fields of an access log line
+#[cfg(feature = "server")]
+#[derive(Debug, Clone)]
+pub struct AccessSpan;
+
+#[cfg(feature = "server")]
+impl<B> MakeSpan<B> for AccessSpan {
+    fn make_span(&mut self, request: &Request<B>) -> Span {
+        let client = request.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        tracing::info_span!(
+            target: TARGET,
+            "request",
+            client = %client,
+            method = %request.method(),
+            path = %request.uri().path(),
+        )
+    }
+}
+
+/// Writes the access log line once the response status is known
+#[cfg(feature = "server")]
+#[derive(Debug, Clone)]
+pub struct AccessResponse;
+
+#[cfg(feature = "server")]
+impl<B> OnResponse<B> for AccessResponse {
+    fn on_response(self, response: &Response<B>, latency: Duration, _span: &Span) {
+        tracing::info!(
+            target: TARGET,
+            status = response.status().as_u16(),
+            latency_ms = latency.as_millis() as u64,
+        );
+    }
+}
+
+/// Size-rotated log file: `access.log` is renamed to `access.log.1` past [`MAX_LOG_BYTES`]
+pub struct RotatingFile {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl RotatingFile {
+    pub fn access_log() -> Self {
+        Self {
+            path: Config::data_path(ACCESS_LOG_FILE),
+            file: Mutex::new(None),
+        }
+    }
+
+    fn open(&self) -> io::Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&self.path)
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = guard.as_ref() {
+            if file.metadata()?.len() >= MAX_LOG_BYTES {
+                *guard = None;
+                fs::rename(&self.path, self.path.with_extension("log.1"))?;
+            }
+        }
+        let file = match guard.as_mut() {
+            Some(file) => file,
+            None => guard.insert(self.open()?),
+        };
+        file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = &'a RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
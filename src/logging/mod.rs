@@ -0,0 +1,41 @@
+use tracing::Level;
+use tracing_subscriber::{filter::Targets, prelude::*};
+
+use crate::{cli::Verbosity, config::Config};
+
+pub mod access;
+
+/// Install the global tracing subscriber, writing to stderr so JSON on stdout stays clean.
+/// Not used in TUI mode, where stderr output would corrupt the screen.
+/// With `access_log` enabled in the config, request lines also go to the access log file.
+pub fn init(verbosity: Verbosity) {
+    let (level, dependency_level) = match verbosity {
+        Verbosity::Quiet => (Level::ERROR, Level::ERROR),
+        Verbosity::Normal => (Level::INFO, Level::WARN),
+        Verbosity::Verbose => (Level::DEBUG, Level::WARN),
+        Verbosity::Trace => (Level::TRACE, Level::INFO),
+    };
+
+    let filter = Targets::new()
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_target(access::TARGET, Level::ERROR)
+        .with_default(dependency_level);
+
+    let access_log = Config::load().is_ok_and(|c| c.access_log).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(access::RotatingFile::access_log())
+            .with_ansi(false)
+            .with_target(false)
+            .with_filter(Targets::new().with_target(access::TARGET, Level::INFO))
+    });
+
+    let _ = tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_target(false)
+                .with_filter(filter),
+        )
+        .with(access_log)
+        .try_init();
+}
@@ -1,83 +1,386 @@
 use anyhow::Result;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 
 // Import TUI dependencies
-use crossterm::{terminal::{EnterAlternateScreen, LeaveAlternateScreen}, execute, event::{EnableMouseCapture, DisableMouseCapture}};
+#[cfg(feature = "tui")]
+use crossterm::{terminal::{EnterAlternateScreen, LeaveAlternateScreen}, execute, event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture}};
+#[cfg(feature = "tui")]
 use ratatui::{backend::CrosstermBackend, Terminal};
 
+mod alert;
+mod allowlist;
+mod audit;
+mod backup;
+mod cli;
 mod config;
+mod control;
+mod coupons;
+mod daemon;
+mod error;
+mod images;
+#[cfg(feature = "server")]
+mod inflight;
+#[cfg(feature = "web")]
+mod jobs;
+mod logging;
 mod mcp;
+#[cfg(feature = "mcp-server")]
 mod mcp_server;
+mod migrate;
+mod mqtt;
+mod preflight;
+#[cfg(any(feature = "tui", feature = "web"))]
+mod progress;
+mod queue;
+mod retention;
+mod scheduler;
+#[cfg(feature = "web")]
+mod share;
+#[cfg(feature = "storage")]
+mod sync;
+#[cfg(feature = "tui")]
 mod ui;
 mod utils;
+#[cfg(any(feature = "tui", feature = "mcp-server"))]
 use utils::open_mcp_login_page;
+#[cfg(feature = "web")]
 mod web;
 
 /// Application mode
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 enum Mode {
     /// Terminal User Interface mode
     Tui,
+    /// Line-based TUI for screen readers and dumb terminals
+    Plain,
     /// HTML web interface mode
     Html,
     /// MCP Server mode
     McpServer,
 }
 
+impl Mode {
+    /// The Cargo feature the mode is built with, and whether this build has it
+    fn feature(self) -> (&'static str, &'static str, bool) {
+        match self {
+            Mode::Tui | Mode::Plain => ("终端模式", "tui", cfg!(feature = "tui")),
+            Mode::Html => ("网页模式", "web", cfg!(feature = "web")),
+            Mode::McpServer => ("MCP服务器模式", "mcp-server", cfg!(feature = "mcp-server")),
+        }
+    }
+}
+
+/// Whether any mode was built in; without one only the commands are available
+const HAS_MODES: bool = cfg!(any(feature = "tui", feature = "web", feature = "mcp-server"));
+
+impl From<config::StartMode> for Mode {
+    fn from(mode: config::StartMode) -> Self {
+        match mode {
+            config::StartMode::Tui => Mode::Tui,
+            config::StartMode::Plain => Mode::Plain,
+            config::StartMode::Html => Mode::Html,
+            config::StartMode::McpServer => Mode::McpServer,
+        }
+    }
+}
+
 fn main() -> Result<()> {
+    // Reported by `debug timings`
+    let started = std::time::Instant::now();
     // Check command line arguments
-    let args: Vec<String> = std::env::args().collect();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (mut options, args) = match cli::parse_global_options(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("{}", e);
+            cli::print_help();
+            return Ok(());
+        }
+    };
+
+    if let Some(profile) = &options.profile {
+        // Through the environment, so processes started by `daemon` use it too
+        std::env::set_var(config::PROFILE_ENV, profile);
+    }
+    match config::Config::check_profile() {
+        Ok(Some(profile)) => eprintln!("正在使用配置 profile: {}", profile),
+        Ok(None) => {}
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
 
-    let mode = if args.len() > 1 {
+    // TUI mode draws on the terminal, so it must not get log lines on stderr.
+    // Without arguments the mode is only known after the menu, see below.
+    let plain = args.iter().any(|a| a == "--plain");
+    let defer_logging = !plain && args
+        .first()
+        .is_none_or(|a| matches!(a.to_lowercase().as_str(), "tui" | "-tui" | "--tui" | "1"));
+    if !defer_logging {
+        logging::init(options.verbosity);
+    }
+
+    // Help, completions and the like only print, so they leave the data files as they are
+    if touches_data(args.first().map(String::as_str)) {
+        prepare_data();
+    }
+
+    let config = config::ConfigService::global().current();
+    apply_settings(&config);
+    follow_settings();
+
+    if let Some(path) = &options.record {
+        if let Err(e) = mcp::session::start_recording(path) {
+            println!("无法开始录制: {:#}", anyhow::Error::from(e));
+            std::process::exit(1);
+        }
+        eprintln!("正在录制与麦当劳服务的交互到 {}（不含 Token）", path.display());
+    }
+    if let Some(path) = &options.replay {
+        match mcp::session::start_replay(path) {
+            Ok(count) => eprintln!("正在回放 {}（{} 条记录），不会连接麦当劳服务", path.display(), count),
+            Err(e) => {
+                println!("无法回放录制文件: {:#}", anyhow::Error::from(e));
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(chaos) = &options.chaos {
+        eprintln!("混沌测试已启用（{}），上游响应可能是模拟的", chaos.describe());
+        mcp::chaos::enable(chaos.clone());
+    }
+
+    let mode = if let Some(command) = args.first() {
         // Parse command line argument
-        match args[1].to_lowercase().as_str() {
+        match command.to_lowercase().as_str() {
+            "tui" | "-tui" | "--tui" | "1" if plain => Mode::Plain,
             "tui" | "-tui" | "--tui" | "1" => Mode::Tui,
+            "--plain" => Mode::Plain,
             "html" | "-html" | "--html" | "web" | "-web" | "--web" | "2" => Mode::Html,
             "mcpserver" | "-mcpserver" | "--mcpserver" | "mcp-server" | "3" => Mode::McpServer,
+            "claim" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::claim(&options))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "plan" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::plan(&options))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "list" | "ls" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::list(&options, &args[1..]))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "watch" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::watch(&options))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "rules" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::rules(&options))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "validate" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::validate(&options))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "backup" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::backup(&options, &args[1..]))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "ctl" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::ctl(&options, &args[1..]))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "daemon" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::daemon(&options, &args[1..]))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "debug" => {
+                let runtime = tokio::runtime::Runtime::new()?;
+                if !runtime.block_on(cli::commands::debug(&options, &args[1..], started))? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "stats" => {
+                if !cli::commands::stats(&options, &args[1..])? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "audit" => {
+                if !cli::commands::audit(&options, &args[1..])? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "prune" => {
+                if !cli::commands::prune(&options, &args[1..])? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "set-default" => {
+                if !cli::commands::set_default(&options, &args[1..])? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "sync" => {
+                if !cli::commands::sync(&options, &args[1..])? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            "completions" => {
+                let Some(shell) = args.get(1) else {
+                    println!("请指定 shell: {}", cli::SHELLS.join(" | "));
+                    return Ok(());
+                };
+                print!("{}", cli::completions::generate(shell)?);
+                return Ok(());
+            }
+            "manpage" => {
+                print!("{}", cli::manpage::generate());
+                return Ok(());
+            }
             "-h" | "--help" | "help" => {
-                print_help();
+                cli::print_help();
                 return Ok(());
             }
             _ => {
-                println!("未知参数: {}", args[1]);
-                print_help();
+                println!("未知参数: {}", command);
+                cli::print_help();
                 return Ok(());
             }
         }
-    } else {
+    } else if let Some(mode) = config.default_mode.filter(|_| !options.menu) {
+        println!("正在启动{}（默认模式，加 --menu 可重新选择）...", mode.label());
+        mode.into()
+    } else if !HAS_MODES {
+        // A commands-only build has nothing to pick from
+        cli::print_help();
+        return Ok(());
+    } else if io::stdin().is_terminal() && io::stdout().is_terminal() {
         // No arguments - show interactive menu
         show_mode_menu()?
+    } else {
+        // Scripts and pipes cannot answer the menu, so fail instead of waiting on stdin
+        eprintln!("未指定运行模式，且当前不是交互式终端，无法显示模式选择菜单。");
+        eprintln!("请在命令行中指定模式，例如: {} html | mcpserver | claim", cli::BIN_NAME);
+        eprintln!("运行 {} --help 查看所有命令。", cli::BIN_NAME);
+        std::process::exit(1);
     };
 
+    let (label, feature, compiled) = mode.feature();
+    if !compiled {
+        println!("{}", cli::not_compiled(label, feature));
+        std::process::exit(1);
+    }
+
+    // Plain mode is read line by line, so it never gets colors either
+    if matches!(mode, Mode::Plain) {
+        options.color = cli::ColorChoice::Never;
+    }
+
+    // Catch missing prerequisites up front, with a hint on how to fix each
+    let target = match mode {
+        Mode::Tui => preflight::Target::Tui,
+        Mode::Plain => preflight::Target::Plain,
+        Mode::Html => preflight::Target::Html,
+        Mode::McpServer => preflight::Target::McpServer,
+    };
+    if !tokio::runtime::Runtime::new()?.block_on(preflight::run(target, &options)) {
+        std::process::exit(1);
+    }
+
     match mode {
-        Mode::Tui => {
-            run_tui_mode()?;
+        #[cfg(feature = "tui")]
+        Mode::Tui => run_tui_mode(options.color),
+        #[cfg(feature = "tui")]
+        Mode::Plain => {
+            let runtime = tokio::runtime::Runtime::new()?;
+            runtime.block_on(ui::plain::run())
         },
+        #[cfg(feature = "web")]
         Mode::Html => {
+            logging::init(options.verbosity);
             let runtime = tokio::runtime::Runtime::new()?;
-            runtime.block_on(web::run())?;
+            runtime.block_on(web::run(!args.iter().any(|a| a == "--no-browser")))
         },
+        #[cfg(feature = "mcp-server")]
         Mode::McpServer => {
+            logging::init(options.verbosity);
             let runtime = tokio::runtime::Runtime::new()?;
-            runtime.block_on(run_mcp_server_mode())?;
+            runtime.block_on(run_mcp_server_mode())
         },
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("modes left out of the build are refused above"),
     }
+}
 
-    Ok(())
+/// Apply the config options that are kept in globals rather than read on use
+fn apply_settings(config: &config::Config) {
+    if let Err(e) = utils::set_timezone(config.timezone.as_deref()) {
+        tracing::warn!("{:#}", e);
+    }
+    if let Some(limit) = config.max_response_bytes {
+        mcp::client::set_max_body_bytes(limit);
+    }
+    if let Err(e) = mcp::client::set_upstream_headers(config.user_agent.as_deref(), &config.upstream_headers) {
+        tracing::warn!("{}", e);
+    }
+    coupons::set_keep_duplicates(config.keep_duplicate_coupons);
+    coupons::set_show_calories(config.show_calories);
+    coupons::set_deep_links(config.deep_links.clone());
+    if let Err(e) = coupons::set_field_labels(&config.field_labels) {
+        tracing::warn!("{:#}", e);
+    }
 }
 
-/// Print help information
-fn print_help() {
-    println!();
-    println!("麦当劳优惠券自动领取工具");
-    println!();
-    println!("用法:");
-    println!("  mcd-coupon          交互式选择模式");
-    println!("  mcd-coupon tui      终端界面模式");
-    println!("  mcd-coupon html     网页界面模式");
-    println!("  mcd-coupon mcpserver MCP服务器模式");
-    println!("  mcd-coupon --help   显示帮助信息");
-    println!();
+/// Re-apply the global options whenever the config changes, so edits made while
+/// running take effect without a restart
+fn follow_settings() {
+    let mut changes = config::ConfigService::global().subscribe();
+    std::thread::spawn(move || {
+        let Ok(runtime) = tokio::runtime::Builder::new_current_thread().build() else {
+            return;
+        };
+        runtime.block_on(async {
+            while changes.changed().await.is_ok() {
+                let config = changes.borrow_and_update().clone();
+                apply_settings(&config);
+            }
+        });
+    });
 }
 
 /// Show interactive mode selection menu
@@ -107,59 +410,143 @@ fn show_mode_menu() -> Result<Mode> {
     io::stdin().read_line(&mut input)?;
     let input = input.trim();
 
-    match input {
-        "" | "1" | "html" | "web" => {
+    let mode = match input {
+        "" | "1" | "html" | "web" => config::StartMode::Html,
+        "2" | "tui" => config::StartMode::Tui,
+        "3" | "mcpserver" | "mcp-server" => config::StartMode::McpServer,
+        _ => {
             println!();
-            println!("正在启动网页模式...");
-            Ok(Mode::Html)
+            println!("无效选项，默认启动网页模式...");
+            return Ok(Mode::Html);
         }
-        "2" | "tui" => {
-            println!();
-            println!("正在启动终端模式...");
-            Ok(Mode::Tui)
+    };
+    offer_default(mode)?;
+
+    println!();
+    println!("正在启动{}...", mode.label());
+    Ok(mode.into())
+}
+
+/// Ask whether to remember the choice, so the next run skips the menu
+fn offer_default(mode: config::StartMode) -> Result<()> {
+    print!("记住我的选择，下次直接启动{}？[y/N]: ", mode.label());
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    if !input.trim().eq_ignore_ascii_case("y") {
+        return Ok(());
+    }
+    match cli::commands::save_default_mode(Some(mode)) {
+        Ok(()) => println!("已记住，下次运行将直接启动{}；运行 {} --menu 可重新选择，{} set-default menu 可取消", mode.label(), cli::BIN_NAME, cli::BIN_NAME),
+        Err(e) => println!("保存默认模式失败: {:#}", e),
+    }
+    Ok(())
+}
+
+/// Commands that only print, see `touches_data`
+const PRINT_ONLY: &[&str] = &["completions", "manpage", "help"];
+
+/// Whether `command` reads or writes the local data files. Print-only commands and anything
+/// not recognized, which just shows the help, leave them untouched.
+fn touches_data(command: Option<&str>) -> bool {
+    let Some(command) = command.map(str::to_lowercase) else {
+        return true;
+    };
+    let name = match command.as_str() {
+        "--plain" => "tui",
+        other => other,
+    };
+    cli::COMMANDS.iter()
+        .find(|c| c.name == name.trim_start_matches('-') || c.aliases.contains(&name))
+        .is_some_and(|c| !PRINT_ONLY.contains(&c.name))
+}
+
+/// Move and upgrade the local data files left by an older build, exiting when that fails
+fn prepare_data() {
+    // Move data files out of the config directory, where older builds kept them
+    match migrate::relocate() {
+        Ok(Some(relocation)) => {
+            if !relocation.moved.is_empty() {
+                eprintln!(
+                    "已将 {} 项本地数据从 {} 移到新位置（数据目录 {}，缓存目录 {}）",
+                    relocation.moved.len(),
+                    relocation.from.display(),
+                    config::Config::data_dir().display(),
+                    config::Config::cache_dir().display()
+                );
+            }
+            for path in &relocation.kept {
+                eprintln!("新位置已有同名文件，未移动 {}，确认不再需要后可删除", path.display());
+            }
         }
-        "3" | "mcpserver" | "mcp-server" => {
-            println!();
-            println!("正在启动MCP服务器模式...");
-            Ok(Mode::McpServer)
+        Ok(None) => {}
+        Err(e) => {
+            println!("无法移动本地数据: {:#}", e);
+            std::process::exit(1);
         }
-        _ => {
-            println!();
-            println!("无效选项，默认启动网页模式...");
-            Ok(Mode::Html)
+    }
+
+    // Upgrade data files left by an older build before anything reads them
+    match migrate::run() {
+        Ok(Some(report)) => eprintln!(
+            "已将本地数据从版本 {} 升级到 {}，升级前的数据已备份到 {}",
+            report.from,
+            report.to,
+            report.backup.display()
+        ),
+        Ok(None) => {}
+        Err(e) => {
+            println!("无法升级本地数据: {:#}", e);
+            std::process::exit(1);
         }
     }
 }
 
 /// Run the application in TUI mode
-fn run_tui_mode() -> Result<()> {
-    // Set up terminal
+#[cfg(feature = "tui")]
+fn run_tui_mode(color: cli::ColorChoice) -> Result<()> {
+    if !io::stdout().is_terminal() {
+        return Err(anyhow::anyhow!("终端模式需要在交互式终端中运行，脚本中请使用 html、mcpserver 或 claim 等命令"));
+    }
+
+    // Load configuration and validate key bindings before taking over the terminal
+    let config = config::ConfigService::global().current();
+    let keymap = ui::KeyMap::from_config(&config.keybindings)?;
+
+    // Restore the terminal before a panic message is printed, so it is readable. Tokio would
+    // catch a panic in a spawned task and the TUI would keep drawing into the restored terminal,
+    // so any panic ends the process here.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+        std::process::abort();
+    }));
+
+    // Set up terminal; pasted text arrives as one event instead of a burst of keys.
+    // The guard restores it however this function returns.
+    let _guard = TerminalGuard;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
     crossterm::terminal::enable_raw_mode()?;
 
     // Create backend and terminal
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Load configuration
-    let config = config::Config::load()?;
-
     // Initialize application
     let mut app = ui::App::new();
+    app.keymap = keymap;
+    app.color = color.enabled(true);
+    app.theme = ui::Theme::new(config.theme, app.color);
 
-    // Set up MCP client if token exists
+    // Load and validate a saved token behind the splash screen, so the first frame
+    // is drawn before any network I/O
+    let runtime = tokio::runtime::Runtime::new()?;
     if config.has_valid_token() {
-        match mcp::McpClient::new(config.token.clone()) {
-            Ok(client) => {
-                app.mcp_client = Some(std::sync::Arc::new(tokio::sync::Mutex::new(client)));
-                app.current_screen = ui::screens::ScreenType::Main(ui::screens::MainScreen::new());
-                app.add_log("已加载保存的Token".to_string());
-            },
-            Err(e) => {
-                app.add_log(format!("加载Token失败: {}", e));
-            },
-        }
+        let _runtime = runtime.enter();
+        let url = config.upstream_url().map(str::to_string);
+        app.current_screen = ui::screens::ScreenType::Splash(ui::screens::SplashScreen::start(config.token.clone(), url));
     } else {
         // If no valid token, start with token input screen and help open login page
         println!("未检测到已保存的 Token，请在浏览器打开获取：https://open.mcd.cn/mcp/login");
@@ -168,21 +555,32 @@ fn run_tui_mode() -> Result<()> {
     }
 
     // Run application
-    let runtime = tokio::runtime::Runtime::new()?;
-    let result = runtime.block_on(app.run(&mut terminal));
+    runtime.block_on(app.run(&mut terminal))
+}
 
-    // Clean up
-    crossterm::terminal::disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
-    terminal.show_cursor()?;
+/// Leaves the TUI terminal state when dropped
+#[cfg(feature = "tui")]
+struct TerminalGuard;
 
-    result
+#[cfg(feature = "tui")]
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Back to the normal screen in cooked mode, ignoring errors as this runs during cleanup
+#[cfg(feature = "tui")]
+fn restore_terminal() {
+    let _ = crossterm::terminal::disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, DisableBracketedPaste, crossterm::cursor::Show);
 }
 
 /// Run the application in MCP Server mode
+#[cfg(feature = "mcp-server")]
 async fn run_mcp_server_mode() -> Result<()> {
     // Load configuration
-    let mut config = config::Config::load()?;
+    let mut config = config::ConfigService::global().current();
 
     // If no token, allow user to input once and save
     if !config.has_valid_token() {
@@ -207,18 +605,21 @@ async fn run_mcp_server_mode() -> Result<()> {
             return Ok(());
         }
 
-        config.token = token;
-        if let Err(e) = config.save() {
-            println!("保存 Token 失败: {}", e);
-            println!("配置文件位置: {}", config::Config::get_config_path().display());
-            return Ok(());
-        }
+        config = match config::ConfigService::global().update(|config| config.token = token) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("保存 Token 失败: {}", e);
+                println!("配置文件位置: {}", config::Config::get_config_path().display());
+                return Ok(());
+            },
+        };
+        audit::record("cli", audit::Action::TokenSet, true, utils::mask_token(&config.token));
 
         println!("Token 已保存到配置文件: {}", config::Config::get_config_path().display());
     }
 
     // Initialize MCP client
-    let mcp_client = match mcp::McpClient::new(config.token.clone()) {
+    let mcp_client = match mcp::McpClient::with_url(config.token.clone(), config.upstream_url()) {
         Ok(client) => client,
         Err(e) => {
             println!("初始化MCP客户端失败: {}", e);
@@ -0,0 +1,118 @@
+use std::{collections::hash_map::RandomState, hash::BuildHasher, str::FromStr, sync::{atomic::{AtomicU64, Ordering}, Mutex}, time::Duration};
+
+use crate::mcp::client::Answer;
+
+/// `Retry-After` sent with an injected 429, short enough to be retried automatically
+const INJECTED_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Misbehaviour injected into upstream calls for resilience testing, from `--chaos`,
+/// e.g. `latency=200-1500,fail=0.1,limit=0.05,malformed=0.1`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChaosConfig {
+    /// Extra delay before every call, picked between the two bounds in milliseconds
+    pub latency_ms: (u64, u64),
+    /// Chance of an HTTP 500 answer
+    pub fail: f64,
+    /// Chance of an HTTP 429 answer with a short `Retry-After`
+    pub rate_limit: f64,
+    /// Chance of a truncated, non-JSON or empty answer
+    pub malformed: f64,
+}
+
+impl FromStr for ChaosConfig {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part.split_once('=')
+                .ok_or_else(|| format!("无法识别 \"{}\"，应为 键=值", part))?;
+            match key.trim() {
+                "latency" => {
+                    let (low, high) = value.split_once('-').unwrap_or((value, value));
+                    let parse = |v: &str| v.trim().parse::<u64>().map_err(|_| format!("延迟应为毫秒数或范围，如 300 或 100-2000: {}", value));
+                    let (low, high) = (parse(low)?, parse(high)?);
+                    config.latency_ms = (low.min(high), low.max(high));
+                },
+                "fail" => config.fail = probability(key, value)?,
+                "limit" => config.rate_limit = probability(key, value)?,
+                "malformed" => config.malformed = probability(key, value)?,
+                other => return Err(format!("未知的混沌测试项: {} (可选: latency, fail, limit, malformed)", other)),
+            }
+        }
+        if config.fail + config.rate_limit + config.malformed > 1.0 {
+            return Err("fail、limit 和 malformed 的概率之和不能超过 1".to_string());
+        }
+        Ok(config)
+    }
+}
+
+impl ChaosConfig {
+    /// One-line description for the startup notice
+    pub fn describe(&self) -> String {
+        let (low, high) = self.latency_ms;
+        let latency = if low == high { format!("{} ms", low) } else { format!("{}-{} ms", low, high) };
+        format!(
+            "延迟 {}，失败 {:.0}%，限流 {:.0}%，畸形响应 {:.0}%",
+            latency,
+            self.fail * 100.0,
+            self.rate_limit * 100.0,
+            self.malformed * 100.0
+        )
+    }
+}
+
+fn probability(key: &str, value: &str) -> Result<f64, String> {
+    value.trim().parse::<f64>().ok()
+        .filter(|p| (0.0..=1.0).contains(p))
+        .ok_or_else(|| format!("{} 应为 0 到 1 之间的概率: {}", key, value))
+}
+
+static CHAOS: Mutex<Option<ChaosConfig>> = Mutex::new(None);
+
+/// Inject `config` into every upstream call from now on
+pub fn enable(config: ChaosConfig) {
+    *CHAOS.lock().unwrap_or_else(|e| e.into_inner()) = Some(config);
+}
+
+/// Sit out the injected latency, then maybe answer in place of upstream.
+/// None when chaos testing is off or this call is let through.
+pub(crate) async fn inject(tool_name: &str) -> Option<Answer> {
+    let config = CHAOS.lock().unwrap_or_else(|e| e.into_inner()).clone()?;
+
+    let (low, high) = config.latency_ms;
+    if high > 0 {
+        let delay = low + (roll() * (high - low) as f64) as u64;
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+
+    let dice = roll();
+    let injected = if dice < config.fail {
+        (reqwest::StatusCode::INTERNAL_SERVER_ERROR, "application/json".to_string(), r#"{"error":"chaos"}"#.to_string(), None)
+    } else if dice < config.fail + config.rate_limit {
+        (reqwest::StatusCode::TOO_MANY_REQUESTS, "application/json".to_string(), r#"{"error":"chaos"}"#.to_string(), Some(INJECTED_RETRY_AFTER))
+    } else if dice < config.fail + config.rate_limit + config.malformed {
+        malformed()
+    } else {
+        return None;
+    };
+    tracing::warn!("混沌测试: {} 返回模拟响应 HTTP {}", tool_name, injected.0);
+    Some(injected)
+}
+
+/// One of the broken answers seen from real upstreams
+fn malformed() -> Answer {
+    let ok = reqwest::StatusCode::OK;
+    match (roll() * 3.0) as u8 {
+        0 => (ok, "application/json".to_string(), r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"te"#.to_string(), None),
+        1 => (ok, "text/html".to_string(), "<html><body>chaos</body></html>".to_string(), None),
+        _ => (ok, "application/json".to_string(), String::new(), None),
+    }
+}
+
+/// Uniform number in [0, 1), from the std hasher's random keys like the retry jitter
+fn roll() -> f64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = RandomState::new().hash_one(COUNTER.fetch_add(1, Ordering::Relaxed));
+    (n >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -1,10 +1,94 @@
-use crate::mcp::types::*;
-use anyhow::{anyhow, Result};
-use reqwest::Client;
-use std::time::Duration;
+use crate::{error::{McdError, Result}, mcp::{chaos, limiter, metrics, session, types::*, UpstreamError}};
+#[cfg(feature = "server")]
+use crate::utils::parse_server_time;
+#[cfg(feature = "server")]
+use chrono::{DateTime, Utc};
+use reqwest::{header::{HeaderMap, HeaderName, HeaderValue}, Client};
+use std::collections::{hash_map::RandomState, BTreeMap};
+use std::hash::BuildHasher;
+use std::sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 const MCP_SERVER_URL: &str = "https://mcp.mcd.cn/mcp-servers/mcd-mcp";
 const TIMEOUT: Duration = Duration::from_secs(30);
+/// How long an idle pooled connection is kept for reuse
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// Idle connections kept per host, enough for concurrent multi-account claims
+const POOL_MAX_IDLE_PER_HOST: usize = 8;
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// Default cap on upstream response bodies (4 MiB)
+pub const DEFAULT_MAX_BODY_BYTES: usize = 4 * 1024 * 1024;
+/// Response bodies longer than this are truncated in logs and error messages
+const LOG_BODY_LIMIT: usize = 2048;
+/// Header carrying the id of each upstream call, for matching our logs with upstream's
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// `User-Agent` sent unless the `user_agent` config option replaces it
+pub const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+/// Headers every request sets itself, which `upstream_headers` may not override
+const RESERVED_HEADERS: &[&str] = &["authorization", "content-type", "content-length", "host", "x-request-id"];
+
+static MAX_BODY_BYTES: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_BODY_BYTES);
+static HEADERS: Mutex<Option<HeaderMap>> = Mutex::new(None);
+
+/// Set the response size limit used by every client, from the `max_response_bytes` config option
+pub fn set_max_body_bytes(limit: usize) {
+    MAX_BODY_BYTES.store(limit, Ordering::Relaxed);
+}
+
+/// Set the `User-Agent` and extra headers sent by every client, from the `user_agent` and
+/// `upstream_headers` config options. Invalid entries are left out and reported.
+pub fn set_upstream_headers(user_agent: Option<&str>, extra: &BTreeMap<String, String>) -> Result<()> {
+    let (headers, result) = upstream_headers(user_agent, extra);
+    *HEADERS.lock().unwrap_or_else(|e| e.into_inner()) = Some(headers);
+    result
+}
+
+/// Whether the `user_agent` and every `upstream_headers` entry make valid headers
+pub fn check_upstream_headers(user_agent: Option<&str>, extra: &BTreeMap<String, String>) -> Result<()> {
+    upstream_headers(user_agent, extra).1
+}
+
+fn upstream_headers(user_agent: Option<&str>, extra: &BTreeMap<String, String>) -> (HeaderMap, Result<()>) {
+    let mut headers = HeaderMap::new();
+    let mut invalid = Vec::new();
+    let user_agent = user_agent.map(str::trim).filter(|ua| !ua.is_empty()).unwrap_or(DEFAULT_USER_AGENT);
+    match HeaderValue::from_str(user_agent) {
+        Ok(value) => { headers.insert(reqwest::header::USER_AGENT, value); },
+        Err(_) => {
+            invalid.push(format!("user_agent \"{}\"", user_agent));
+            headers.insert(reqwest::header::USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
+        },
+    }
+    for (name, value) in extra {
+        let parsed = HeaderName::from_bytes(name.trim().as_bytes()).ok()
+            .filter(|name| !RESERVED_HEADERS.contains(&name.as_str()))
+            .zip(HeaderValue::from_str(value.trim()).ok());
+        match parsed {
+            Some((name, value)) => { headers.insert(name, value); },
+            None => invalid.push(format!("\"{}\": \"{}\"", name, value)),
+        }
+    }
+    let result = if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(McdError::Parse(format!(
+            "{} 不是有效的请求头（upstream_headers 不能设置 {}）",
+            invalid.join("、"),
+            RESERVED_HEADERS.join("、")
+        )))
+    };
+    (headers, result)
+}
+
+/// Headers set by `set_upstream_headers`, or the default `User-Agent` before it is called
+fn configured_headers() -> HeaderMap {
+    HEADERS.lock().unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| upstream_headers(None, &BTreeMap::new()).0)
+}
+
+/// Upstream answer: status, content type, body and `Retry-After` delay
+pub(crate) type Answer = (reqwest::StatusCode, String, String, Option<Duration>);
 
 /// MCP Client for interacting with McDonald's MCP Server
 #[derive(Debug, Clone)]
@@ -17,9 +101,7 @@ pub struct McpClient {
 impl McpClient {
     /// Create a new MCP client with the given token
     pub fn new(token: String) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(TIMEOUT)
-            .build()?;
+        let client = shared_http_client()?;
 
         Ok(Self {
             client,
@@ -28,13 +110,22 @@ impl McpClient {
         })
     }
 
+    /// Create a client for a custom upstream URL, or the default one when `url` is None
+    pub fn with_url(token: String, url: Option<&str>) -> Result<Self> {
+        let mut client = Self::new(token)?;
+        if let Some(url) = url {
+            client.set_url(url.to_string());
+        }
+        Ok(client)
+    }
+
     /// Set a custom MCP server URL
     pub fn set_url(&mut self, url: String) {
         self.url = url;
     }
 
     /// Validate if the token is valid by making a test request
-    pub async fn validate_token(&self) -> Result<bool, String> {
+    pub async fn validate_token(&self) -> Result<bool> {
         // Instead of using 'test' method, use a simple RPC request with a known structure
         // Even if method doesn't exist, we can still check authorization status
         let rpc_request = serde_json::json!({
@@ -44,16 +135,29 @@ impl McpClient {
             "id": 1
         });
         
+        if let Some(replayed) = session::replay("system.listMethods") {
+            return Ok(replayed?.status != reqwest::StatusCode::UNAUTHORIZED.as_u16());
+        }
+        limiter::wait().await?;
+
+        let request_id = new_request_id();
+        tracing::debug!("验证 Token ({}, 请求 ID {})", self.url, request_id);
         match self.client
             .post(&self.url)
+            .headers(configured_headers())
             .header("Authorization", &self.token)
             .header("Content-Type", "application/json")
+            .header(REQUEST_ID_HEADER, &request_id)
             .json(&rpc_request)
             .send()
             .await
         {
             Ok(response) => {
                 let status = response.status();
+                session::record("system.listMethods", &rpc_request, status.as_u16(), "", "", &self.token);
+                if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    return Err(limiter::limited(status.as_u16(), retry_after(&response)).into());
+                }
                 
                 // If we get 401 Unauthorized, token is definitely invalid
                 if status == reqwest::StatusCode::UNAUTHORIZED {
@@ -64,15 +168,21 @@ impl McpClient {
                     Ok(true)
                 }
             },
-            Err(e) => {
-                let error_msg = format!("网络请求失败: {}", e);
-                Err(error_msg)
-            }
+            Err(e) => Err(McdError::Network(e).traced(request_id)),
         }
     }
 
-    /// Call an MCP tool with the given parameters
+    /// Call an MCP tool with the given parameters. Failures carry the id the call
+    /// was sent with in `X-Request-Id`, and are logged with it.
     pub async fn call_tool(&self, tool_name: &str, params: serde_json::Value) -> Result<String> {
+        let request_id = new_request_id();
+        self.call(tool_name, params, &request_id).await.map_err(|e| {
+            tracing::warn!("上游工具 {} 调用失败 (请求 ID {}): {}", tool_name, request_id, e);
+            e.traced(request_id)
+        })
+    }
+
+    async fn call(&self, tool_name: &str, params: serde_json::Value, request_id: &str) -> Result<String> {
         // Build MCP tools/call request per MCP 2025-06-18 spec
         let request = McpRequest {
             jsonrpc: "2.0".to_string(),
@@ -88,33 +198,31 @@ impl McpClient {
             id: 1,
         };
 
-        let response = self.client
-            .post(&self.url)
-            .header("Authorization", &self.token)
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+        tracing::debug!("调用上游工具 {} ({}, 请求 ID {})", tool_name, self.url, request_id);
+        tracing::trace!("请求内容: {}", serde_json::to_string(&request).unwrap_or_default());
 
-        let status = response.status();
-        let body = response.text().await?;
-        
-        if !status.is_success() {
-            return Err(anyhow!("MCP Server error: {} - {}", status, body));
-        }
-
-        // Parse MCP response
-        let mcp_response: McpResponse = serde_json::from_str(&body)
-            .map_err(|e| anyhow!("Failed to parse MCP response: {} - body: {}", e, body))?;
+        let mut retried = false;
+        let mcp_response = loop {
+            limiter::wait().await?;
+            let started = Instant::now();
+            let response = self.send(tool_name, &request, request_id, started).await;
+            metrics::record(response.is_ok(), started.elapsed());
+            match response {
+                // A short pause is sat out by `limiter::wait`, then the call is tried once more
+                Err(McdError::Upstream(UpstreamError::RateLimited { retry_after: Some(secs), .. }))
+                    if !retried && !session::is_replaying() && Duration::from_secs(secs) <= limiter::MAX_WAIT => retried = true,
+                response => break response?,
+            }
+        };
 
         // Check for JSON-RPC error
         if let Some(error) = mcp_response.error {
-            return Err(anyhow!("MCP error {}: {}", error.code, error.message));
+            return Err(McdError::Rpc { code: error.code, message: error.message });
         }
 
         // Extract result
         let result = mcp_response.result
-            .ok_or_else(|| anyhow!("MCP response missing result"))?;
+            .ok_or_else(|| McdError::Parse("MCP response missing result".to_string()))?;
 
         if result.is_error {
             // Collect error text from content
@@ -123,7 +231,7 @@ impl McpClient {
                 .cloned()
                 .collect::<Vec<_>>()
                 .join("\n");
-            return Err(anyhow!("MCP tool error: {}", error_text));
+            return Err(McdError::UpstreamTool(error_text));
         }
 
         // Collect text content from result
@@ -137,6 +245,80 @@ impl McpClient {
         Ok(text)
     }
 
+    /// Post a JSON-RPC request and parse the response, failing on transport errors,
+    /// block pages, HTTP errors and malformed bodies
+    async fn send(&self, tool_name: &str, request: &McpRequest, request_id: &str, started: Instant) -> Result<McpResponse> {
+        if let Some(injected) = chaos::inject(tool_name).await {
+            return self.parse(tool_name, injected);
+        }
+        let (status, content_type, body, retry_after) = match session::replay(tool_name) {
+            Some(replayed) => {
+                let replayed = replayed?;
+                let status = reqwest::StatusCode::from_u16(replayed.status)
+                    .map_err(|_| McdError::Parse(format!("录制文件中的状态码无效: {}", replayed.status)))?;
+                (status, replayed.content_type, replayed.body, None)
+            }
+            None => self.post(tool_name, request, request_id, started).await?,
+        };
+        self.parse(tool_name, (status, content_type, body, retry_after))
+    }
+
+    /// Turn an upstream answer into an MCP response or the error it stands for
+    fn parse(&self, tool_name: &str, (status, content_type, body, retry_after): Answer) -> Result<McpResponse> {
+        tracing::debug!("上游工具 {} 返回 {} ({} 字节)", tool_name, status, body.len());
+        tracing::trace!("响应内容: {}", truncate_for_log(&body));
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(limiter::limited(status.as_u16(), retry_after).into());
+        }
+        // Block pages (captcha, maintenance, rate limiting) come back as HTML
+        if UpstreamError::is_non_json(&content_type, &body) {
+            let error = match UpstreamError::classify(status.as_u16(), &content_type, &body) {
+                UpstreamError::RateLimited { .. } => limiter::limited(status.as_u16(), retry_after),
+                error => error,
+            };
+            tracing::warn!("上游返回非 JSON 响应: {}", error);
+            return Err(error.into());
+        }
+        
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(McdError::TokenInvalid);
+        }
+        if !status.is_success() {
+            return Err(McdError::UpstreamStatus { status: status.as_u16(), body: truncate_for_log(&body) });
+        }
+
+        // Parse MCP response
+        serde_json::from_str(&body)
+            .map_err(|e| McdError::Parse(format!("Failed to parse MCP response: {} - body: {}", e, truncate_for_log(&body))))
+    }
+
+    /// Send a request over the network, recording the exchange when a recording is running.
+    /// Also returns the `Retry-After` delay, if upstream sent one.
+    async fn post(&self, tool_name: &str, request: &McpRequest, request_id: &str, started: Instant) -> Result<Answer> {
+        let response = self.client
+            .post(&self.url)
+            .headers(configured_headers())
+            .header("Authorization", &self.token)
+            .header("Content-Type", "application/json")
+            .header(REQUEST_ID_HEADER, request_id)
+            .json(request)
+            .send()
+            .await?;
+        tracing::debug!("上游工具 {} 响应头耗时 {} ms ({:?})", tool_name, started.elapsed().as_millis(), response.version());
+
+        let status = response.status();
+        let content_type = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let retry_after = retry_after(&response);
+        let body = read_body(response).await?;
+        session::record(tool_name, request, status.as_u16(), &content_type, &body, &self.token);
+        Ok((status, content_type, body, retry_after))
+    }
+
     /// Get all available coupons for the user (returns markdown text)
     pub async fn get_available_coupons(&self) -> Result<String> {
         self.call_tool("available-coupons", serde_json::json!({})).await
@@ -153,8 +335,80 @@ impl McpClient {
     }
 
     /// Get current time information from the server
+    #[cfg(feature = "server")]
     pub async fn get_current_time(&self) -> Result<String> {
         self.call_tool("now-time-info", serde_json::json!({})).await
     }
+
+    /// Server clock, read from the `now-time-info` output
+    #[cfg(feature = "server")]
+    pub async fn get_server_time(&self) -> Result<DateTime<Utc>> {
+        let text = self.get_current_time().await?;
+        parse_server_time(&text).ok_or_else(|| McdError::Parse("无法识别服务器返回的时间".to_string()))
+    }
+}
+
+
+/// HTTP client shared by every McpClient, so all accounts and modes reuse one connection pool
+fn shared_http_client() -> Result<Client> {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+        .tcp_keepalive(KEEPALIVE_INTERVAL)
+        .http2_adaptive_window(true)
+        .http2_keep_alive_interval(KEEPALIVE_INTERVAL)
+        .http2_keep_alive_while_idle(true)
+        .build()?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
+/// Random id for one upstream call, unique within the process thanks to the counter
+fn new_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!("{:016x}", RandomState::new().hash_one(COUNTER.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Delay asked for by the `Retry-After` header
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response.headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(limiter::parse_retry_after)
 }
 
+/// Read a response body chunk by chunk, aborting as soon as it exceeds the size limit
+async fn read_body(mut response: reqwest::Response) -> Result<String> {
+    let limit = MAX_BODY_BYTES.load(Ordering::Relaxed);
+    if response.content_length().is_some_and(|len| len > limit as u64) {
+        return Err(UpstreamError::TooLarge { limit }.into());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            tracing::warn!("上游响应超过 {} 字节，已中止读取", limit);
+            return Err(UpstreamError::TooLarge { limit }.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Shorten a response body for logs, marking how much was cut
+fn truncate_for_log(body: &str) -> String {
+    if body.len() <= LOG_BODY_LIMIT {
+        return body.to_string();
+    }
+    let mut end = LOG_BODY_LIMIT;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…[已截断，共 {} 字节]", &body[..end], body.len())
+}
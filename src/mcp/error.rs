@@ -0,0 +1,81 @@
+use thiserror::Error;
+
+use crate::error::McdError;
+
+/// Upstream answered with something other than a usable MCP response:
+/// a non-JSON page (usually HTML) or an oversized body
+#[derive(Debug, Error)]
+pub enum UpstreamError {
+    #[error("麦当劳服务正在维护 (HTTP {status})")]
+    Maintenance { status: u16 },
+    #[error("麦当劳服务要求人机验证 (HTTP {status})")]
+    Captcha { status: u16 },
+    #[error("{}", rate_limited_message(*.status, *.retry_after))]
+    RateLimited {
+        status: u16,
+        /// Seconds until requests are sent again
+        retry_after: Option<u64>,
+    },
+    #[error("上游返回了非 JSON 响应 (HTTP {status}, {content_type})")]
+    UnexpectedContent { status: u16, content_type: String },
+    #[error("上游响应超过 {limit} 字节上限，已中止读取")]
+    TooLarge { limit: usize },
+}
+
+impl UpstreamError {
+    /// Whether a response with this content type and body is not an MCP JSON response
+    pub fn is_non_json(content_type: &str, body: &str) -> bool {
+        let content_type = content_type.to_ascii_lowercase();
+        let non_json_type = !content_type.is_empty()
+            && !content_type.contains("json")
+            && !content_type.contains("event-stream");
+        non_json_type || body.trim_start().starts_with('<')
+    }
+
+    /// Classify a non-JSON response from its status code and page content
+    pub fn classify(status: u16, content_type: &str, body: &str) -> Self {
+        let page = body.to_lowercase();
+        let mentions = |keywords: &[&str]| keywords.iter().any(|k| page.contains(k));
+
+        if status == 429 || mentions(&["too many requests", "rate limit", "访问频繁", "请求过于频繁", "操作频繁"]) {
+            UpstreamError::RateLimited { status, retry_after: None }
+        } else if mentions(&["captcha", "geetest", "验证码", "人机验证", "滑动验证", "安全验证"]) {
+            UpstreamError::Captcha { status }
+        } else if status == 503 || mentions(&["maintenance", "维护", "升级中"]) {
+            UpstreamError::Maintenance { status }
+        } else {
+            UpstreamError::UnexpectedContent {
+                status,
+                content_type: if content_type.is_empty() { "未知类型".to_string() } else { content_type.to_string() },
+            }
+        }
+    }
+
+    /// What the user can do about it
+    pub fn advice(&self) -> &'static str {
+        match self {
+            UpstreamError::Maintenance { .. } => "请稍后再试，维护通常会在几小时内结束",
+            UpstreamError::Captcha { .. } => "请先在浏览器中打开麦当劳 MCP 平台完成验证，再回来重试",
+            UpstreamError::RateLimited { retry_after: Some(_), .. } => "期间的请求会暂停，避免短时间内重复领取",
+            UpstreamError::RateLimited { retry_after: None, .. } => "请等待几分钟后再试，避免短时间内重复领取",
+            UpstreamError::UnexpectedContent { .. } => "请检查网络代理设置，或稍后再试",
+            UpstreamError::TooLarge { .. } => "如确需处理更大的响应，可在配置文件中调大 max_response_bytes",
+        }
+    }
+}
+
+fn rate_limited_message(status: u16, retry_after: Option<u64>) -> String {
+    match retry_after {
+        Some(secs) => format!("被限流，将在 {} 秒后重试 (HTTP {})", secs, status),
+        None => format!("请求过于频繁，已被限流 (HTTP {})", status),
+    }
+}
+
+/// Error text for display, with advice appended when the upstream served a block page
+pub fn describe_error(error: &McdError) -> String {
+    match error {
+        McdError::Upstream(upstream) => format!("{}，{}", upstream, upstream.advice()),
+        McdError::Traced { request_id, error } => format!("{}（请求 ID: {}）", describe_error(error), request_id),
+        _ => error.to_string(),
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use std::{sync::Mutex, time::{Duration, Instant}};
+
+use crate::{error::Result, mcp::{session, UpstreamError}};
+
+/// Pause after a 429 that did not say how long to wait
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(30);
+/// Longest pause a request sits out before being sent; beyond it the request fails at once
+pub const MAX_WAIT: Duration = Duration::from_secs(60);
+/// Longest pause honored from a `Retry-After` header
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+
+/// Until when upstream asked us to hold off, shared by every client and account
+static BLOCKED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Hold off every upstream request for `wait`, unless an earlier 429 asked for longer
+pub fn block_for(wait: Duration) {
+    let Some(until) = Instant::now().checked_add(wait.min(MAX_RETRY_AFTER)) else {
+        return;
+    };
+    let mut blocked = BLOCKED_UNTIL.lock().unwrap_or_else(|e| e.into_inner());
+    if blocked.is_none_or(|current| current < until) {
+        *blocked = Some(until);
+    }
+}
+
+/// Time left before upstream requests resume, None when not rate limited
+pub fn remaining() -> Option<Duration> {
+    let blocked = *BLOCKED_UNTIL.lock().unwrap_or_else(|e| e.into_inner());
+    blocked
+        .and_then(|until| until.checked_duration_since(Instant::now()))
+        .filter(|left| !left.is_zero())
+}
+
+/// Sit out the current pause before sending a request. A pause longer than `MAX_WAIT`
+/// fails the request straight away instead of hanging the caller.
+pub async fn wait() -> Result<()> {
+    if session::is_replaying() {
+        return Ok(());
+    }
+    let Some(left) = remaining() else {
+        return Ok(());
+    };
+    if left > MAX_WAIT {
+        return Err(UpstreamError::RateLimited { status: 429, retry_after: Some(seconds(left)) }.into());
+    }
+    tracing::info!("被限流，将在 {} 秒后重试", seconds(left));
+    tokio::time::sleep(left).await;
+    Ok(())
+}
+
+/// Error for a rate limited response, pausing every later request for the
+/// `Retry-After` delay or `DEFAULT_BACKOFF`
+pub fn limited(status: u16, retry_after: Option<Duration>) -> UpstreamError {
+    let wait = retry_after.unwrap_or(DEFAULT_BACKOFF);
+    if !session::is_replaying() {
+        block_for(wait);
+    }
+    tracing::warn!("上游限流 (HTTP {})，暂停请求 {} 秒", status, seconds(wait));
+    UpstreamError::RateLimited { status, retry_after: Some(seconds(wait)) }
+}
+
+/// Parse a `Retry-After` header: delay seconds or an HTTP date, capped at `MAX_RETRY_AFTER`
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let wait = match value.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => {
+            let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+            (at - Utc::now()).to_std().unwrap_or_default()
+        }
+    };
+    Some(wait.min(MAX_RETRY_AFTER))
+}
+
+/// Whole seconds, rounded up so a pause never reads as 0
+pub fn seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
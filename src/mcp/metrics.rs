@@ -0,0 +1,80 @@
+#[cfg(any(feature = "tui", feature = "web"))]
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+#[cfg(any(feature = "tui", feature = "web"))]
+use crate::mcp::limiter;
+
+/// Number of recent upstream calls the rolling stats cover
+const WINDOW: usize = 50;
+
+/// Outcome of one upstream call
+#[cfg_attr(not(any(feature = "tui", feature = "web")), allow(dead_code))]
+struct Sample {
+    success: bool,
+    latency: Duration,
+}
+
+static SAMPLES: Mutex<VecDeque<Sample>> = Mutex::new(VecDeque::new());
+
+/// Rolling success rate and latency of recent upstream calls
+#[cfg(any(feature = "tui", feature = "web"))]
+#[derive(Debug, Clone, Copy, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema, async_graphql::SimpleObject))]
+pub struct UpstreamStats {
+    /// Calls in the window, at most 50
+    pub calls: usize,
+    /// Share of calls that got a well-formed response, 0-100
+    pub success_rate: f64,
+    pub avg_latency_ms: u64,
+    /// Seconds until requests resume after upstream rate limited us
+    pub retry_after_secs: Option<u64>,
+}
+
+#[cfg(feature = "tui")]
+impl UpstreamStats {
+    /// Short status text, e.g. "上游: 98% / 320ms", or the rate limit pause while one lasts
+    pub fn summary(&self) -> String {
+        if let Some(secs) = self.retry_after_secs {
+            return format!("上游: 被限流，将在 {} 秒后重试", secs);
+        }
+        if self.calls == 0 {
+            return "上游: 暂无数据".to_string();
+        }
+        format!("上游: {:.0}% / {}ms", self.success_rate, self.avg_latency_ms)
+    }
+}
+
+/// Record an upstream call. Success means a well-formed JSON-RPC response arrived,
+/// whatever the tool itself answered.
+pub fn record(success: bool, latency: Duration) {
+    let mut samples = SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+    if samples.len() == WINDOW {
+        samples.pop_front();
+    }
+    samples.push_back(Sample { success, latency });
+}
+
+/// Current rolling stats
+#[cfg(any(feature = "tui", feature = "web"))]
+pub fn snapshot() -> UpstreamStats {
+    let samples = SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+    let calls = samples.len();
+    let retry_after_secs = limiter::remaining().map(limiter::seconds);
+    if calls == 0 {
+        return UpstreamStats {
+            calls,
+            success_rate: 0.0,
+            avg_latency_ms: 0,
+            retry_after_secs,
+        };
+    }
+    let succeeded = samples.iter().filter(|s| s.success).count();
+    let total: Duration = samples.iter().map(|s| s.latency).sum();
+    UpstreamStats {
+        calls,
+        success_rate: succeeded as f64 * 100.0 / calls as f64,
+        avg_latency_ms: (total / calls as u32).as_millis() as u64,
+        retry_after_secs,
+    }
+}
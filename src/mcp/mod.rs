@@ -1,5 +1,11 @@
+pub mod chaos;
 pub mod client;
+pub mod error;
+pub mod limiter;
+pub mod metrics;
+pub mod session;
 pub mod types;
 
 pub use client::McpClient;
+pub use error::{describe_error, UpstreamError};
 pub use types::*;
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, sync::Mutex};
+
+use crate::{error::{McdError, Result}, utils::format_current_time};
+
+/// Bumped when the file layout changes incompatibly
+const FORMAT_VERSION: u32 = 1;
+/// Written in place of the token wherever upstream echoes it back
+const REDACTED: &str = "[已隐藏]";
+
+/// One upstream request and the response it got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// Tool name, or the JSON-RPC method for non-tool calls
+    pub method: String,
+    pub request: serde_json::Value,
+    pub status: u16,
+    #[serde(default)]
+    pub content_type: String,
+    #[serde(default)]
+    pub body: String,
+    pub recorded_at: String,
+}
+
+/// Layout of a `--record` / `--replay` file
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionFile {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+enum Session {
+    Record { path: PathBuf, interactions: Vec<Interaction> },
+    /// Interactions by method, with how many of each have been served
+    Replay { interactions: HashMap<String, Vec<Interaction>>, served: HashMap<String, usize> },
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+/// Save every upstream exchange to `path` from now on, with the token left out
+pub fn start_recording(path: &Path) -> Result<()> {
+    write(path, &[])?;
+    *SESSION.lock().unwrap_or_else(|e| e.into_inner()) = Some(Session::Record {
+        path: path.to_path_buf(),
+        interactions: Vec::new(),
+    });
+    Ok(())
+}
+
+/// Answer upstream calls from the file at `path` instead of the network.
+/// Returns the number of recorded interactions.
+pub fn start_replay(path: &Path) -> Result<usize> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| McdError::config(format!("无法读取录制文件: {}", path.display()), e))?;
+    let file: SessionFile = serde_json::from_str(&text)
+        .map_err(|e| McdError::config(format!("无法解析录制文件: {}", path.display()), e))?;
+    if file.version != FORMAT_VERSION {
+        return Err(McdError::Parse(format!("不支持的录制文件版本: {}", file.version)));
+    }
+
+    let count = file.interactions.len();
+    let mut interactions: HashMap<String, Vec<Interaction>> = HashMap::new();
+    for interaction in file.interactions {
+        interactions.entry(interaction.method.clone()).or_default().push(interaction);
+    }
+    *SESSION.lock().unwrap_or_else(|e| e.into_inner()) = Some(Session::Replay {
+        interactions,
+        served: HashMap::new(),
+    });
+    Ok(count)
+}
+
+/// Whether upstream calls are answered from a recording, so no token is needed
+pub fn is_replaying() -> bool {
+    matches!(*SESSION.lock().unwrap_or_else(|e| e.into_inner()), Some(Session::Replay { .. }))
+}
+
+/// Next recorded response for `method`, or None when not replaying.
+/// Responses are served in recorded order; the last one repeats once they run out.
+pub(crate) fn replay(method: &str) -> Option<Result<Interaction>> {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(Session::Replay { interactions, served }) = session.as_mut() else {
+        return None;
+    };
+    let Some(recorded) = interactions.get(method).filter(|list| !list.is_empty()) else {
+        return Some(Err(McdError::Parse(format!("录制文件中没有 {} 的响应", method))));
+    };
+    let count = served.entry(method.to_string()).or_default();
+    let interaction = recorded[(*count).min(recorded.len() - 1)].clone();
+    *count += 1;
+    tracing::debug!("回放 {} 的第 {} 个响应", method, *count);
+    Some(Ok(interaction))
+}
+
+/// Append an exchange to the recording, if one is running
+pub(crate) fn record(method: &str, request: &impl Serialize, status: u16, content_type: &str, body: &str, token: &str) {
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(Session::Record { path, interactions }) = session.as_mut() else {
+        return;
+    };
+    let request = serde_json::to_value(request).unwrap_or_default();
+    interactions.push(Interaction {
+        method: method.to_string(),
+        request: serde_json::from_str(&redact(&request.to_string(), token)).unwrap_or(request),
+        status,
+        content_type: content_type.to_string(),
+        body: redact(body, token),
+        recorded_at: format_current_time(),
+    });
+    // Rewritten after every exchange so the recording survives a crash
+    if let Err(e) = write(path, interactions) {
+        tracing::warn!("写入录制文件失败: {}", e);
+    }
+}
+
+fn write(path: &Path, interactions: &[Interaction]) -> Result<()> {
+    let file = SessionFile {
+        version: FORMAT_VERSION,
+        interactions: interactions.to_vec(),
+    };
+    let text = serde_json::to_string_pretty(&file)
+        .map_err(|e| McdError::config("无法序列化录制内容", e))?;
+    fs::write(path, text)
+        .map_err(|e| McdError::config(format!("无法写入录制文件: {}", path.display()), e))
+}
+
+/// Remove the token, with and without its "Bearer " prefix
+fn redact(text: &str, token: &str) -> String {
+    let bare = token.trim().trim_start_matches("Bearer ").trim();
+    if bare.is_empty() {
+        return text.to_string();
+    }
+    text.replace(bare, REDACTED)
+}
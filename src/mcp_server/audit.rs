@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use axum::http::{header, HeaderMap};
+use serde::Serialize;
+use std::{fs::OpenOptions, io::Write};
+
+use crate::{audit::{self, Action}, config::Config, utils::format_current_time};
+
+/// Who made an MCP request
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Session id issued by the server in the `initialize` response
+    pub session: String,
+    /// `User-Agent` header
+    pub client: Option<String>,
+}
+
+impl Caller {
+    pub fn new(session: String, headers: &HeaderMap) -> Self {
+        Self {
+            session,
+            client: headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()).map(str::to_string),
+        }
+    }
+}
+
+/// One audited tool call
+#[derive(Debug, Serialize)]
+pub struct AuditEntry<'a> {
+    pub at: String,
+    pub tool: &'a str,
+    pub session: &'a str,
+    pub client: Option<&'a str>,
+    /// Whether the rate limit was explicitly overridden
+    pub forced: bool,
+    /// "success", "failed", "nothing_bound", "rate_limited" or "declined"
+    pub outcome: &'a str,
+    pub message: &'a str,
+}
+
+impl<'a> AuditEntry<'a> {
+    pub fn new(tool: &'a str, caller: &'a Caller, forced: bool, outcome: &'a str, message: &'a str) -> Self {
+        Self {
+            at: format_current_time(),
+            tool,
+            session: &caller.session,
+            client: caller.client.as_deref(),
+            forced,
+            outcome,
+            message,
+        }
+    }
+
+    /// Append to the audit log. Failures are only logged so auditing never blocks a tool call.
+    pub fn write(&self) {
+        if let Err(e) = self.append() {
+            tracing::warn!("写入审计日志失败: {:#}", e);
+        }
+        let detail = match self.client {
+            Some(client) => format!("{} ({}, {})", self.message, self.outcome, client),
+            None => format!("{} ({})", self.message, self.outcome),
+        };
+        audit::record(&format!("mcp:{}", self.session), Action::Claim, self.outcome == "success", detail);
+    }
+
+    fn append(&self) -> Result<()> {
+        let path = Config::data_path(audit::MCP_AUDIT_FILE);
+        let _lock = audit::lock()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context(format!("无法打开文件: {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(self)?)
+            .context(format!("无法写入文件: {}", path.display()))
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use crate::{audit, config::ConfigService, control::{self, Command, InstanceStatus, Reply}, coupons::{claim_verified, ClaimHistory, ClaimVerification}, inflight::{self, Operation, Outcome}, mcp::{describe_error, McpClient}, mcp_server::McpServerState, mqtt};
+
+/// Answer commands from the local control socket with the MCP server state
+pub fn spawn(state: Arc<McpServerState>) {
+    let Some(mut commands) = control::spawn("mcp") else {
+        return;
+    };
+    tokio::spawn(async move {
+        while let Some(pending) = commands.recv().await {
+            let reply = match pending.command {
+                Command::Claim => claim(&state).await,
+                Command::Status => InstanceStatus::reply("mcp", state.config().has_valid_token(), state.coupons().len()),
+                Command::ReloadConfig => reload(&state).await,
+            };
+            pending.reply(reply);
+        }
+    });
+}
+
+async fn claim(state: &McpServerState) -> Reply {
+    let client = state.client();
+    let account = inflight::account(&state.config().token);
+    let ticket = match inflight::begin(Operation::Claim, &account, None) {
+        Ok(started) => started,
+        Err(existing) => return match existing.resolve().await {
+            Ok(outcome) if outcome.success => Reply::ok(outcome.message),
+            Ok(outcome) => Reply::failed(outcome.message),
+            Err(conflict) => Reply::failed(conflict.to_string()),
+        },
+    };
+    let (result, verification) = claim_verified(&client).await;
+    ClaimHistory::record("mcp", &result, verification.as_ref());
+    audit::record_claim(control::ACTOR, &result, verification.as_ref());
+    mqtt::publish_claim("mcp", &result, verification.as_ref());
+    let success = ClaimVerification::succeeded(&result, verification.as_ref());
+    ticket.finish(match &result {
+        Ok(_) => Outcome { success, message: ClaimVerification::message(verification.as_ref()) },
+        Err(e) => Outcome { success: false, message: format!("领取失败: {}", describe_error(e)) },
+    });
+    match result {
+        Ok(_) if success => Reply::ok(ClaimVerification::message(verification.as_ref())),
+        Ok(_) => Reply::failed(ClaimVerification::message(verification.as_ref())),
+        Err(e) => Reply::failed(format!("领取失败: {}", describe_error(&e))),
+    }
+}
+
+/// Re-read the config file and rebuild the client from its token.
+/// The port and IP allowlist are only read at startup.
+async fn reload(state: &McpServerState) -> Reply {
+    let config = match ConfigService::global().reload() {
+        Ok(config) => config,
+        Err(e) => return Reply::failed(format!("读取配置失败: {}", e)),
+    };
+    if !config.has_valid_token() {
+        return Reply::failed("配置文件中没有 Token，未重新加载");
+    }
+    let client = match McpClient::with_url(config.token.clone(), config.upstream_url()) {
+        Ok(client) => client,
+        Err(e) => return Reply::failed(format!("创建客户端失败: {}", e)),
+    };
+    state.replace(client, config);
+    tracing::info!("已通过控制接口重新加载配置");
+    Reply::ok("已重新加载配置（端口和 allowed_ips 需重启后生效）")
+}
@@ -0,0 +1,121 @@
+use axum::{body::Body, http::{header, HeaderMap, StatusCode}, response::Response};
+use futures_util::stream;
+use std::{collections::{HashMap, HashSet}, convert::Infallible, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{mcp_server::{audit::{AuditEntry, Caller}, handlers::{handle_tools_call, McpServerState}, types::*}, utils::random_id};
+
+/// How long the user has to answer a confirmation before the claim is cancelled
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Server-initiated elicitation requests and the sessions that can answer them
+#[derive(Default)]
+pub struct Elicitations {
+    /// Requests waiting for the client's answer, keyed by their random JSON-RPC id,
+    /// with the session that was asked
+    pending: HashMap<String, (String, oneshot::Sender<serde_json::Value>)>,
+    /// Sessions whose client declared the elicitation capability in initialize
+    capable: HashSet<String>,
+}
+
+impl Elicitations {
+    /// Remember whether the client of a session supports elicitation
+    pub fn set_capable(&mut self, session: &str, capable: bool) {
+        if capable {
+            self.capable.insert(session.to_string());
+        } else {
+            self.capable.remove(session);
+        }
+    }
+
+    /// Pass a client's JSON-RPC response to the request waiting for it.
+    /// Returns false if no request with that id is pending for the answering session.
+    pub fn deliver(&mut self, id: &str, session: &str, answer: serde_json::Value) -> bool {
+        if self.pending.get(id).is_none_or(|(asked, _)| asked != session) {
+            return false;
+        }
+        match self.pending.remove(id) {
+            Some((_, sender)) => sender.send(answer).is_ok(),
+            None => false,
+        }
+    }
+
+    fn register(&mut self, session: &str) -> (String, oneshot::Receiver<serde_json::Value>) {
+        let id = random_id();
+        let (sender, receiver) = oneshot::channel();
+        self.pending.insert(id.clone(), (session.to_string(), sender));
+        (id, receiver)
+    }
+}
+
+/// Whether an auto-bind-coupons call should first be confirmed with the user.
+/// Needs a client that declared elicitation and accepts an SSE response, and no auto-approval.
+pub async fn should_confirm(state: &Arc<McpServerState>, caller: &Caller, headers: &HeaderMap) -> bool {
+    let accepts_sse = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    accepts_sse && !state.config().mcp_auto_approve_claims && state.elicitations().capable.contains(&caller.session)
+}
+
+/// Answer a tools/call with an SSE stream: ask the client to confirm the claim via
+/// elicitation/create, then send the tool result once the user accepts.
+pub fn confirm_then_call(state: Arc<McpServerState>, request: McpRequest, caller: Caller) -> Response<Body> {
+    let (events, receiver) = mpsc::unbounded_channel::<String>();
+
+    tokio::spawn(async move {
+        let id = request.id.unwrap_or(0);
+        let (elicitation_id, answer) = state.elicitations().register(&caller.session);
+
+        let ask = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": elicitation_id,
+            "method": "elicitation/create",
+            "params": {
+                "message": "智能体请求为你一键领取所有可用的麦当劳优惠券，是否继续？",
+                "requestedSchema": {
+                    "type": "object",
+                    "properties": {
+                        "confirm": {
+                            "type": "boolean",
+                            "title": "确认领取",
+                            "description": "领取后会占用账号的领券次数"
+                        }
+                    },
+                    "required": ["confirm"]
+                }
+            }
+        });
+        let _ = events.send(sse_event(&ask));
+
+        let answer = tokio::time::timeout(ANSWER_TIMEOUT, answer).await.ok().and_then(Result::ok);
+        let accepted = answer.as_ref().is_some_and(|a| {
+            a.pointer("/result/action").and_then(|v| v.as_str()) == Some("accept")
+                && a.pointer("/result/content/confirm").and_then(|v| v.as_bool()) == Some(true)
+        });
+
+        let response = if accepted {
+            handle_tools_call(&state, &request, &caller).await.0
+        } else {
+            state.elicitations().pending.remove(&elicitation_id);
+            let message = if answer.is_some() { "用户已拒绝领取" } else { "等待用户确认超时，已取消领取" };
+            AuditEntry::new("auto-bind-coupons", &caller, false, "declined", message).write();
+            McpResponse::tool_error(id, message)
+        };
+        let _ = events.send(sse_event(&response));
+    });
+
+    let body = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (Ok::<_, Infallible>(event), receiver))
+    });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from_stream(body))
+        .unwrap()
+}
+
+fn sse_event<T: serde::Serialize>(message: &T) -> String {
+    format!("event: message\ndata: {}\n\n", serde_json::to_string(message).unwrap_or_default())
+}
@@ -0,0 +1,99 @@
+use serde::Serialize;
+
+use crate::{error::McdError, mcp::{describe_error, UpstreamError}};
+
+use super::types::McpResponse;
+
+/// JSON-RPC server error codes (-32000 to -32099) for upstream failures
+pub const AUTH_FAILED: i32 = -32001;
+pub const RATE_LIMITED: i32 = -32002;
+pub const UPSTREAM_UNAVAILABLE: i32 = -32003;
+pub const UPSTREAM_INVALID: i32 = -32004;
+/// Internal error, for upstream JSON-RPC errors passed through
+const INTERNAL_ERROR: i32 = -32603;
+
+/// `data` of an upstream error response, so clients can decide whether and when to retry
+#[derive(Debug, Serialize)]
+pub struct UpstreamErrorData {
+    /// auth | rate_limit | network | unavailable | parse | upstream
+    pub kind: &'static str,
+    pub retryable: bool,
+    /// Suggested wait before retrying
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_seconds: Option<u64>,
+    /// HTTP status or JSON-RPC code reported by upstream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_code: Option<i64>,
+    /// What the user can do about it
+    pub advice: &'static str,
+    /// `X-Request-Id` of the failed upstream call, to quote when reporting the failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl McpResponse {
+    /// Response for a failed upstream call. Errors reported by the upstream tool itself, and
+    /// claims skipped for the monthly budget, are ordinary tool results; transport, auth and
+    /// format failures become JSON-RPC errors with `data`.
+    pub fn upstream_error(id: u32, error: &McdError) -> Self {
+        if let McdError::UpstreamTool(_) | McdError::OverBudget(_) = error.untraced() {
+            return Self::tool_error(id, &describe_error(error));
+        }
+        let (code, data) = classify(error);
+        Self::error_with_data(id, code, &describe_error(error), serde_json::to_value(data).unwrap_or_default())
+    }
+}
+
+/// JSON-RPC code and retry hints for an upstream failure
+fn classify(error: &McdError) -> (i32, UpstreamErrorData) {
+    let data = |kind, retry_after_seconds: Option<u64>, upstream_code: Option<i64>, advice| UpstreamErrorData {
+        kind,
+        retryable: retry_after_seconds.is_some(),
+        retry_after_seconds,
+        upstream_code,
+        advice,
+        request_id: error.request_id().map(str::to_string),
+    };
+    const RELOGIN: &str = "Token 无效或已过期，请在网页模式或终端模式中重新设置 Token 后再试";
+
+    match error.untraced() {
+        McdError::TokenInvalid => (AUTH_FAILED, data("auth", None, Some(401), RELOGIN)),
+        McdError::UpstreamStatus { status: status @ (401 | 403), .. } => {
+            (AUTH_FAILED, data("auth", None, Some(*status as i64), RELOGIN))
+        }
+        McdError::UpstreamStatus { status, .. } if *status >= 500 => {
+            (UPSTREAM_UNAVAILABLE, data("unavailable", Some(60), Some(*status as i64), "麦当劳服务暂时不可用，请稍后再试"))
+        }
+        McdError::UpstreamStatus { status, .. } => {
+            (UPSTREAM_INVALID, data("upstream", None, Some(*status as i64), "请求被麦当劳服务拒绝，重试不会改变结果"))
+        }
+        McdError::Upstream(upstream) => {
+            let (code, kind, retry_after) = match upstream {
+                UpstreamError::RateLimited { retry_after, .. } => (RATE_LIMITED, "rate_limit", Some(retry_after.unwrap_or(300))),
+                UpstreamError::Maintenance { .. } => (UPSTREAM_UNAVAILABLE, "unavailable", Some(1800)),
+                // Needs the user to solve a captcha in the browser first
+                UpstreamError::Captcha { .. } => (UPSTREAM_UNAVAILABLE, "unavailable", None),
+                UpstreamError::UnexpectedContent { .. } => (UPSTREAM_INVALID, "parse", Some(60)),
+                UpstreamError::TooLarge { .. } => (UPSTREAM_INVALID, "parse", None),
+            };
+            let status = match upstream {
+                UpstreamError::RateLimited { status, .. }
+                | UpstreamError::Maintenance { status }
+                | UpstreamError::Captcha { status }
+                | UpstreamError::UnexpectedContent { status, .. } => Some(*status as i64),
+                UpstreamError::TooLarge { .. } => None,
+            };
+            (code, data(kind, retry_after, status, upstream.advice()))
+        }
+        McdError::Network(_) => {
+            (UPSTREAM_UNAVAILABLE, data("network", Some(30), None, "无法连接麦当劳服务，请检查网络或代理设置"))
+        }
+        McdError::Parse(_) => (UPSTREAM_INVALID, data("parse", None, None, "麦当劳服务返回了无法识别的响应，请稍后再试")),
+        McdError::Rpc { code, .. } => {
+            (INTERNAL_ERROR, data("upstream", None, Some(*code as i64), "麦当劳服务处理请求失败，请稍后再试"))
+        }
+        McdError::Config { .. } | McdError::UpstreamTool(_) | McdError::OverBudget(_) | McdError::Traced { .. } => {
+            (INTERNAL_ERROR, data("upstream", None, None, "请检查配置文件后重试"))
+        }
+    }
+}
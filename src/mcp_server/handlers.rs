@@ -1,31 +1,120 @@
 use axum::{extract::State, response::{Json, Response}, routing::{post, get}, Router, http::{HeaderMap, StatusCode, header}, body::Body};
-use std::sync::Arc;
+use std::{collections::HashMap, net::SocketAddr, sync::{Arc, Mutex as StdMutex, MutexGuard, RwLock}, time::{Duration, Instant}};
 use tokio::sync::Mutex;
 use anyhow::Result;
-use crate::{mcp::McpClient, config::Config, mcp_server::types::*};
+use crate::{allowlist::{self, Allowlist}, backup, control, inflight::{self, Existing, Operation, Outcome}, logging, mcp::{describe_error, McpClient}, mqtt, config::{Config, ConfigService}, coupons::{claim_verified, compare_accounts, parse_coupons_from_markdown, pick_accounts, pick_by_title, ClaimHistory, ClaimVerification, Coupon, MealPlan, UsageStore, recommend}, mcp_server::{audit::{AuditEntry, Caller}, control_api, elicitation::{self, Elicitations}, prompts, types::*}, retention, scheduler, utils::random_id};
 
-/// MCP server state
+/// Header carrying the session id issued by `initialize`
+const SESSION_HEADER: &str = "mcp-session-id";
+/// Sessions remembered at once; the least recently used is forgotten beyond this
+const MAX_SESSIONS: usize = 1000;
+/// Default minimum minutes between auto-bind-coupons calls from one session
+const DEFAULT_CLAIM_INTERVAL_MINUTES: u64 = 10;
+/// Protocol versions the server can speak, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+/// Phrase an agent must pass as `confirm` together with `force` to bypass the claim rate limit
+const CLAIM_CONFIRM_PHRASE: &str = "确认领取";
+/// Coupons recommend-coupons returns when `limit` is not given
+const DEFAULT_RECOMMENDATIONS: usize = 5;
+
+/// MCP server state, shared by concurrent requests. Locks are held only to read or
+/// update a field, never across an upstream call, except the claim lock.
 pub struct McpServerState {
-    pub mcp_client: Arc<Mutex<McpClient>>,
-    pub config: Config,
+    /// Replaced whole when the config is reloaded; callers clone the Arc and call without a lock
+    mcp_client: RwLock<Arc<McpClient>>,
+    config: RwLock<Arc<Config>>,
+    /// Held coupons from the last my-coupons call, used for argument completion
+    coupons: StdMutex<Vec<Coupon>>,
+    /// Session ids issued by `initialize` and when each was last used
+    sessions: StdMutex<HashMap<String, Instant>>,
+    /// Time of the last auto-bind-coupons call per session, for rate limiting
+    last_claims: StdMutex<HashMap<String, Instant>>,
+    /// Pending claim confirmations sent to clients
+    elicitations: StdMutex<Elicitations>,
+    /// Held for the whole of an auto-bind-coupons call, so claims run one at a time
+    claim: Mutex<()>,
 }
 
 impl McpServerState {
-    pub fn new(mcp_client: McpClient, config: Config) -> Self {
+    pub fn new(mcp_client: McpClient, config: Arc<Config>) -> Self {
         Self {
-            mcp_client: Arc::new(Mutex::new(mcp_client)),
-            config,
+            mcp_client: RwLock::new(Arc::new(mcp_client)),
+            config: RwLock::new(config),
+            coupons: StdMutex::new(Vec::new()),
+            sessions: StdMutex::new(HashMap::new()),
+            last_claims: StdMutex::new(HashMap::new()),
+            elicitations: StdMutex::new(Elicitations::default()),
+            claim: Mutex::new(()),
         }
     }
+
+    /// Client for the current token
+    pub fn client(&self) -> Arc<McpClient> {
+        self.mcp_client.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn coupons(&self) -> MutexGuard<'_, Vec<Coupon>> {
+        self.coupons.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn elicitations(&self) -> MutexGuard<'_, Elicitations> {
+        self.elicitations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn last_claims(&self) -> MutexGuard<'_, HashMap<String, Instant>> {
+        self.last_claims.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashMap<String, Instant>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Issue a new session id, forgetting the least recently used session when full
+    fn open_session(&self) -> String {
+        let mut sessions = self.sessions();
+        if sessions.len() >= MAX_SESSIONS {
+            if let Some(oldest) = sessions.iter().min_by_key(|(_, used)| **used).map(|(id, _)| id.clone()) {
+                sessions.remove(&oldest);
+                self.last_claims().remove(&oldest);
+                self.elicitations().set_capable(&oldest, false);
+            }
+        }
+        let session = random_id();
+        sessions.insert(session.clone(), Instant::now());
+        session
+    }
+
+    /// Mark a session as used, returning false for ids the server did not issue
+    fn touch_session(&self, session: &str) -> bool {
+        match self.sessions().get_mut(session) {
+            Some(used) => {
+                *used = Instant::now();
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Switch to a reloaded config and its client, dropping the cached coupons
+    pub fn replace(&self, mcp_client: McpClient, config: Arc<Config>) {
+        *self.mcp_client.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(mcp_client);
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+        self.coupons().clear();
+    }
 }
 
 /// Handle MCP JSON-RPC requests
 async fn handle_mcp_request(
-    State(state): State<Arc<Mutex<McpServerState>>>,
+    State(state): State<Arc<McpServerState>>,
+    headers: HeaderMap,
     Json(request): Json<McpRequest>,
 ) -> Response<Body> {
     // Handle notifications (requests without id) - don't send response
-    if request.id.is_none() {
+    if request.id.is_none() && !request.is_response() {
         // For notifications, we don't send a response per JSON-RPC 2.0 spec
         return Response::builder()
             .status(StatusCode::OK)
@@ -34,13 +123,46 @@ async fn handle_mcp_request(
             .unwrap();
     }
     
-    let id = request.id.unwrap();
+    let id = request.id.unwrap_or(0);
+
+    // Sessions are issued by initialize; any other request must carry a known one, so a
+    // client cannot pick its own id to dodge the claim rate limit or the audit log
+    let session = if request.method == "initialize" {
+        state.open_session()
+    } else {
+        match headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) {
+            Some(session) if state.touch_session(session) => session.to_string(),
+            Some(_) => return session_error(StatusCode::NOT_FOUND, id, "未知或已过期的会话，请重新 initialize"),
+            None => return session_error(StatusCode::BAD_REQUEST, id, "缺少 Mcp-Session-Id 请求头，请先调用 initialize"),
+        }
+    };
+    let caller = Caller::new(session, &headers);
+
+    // Answers to elicitation/create requests are routed to the waiting tool call
+    if let Some(answers) = &request.answers {
+        let answer = serde_json::json!({ "result": request.result, "error": request.error });
+        let status = if state.elicitations().deliver(answers, &caller.session, answer) {
+            StatusCode::ACCEPTED
+        } else {
+            StatusCode::NOT_FOUND
+        };
+        return Response::builder().status(status).body(Body::empty()).unwrap();
+    }
+
+    if request.tool_name() == Some("auto-bind-coupons") && elicitation::should_confirm(&state, &caller, &headers).await {
+        return elicitation::confirm_then_call(state, request, caller);
+    }
+
+    let session = caller.session.clone();
     let response: McpResponse = match request.method.as_str() {
         // Standard MCP initialization method
-        "initialize" => handle_initialize(&state, id).await.0,
+        "initialize" => handle_initialize(&state, &request, &caller).await.0,
         // Standard MCP methods
         "tools/list" => handle_tools_list(&state, id).await.0,
-        "tools/call" => handle_tools_call(&state, &request).await.0,
+        "tools/call" => handle_tools_call(&state, &request, &caller).await.0,
+        "prompts/list" => prompts::handle_prompts_list(id).await.0,
+        "prompts/get" => prompts::handle_prompts_get(&state, &request).await.0,
+        "completion/complete" => prompts::handle_completion_complete(&state, &request).await.0,
         "system.listMethods" => handle_list_methods(&state, id).await.0,
         "system.describeMethod" => handle_describe_method(&state, &request).await.0,
         _ => McpResponse::error(
@@ -53,19 +175,44 @@ async fn handle_mcp_request(
     Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "application/json")
+        .header(SESSION_HEADER, session)
         .body(Body::from(serde_json::to_string(&response).unwrap_or_default()))
         .unwrap()
 }
 
+/// JSON-RPC error for a request without a valid session
+fn session_error(status: StatusCode, id: u32, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(&McpResponse::error(id, -32000, message)).unwrap_or_default()))
+        .unwrap()
+}
+
 /// Handle initialize method - required for MCP protocol
 async fn handle_initialize(
-    _state: &Arc<Mutex<McpServerState>>,
-    id: u32,
+    state: &Arc<McpServerState>,
+    request: &McpRequest,
+    caller: &Caller,
 ) -> Json<McpResponse> {
+    let id = request.id.unwrap_or(0);
+    let params = request.params.as_ref();
+
+    // Answer with the client's version when supported, otherwise the oldest one we speak
+    let requested = params.and_then(|p| p.get("protocolVersion")).and_then(|v| v.as_str());
+    let version = PROTOCOL_VERSIONS.iter()
+        .find(|v| Some(**v) == requested)
+        .unwrap_or(&PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]);
+
+    let elicitation = params.and_then(|p| p.pointer("/capabilities/elicitation")).is_some();
+    state.elicitations().set_capable(&caller.session, elicitation);
+
     let result = serde_json::json!({
-        "protocolVersion": "2024-11-05",
+        "protocolVersion": version,
         "capabilities": {
-            "tools": {}
+            "tools": {},
+            "prompts": {},
+            "completions": {}
         },
         "serverInfo": {
             "name": "mcd-coupon",
@@ -78,7 +225,7 @@ async fn handle_initialize(
 
 /// Handle tools/list method - returns list of available tools
 async fn handle_tools_list(
-    _state: &Arc<Mutex<McpServerState>>,
+    _state: &Arc<McpServerState>,
     id: u32,
 ) -> Json<McpResponse> {
     let tools = vec![
@@ -89,15 +236,42 @@ async fn handle_tools_list(
                 "type": "object",
                 "properties": {},
                 "required": []
+            },
+            "annotations": {
+                "title": "查询可领取优惠券",
+                "readOnlyHint": true,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": true
             }
         }),
         serde_json::json!({
             "name": "auto-bind-coupons",
-            "description": "一键领取所有可用的麦当劳优惠券",
+            "description": "一键领取所有可用的麦当劳优惠券。同一会话默认 10 分钟内只能调用一次",
             "inputSchema": {
                 "type": "object",
-                "properties": {},
+                "properties": {
+                    "force": {
+                        "type": "boolean",
+                        "description": "忽略频率限制立即领取，须先征得用户同意，并同时传入 confirm"
+                    },
+                    "confirm": {
+                        "type": "string",
+                        "description": "与 force 一起使用，须为「确认领取」"
+                    },
+                    "idempotency_key": {
+                        "type": "string",
+                        "description": "重试时传入与上次相同的值，会返回上次领取的结果而不会重复领取"
+                    }
+                },
                 "required": []
+            },
+            "annotations": {
+                "title": "领取优惠券",
+                "readOnlyHint": false,
+                "destructiveHint": true,
+                "idempotentHint": false,
+                "openWorldHint": true
             }
         }),
         serde_json::json!({
@@ -107,6 +281,13 @@ async fn handle_tools_list(
                 "type": "object",
                 "properties": {},
                 "required": []
+            },
+            "annotations": {
+                "title": "查看已领取优惠券",
+                "readOnlyHint": true,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": true
             }
         }),
         serde_json::json!({
@@ -116,6 +297,135 @@ async fn handle_tools_list(
                 "type": "object",
                 "properties": {},
                 "required": []
+            },
+            "annotations": {
+                "title": "获取当前时间",
+                "readOnlyHint": true,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": true
+            }
+        }),
+        serde_json::json!({
+            "name": "plan-meal",
+            "description": "根据选择的已领取优惠券生成点餐清单，计算合计金额并提示冲突",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "titles": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "要使用的优惠券标题"
+                    }
+                },
+                "required": ["titles"]
+            },
+            "annotations": {
+                "title": "生成点餐清单",
+                "readOnlyHint": true,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": true
+            }
+        }),
+        serde_json::json!({
+            "name": "claim-history",
+            "description": "查询本机记录的领取历史，可按日期范围和账号筛选，结果按时间倒序",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "from": {"type": "string", "format": "date", "description": "开始日期 (含)，如 2025-01-01"},
+                    "to": {"type": "string", "format": "date", "description": "结束日期 (含)，如 2025-01-07"},
+                    "account": {"type": "string", "description": "账号名称，不填则查询所有账号"}
+                },
+                "required": []
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "total": {"type": "integer"},
+                    "entries": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "at": {"type": "string"},
+                                "account": {"type": ["string", "null"]},
+                                "source": {"type": "string"},
+                                "success": {"type": "boolean"},
+                                "message": {"type": "string"},
+                                "coupons": {"type": "array", "items": {"type": "string"}}
+                            },
+                            "required": ["at", "source", "success", "message", "coupons"]
+                        }
+                    }
+                },
+                "required": ["total", "entries"]
+            },
+            "annotations": {
+                "title": "查询领取历史",
+                "readOnlyHint": true,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": false
+            }
+        }),
+        serde_json::json!({
+            "name": "compare-accounts",
+            "description": "对比两个已保存账号持有的优惠券，列出仅一方持有的和双方都有的，用于决定点餐用哪个账号",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "a": {"type": "string", "description": "账号 A 的名称"},
+                    "b": {"type": "string", "description": "账号 B 的名称"}
+                },
+                "required": ["a", "b"]
+            },
+            "annotations": {
+                "title": "对比账号优惠券",
+                "readOnlyHint": true,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": true
+            }
+        }),
+        serde_json::json!({
+            "name": "recommend-coupons",
+            "description": "按与本机领取和使用记录的相似度（标题和标签的 TF-IDF）为当前可领取的优惠券排序，返回推荐理由，如「你领取过 5 次「板烧鸡腿堡」」",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "limit": {"type": "integer", "minimum": 1, "description": "最多返回的优惠券数量，默认 5"}
+                },
+                "required": []
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "message": {"type": "string"},
+                    "recommendations": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "title": {"type": "string"},
+                                "price": {"type": "string"},
+                                "expiry": {"type": "string"},
+                                "score": {"type": "number"},
+                                "reasons": {"type": "array", "items": {"type": "string"}}
+                            },
+                            "required": ["title", "price", "expiry", "score", "reasons"]
+                        }
+                    }
+                },
+                "required": ["message", "recommendations"]
+            },
+            "annotations": {
+                "title": "推荐优惠券",
+                "readOnlyHint": true,
+                "destructiveHint": false,
+                "idempotentHint": true,
+                "openWorldHint": true
             }
         }),
     ];
@@ -128,9 +438,10 @@ async fn handle_tools_list(
 }
 
 /// Handle tools/call method
-async fn handle_tools_call(
-    state: &Arc<Mutex<McpServerState>>,
+pub(crate) async fn handle_tools_call(
+    state: &Arc<McpServerState>,
     request: &McpRequest,
+    caller: &Caller,
 ) -> Json<McpResponse> {
     // request.id should always be Some at this point (checked in handle_mcp_request)
     let id = request.id.unwrap_or(0);
@@ -155,9 +466,13 @@ async fn handle_tools_call(
     // Handle the tool call based on tool name
     match tool_params.name.as_str() {
         "available-coupons" => handle_available_coupons(&state, id).await,
-        "auto-bind-coupons" => handle_auto_bind_coupons(&state, id).await,
+        "auto-bind-coupons" => handle_auto_bind_coupons(state, id, tool_params.arguments.as_ref(), caller).await,
         "my-coupons" => handle_my_coupons(&state, id).await,
         "now-time-info" => handle_current_time(&state, id).await,
+        "plan-meal" => handle_plan_meal(state, id, tool_params.arguments.as_ref()).await,
+        "claim-history" => handle_claim_history(id, tool_params.arguments.as_ref()),
+        "compare-accounts" => handle_compare_accounts(id, tool_params.arguments.as_ref()).await,
+        "recommend-coupons" => handle_recommend_coupons(state, id, tool_params.arguments.as_ref()).await,
         _ => Json(McpResponse::error(
             id,
             -32601,
@@ -168,13 +483,16 @@ async fn handle_tools_call(
 
 /// Handle system.listMethods method
 async fn handle_list_methods(
-    _state: &Arc<Mutex<McpServerState>>,
+    _state: &Arc<McpServerState>,
     id: u32,
 ) -> Json<McpResponse> {
     let mut all_methods = vec![
         "initialize".to_string(),
         "tools/list".to_string(),
         "tools/call".to_string(),
+        "prompts/list".to_string(),
+        "prompts/get".to_string(),
+        "completion/complete".to_string(),
         "system.listMethods".to_string(),
         "system.describeMethod".to_string(),
     ];
@@ -185,6 +503,10 @@ async fn handle_list_methods(
         "auto-bind-coupons",
         "my-coupons",
         "now-time-info",
+        "plan-meal",
+        "claim-history",
+        "compare-accounts",
+        "recommend-coupons",
     ];
 
     all_methods.extend(tools.iter().map(|tool| format!("tools/call:{}", tool)));
@@ -194,7 +516,7 @@ async fn handle_list_methods(
 
 /// Handle system.describeMethod method
 async fn handle_describe_method(
-    _state: &Arc<Mutex<McpServerState>>,
+    _state: &Arc<McpServerState>,
     request: &McpRequest,
 ) -> Json<McpResponse> {
     // request.id should always be Some at this point (checked in handle_mcp_request)
@@ -222,12 +544,19 @@ async fn handle_describe_method(
         "initialize" => describe_initialize(),
         "tools/list" => describe_tools_list(),
         "tools/call" => describe_tools_call(),
+        "prompts/list" => describe_prompts_list(),
+        "prompts/get" => describe_prompts_get(),
+        "completion/complete" => describe_completion_complete(),
         "system.listMethods" => describe_list_methods(),
         "system.describeMethod" => describe_describe_method(),
         "available-coupons" | "tools/call:available-coupons" => describe_available_coupons_tool(),
         "auto-bind-coupons" | "tools/call:auto-bind-coupons" => describe_auto_bind_coupons_tool(),
         "my-coupons" | "tools/call:my-coupons" => describe_my_coupons_tool(),
         "now-time-info" | "tools/call:now-time-info" => describe_current_time_tool(),
+        "plan-meal" | "tools/call:plan-meal" => describe_plan_meal_tool(),
+        "claim-history" | "tools/call:claim-history" => describe_claim_history_tool(),
+        "compare-accounts" | "tools/call:compare-accounts" => describe_compare_accounts_tool(),
+        "recommend-coupons" | "tools/call:recommend-coupons" => describe_recommend_coupons_tool(),
         _ => {
             return Json(McpResponse::error(
                 id,
@@ -242,70 +571,249 @@ async fn handle_describe_method(
 
 /// Handle available-coupons tool
 async fn handle_available_coupons(
-    state: &Arc<Mutex<McpServerState>>,
+    state: &Arc<McpServerState>,
     id: u32,
 ) -> Json<McpResponse> {
-    let state = state.lock().await;
-    let client = state.mcp_client.lock().await;
-
-    match client.get_available_coupons().await {
+    match state.client().get_available_coupons().await {
         Ok(result) => {
             let content = vec![McpContent::text(&result)];
             Json(McpResponse::success_tool_result(id, content))
         }
-        Err(e) => Json(McpResponse::tool_error(id, &e.to_string())),
+        Err(e) => Json(McpResponse::upstream_error(id, &e)),
     }
 }
 
-/// Handle auto-bind-coupons tool
+/// Handle auto-bind-coupons tool. Calls are rate limited per session and audited;
+/// one made while a claim runs, or retrying one by its idempotency key, does not claim again.
 async fn handle_auto_bind_coupons(
-    state: &Arc<Mutex<McpServerState>>,
+    state: &Arc<McpServerState>,
     id: u32,
+    arguments: Option<&serde_json::Value>,
+    caller: &Caller,
 ) -> Json<McpResponse> {
-    let state = state.lock().await;
-    let client = state.mcp_client.lock().await;
+    const TOOL: &str = "auto-bind-coupons";
+    let force = arguments.and_then(|args| args.get("force")).and_then(|v| v.as_bool()).unwrap_or(false);
+    let confirm = arguments.and_then(|args| args.get("confirm")).and_then(|v| v.as_str()).unwrap_or_default();
+    if force && confirm != CLAIM_CONFIRM_PHRASE {
+        return Json(McpResponse::error(
+            id,
+            -32602,
+            &format!("Invalid params: force 需要同时传入 confirm: \"{}\"", CLAIM_CONFIRM_PHRASE),
+        ));
+    }
+    let key = arguments.and_then(|args| args.get("idempotency_key")).and_then(|v| v.as_str()).map(str::trim).filter(|k| !k.is_empty());
+    // Answered at once rather than after waiting for the claim lock
+    if let Some(existing) = inflight::existing(Operation::Claim, key) {
+        return existing_claim(id, existing, caller, force).await;
+    }
 
-    match client.auto_bind_coupons().await {
+    let _claim = state.claim.lock().await;
+    let minutes = state.config().mcp_claim_interval_minutes.unwrap_or(DEFAULT_CLAIM_INTERVAL_MINUTES);
+    let interval = Duration::from_secs(minutes * 60);
+    let last_claim = state.last_claims().get(&caller.session).map(Instant::elapsed);
+    if let Some(elapsed) = last_claim {
+        if !force && elapsed < interval {
+            let wait = (interval - elapsed).as_secs().div_ceil(60);
+            let message = format!(
+                "领取过于频繁：本会话 {} 分钟内已调用过领取，请约 {} 分钟后再试。如确需立即领取，请先征得用户同意，再传入 {{\"force\": true, \"confirm\": \"{}\"}}",
+                minutes, wait, CLAIM_CONFIRM_PHRASE
+            );
+            AuditEntry::new(TOOL, caller, force, "rate_limited", &message).write();
+            return Json(McpResponse::tool_error(id, &message));
+        }
+    }
+    let account = inflight::account(&state.config().token);
+    let ticket = match inflight::begin(Operation::Claim, &account, key) {
+        Ok(ticket) => ticket,
+        Err(existing) => return existing_claim(id, existing, caller, force).await,
+    };
+    state.last_claims().insert(caller.session.clone(), Instant::now());
+
+    let (result, verification) = claim_verified(&state.client()).await;
+    ClaimHistory::record("mcp", &result, verification.as_ref());
+    mqtt::publish_claim("mcp", &result, verification.as_ref());
+    let success = ClaimVerification::succeeded(&result, verification.as_ref());
+    ticket.finish(match &result {
+        Ok(_) => Outcome { success, message: ClaimVerification::message(verification.as_ref()) },
+        Err(e) => Outcome { success: false, message: describe_error(e) },
+    });
+    match result {
+        Ok(_) if !success => {
+            let message = ClaimVerification::message(verification.as_ref());
+            AuditEntry::new(TOOL, caller, force, "nothing_bound", &message).write();
+            Json(McpResponse::tool_error(id, &message))
+        }
         Ok(result) => {
-            let content = vec![McpContent::text(&result)];
+            let message = ClaimVerification::message(verification.as_ref());
+            AuditEntry::new(TOOL, caller, force, "success", &message).write();
+            // Tell the model what was verified, so it does not repeat an empty success
+            let content = vec![McpContent::text(&result), McpContent::text(&message)];
             Json(McpResponse::success_tool_result(id, content))
         }
-        Err(e) => Json(McpResponse::tool_error(id, &e.to_string())),
+        Err(e) => {
+            AuditEntry::new(TOOL, caller, force, "failed", &describe_error(&e)).write();
+            Json(McpResponse::upstream_error(id, &e))
+        }
+    }
+}
+
+/// Answer an auto-bind-coupons call with the claim already running or made, without claiming
+async fn existing_claim(id: u32, existing: Existing, caller: &Caller, force: bool) -> Json<McpResponse> {
+    const TOOL: &str = "auto-bind-coupons";
+    match existing.resolve().await {
+        Ok(outcome) => {
+            AuditEntry::new(TOOL, caller, force, "replayed", &outcome.message).write();
+            let message = format!("此前的领取结果: {}", outcome.message);
+            if outcome.success {
+                Json(McpResponse::success_tool_result(id, vec![McpContent::text(&message)]))
+            } else {
+                Json(McpResponse::tool_error(id, &message))
+            }
+        },
+        Err(conflict) => {
+            let message = format!("{}。请勿重复调用；如需重试，请传入相同的 idempotency_key 等待其结果", conflict);
+            AuditEntry::new(TOOL, caller, force, "conflict", &message).write();
+            Json(McpResponse::tool_error(id, &message))
+        },
     }
 }
 
 /// Handle my-coupons tool
 async fn handle_my_coupons(
-    state: &Arc<Mutex<McpServerState>>,
+    state: &Arc<McpServerState>,
     id: u32,
 ) -> Json<McpResponse> {
-    let state = state.lock().await;
-    let client = state.mcp_client.lock().await;
-
-    match client.get_my_coupons().await {
+    match state.client().get_my_coupons().await {
         Ok(result) => {
+            *state.coupons() = parse_coupons_from_markdown(&result);
             let content = vec![McpContent::text(&result)];
             Json(McpResponse::success_tool_result(id, content))
         }
-        Err(e) => Json(McpResponse::tool_error(id, &e.to_string())),
+        Err(e) => Json(McpResponse::upstream_error(id, &e)),
     }
 }
 
 /// Handle now-time-info tool
 async fn handle_current_time(
-    state: &Arc<Mutex<McpServerState>>,
+    state: &Arc<McpServerState>,
     id: u32,
 ) -> Json<McpResponse> {
-    let state = state.lock().await;
-    let client = state.mcp_client.lock().await;
-
-    match client.get_current_time().await {
+    match state.client().get_current_time().await {
         Ok(result) => {
             let content = vec![McpContent::text(&result)];
             Json(McpResponse::success_tool_result(id, content))
         }
-        Err(e) => Json(McpResponse::tool_error(id, &e.to_string())),
+        Err(e) => Json(McpResponse::upstream_error(id, &e)),
+    }
+}
+
+/// Handle plan-meal tool
+async fn handle_plan_meal(
+    state: &Arc<McpServerState>,
+    id: u32,
+    arguments: Option<&serde_json::Value>,
+) -> Json<McpResponse> {
+    let titles: Vec<String> = match arguments.and_then(|args| args.get("titles")).map(|v| serde_json::from_value(v.clone())) {
+        Some(Ok(titles)) => titles,
+        Some(Err(e)) => return Json(McpResponse::error(id, -32602, &format!("Invalid params: {}", e))),
+        None => return Json(McpResponse::error(id, -32602, "Invalid params: Missing titles")),
+    };
+    if titles.is_empty() {
+        return Json(McpResponse::tool_error(id, "请至少选择一张优惠券"));
+    }
+
+    let mut coupons = match prompts::cached_coupons(state).await {
+        Ok(coupons) => coupons,
+        Err(e) => return Json(McpResponse::upstream_error(id, &e)),
+    };
+    UsageStore::load().unwrap_or_default().apply(&mut coupons);
+
+    let (picked, missing) = pick_by_title(&coupons, &titles);
+    if !missing.is_empty() {
+        return Json(McpResponse::tool_error(id, &format!("未找到已领取的优惠券: {}", missing.join("、"))));
+    }
+
+    let plan = MealPlan::build(&picked);
+    let content = vec![McpContent::text(&plan.checklist)];
+    Json(McpResponse::success_tool_result(id, content))
+}
+
+/// Handle claim-history tool
+fn handle_claim_history(id: u32, arguments: Option<&serde_json::Value>) -> Json<McpResponse> {
+    let argument = |name: &str| arguments.and_then(|args| args.get(name)).and_then(|v| v.as_str()).filter(|v| !v.is_empty());
+
+    let mut dates = [None, None];
+    for (slot, name) in dates.iter_mut().zip(["from", "to"]) {
+        if let Some(text) = argument(name) {
+            match chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+                Ok(date) => *slot = Some(date),
+                Err(_) => return Json(McpResponse::error(id, -32602, &format!("Invalid params: {} 应为 YYYY-MM-DD 格式", name))),
+            }
+        }
     }
+
+    let history = match ClaimHistory::load() {
+        Ok(history) => history,
+        Err(e) => return Json(McpResponse::tool_error(id, &format!("读取领取记录失败: {}", e))),
+    };
+    let entries = history.query(dates[0], dates[1], argument("account"));
+
+    Json(McpResponse::structured_tool_result(id, &serde_json::json!({
+        "total": entries.len(),
+        "entries": entries
+    })))
+}
+
+/// Handle compare-accounts tool. Reads the current config, so accounts added from the terminal mode are seen.
+async fn handle_compare_accounts(id: u32, arguments: Option<&serde_json::Value>) -> Json<McpResponse> {
+    let argument = |name: &str| arguments.and_then(|args| args.get(name)).and_then(|v| v.as_str()).filter(|v| !v.is_empty());
+    let (Some(a), Some(b)) = (argument("a"), argument("b")) else {
+        return Json(McpResponse::error(id, -32602, "Invalid params: Missing a or b"));
+    };
+
+    let config = ConfigService::global().current();
+    let (a, b) = match pick_accounts(&config, a, b) {
+        Ok(pair) => pair,
+        Err(message) => return Json(McpResponse::tool_error(id, &message)),
+    };
+    match compare_accounts(&config, a, b).await {
+        Ok(comparison) => Json(McpResponse::success_tool_result(id, vec![McpContent::text(&comparison.to_markdown())])),
+        Err(e) => Json(McpResponse::upstream_error(id, &e)),
+    }
+}
+
+/// Handle recommend-coupons tool
+async fn handle_recommend_coupons(
+    state: &Arc<McpServerState>,
+    id: u32,
+    arguments: Option<&serde_json::Value>,
+) -> Json<McpResponse> {
+    let limit = match arguments.and_then(|args| args.get("limit")) {
+        None => DEFAULT_RECOMMENDATIONS,
+        Some(value) => match value.as_u64().filter(|n| *n > 0) {
+            Some(limit) => limit as usize,
+            None => return Json(McpResponse::error(id, -32602, "Invalid params: limit 应为正整数")),
+        },
+    };
+    let history = match ClaimHistory::load() {
+        Ok(history) => history,
+        Err(e) => return Json(McpResponse::tool_error(id, &format!("读取领取记录失败: {}", e))),
+    };
+    let available = match state.client().get_available_coupons().await {
+        Ok(text) => parse_coupons_from_markdown(&text),
+        Err(e) => return Json(McpResponse::upstream_error(id, &e)),
+    };
+
+    let recommendations = recommend(&available, &history, &UsageStore::load().unwrap_or_default(), limit);
+    let message = if recommendations.is_empty() {
+        format!("当前 {} 张可领取的优惠券与领取和使用记录都不相似，或还没有记录", available.len())
+    } else {
+        format!("从 {} 张可领取的优惠券中推荐 {} 张", available.len(), recommendations.len())
+    };
+    Json(McpResponse::structured_tool_result(id, &serde_json::json!({
+        "message": message,
+        "recommendations": recommendations
+    })))
 }
 
 /// Describe initialize method
@@ -392,6 +900,74 @@ fn describe_tools_call() -> McpToolDescription {
     }
 }
 
+/// Describe prompts/list method
+fn describe_prompts_list() -> McpToolDescription {
+    McpToolDescription {
+        name: "prompts/list".to_string(),
+        description: "列出所有可用的提示模板".to_string(),
+        parameters: serde_json::Value::Object(serde_json::Map::new()),
+        returns: serde_json::Value::Object(serde_json::Map::new()),
+        tags: vec!["prompts".to_string(), "introspection".to_string()],
+        examples: None,
+    }
+}
+
+/// Describe prompts/get method
+fn describe_prompts_get() -> McpToolDescription {
+    McpToolDescription {
+        name: "prompts/get".to_string(),
+        description: "按名称和参数获取提示模板内容".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "arguments": {"type": "object"}
+            },
+            "required": ["name"]
+        }),
+        returns: serde_json::Value::Object(serde_json::Map::new()),
+        tags: vec!["prompts".to_string()],
+        examples: None,
+    }
+}
+
+/// Describe completion/complete method
+fn describe_completion_complete() -> McpToolDescription {
+    McpToolDescription {
+        name: "completion/complete".to_string(),
+        description: "根据已缓存的优惠券列表补全提示模板参数（标签、优惠券标题）".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "ref": {"type": "object"},
+                "argument": {
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string"},
+                        "value": {"type": "string"}
+                    }
+                }
+            },
+            "required": ["ref", "argument"]
+        }),
+        returns: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "completion": {
+                    "type": "object",
+                    "properties": {
+                        "values": {"type": "array", "items": {"type": "string"}},
+                        "total": {"type": "integer"},
+                        "hasMore": {"type": "boolean"}
+                    }
+                }
+            }
+        }),
+        tags: vec!["prompts".to_string(), "completion".to_string()],
+        examples: None,
+    }
+}
+
 /// Describe system.listMethods method
 fn describe_list_methods() -> McpToolDescription {
     McpToolDescription {
@@ -432,8 +1008,16 @@ fn describe_available_coupons_tool() -> McpToolDescription {
 fn describe_auto_bind_coupons_tool() -> McpToolDescription {
     McpToolDescription {
         name: "auto-bind-coupons".to_string(),
-        description: "一键领取所有可用的麦当劳优惠券".to_string(),
-        parameters: serde_json::Value::Object(serde_json::Map::new()),
+        description: "一键领取所有可用的麦当劳优惠券。同一会话默认 10 分钟内只能调用一次".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "force": {"type": "boolean"},
+                "confirm": {"type": "string", "enum": [CLAIM_CONFIRM_PHRASE]},
+                "idempotency_key": {"type": "string"}
+            },
+            "required": []
+        }),
         returns: serde_json::Value::Object(serde_json::Map::new()),
         tags: vec!["coupons".to_string(), "claim".to_string()],
         examples: None,
@@ -464,12 +1048,144 @@ fn describe_current_time_tool() -> McpToolDescription {
     }
 }
 
+/// Describe plan-meal tool
+fn describe_plan_meal_tool() -> McpToolDescription {
+    McpToolDescription {
+        name: "plan-meal".to_string(),
+        description: "根据选择的已领取优惠券生成点餐清单，计算合计金额并提示冲突".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "titles": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["titles"]
+        }),
+        returns: serde_json::json!({
+            "type": "string",
+            "description": "Markdown 格式的点餐清单"
+        }),
+        tags: vec!["coupons".to_string(), "plan".to_string()],
+        examples: Some(vec![McpToolExample {
+            name: "汉堡加薯条".to_string(),
+            description: "用两张优惠券组合一餐".to_string(),
+            parameters: serde_json::json!({"titles": ["麦辣鸡腿堡", "中薯条"]}),
+            returns: serde_json::json!("# 点餐清单 ...\n- [ ] 麦辣鸡腿堡 — ¥15 ...\n\n**合计**: ¥24.00"),
+        }]),
+    }
+}
+
+/// Describe claim-history tool
+fn describe_claim_history_tool() -> McpToolDescription {
+    McpToolDescription {
+        name: "claim-history".to_string(),
+        description: "查询本机记录的领取历史，可按日期范围和账号筛选，结果按时间倒序".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "from": {"type": "string", "format": "date"},
+                "to": {"type": "string", "format": "date"},
+                "account": {"type": "string"}
+            },
+            "required": []
+        }),
+        returns: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "total": {"type": "integer"},
+                "entries": {"type": "array"}
+            }
+        }),
+        tags: vec!["history".to_string(), "claim".to_string()],
+        examples: Some(vec![McpToolExample {
+            name: "上周的领取记录".to_string(),
+            description: "查询一周内领取了哪些券".to_string(),
+            parameters: serde_json::json!({"from": "2025-01-01", "to": "2025-01-07"}),
+            returns: serde_json::json!({
+                "total": 1,
+                "entries": [{
+                    "at": "2025-01-03 09:00:00",
+                    "account": "我的账号",
+                    "source": "cli",
+                    "success": true,
+                    "message": "领取成功！",
+                    "coupons": ["麦辣鸡腿堡"]
+                }]
+            }),
+        }]),
+    }
+}
+
+/// Describe compare-accounts tool
+fn describe_compare_accounts_tool() -> McpToolDescription {
+    McpToolDescription {
+        name: "compare-accounts".to_string(),
+        description: "对比两个已保存账号持有的优惠券，列出仅一方持有的和双方都有的".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "a": {"type": "string"},
+                "b": {"type": "string"}
+            },
+            "required": ["a", "b"]
+        }),
+        returns: serde_json::json!({
+            "type": "string",
+            "description": "Markdown 格式的对比结果"
+        }),
+        tags: vec!["coupons".to_string(), "accounts".to_string()],
+        examples: Some(vec![McpToolExample {
+            name: "我和家人的优惠券".to_string(),
+            description: "看看哪些券只有一个账号有".to_string(),
+            parameters: serde_json::json!({"a": "我的账号", "b": "家人"}),
+            returns: serde_json::json!("# 账号对比: 我的账号 / 家人\n\n## 仅「我的账号」持有 (1 张)\n- 麦辣鸡腿堡 — ¥15 · 2025-01-31 ..."),
+        }]),
+    }
+}
+
+/// Describe recommend-coupons tool
+fn describe_recommend_coupons_tool() -> McpToolDescription {
+    McpToolDescription {
+        name: "recommend-coupons".to_string(),
+        description: "按与领取和使用记录的相似度为当前可领取的优惠券排序，返回推荐理由".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "limit": {"type": "integer", "minimum": 1}
+            },
+            "required": []
+        }),
+        returns: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "message": {"type": "string"},
+                "recommendations": {"type": "array"}
+            }
+        }),
+        tags: vec!["coupons".to_string(), "history".to_string()],
+        examples: Some(vec![McpToolExample {
+            name: "今天有什么常领的券".to_string(),
+            description: "看看常领的券今天是否又有了".to_string(),
+            parameters: serde_json::json!({"limit": 3}),
+            returns: serde_json::json!({
+                "message": "从 12 张可领取的优惠券中推荐 1 张",
+                "recommendations": [{
+                    "title": "板烧鸡腿堡",
+                    "price": "¥13.9",
+                    "expiry": "2025-01-01 至 2025-01-31",
+                    "score": 0.82,
+                    "reasons": ["你领取过 5 次「板烧鸡腿堡」", "你常领「午餐」类优惠券（8 张）"]
+                }]
+            }),
+        }]),
+    }
+}
+
 /// Handle MCP GET requests for SSE/streamable connections
 /// For SSE: GET request establishes the connection, responses come via POST
 /// For streamable HTTP: GET request is just a health check
 async fn handle_mcp_get_request(
     headers: HeaderMap,
-    State(_state): State<Arc<Mutex<McpServerState>>>,
+    State(_state): State<Arc<McpServerState>>,
 ) -> Response<Body> {
     // Check if this is an SSE request by looking for Accept header
     if let Some(accept) = headers.get(header::ACCEPT) {
@@ -500,9 +1216,19 @@ async fn handle_mcp_get_request(
 }
 
 /// Run the MCP server
-pub async fn run_mcp_server(config: Config, mcp_client: McpClient) -> Result<()> {
+pub async fn run_mcp_server(config: Arc<Config>, mcp_client: McpClient) -> Result<()> {
     let port = config.mcp_server_port.unwrap_or(8080);
-    let state = Arc::new(Mutex::new(McpServerState::new(mcp_client, config.clone())));
+    let allowlist = Arc::new(Allowlist::from_config(&config.allowed_ips, "allowed_ips")?);
+    let bind = allowlist::bind_address(config.mcp_server_bind.as_deref(), "mcp_server_bind")?;
+    if let Some(warning) = allowlist::check_bind(&config, bind, "mcp_server_bind")? {
+        tracing::warn!("{}", warning);
+    }
+    backup::spawn_scheduler(&config);
+    retention::spawn(&config);
+    mqtt::spawn(&config);
+    scheduler::spawn(&config)?;
+    let state = Arc::new(McpServerState::new(mcp_client, config.clone()));
+    control_api::spawn(state.clone());
 
     // Create router with MCP endpoints
     // POST for JSON-RPC 2.0 requests
@@ -510,14 +1236,19 @@ pub async fn run_mcp_server(config: Config, mcp_client: McpClient) -> Result<()>
     let app = Router::new()
         .route("/", post(handle_mcp_request))
         .route("/", get(handle_mcp_get_request))
-        .with_state(state);
+        .with_state(state)
+        .layer(axum::middleware::from_fn_with_state(allowlist, allowlist::enforce))
+        .layer(logging::access::layer());
 
     // Start server
-    println!("MCP server starting on port {}", port);
+    let addr = SocketAddr::new(bind, port);
+    println!("MCP server starting on {}", addr);
     axum::serve(
-        tokio::net::TcpListener::bind(&format!("0.0.0.0:{}", port)).await?,
-        app.into_make_service()
-    ).await?;
+        tokio::net::TcpListener::bind(addr).await?,
+        app.into_make_service_with_connect_info::<SocketAddr>()
+    )
+    .with_graceful_shutdown(control::stopped())
+    .await?;
 
     Ok(())
 }
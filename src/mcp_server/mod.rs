@@ -1,4 +1,9 @@
+pub mod audit;
+pub mod control_api;
+pub mod elicitation;
+pub mod errors;
 pub mod handlers;
+pub mod prompts;
 pub mod types;
 
 pub use handlers::{run_mcp_server, McpServerState};
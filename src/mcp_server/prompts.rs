@@ -0,0 +1,181 @@
+use axum::Json;
+use std::sync::Arc;
+
+use crate::{error::Result, coupons::{contains_key, match_key, parse_coupons_from_markdown, Coupon}, mcp_server::{handlers::McpServerState, types::*}};
+
+/// Maximum number of suggestions returned by completion/complete, per MCP spec
+const MAX_COMPLETIONS: usize = 100;
+
+/// Handle prompts/list method
+pub async fn handle_prompts_list(id: u32) -> Json<McpResponse> {
+    let result = serde_json::json!({
+        "prompts": [
+            {
+                "name": "coupons-by-tag",
+                "description": "按标签查找已领取的优惠券",
+                "arguments": [
+                    {
+                        "name": "tag",
+                        "description": "优惠券标签，如 主食、小吃",
+                        "required": true
+                    }
+                ]
+            },
+            {
+                "name": "coupon-detail",
+                "description": "查看某张已领取优惠券的详情",
+                "arguments": [
+                    {
+                        "name": "title",
+                        "description": "优惠券标题",
+                        "required": true
+                    }
+                ]
+            }
+        ]
+    });
+
+    Json(McpResponse::success(id, result))
+}
+
+/// Handle prompts/get method
+pub async fn handle_prompts_get(
+    state: &Arc<McpServerState>,
+    request: &McpRequest,
+) -> Json<McpResponse> {
+    let id = request.id.unwrap_or(0);
+
+    let params: McpPromptGetParams = match request.params.clone().map(serde_json::from_value) {
+        Some(Ok(params)) => params,
+        Some(Err(e)) => return Json(McpResponse::error(id, -32602, &format!("Invalid params: {}", e))),
+        None => return Json(McpResponse::error(id, -32602, "Invalid params: Missing params")),
+    };
+
+    let argument = |name: &str| {
+        params.arguments.as_ref()
+            .and_then(|args| args.get(name))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let coupons = match cached_coupons(state).await {
+        Ok(coupons) => coupons,
+        Err(e) => return Json(McpResponse::upstream_error(id, &e)),
+    };
+
+    let (description, text) = match params.name.as_str() {
+        "coupons-by-tag" => {
+            let tag = argument("tag");
+            let matching: Vec<&Coupon> = coupons.iter()
+                .filter(|c| c.tag_list().iter().any(|t| match_key(t) == match_key(&tag)))
+                .collect();
+            (
+                format!("标签为「{}」的优惠券", tag),
+                format!(
+                    "以下是我已领取的、标签为「{}」的麦当劳优惠券，请帮我总结并推荐优先使用哪张：\n\n{}",
+                    tag,
+                    format_coupons(&matching)
+                ),
+            )
+        }
+        "coupon-detail" => {
+            let title = argument("title");
+            let matching: Vec<&Coupon> = coupons.iter().filter(|c| match_key(&c.title) == match_key(&title)).collect();
+            (
+                format!("优惠券「{}」详情", title),
+                format!(
+                    "请根据以下信息说明这张麦当劳优惠券的使用方式和注意事项：\n\n{}",
+                    format_coupons(&matching)
+                ),
+            )
+        }
+        _ => return Json(McpResponse::error(id, -32602, &format!("Prompt not found: {}", params.name))),
+    };
+
+    let result = serde_json::json!({
+        "description": description,
+        "messages": [
+            {
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": text
+                }
+            }
+        ]
+    });
+
+    Json(McpResponse::success(id, result))
+}
+
+/// Handle completion/complete method - suggests prompt argument values from the cached coupon list
+pub async fn handle_completion_complete(
+    state: &Arc<McpServerState>,
+    request: &McpRequest,
+) -> Json<McpResponse> {
+    let id = request.id.unwrap_or(0);
+
+    let params: McpCompleteParams = match request.params.clone().map(serde_json::from_value) {
+        Some(Ok(params)) => params,
+        Some(Err(e)) => return Json(McpResponse::error(id, -32602, &format!("Invalid params: {}", e))),
+        None => return Json(McpResponse::error(id, -32602, "Invalid params: Missing params")),
+    };
+
+    let candidates: Vec<String> = match (params.reference.ref_type.as_str(), params.argument.name.as_str()) {
+        ("ref/prompt", "tag") => {
+            let coupons = cached_coupons(state).await.unwrap_or_default();
+            let mut tags: Vec<String> = coupons.iter().flat_map(|c| c.tag_list()).collect();
+            tags.sort();
+            tags.dedup();
+            tags
+        }
+        ("ref/prompt", "title") => {
+            let coupons = cached_coupons(state).await.unwrap_or_default();
+            let mut titles: Vec<String> = coupons.into_iter().map(|c| c.title).collect();
+            titles.dedup();
+            titles
+        }
+        _ => Vec::new(),
+    };
+
+    let matches: Vec<String> = candidates.into_iter()
+        .filter(|c| contains_key(c, &params.argument.value))
+        .collect();
+    let total = matches.len();
+    let values: Vec<String> = matches.into_iter().take(MAX_COMPLETIONS).collect();
+
+    let result = serde_json::json!({
+        "completion": {
+            "values": values,
+            "total": total,
+            "hasMore": total > MAX_COMPLETIONS
+        }
+    });
+
+    Json(McpResponse::success(id, result))
+}
+
+/// Held coupons, fetched from upstream on first use and cached in the server state
+pub(crate) async fn cached_coupons(state: &Arc<McpServerState>) -> Result<Vec<Coupon>> {
+    let cached = state.coupons().clone();
+    if !cached.is_empty() {
+        return Ok(cached);
+    }
+    let coupons = parse_coupons_from_markdown(&state.client().get_my_coupons().await?);
+    *state.coupons() = coupons.clone();
+    Ok(coupons)
+}
+
+fn format_coupons(coupons: &[&Coupon]) -> String {
+    if coupons.is_empty() {
+        return "（没有匹配的优惠券）".to_string();
+    }
+    coupons.iter()
+        .map(|c| format!(
+            "## {}{}\n- **优惠**: {}\n- **有效期**: {}\n- **标签**: {}",
+            c.title, c.count_badge(), c.price, c.expiry, c.tags
+        ))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
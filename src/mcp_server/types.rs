@@ -4,21 +4,74 @@ use serde::{Deserialize, Serialize};
 /// Per JSON-RPC 2.0 spec: id can be string, number, or null (for notifications)
 /// Notifications don't have an id field
 #[derive(Debug, Deserialize)]
+#[serde(try_from = "RawMcpRequest")]
 pub struct McpRequest {
     pub jsonrpc: String,
+    /// Empty for responses to server-initiated requests
     pub method: String,
     pub params: Option<serde_json::Value>,
-    #[serde(default, deserialize_with = "deserialize_optional_id")]
+    /// Empty for notifications and for responses to server-initiated requests
     pub id: Option<u32>,
+    /// Id of the server-initiated request a response answers
+    pub answers: Option<String>,
+    /// Set when the client answers a server-initiated request
+    pub result: Option<serde_json::Value>,
+    pub error: Option<serde_json::Value>,
+}
+
+impl McpRequest {
+    /// Whether this is the client's response to a server-initiated request
+    pub fn is_response(&self) -> bool {
+        self.answers.is_some()
+    }
+
+    /// Name of the tool for a tools/call request
+    pub fn tool_name(&self) -> Option<&str> {
+        (self.method == "tools/call")
+            .then(|| self.params.as_ref()?.get("name")?.as_str())
+            .flatten()
+    }
+}
+
+/// Request as sent, before the id is interpreted
+#[derive(Deserialize)]
+struct RawMcpRequest {
+    jsonrpc: String,
+    #[serde(default)]
+    method: String,
+    params: Option<serde_json::Value>,
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    #[serde(default)]
+    result: Option<serde_json::Value>,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+impl TryFrom<RawMcpRequest> for McpRequest {
+    type Error = String;
+
+    fn try_from(raw: RawMcpRequest) -> Result<Self, Self::Error> {
+        // Responses echo the server's own string id; requests must use numeric ids
+        let is_response = raw.method.is_empty() && (raw.result.is_some() || raw.error.is_some());
+        let (id, answers) = match raw.id {
+            Some(serde_json::Value::String(s)) if is_response => (None, Some(s)),
+            Some(serde_json::Value::Number(n)) if is_response => (None, Some(n.to_string())),
+            id => (parse_id(id)?, None),
+        };
+        Ok(Self {
+            jsonrpc: raw.jsonrpc,
+            method: raw.method,
+            params: raw.params,
+            id,
+            answers,
+            result: raw.result,
+            error: raw.error,
+        })
+    }
 }
 
-/// Deserialize optional ID field - supports number, string, null, or missing
-fn deserialize_optional_id<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    use serde::Deserialize;
-    let value: Option<serde_json::Value> = Option::deserialize(deserializer)?;
+fn parse_id(value: Option<serde_json::Value>) -> Result<Option<u32>, String> {
     match value {
         None => Ok(None),
         Some(serde_json::Value::Null) => Ok(None),
@@ -26,14 +79,14 @@ where
             n.as_u64()
                 .and_then(|v| u32::try_from(v).ok())
                 .map(Some)
-                .ok_or_else(|| serde::de::Error::custom("Invalid ID: number out of range"))
+                .ok_or_else(|| "Invalid ID: number out of range".to_string())
         }
         Some(serde_json::Value::String(s)) => {
             s.parse::<u32>()
                 .map(Some)
-                .map_err(|_| serde::de::Error::custom("Invalid ID: string is not a valid number"))
+                .map_err(|_| "Invalid ID: string is not a valid number".to_string())
         }
-        _ => Err(serde::de::Error::custom("Invalid ID: must be number, string, or null")),
+        _ => Err("Invalid ID: must be number, string, or null".to_string()),
     }
 }
 
@@ -58,6 +111,40 @@ pub struct McpDescribeMethodParams {
     pub name: String,
 }
 
+/// MCP prompts/get parameters
+#[derive(Debug, Deserialize)]
+pub struct McpPromptGetParams {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// MCP completion/complete parameters
+#[derive(Debug, Deserialize)]
+pub struct McpCompleteParams {
+    #[serde(rename = "ref")]
+    pub reference: McpCompletionRef,
+    pub argument: McpCompletionArgument,
+}
+
+/// Prompt or resource whose argument is being completed
+#[derive(Debug, Deserialize)]
+pub struct McpCompletionRef {
+    /// "ref/prompt" or "ref/resource"
+    #[serde(rename = "type")]
+    pub ref_type: String,
+    pub name: Option<String>,
+    pub uri: Option<String>,
+}
+
+/// Argument being completed and the partial value typed so far
+#[derive(Debug, Deserialize)]
+pub struct McpCompletionArgument {
+    pub name: String,
+    #[serde(default)]
+    pub value: String,
+}
+
 /// MCP JSON-RPC response structure
 /// Per JSON-RPC 2.0 spec: response must have either "result" or "error", but not both
 #[derive(Debug, Serialize)]
@@ -85,6 +172,9 @@ pub struct McpToolResult {
     pub content: Vec<McpContent>,
     #[serde(rename = "isError", default)]
     pub is_error: bool,
+    /// Machine-readable result matching the tool's outputSchema
+    #[serde(rename = "structuredContent", skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<serde_json::Value>,
 }
 
 /// MCP content item - can be text, image, etc.
@@ -124,6 +214,7 @@ impl McpResponse {
         let tool_result = McpToolResult {
             content,
             is_error: false,
+            structured_content: None,
         };
         Self {
             jsonrpc: "2.0".to_string(),
@@ -133,6 +224,22 @@ impl McpResponse {
         }
     }
     
+    /// Create a tool result carrying structured content, mirrored as JSON text for older clients
+    pub fn structured_tool_result<T: Serialize>(id: u32, data: &T) -> Self {
+        let value = serde_json::to_value(data).unwrap();
+        let tool_result = McpToolResult {
+            content: vec![McpContent::text(&serde_json::to_string_pretty(&value).unwrap())],
+            is_error: false,
+            structured_content: Some(value),
+        };
+        Self {
+            jsonrpc: "2.0".to_string(),
+            result: Some(serde_json::to_value(tool_result).unwrap()),
+            error: None,
+            id,
+        }
+    }
+
     /// Create a generic success response
     pub fn success<T: Serialize>(id: u32, data: T) -> Self {
         Self {
@@ -180,6 +287,7 @@ impl McpResponse {
                 data: None,
             }],
             is_error: true,
+            structured_content: None,
         };
         Self {
             jsonrpc: "2.0".to_string(),
@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs, path::PathBuf};
+
+use crate::{audit, backup::archive, config::Config, coupons::{normalize_text, HISTORY_FILE, USAGE_FILE}, daemon, images, logging::access::ACCESS_LOG_FILE, queue::QUEUE_FILE, utils::{format_current_time, local_now}};
+
+/// Records which layout the local data files are in, in the data directory
+pub const VERSION_FILE: &str = "data-version.json";
+
+/// One upgrade of the local data files: what it does, and the step itself
+type Migration = (&'static str, fn() -> Result<()>);
+
+/// `MIGRATIONS[n]` upgrades the data files from version `n` to `n + 1`.
+/// Append a step here whenever a data file changes incompatibly; never edit or reorder a released one.
+const MIGRATIONS: &[Migration] = &[
+    ("统一优惠券标题和标签的写法", normalize_titles),
+];
+
+/// Layout version this build reads and writes
+pub const DATA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Contents of the version file
+#[derive(Debug, Serialize, Deserialize)]
+struct DataVersion {
+    version: u32,
+    /// Time of the last migration
+    migrated_at: String,
+}
+
+/// What a startup migration did
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    /// Archive written before the first step
+    pub backup: PathBuf,
+}
+
+/// What `relocate` did
+pub struct Relocation {
+    /// Config directory the files were in
+    pub from: PathBuf,
+    /// New paths of the files moved
+    pub moved: Vec<PathBuf>,
+    /// Files left where they were, since the new location already had one
+    pub kept: Vec<PathBuf>,
+}
+
+/// Move the data files and image cache that older builds kept next to the config file
+/// into the data and cache directories. Returns `None` when nothing was left behind.
+pub fn relocate() -> Result<Option<Relocation>> {
+    let config_path = Config::get_config_path();
+    let Some(from) = config_path.parent() else {
+        return Ok(None);
+    };
+    let Ok(entries) = fs::read_dir(from) else {
+        return Ok(None);
+    };
+    let access_log_rotated = format!("{}.1", ACCESS_LOG_FILE);
+    let data_files = [
+        HISTORY_FILE,
+        USAGE_FILE,
+        audit::AUDIT_FILE,
+        audit::MCP_AUDIT_FILE,
+        ACCESS_LOG_FILE,
+        &access_log_rotated,
+        QUEUE_FILE,
+        VERSION_FILE,
+        daemon::PID_FILE,
+        daemon::LOCK_FILE,
+        daemon::LOG_FILE,
+    ];
+    let cache = images::cache_dir();
+    let mut moves = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_backup = name.starts_with("pre-migrate-v") && name.ends_with(".zip");
+        if data_files.contains(&name.as_str()) || is_backup {
+            moves.push((entry.path(), Config::data_path(&name)));
+        } else if cache.file_name().is_some_and(|cache| cache == name.as_str()) {
+            moves.push((entry.path(), cache.clone()));
+        }
+    }
+
+    let mut relocation = Relocation { from: from.to_path_buf(), moved: Vec::new(), kept: Vec::new() };
+    for (source, target) in moves.into_iter().filter(|(source, target)| source != target) {
+        if target.exists() {
+            relocation.kept.push(source);
+            continue;
+        }
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)
+                .context(format!("无法创建目录: {}", dir.display()))?;
+        }
+        // Across file systems a rename fails; files are copied instead, the image cache is just left
+        if fs::rename(&source, &target).is_err() {
+            if source.is_dir() {
+                relocation.kept.push(source);
+                continue;
+            }
+            fs::copy(&source, &target)
+                .context(format!("无法复制文件: {} -> {}", source.display(), target.display()))?;
+            fs::remove_file(&source)
+                .context(format!("无法删除文件: {}", source.display()))?;
+        }
+        relocation.moved.push(target);
+    }
+    Ok((!relocation.moved.is_empty() || !relocation.kept.is_empty()).then_some(relocation))
+}
+
+/// Bring the local data files up to [`DATA_VERSION`], backing them up to a full archive first.
+/// Returns `None` when there was nothing to do. Data written by a newer build is refused,
+/// since this build cannot know how to read it.
+pub fn run() -> Result<Option<MigrationReport>> {
+    let from = current_version()?;
+    if from > DATA_VERSION {
+        return Err(anyhow!(
+            "本地数据由较新的版本生成（数据版本 {}，当前程序支持 {}），请升级本工具后再运行",
+            from,
+            DATA_VERSION
+        ));
+    }
+    if from == DATA_VERSION {
+        return Ok(None);
+    }
+    // A fresh install has nothing to upgrade, only the version to remember
+    if !has_data() {
+        write_version(DATA_VERSION)?;
+        return Ok(None);
+    }
+
+    let backup = Config::data_path(&format!(
+        "pre-migrate-v{}-{}.zip",
+        from,
+        local_now().format("%Y%m%d%H%M%S")
+    ));
+    archive::create(&backup).context("迁移前备份失败，未修改任何数据")?;
+
+    for (version, (description, step)) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        step().with_context(|| format!(
+            "数据迁移失败（{}），可用 backup restore {} 恢复",
+            description,
+            backup.display()
+        ))?;
+        // Saved after every step so a failure resumes from the step that broke
+        write_version(version as u32 + 1)?;
+    }
+
+    Ok(Some(MigrationReport { from, to: DATA_VERSION, backup }))
+}
+
+/// Version of the data on disk. Data from before versioning counts as version 0.
+fn current_version() -> Result<u32> {
+    let path = Config::data_path(VERSION_FILE);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let text = fs::read_to_string(&path)
+        .context(format!("无法读取文件: {}", path.display()))?;
+    let data: DataVersion = serde_json::from_str(&text)
+        .context(format!("无法解析文件: {}", path.display()))?;
+    Ok(data.version)
+}
+
+fn write_version(version: u32) -> Result<()> {
+    let path = Config::data_path(VERSION_FILE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .context(format!("无法创建目录: {}", dir.display()))?;
+    }
+    let data = DataVersion { version, migrated_at: format_current_time() };
+    let text = serde_json::to_string_pretty(&data).context("无法序列化数据版本")?;
+    fs::write(&path, text)
+        .context(format!("无法写入文件: {}", path.display()))
+}
+
+/// Whether any versioned data file exists yet
+fn has_data() -> bool {
+    [HISTORY_FILE, USAGE_FILE, audit::AUDIT_FILE]
+        .iter()
+        .any(|name| Config::data_path(name).exists())
+}
+
+/// v0 to v1: titles and tags saved before upstream text was normalized may hold full-width and
+/// traditional characters. Rewritten in the display form, so they read and match like new ones.
+/// Works on plain JSON, as the structs may change in later versions.
+fn normalize_titles() -> Result<()> {
+    rewrite(USAGE_FILE, |data| {
+        for used in items(data, "used") {
+            normalize_field(used, "title");
+            normalize_field(used, "tags");
+        }
+    })?;
+    rewrite(HISTORY_FILE, |data| {
+        for entry in items(data, "entries") {
+            for field in ["coupons", "verified", "tags"] {
+                normalize_field(entry, field);
+            }
+        }
+    })
+}
+
+/// Apply `change` to a JSON data file, if it exists
+fn rewrite(name: &str, change: impl FnOnce(&mut Value)) -> Result<()> {
+    let path = Config::data_path(name);
+    if !path.exists() {
+        return Ok(());
+    }
+    let text = fs::read_to_string(&path)
+        .context(format!("无法读取文件: {}", path.display()))?;
+    let mut data: Value = serde_json::from_str(&text)
+        .context(format!("无法解析文件: {}", path.display()))?;
+    change(&mut data);
+    let text = serde_json::to_string_pretty(&data).context("无法序列化数据")?;
+    fs::write(&path, text)
+        .context(format!("无法写入文件: {}", path.display()))
+}
+
+/// Elements of the array under `key`
+fn items<'a>(data: &'a mut Value, key: &str) -> impl Iterator<Item = &'a mut Value> {
+    data.get_mut(key).and_then(Value::as_array_mut).into_iter().flatten()
+}
+
+/// Normalize a string field, or each string in an array field
+fn normalize_field(object: &mut Value, key: &str) {
+    match object.get_mut(key) {
+        Some(Value::String(text)) => *text = normalize_text(text),
+        Some(Value::Array(list)) => {
+            for item in list {
+                if let Value::String(text) = item {
+                    *text = normalize_text(text);
+                }
+            }
+        },
+        _ => {},
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Result};
+#[cfg(feature = "notifications")]
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use serde::Serialize;
+#[cfg(any(feature = "server", feature = "notifications"))]
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::{cli, config::{Config, ConfigService, MqttConfig}, coupons::{parse_coupons_from_markdown, ClaimVerification}, error::McdError, mcp::describe_error, utils::format_current_time};
+// Only the long-running servers publish the held coupons on a timer
+#[cfg(feature = "server")]
+use std::collections::HashSet;
+#[cfg(feature = "server")]
+use crate::{coupons::{Coupon, UsageStore}, mcp::McpClient};
+#[cfg(feature = "web")]
+use crate::queue::ClaimRequest;
+
+/// Longest wait for the broker to acknowledge a batch of messages
+#[cfg(feature = "notifications")]
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Payload on `claim_topic`
+#[derive(Debug, Serialize)]
+struct ClaimMessage<'a> {
+    at: String,
+    /// tui, web, cli, watch, schedule or mcp
+    source: &'a str,
+    account: Option<String>,
+    success: bool,
+    message: String,
+    /// Titles of the claimed coupons
+    coupons: Vec<String>,
+}
+
+/// Retained payload on `coupons_topic`
+#[cfg(feature = "server")]
+#[derive(Debug, Serialize)]
+struct CouponsMessage {
+    at: String,
+    held: usize,
+    unused: usize,
+    expiring_soon: usize,
+    titles: Vec<String>,
+}
+
+/// Payload on `expiring_topic`, once per coupon
+#[cfg(feature = "server")]
+#[derive(Debug, Serialize)]
+struct ExpiringMessage<'a> {
+    title: &'a str,
+    expiry: &'a str,
+    days_left: i64,
+    /// Opens the coupon in the McDonald's app when the notification is tapped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app_link: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    universal_link: Option<&'a str>,
+}
+
+/// Payload on `request_topic`
+#[cfg(feature = "web")]
+#[derive(Debug, Serialize)]
+struct RequestMessage<'a> {
+    at: String,
+    /// submit, approve or reject
+    event: &'a str,
+    #[serde(flatten)]
+    request: &'a ClaimRequest,
+}
+
+/// The MQTT settings, when a broker is configured and this build can publish to it
+fn settings(config: &Config) -> Option<MqttConfig> {
+    let mqtt = config.mqtt.clone().filter(|m| !m.host.trim().is_empty())?;
+    if !cfg!(feature = "notifications") {
+        tracing::warn!("{}", cli::not_compiled("MQTT 发布", "notifications"));
+        return None;
+    }
+    Some(mqtt)
+}
+
+/// Publish a claim outcome in the background. Short-lived commands should await
+/// the handle before exiting, or the message may never leave.
+pub fn publish_claim(source: &str, result: &Result<String, McdError>, verification: Option<&ClaimVerification>) -> Option<JoinHandle<()>> {
+    let config = ConfigService::global().current();
+    let mqtt = settings(&config)?;
+    let message = ClaimMessage {
+        at: format_current_time(),
+        source,
+        account: config.active_account.clone(),
+        success: ClaimVerification::succeeded(result, verification),
+        message: match result {
+            Ok(_) => ClaimVerification::message(verification),
+            Err(e) => format!("领取失败: {}", describe_error(e)),
+        },
+        coupons: match result {
+            Ok(text) => parse_coupons_from_markdown(text).into_iter().map(|c| c.title).collect(),
+            Err(_) => Vec::new(),
+        },
+    };
+    let payload = serde_json::to_vec(&message).ok()?;
+    Some(tokio::spawn(async move {
+        if let Err(e) = publish(&mqtt, vec![(mqtt.claim_topic.clone(), payload, false)]).await {
+            tracing::warn!("MQTT 发布领取结果失败: {:#}", e);
+        }
+    }))
+}
+
+/// Publish a family claim request after `event` (submit, approve or reject) in the background
+#[cfg(feature = "web")]
+pub fn publish_request(event: &str, request: &ClaimRequest) -> Option<JoinHandle<()>> {
+    let mqtt = settings(&ConfigService::global().current())?;
+    let message = RequestMessage { at: format_current_time(), event, request };
+    let payload = serde_json::to_vec(&message).ok()?;
+    Some(tokio::spawn(async move {
+        if let Err(e) = publish(&mqtt, vec![(mqtt.request_topic.clone(), payload, false)]).await {
+            tracing::warn!("MQTT 发布代领请求失败: {:#}", e);
+        }
+    }))
+}
+
+/// Publish the held coupon counts every `interval_minutes` in the background, plus an
+/// alert for each coupon the first time it is seen expiring soon
+#[cfg(feature = "server")]
+pub fn spawn(config: &Config) {
+    let Some(minutes) = settings(config).map(|m| m.interval_minutes).filter(|m| *m > 0) else {
+        return;
+    };
+    tracing::info!("已启用 MQTT 发布，每 {} 分钟更新一次优惠券数量", minutes);
+
+    tokio::spawn(async move {
+        let mut announced = HashSet::new();
+        let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+        loop {
+            interval.tick().await;
+            // Re-read the config so edits made while running are picked up
+            let config = ConfigService::global().current();
+            let Some(mqtt) = settings(&config) else {
+                continue;
+            };
+            if let Err(e) = publish_coupons(&config, &mqtt, &mut announced).await {
+                tracing::warn!("MQTT 发布优惠券数量失败: {:#}", e);
+            }
+        }
+    });
+}
+
+/// Fetch the held coupons and publish their counts and any newly expiring ones
+#[cfg(feature = "server")]
+async fn publish_coupons(config: &Config, mqtt: &MqttConfig, announced: &mut HashSet<(String, String)>) -> Result<()> {
+    if !config.has_valid_token() {
+        return Err(anyhow!("未设置Token"));
+    }
+    let client = McpClient::with_url(config.token.clone(), config.upstream_url())?;
+    let text = client.get_my_coupons().await.map_err(|e| anyhow!(describe_error(&e)))?;
+    let mut coupons = parse_coupons_from_markdown(&text);
+    UsageStore::load().unwrap_or_default().apply(&mut coupons);
+
+    let expiring: Vec<(&Coupon, i64)> = coupons.iter()
+        .filter(|c| !c.used)
+        .filter_map(|c| c.days_left().map(|days| (c, days)))
+        .filter(|(_, days)| (0..=mqtt.expiring_days).contains(days))
+        .collect();
+    let counts = CouponsMessage {
+        at: format_current_time(),
+        held: coupons.len(),
+        unused: coupons.iter().filter(|c| !c.used).count(),
+        expiring_soon: expiring.len(),
+        titles: coupons.iter().map(|c| c.title.clone()).collect(),
+    };
+
+    let mut messages = vec![(mqtt.coupons_topic.clone(), serde_json::to_vec(&counts)?, true)];
+    for (coupon, days_left) in expiring {
+        if announced.insert((coupon.title.clone(), coupon.expiry.clone())) {
+            let alert = ExpiringMessage {
+                title: &coupon.title,
+                expiry: &coupon.expiry,
+                days_left,
+                app_link: coupon.app_link.as_deref(),
+                universal_link: coupon.universal_link.as_deref(),
+            };
+            messages.push((mqtt.expiring_topic.clone(), serde_json::to_vec(&alert)?, false));
+        }
+    }
+    publish(mqtt, messages).await
+}
+
+/// Connect, publish `(topic, payload, retain)` messages at least once, and wait for the broker to acknowledge them
+#[cfg(feature = "notifications")]
+async fn publish(mqtt: &MqttConfig, messages: Vec<(String, Vec<u8>, bool)>) -> Result<()> {
+    let mut options = MqttOptions::new(format!("mcd-coupon-{}", std::process::id()), mqtt.host.trim(), mqtt.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if !mqtt.username.is_empty() {
+        options.set_credentials(&mqtt.username, &mqtt.password);
+    }
+    let total = messages.len();
+    let (client, mut eventloop) = AsyncClient::new(options, total + 1);
+    for (topic, payload, retain) in messages {
+        client.publish(topic, QoS::AtLeastOnce, retain, payload).await?;
+    }
+
+    let acknowledged = tokio::time::timeout(PUBLISH_TIMEOUT, async {
+        let mut acked = 0;
+        while acked < total {
+            if let Event::Incoming(Packet::PubAck(_)) = eventloop.poll().await? {
+                acked += 1;
+            }
+        }
+        Ok::<_, rumqttc::ConnectionError>(())
+    })
+    .await;
+    match acknowledged {
+        Ok(Ok(())) => {
+            // Best effort; the broker drops the session either way
+            let _ = client.disconnect().await;
+            let _ = tokio::time::timeout(Duration::from_secs(1), eventloop.poll()).await;
+            Ok(())
+        },
+        Ok(Err(e)) => Err(anyhow!("无法连接 MQTT 服务器 {}:{}: {}", mqtt.host, mqtt.port, e)),
+        Err(_) => Err(anyhow!("MQTT 服务器 {}:{} 在 {} 秒内未确认", mqtt.host, mqtt.port, PUBLISH_TIMEOUT.as_secs())),
+    }
+}
+
+#[cfg(not(feature = "notifications"))]
+async fn publish(_mqtt: &MqttConfig, _messages: Vec<(String, Vec<u8>, bool)>) -> Result<()> {
+    Err(anyhow!(cli::not_compiled("MQTT 发布", "notifications")))
+}
@@ -0,0 +1,266 @@
+use chrono::NaiveDateTime;
+use std::{fs, io::IsTerminal, net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener}, path::PathBuf, time::Duration};
+
+use crate::{allowlist::{self, Allowlist}, cli::{GlobalOptions, Verbosity}, config::{Config, ConfigService}, coupons, mcp::{self, McpClient}, scheduler, utils::{self, local_now}};
+#[cfg(feature = "tui")]
+use crate::ui::KeyMap;
+#[cfg(feature = "web")]
+use crate::web;
+
+/// How long the upstream probe may take before it counts as unreachable
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+/// A token validated this recently is trusted when upstream cannot be reached
+const TOKEN_CACHE_HOURS: i64 = 24;
+/// Smallest terminal the TUI lays out without clipping
+#[cfg(feature = "tui")]
+const MIN_TERMINAL_SIZE: (u16, u16) = (60, 20);
+
+/// Mode about to start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Tui,
+    /// Line-based TUI, which needs no particular terminal
+    Plain,
+    Html,
+    McpServer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One line of the checklist
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    /// What to do about a warning or failure
+    fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: Status::Pass, detail: detail.into(), fix: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Warn, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Fail, detail: detail.into(), fix: Some(fix.into()) }
+    }
+}
+
+/// Check everything the mode needs before starting it and print a checklist.
+/// Returns false when a check failed and the mode should not start.
+pub async fn run(target: Target, options: &GlobalOptions) -> bool {
+    let config = match Config::check_files() {
+        Ok(path) => {
+            let detail = path.map_or("尚未创建，将使用默认配置".to_string(), |p| p.display().to_string());
+            Check::pass("配置文件", detail)
+        }
+        Err(e) => Check::fail("配置文件", format!("{:#}", e), "修正文件中的 JSON 格式，或删除该文件后重新设置 Token"),
+    };
+    let mut checks = vec![
+        config,
+        check_writable("配置目录", Config::get_config_path().with_file_name(".preflight"), "检查该目录的权限，或在当前目录放置 mcd-coupon-config.json"),
+        check_writable("数据目录", Config::data_path(".preflight"), "检查该目录的权限，或用环境变量 MCD_COUPON_DATA_DIR 指定其他目录"),
+    ];
+
+    let config = ConfigService::global().current();
+    checks.push(check_settings(target, &config));
+    // The TUI checks the token behind its splash screen instead, so it opens without waiting
+    if target != Target::Tui {
+        checks.extend(check_token(target, &config).await);
+    }
+    // An invalid address already failed the settings check
+    let bind = bind_address(target, &config).unwrap_or(Ipv4Addr::LOCALHOST.into());
+    match target {
+        #[cfg(feature = "tui")]
+        Target::Tui => checks.push(check_terminal()),
+        #[cfg(feature = "web")]
+        Target::Html => checks.push(check_web_port(bind, web::ports(&config))),
+        Target::McpServer => checks.push(check_mcp_port(bind, config.mcp_server_port.unwrap_or(8080))),
+        // Plain mode, or a mode this build left out and main refuses to start
+        _ => {},
+    }
+
+    print(&checks, options);
+    !checks.iter().any(|c| c.status == Status::Fail)
+}
+
+/// Whether a probe file at `path` can be created and removed
+fn check_writable(name: &'static str, path: PathBuf, hint: &'static str) -> Check {
+    let written = path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, b"ok"))
+        .and_then(|_| fs::remove_file(&path));
+    match written {
+        Ok(()) => Check::pass(name, "可写入"),
+        Err(e) => Check::fail(
+            name,
+            format!("{} 无法写入: {}", path.parent().unwrap_or(&path).display(), e),
+            hint,
+        ),
+    }
+}
+
+/// Settings that are otherwise only validated deep inside the mode
+fn check_settings(target: Target, config: &Config) -> Check {
+    let result = match target {
+        #[cfg(feature = "tui")]
+        Target::Tui => KeyMap::from_config(&config.keybindings).map(drop),
+        Target::Html | Target::McpServer => Allowlist::from_config(&config.allowed_ips, "allowed_ips")
+            .and_then(|_| Allowlist::from_config(&config.request_owner_ips, "request_owner_ips"))
+            .and_then(|_| bind_address(target, config))
+            .and_then(|bind| allowlist::check_bind(config, bind, bind_key(target)))
+            .map(drop)
+            .and_then(|_| scheduler::check(config)),
+        _ => Ok(()),
+    }.and_then(|_| Ok(config.check_upstream_urls()?))
+        .and_then(|_| Ok(mcp::client::check_upstream_headers(config.user_agent.as_deref(), &config.upstream_headers)?))
+        .and_then(|_| coupons::check_field_labels(&config.field_labels))
+        .and_then(|_| utils::check_timezone(config.timezone.as_deref()));
+    match result {
+        Ok(()) => Check::pass("配置项", "格式正确"),
+        Err(e) => Check::fail("配置项", format!("{:#}", e), "按提示修改配置文件中的对应字段"),
+    }
+}
+
+/// Setting holding the listen address of a server mode
+fn bind_key(target: Target) -> &'static str {
+    if target == Target::Html { "web_bind" } else { "mcp_server_bind" }
+}
+
+/// Listen address of a server mode
+fn bind_address(target: Target, config: &Config) -> anyhow::Result<IpAddr> {
+    let setting = if target == Target::Html { &config.web_bind } else { &config.mcp_server_bind };
+    allowlist::bind_address(setting.as_deref(), bind_key(target))
+}
+
+/// Token presence and validity, plus upstream reachability, from a single probe
+async fn check_token(target: Target, config: &Config) -> Vec<Check> {
+    let client = McpClient::with_url(config.token.clone(), config.upstream_url());
+    let probe = match &client {
+        Ok(client) => match tokio::time::timeout(UPSTREAM_TIMEOUT, client.validate_token()).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("{} 秒内无响应", UPSTREAM_TIMEOUT.as_secs())),
+        },
+        Err(e) => Err(e.to_string()),
+    };
+
+    let upstream = match &probe {
+        Ok(_) => Check::pass("麦当劳服务", "可以连接"),
+        Err(e) => Check::warn("麦当劳服务", format!("无法连接: {}", e), "检查网络或代理设置，领取和查询会在连接恢复后正常工作"),
+    };
+
+    let token = if !config.has_valid_token() {
+        Check::warn("Token", "未设置", match target {
+            Target::McpServer => "启动后按提示输入 Token，可在 https://open.mcd.cn/mcp/login 获取",
+            _ => "启动后在界面中输入 Token，可在 https://open.mcd.cn/mcp/login 获取",
+        })
+    } else {
+        match probe {
+            Ok(true) => Check::pass("Token", "有效"),
+            Ok(false) if target == Target::McpServer => Check::fail(
+                "Token",
+                "已失效",
+                "在网页模式或终端模式中重新设置 Token 后再启动 MCP 服务器",
+            ),
+            Ok(false) => Check::warn("Token", "已失效", "启动后在界面中重新设置 Token"),
+            Err(_) => match last_validated(config) {
+                Some(at) => Check::pass("Token", format!("{} 验证通过（缓存）", at)),
+                None => Check::warn("Token", "暂时无法验证", "连接恢复后运行 validate 命令确认 Token 是否有效"),
+            },
+        }
+    };
+    vec![upstream, token]
+}
+
+/// When the current token last passed validation, if within the cache window
+fn last_validated(config: &Config) -> Option<String> {
+    let entry = config.token_history.iter().find(|e| e.token == config.token)?;
+    let at = NaiveDateTime::parse_from_str(&entry.last_valid, "%Y-%m-%d %H:%M:%S").ok()?;
+    let age = local_now() - at;
+    (age.num_hours() < TOKEN_CACHE_HOURS).then(|| entry.last_valid.clone())
+}
+
+#[cfg(feature = "tui")]
+fn check_terminal() -> Check {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Check::fail("终端", "当前不是交互式终端", "在终端中直接运行，脚本中请使用 html、mcpserver 或 claim 等命令");
+    }
+    if std::env::var("TERM").is_ok_and(|t| t == "dumb") {
+        return Check::warn("终端", "TERM=dumb，不支持全屏界面", "换用支持 ANSI 的终端，或使用 tui --plain 纯文本模式");
+    }
+    match crossterm::terminal::size() {
+        Ok((width, height)) if width < MIN_TERMINAL_SIZE.0 || height < MIN_TERMINAL_SIZE.1 => Check::warn(
+            "终端",
+            format!("窗口为 {}x{}，界面可能显示不全", width, height),
+            format!("将窗口调整到至少 {}x{}", MIN_TERMINAL_SIZE.0, MIN_TERMINAL_SIZE.1),
+        ),
+        Ok((width, height)) => Check::pass("终端", format!("{}x{}", width, height)),
+        Err(e) => Check::warn("终端", format!("无法读取窗口大小: {}", e), "换用其他终端后重试"),
+    }
+}
+
+fn port_free(addr: SocketAddr) -> bool {
+    TcpListener::bind(addr).is_ok()
+}
+
+#[cfg(feature = "web")]
+fn check_web_port(bind: IpAddr, ports: std::ops::RangeInclusive<u16>) -> Check {
+    let (first, last) = (*ports.start(), *ports.end());
+    match ports.into_iter().find(|port| port_free(SocketAddr::new(bind, *port))) {
+        Some(port) if port == first => Check::pass("端口", format!("{} 可用", port)),
+        Some(port) => Check::warn("端口", format!("{} 已被占用，将改用 {}", first, port), "如需固定地址，请关闭占用该端口的程序"),
+        None => Check::fail("端口", format!("{}-{} 均已被占用", first, last), "关闭占用这些端口的程序后重试，或在配置中修改 web_port"),
+    }
+}
+
+fn check_mcp_port(bind: IpAddr, port: u16) -> Check {
+    if port_free(SocketAddr::new(bind, port)) {
+        Check::pass("端口", format!("{} 可用", port))
+    } else {
+        Check::fail("端口", format!("{} 已被占用", port), "关闭占用该端口的程序，或在配置中修改 mcp_server_port")
+    }
+}
+
+/// Print the checklist; quiet mode only prints problems
+fn print(checks: &[Check], options: &GlobalOptions) {
+    let color = options.color.enabled(std::io::stdout().is_terminal());
+    let shown: Vec<&Check> = checks.iter()
+        .filter(|c| options.verbosity > Verbosity::Quiet || c.status == Status::Fail)
+        .collect();
+    if shown.is_empty() {
+        return;
+    }
+
+    println!("启动检查:");
+    for check in shown {
+        let (mark, fix) = match check.status {
+            Status::Pass => ("✔", None),
+            Status::Warn => ("!", check.fix.as_deref()),
+            Status::Fail => ("✘", check.fix.as_deref()),
+        };
+        // Plain ANSI codes, so builds without the TUI can color it too
+        let mark = match (color, check.status) {
+            (false, _) => mark.to_string(),
+            (true, Status::Pass) => format!("\x1b[32m{}\x1b[0m", mark),
+            (true, Status::Warn) => format!("\x1b[33m{}\x1b[0m", mark),
+            (true, Status::Fail) => format!("\x1b[1;31m{}\x1b[0m", mark),
+        };
+        println!("  {} {}: {}", mark, check.name, check.detail);
+        if let Some(fix) = fix {
+            println!("      → {}", fix);
+        }
+    }
+    if checks.iter().any(|c| c.status == Status::Fail) {
+        println!("启动检查未通过，请按提示处理后重试");
+    }
+    println!();
+}
@@ -0,0 +1,192 @@
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast;
+
+use crate::{coupons::{parse_coupons_from_markdown, ClaimCheck, ClaimVerification}, error::{McdError, Result}, mcp::{describe_error, McpClient}, utils::format_current_time};
+
+/// Progress updates kept for subscribers that fall behind
+const BUS_CAPACITY: usize = 32;
+
+/// Step of the claim operation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Validate,
+    FetchAvailable,
+    Claim,
+    RefreshMine,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Validate, Stage::FetchAvailable, Stage::Claim, Stage::RefreshMine];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::Validate => "验证Token",
+            Stage::FetchAvailable => "获取可领取优惠券",
+            Stage::Claim => "领取优惠券",
+            Stage::RefreshMine => "刷新我的优惠券",
+        }
+    }
+}
+
+/// Where a multi-step operation stands, published on every stage change
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct Progress {
+    /// Mode that started the operation: tui | web
+    pub source: String,
+    /// Stage being run, or the last one reached once finished
+    pub stage: Stage,
+    /// Stages completed so far
+    pub step: usize,
+    pub total: usize,
+    /// Completed share of the stages, 0-100
+    pub percent: u16,
+    pub message: String,
+    pub started_at: String,
+    pub finished: bool,
+    /// Set once finished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+}
+
+static LATEST: Mutex<Option<Progress>> = Mutex::new(None);
+
+fn bus() -> &'static broadcast::Sender<Progress> {
+    static BUS: OnceLock<broadcast::Sender<Progress>> = OnceLock::new();
+    BUS.get_or_init(|| broadcast::channel(BUS_CAPACITY).0)
+}
+
+/// Receive every progress update from now on
+#[cfg(feature = "tui")]
+pub fn subscribe() -> broadcast::Receiver<Progress> {
+    bus().subscribe()
+}
+
+/// Most recent update, finished or not
+#[cfg(feature = "web")]
+pub fn latest() -> Option<Progress> {
+    LATEST.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn publish(progress: Progress) {
+    *LATEST.lock().unwrap_or_else(|e| e.into_inner()) = Some(progress.clone());
+    // No subscribers is fine; the latest update is still kept
+    let _ = bus().send(progress);
+}
+
+/// Reports the stages of one operation
+struct Tracker {
+    progress: Progress,
+}
+
+impl Tracker {
+    fn start(source: &str) -> Self {
+        Self {
+            progress: Progress {
+                source: source.to_string(),
+                stage: Stage::ALL[0],
+                step: 0,
+                total: Stage::ALL.len(),
+                percent: 0,
+                message: String::new(),
+                started_at: format_current_time(),
+                finished: false,
+                success: None,
+            },
+        }
+    }
+
+    /// Mark `stage` as running, with everything before it done
+    fn enter(&mut self, stage: Stage) {
+        let step = Stage::ALL.iter().position(|s| *s == stage).unwrap_or_default();
+        self.progress.stage = stage;
+        self.progress.step = step;
+        self.progress.percent = (step * 100 / self.progress.total) as u16;
+        self.progress.message = format!("{}...", stage.label());
+        publish(self.progress.clone());
+    }
+
+    fn finish(mut self, success: bool, message: String) {
+        if success {
+            self.progress.step = self.progress.total;
+            self.progress.percent = 100;
+        }
+        self.progress.finished = true;
+        self.progress.success = Some(success);
+        self.progress.message = message;
+        publish(self.progress);
+    }
+}
+
+/// Result of a claim run
+#[derive(Debug, Clone)]
+pub struct ClaimRun {
+    /// Coupons offered before claiming
+    pub available: usize,
+    /// Markdown answer of the claim tool
+    pub response: String,
+    /// Held coupons afterwards; None when the refresh failed, which does not undo the claim
+    pub mine: Option<String>,
+    /// What actually appeared, from the held coupons before and after; see `ClaimCheck::finish`
+    pub verification: Option<ClaimVerification>,
+}
+
+impl ClaimRun {
+    /// Claim answer in the shape `ClaimHistory::record` takes, the refreshed held coupons
+    /// and what the claim actually added
+    pub fn split(result: Result<ClaimRun>) -> (Result<String>, Option<String>, Option<ClaimVerification>) {
+        match result {
+            Ok(run) => (Ok(run.response), run.mine, run.verification),
+            Err(e) => (Err(e), None, None),
+        }
+    }
+}
+
+/// Claim all available coupons, reporting each stage:
+/// validate token → fetch available → claim → refresh held coupons.
+/// A monthly budget set to skip refuses the claim before any stage starts.
+pub async fn claim(client: &McpClient, source: &str) -> Result<ClaimRun> {
+    let check = ClaimCheck::start()?;
+    let mut tracker = Tracker::start(source);
+    match run_claim(client, &mut tracker, check).await {
+        Ok(run) => {
+            let mut message = format!("领取完成，领取前可领取 {} 张", run.available);
+            if let Some(verification) = run.verification.as_ref().filter(|v| v.verified) {
+                message.push_str(&format!("，实际新增 {} 张", verification.appeared.len()));
+            }
+            tracker.finish(true, message);
+            Ok(run)
+        },
+        Err(e) => {
+            let message = format!("{}失败: {}", tracker.progress.stage.label(), describe_error(&e));
+            tracker.finish(false, message);
+            Err(e)
+        },
+    }
+}
+
+async fn run_claim(client: &McpClient, tracker: &mut Tracker, mut check: ClaimCheck) -> Result<ClaimRun> {
+    tracker.enter(Stage::Validate);
+    if !client.validate_token().await? {
+        return Err(McdError::TokenInvalid);
+    }
+
+    tracker.enter(Stage::FetchAvailable);
+    // The held coupons before claiming, to verify what the claim actually added
+    let (available, before) = tokio::join!(client.get_available_coupons(), client.get_my_coupons());
+    let available = parse_coupons_from_markdown(&available?).len();
+    check.held_before(&before);
+
+    tracker.enter(Stage::Claim);
+    let response = client.auto_bind_coupons().await?;
+
+    tracker.enter(Stage::RefreshMine);
+    let mine = client.get_my_coupons().await;
+    check.held_after(&mine);
+    let verification = check.finish(&response);
+    let mine = mine.ok();
+    Ok(ClaimRun { available, response, mine, verification })
+}
@@ -0,0 +1,200 @@
+#[cfg(feature = "web")]
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "web")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "web")]
+use std::fs;
+
+#[cfg(feature = "web")]
+use crate::{config::Config, coupons::{match_key, Coupon}, utils::format_current_time};
+
+/// File holding claim requests from family members, in the data directory
+pub const QUEUE_FILE: &str = "claim-requests.json";
+/// Oldest handled requests are dropped beyond this many; pending ones are always kept
+#[cfg(feature = "web")]
+const MAX_REQUESTS: usize = 200;
+/// Longest requester name and note, in characters
+#[cfg(feature = "web")]
+const MAX_TEXT: usize = 100;
+
+/// Where a request stands
+#[cfg(feature = "web")]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum RequestStatus {
+    /// Waiting for the account owner
+    Pending,
+    /// Claimed, and every wanted coupon is now held
+    Claimed,
+    /// Claimed, but some wanted coupons are still not held
+    Partial,
+    /// The claim failed; the owner can approve again
+    Failed,
+    Rejected,
+}
+
+#[cfg(feature = "web")]
+impl RequestStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            RequestStatus::Pending => "待处理",
+            RequestStatus::Claimed => "已领取",
+            RequestStatus::Partial => "部分领取",
+            RequestStatus::Failed => "领取失败",
+            RequestStatus::Rejected => "已拒绝",
+        }
+    }
+
+    /// Whether the owner can still act on it
+    pub fn is_open(self) -> bool {
+        matches!(self, RequestStatus::Pending | RequestStatus::Failed)
+    }
+}
+
+/// Coupons a family member asked the account owner to claim
+#[cfg(feature = "web")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema))]
+pub struct ClaimRequest {
+    pub id: u64,
+    /// Name the requester gave, e.g. "妈妈"
+    pub requester: String,
+    /// Titles of the wanted coupons
+    pub titles: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    pub status: RequestStatus,
+    /// Local time, "YYYY-MM-DD HH:MM:SS"
+    pub created_at: String,
+    pub updated_at: String,
+    /// Claim result or rejection reason, for the requester
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reply: String,
+    /// Wanted titles that were not held after the claim
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+/// Locally persisted claim requests, oldest first
+#[cfg(feature = "web")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestQueue {
+    #[serde(default)]
+    next_id: u64,
+    #[serde(default)]
+    pub requests: Vec<ClaimRequest>,
+}
+
+#[cfg(feature = "web")]
+impl RequestQueue {
+    /// Load the queue, starting empty if the file does not exist yet
+    pub fn load() -> Result<Self> {
+        let path = Config::data_path(QUEUE_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(&path)
+            .context(format!("无法读取文件: {}", path.display()))?;
+        serde_json::from_str(&text)
+            .context(format!("无法解析文件: {}", path.display()))
+    }
+
+    /// Save the queue in the data directory
+    pub fn save(&self) -> Result<()> {
+        let path = Config::data_path(QUEUE_FILE);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .context(format!("无法创建目录: {}", dir.display()))?;
+        }
+        let text = serde_json::to_string_pretty(self).context("无法序列化代领请求")?;
+        fs::write(&path, text)
+            .context(format!("无法写入文件: {}", path.display()))
+    }
+
+    /// Queue a new request, returning a copy of it
+    pub fn submit(&mut self, requester: &str, titles: Vec<String>, note: &str) -> Result<ClaimRequest> {
+        let requester: String = requester.trim().chars().take(MAX_TEXT).collect();
+        if requester.is_empty() {
+            return Err(anyhow!("请填写你的称呼，方便账号主人知道是谁的请求"));
+        }
+        let mut titles: Vec<String> = titles.into_iter()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        titles.dedup();
+        if titles.is_empty() {
+            return Err(anyhow!("请至少选择一张优惠券"));
+        }
+
+        self.next_id += 1;
+        let now = format_current_time();
+        let request = ClaimRequest {
+            id: self.next_id,
+            requester,
+            titles,
+            note: note.trim().chars().take(MAX_TEXT).collect(),
+            status: RequestStatus::Pending,
+            created_at: now.clone(),
+            updated_at: now,
+            reply: String::new(),
+            missing: Vec::new(),
+        };
+        self.requests.push(request.clone());
+        self.trim();
+        Ok(request)
+    }
+
+    /// The request with `id`, if the owner can still act on it
+    pub fn open_mut(&mut self, id: u64) -> Result<&mut ClaimRequest> {
+        let request = self.requests.iter_mut()
+            .find(|r| r.id == id)
+            .ok_or_else(|| anyhow!("未找到代领请求 #{}", id))?;
+        if !request.status.is_open() {
+            return Err(anyhow!("代领请求 #{} {}，无需再处理", id, request.status.label()));
+        }
+        Ok(request)
+    }
+
+    /// Number of requests waiting for the owner
+    pub fn pending(&self) -> usize {
+        self.requests.iter().filter(|r| r.status == RequestStatus::Pending).count()
+    }
+
+    /// Drop the oldest handled requests beyond the limit
+    fn trim(&mut self) {
+        while self.requests.len() > MAX_REQUESTS {
+            match self.requests.iter().position(|r| !r.status.is_open()) {
+                Some(index) => {
+                    self.requests.remove(index);
+                },
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "web")]
+impl ClaimRequest {
+    /// Settle the request after a successful claim, given the coupons now held
+    pub fn settle(&mut self, held: &[Coupon]) {
+        self.missing = self.titles.iter()
+            .filter(|t| !held.iter().any(|c| match_key(&c.title) == match_key(t)))
+            .cloned()
+            .collect();
+        self.status = if self.missing.is_empty() { RequestStatus::Claimed } else { RequestStatus::Partial };
+        self.reply = if self.missing.is_empty() {
+            "已全部领取".to_string()
+        } else {
+            format!("已领取，但以下优惠券未领到（可能已领完或暂不可领）: {}", self.missing.join("、"))
+        };
+        self.updated_at = format_current_time();
+    }
+
+    /// Mark the request failed or rejected with a reason for the requester
+    pub fn close(&mut self, status: RequestStatus, reply: String) {
+        self.status = status;
+        self.reply = reply;
+        self.updated_at = format_current_time();
+    }
+}
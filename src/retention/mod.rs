@@ -0,0 +1,214 @@
+use anyhow::{Context, Result};
+use chrono::{Duration as ChronoDuration, NaiveDateTime};
+use serde::Serialize;
+use std::{fs, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+
+use crate::{audit, config::{Config, RetentionConfig}, coupons::{ClaimHistory, HISTORY_FILE}, daemon, images, logging::access::ACCESS_LOG_FILE, utils::local_now};
+#[cfg(feature = "server")]
+use crate::{audit::Action, config::ConfigService};
+
+/// Time between automatic prunes in the long-running modes
+#[cfg(feature = "server")]
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+
+/// What pruning removed, or would remove, from one kind of data
+#[derive(Debug, Clone, Serialize)]
+pub struct PruneItem {
+    /// Data kind, e.g. "领取记录"
+    pub target: &'static str,
+    /// Entries or files removed
+    pub removed: usize,
+    /// Disk space freed, in bytes
+    pub bytes: u64,
+}
+
+/// Outcome of one prune run
+#[derive(Debug, Clone, Serialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub items: Vec<PruneItem>,
+}
+
+impl PruneReport {
+    pub fn removed(&self) -> usize {
+        self.items.iter().map(|i| i.removed).sum()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.items.iter().map(|i| i.bytes).sum()
+    }
+}
+
+/// Drop data older or larger than `retention` allows. With `dry_run`, only report what would go.
+pub fn prune(retention: &RetentionConfig, dry_run: bool) -> Result<PruneReport> {
+    let now = local_now();
+    let cutoff = |days: u64| (days > 0).then(|| now - ChronoDuration::days(days as i64));
+
+    let mut items = Vec::new();
+    if let Some(cutoff) = cutoff(retention.history_days) {
+        items.push(prune_history(cutoff, dry_run)?);
+    }
+    if let Some(cutoff) = cutoff(retention.audit_days) {
+        items.push(prune_lines("审计日志", &Config::data_path(audit::AUDIT_FILE), cutoff, dry_run)?);
+        items.push(prune_lines("MCP 审计日志", &Config::data_path(audit::MCP_AUDIT_FILE), cutoff, dry_run)?);
+    }
+    items.push(prune_image_cache(retention, dry_run)?);
+    if retention.log_days > 0 {
+        items.push(prune_rotated("访问日志", ACCESS_LOG_FILE, retention.log_days, dry_run)?);
+        items.push(prune_rotated("守护进程日志", daemon::LOG_FILE, retention.log_days, dry_run)?);
+        // Rotated after pruning, so the fresh `daemon.log.1` is kept for another `log_days`
+        if !dry_run {
+            daemon::rotate_log()?;
+        }
+    }
+    Ok(PruneReport { dry_run, items })
+}
+
+/// Prune now and then daily in the background. Used by the long-running html and mcpserver modes.
+#[cfg(feature = "server")]
+pub fn spawn(config: &Config) {
+    if config.retention.is_disabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Re-read the config so edits made while running are picked up
+            let retention = ConfigService::global().current().retention.clone();
+            match prune(&retention, false) {
+                Ok(report) if report.removed() > 0 => {
+                    tracing::info!("已按保留策略清理 {} 项，释放 {} 字节", report.removed(), report.bytes());
+                    audit::record("retention", Action::Prune, true, summary(&report));
+                },
+                Ok(_) => {},
+                Err(e) => tracing::warn!("按保留策略清理失败: {:#}", e),
+            }
+        }
+    });
+}
+
+/// One-line description of a report, e.g. "领取记录 3 条、图片缓存 12 个"
+pub fn summary(report: &PruneReport) -> String {
+    let parts: Vec<String> = report.items.iter()
+        .filter(|i| i.removed > 0)
+        .map(|i| format!("{} {}", i.target, i.removed))
+        .collect();
+    if parts.is_empty() {
+        "没有需要清理的数据".to_string()
+    } else {
+        parts.join("、")
+    }
+}
+
+fn prune_history(cutoff: NaiveDateTime, dry_run: bool) -> Result<PruneItem> {
+    let path = Config::data_path(HISTORY_FILE);
+    let before = file_size(&path);
+    let mut history = ClaimHistory::load()?;
+    let removed = history.prune_before(cutoff);
+    if removed > 0 && !dry_run {
+        history.save()?;
+    }
+    let bytes = if dry_run {
+        // Estimate from the share of entries dropped
+        let total = history.entries.len() + removed;
+        before * removed as u64 / total.max(1) as u64
+    } else {
+        before.saturating_sub(file_size(&path))
+    };
+    Ok(PruneItem { target: "领取记录", removed, bytes })
+}
+
+/// Drop JSON Lines entries whose `at` is before `cutoff`; lines without a readable time are kept.
+/// The log is rewritten through a temporary file under `audit::lock`, so neither a crash nor a
+/// concurrent append loses lines.
+fn prune_lines(target: &'static str, path: &Path, cutoff: NaiveDateTime, dry_run: bool) -> Result<PruneItem> {
+    let _lock = audit::lock()?;
+    if !path.exists() {
+        return Ok(PruneItem { target, removed: 0, bytes: 0 });
+    }
+    let text = fs::read_to_string(path).context(format!("无法读取文件: {}", path.display()))?;
+    let (mut kept, mut removed, mut bytes) = (String::new(), 0, 0);
+    for line in text.lines() {
+        let at = serde_json::from_str::<serde_json::Value>(line).ok()
+            .and_then(|v| v.get("at").and_then(|at| at.as_str()).map(str::to_string))
+            .and_then(|at| NaiveDateTime::parse_from_str(&at, "%Y-%m-%d %H:%M:%S").ok());
+        if at.is_some_and(|at| at < cutoff) {
+            removed += 1;
+            bytes += line.len() as u64 + 1;
+        } else {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    if removed > 0 && !dry_run {
+        let temp = path.with_extension("jsonl.tmp");
+        fs::write(&temp, kept).context(format!("无法写入文件: {}", temp.display()))?;
+        fs::rename(&temp, path).context(format!("无法写入文件: {}", path.display()))?;
+    }
+    Ok(PruneItem { target, removed, bytes })
+}
+
+/// Remove the rotated `<file>.1` of a log once it is older than `days`
+fn prune_rotated(target: &'static str, file: &str, days: u64, dry_run: bool) -> Result<PruneItem> {
+    let rotated = Config::data_path(&format!("{}.1", file));
+    let removed = older_than(&rotated, days).then(|| remove_file(&rotated, dry_run)).transpose()?;
+    Ok(PruneItem {
+        target,
+        removed: removed.map_or(0, |_| 1),
+        bytes: removed.unwrap_or(0),
+    })
+}
+
+/// Drop cached images unused for `image_cache_days`, then the oldest until under `image_cache_mb`
+fn prune_image_cache(retention: &RetentionConfig, dry_run: bool) -> Result<PruneItem> {
+    let mut item = PruneItem { target: "图片缓存", removed: 0, bytes: 0 };
+    let Ok(entries) = fs::read_dir(images::cache_dir()) else {
+        return Ok(item);
+    };
+    // (last modified, size, path), oldest first
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let meta = e.metadata().ok().filter(|m| m.is_file())?;
+            Some((meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), meta.len(), e.path()))
+        })
+        .collect();
+    files.sort();
+
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    let limit = retention.image_cache_mb * 1024 * 1024;
+    for (_, size, path) in files {
+        let expired = retention.image_cache_days > 0 && older_than(&path, retention.image_cache_days);
+        let over = retention.image_cache_mb > 0 && total > limit;
+        if !expired && !over {
+            continue;
+        }
+        remove_file(&path, dry_run)?;
+        total -= size;
+        item.removed += 1;
+        item.bytes += size;
+    }
+    Ok(item)
+}
+
+fn older_than(path: &Path, days: u64) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > Duration::from_secs(days * 24 * 3600))
+}
+
+/// Remove a file unless `dry_run`, returning its size
+fn remove_file(path: &Path, dry_run: bool) -> Result<u64> {
+    let size = file_size(path);
+    if !dry_run {
+        fs::remove_file(path).context(format!("无法删除文件: {}", path.display()))?;
+    }
+    Ok(size)
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+
+use crate::config::{Config, ScheduleConfig};
+
+// Only the web and MCP servers run the scheduler; other modes just check the config
+#[cfg(feature = "server")]
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+#[cfg(feature = "server")]
+use serde::Serialize;
+#[cfg(feature = "server")]
+use std::{collections::hash_map::RandomState, hash::BuildHasher, sync::Mutex, time::Duration};
+#[cfg(feature = "server")]
+use crate::{audit::{self, Action}, config::ConfigService, coupons::{parse_coupons_from_markdown, ClaimCheck, ClaimHistory, ClaimVerification}, inflight::{self, Operation, Outcome}, mcp::{describe_error, McpClient}, mqtt, utils::{format_local_time, from_local, to_local}};
+
+/// The server clock is read again this long before a release, so drift is measured close to it
+#[cfg(feature = "server")]
+const RESYNC_BEFORE: Duration = Duration::from_secs(5 * 60);
+/// Average pause between retries after a release, jittered by up to half either way
+#[cfg(feature = "server")]
+const RETRY_INTERVAL_MS: u64 = 15_000;
+
+/// Scheduled claim status
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "web", derive(utoipa::ToSchema, async_graphql::SimpleObject))]
+pub struct SchedulerStatus {
+    pub enabled: bool,
+    /// Next claim, in the configured time zone
+    pub next_run: Option<String>,
+    pub message: String,
+}
+
+#[cfg(feature = "server")]
+static STATUS: Mutex<Option<SchedulerStatus>> = Mutex::new(None);
+
+/// Current status, disabled until `spawn` starts the scheduler
+#[cfg(feature = "server")]
+pub fn status() -> SchedulerStatus {
+    STATUS.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| SchedulerStatus {
+        enabled: false,
+        next_run: None,
+        message: "未启用定时领取".to_string(),
+    })
+}
+
+#[cfg(feature = "server")]
+fn set_status(next_run: Option<DateTime<Utc>>, message: String) {
+    *STATUS.lock().unwrap_or_else(|e| e.into_inner()) = Some(SchedulerStatus {
+        enabled: true,
+        next_run: next_run.map(format_local_time),
+        message,
+    });
+}
+
+/// Claim shortly after each configured release time, retrying with jitter for the first few minutes.
+/// Used by the long-running html and mcpserver modes; fails when a release time cannot be parsed.
+#[cfg(feature = "server")]
+pub fn spawn(config: &Config) -> Result<()> {
+    let Some(schedule) = config.schedule.clone().filter(|s| !s.release_times.is_empty()) else {
+        return Ok(());
+    };
+    let times = parse_times(&schedule)?;
+    let labels: Vec<String> = times.iter().map(|t| t.format("%H:%M:%S").to_string()).collect();
+    let plan = format!("每天 {} 开抢后 {} 秒领取", labels.join("、"), schedule.delay_seconds);
+    tracing::info!("已启用定时领取，{}", plan);
+    set_status(None, plan.clone());
+
+    tokio::spawn(run(schedule, times, plan));
+    Ok(())
+}
+
+/// Check the configured release times without starting anything
+pub fn check(config: &Config) -> Result<()> {
+    config.schedule.as_ref().map_or(Ok(()), |schedule| parse_times(schedule).map(drop))
+}
+
+#[cfg(feature = "server")]
+async fn run(schedule: ScheduleConfig, times: Vec<NaiveTime>, plan: String) {
+    let delay = ChronoDuration::seconds(schedule.delay_seconds as i64);
+    let window = ChronoDuration::minutes(schedule.retry_minutes as i64);
+    let mut last = None;
+    loop {
+        // Release times follow the server clock, so sleep by it rather than the local one
+        let offset = clock_offset().await;
+        let now = Utc::now() + offset;
+        let Some(release) = next_release(&times, delay, now) else {
+            return;
+        };
+        let fire_at = release + delay;
+        set_status(Some(fire_at), last.clone().map_or(plan.clone(), |last| format!("{}；{}", plan, last)));
+
+        let wait = (fire_at - now).to_std().unwrap_or_default();
+        if wait > RESYNC_BEFORE {
+            tokio::time::sleep(wait - RESYNC_BEFORE).await;
+            continue;
+        }
+        tokio::time::sleep(wait).await;
+
+        audit::record("scheduler", Action::ScheduleTrigger, true, format!("{} 开抢后定时领取", format_local_time(release)));
+        let outcome = claim_until(release + window, offset).await;
+        tracing::info!("定时领取: {}", outcome);
+        last = Some(format!("上次 {} {}", format_local_time(Utc::now() + offset), outcome));
+    }
+}
+
+/// Claim until coupons come back or the retry window closes, returning a summary for the status.
+/// Each attempt holds the account's claim ticket; a web or MCP claim already running ends the run.
+#[cfg(feature = "server")]
+async fn claim_until(deadline: DateTime<Utc>, offset: ChronoDuration) -> String {
+    let mut check = match ClaimCheck::start() {
+        Ok(check) => check,
+        Err(e) => return describe_error(&e),
+    };
+    // Held coupons are fetched once up front, not between the time-critical retries
+    if let Some(client) = client() {
+        check.held_before(&client.get_my_coupons().await);
+    }
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let Some(client) = client() else {
+            return "未设置Token，已跳过".to_string();
+        };
+        let account = inflight::account(&ConfigService::global().current().token);
+        let ticket = match inflight::begin(Operation::Claim, &account, None) {
+            Ok(ticket) => ticket,
+            Err(existing) => return match existing.resolve().await {
+                Ok(outcome) => outcome.message,
+                Err(conflict) => format!("{}，本次定时领取已跳过", conflict),
+            },
+        };
+        let result = client.auto_bind_coupons().await;
+        let claimed = result.as_ref().map_or(0, |text| parse_coupons_from_markdown(text).len());
+
+        // Batches are not always live the second they are announced, so keep trying for a while
+        let pause = retry_pause(attempt);
+        let retry = claimed == 0 && Utc::now() + offset + ChronoDuration::from_std(pause).unwrap_or_default() < deadline;
+        if !retry {
+            let verification = match &result {
+                Ok(response) => check.verify(&client, response).await,
+                Err(_) => None,
+            };
+            ClaimHistory::record("schedule", &result, verification.as_ref());
+            audit::record_claim("scheduler", &result, verification.as_ref());
+            mqtt::publish_claim("schedule", &result, verification.as_ref());
+            let success = ClaimVerification::succeeded(&result, verification.as_ref());
+            ticket.finish(match &result {
+                Ok(_) => Outcome { success, message: ClaimVerification::message(verification.as_ref()) },
+                Err(e) => Outcome { success: false, message: format!("领取失败: {}", describe_error(e)) },
+            });
+            return match (result, verification) {
+                (Ok(_), Some(verification)) => format!("第 {} 次尝试: {}", attempt, ClaimVerification::message(Some(&verification))),
+                (Ok(_), None) => format!("第 {} 次尝试领取到 {} 张", attempt, claimed),
+                (Err(e), _) => format!("第 {} 次尝试领取失败: {}", attempt, describe_error(&e)),
+            };
+        }
+        match &result {
+            Ok(_) => tracing::info!("定时领取第 {} 次尝试未领到新优惠券，{} 毫秒后重试", attempt, pause.as_millis()),
+            Err(e) => tracing::warn!("定时领取第 {} 次尝试失败: {}，{} 毫秒后重试", attempt, describe_error(e), pause.as_millis()),
+        }
+        // Let other claims run between the attempts
+        drop(ticket);
+        tokio::time::sleep(pause).await;
+    }
+}
+
+/// Client for the current token, re-read from the config so a changed token is picked up
+#[cfg(feature = "server")]
+fn client() -> Option<McpClient> {
+    Some(ConfigService::global().current())
+        .filter(|config| config.has_valid_token())
+        .and_then(|config| McpClient::with_url(config.token.clone(), config.upstream_url()).ok())
+}
+
+/// Server clock minus the local clock, zero when the server time cannot be read
+#[cfg(feature = "server")]
+async fn clock_offset() -> ChronoDuration {
+    let Some(client) = client() else {
+        return ChronoDuration::zero();
+    };
+    let sent = Utc::now();
+    match client.get_server_time().await {
+        Ok(server) => server - (sent + (Utc::now() - sent) / 2),
+        Err(e) => {
+            tracing::warn!("读取服务器时间失败，按本机时间定时: {}", describe_error(&e));
+            ChronoDuration::zero()
+        }
+    }
+}
+
+fn parse_times(schedule: &ScheduleConfig) -> Result<Vec<NaiveTime>> {
+    let mut times = schedule.release_times.iter()
+        .map(|text| {
+            let text = text.trim();
+            NaiveTime::parse_from_str(text, "%H:%M:%S")
+                .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+                .map_err(|_| anyhow!("schedule.release_times 配置无效: {}", text))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    times.sort();
+    times.dedup();
+    Ok(times)
+}
+
+/// Earliest release whose claim time (release + delay) is still ahead of `now`.
+/// Release times are read in the configured time zone.
+#[cfg(feature = "server")]
+fn next_release(times: &[NaiveTime], delay: ChronoDuration, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let today = to_local(now).date();
+    [today, today.succ_opt()?].into_iter()
+        .flat_map(|day| times.iter().filter_map(move |time| from_local(day.and_time(*time))))
+        .find(|release| *release + delay > now)
+}
+
+/// Retry pause between 0.5x and 1.5x the average, so many clients do not retry in lockstep
+#[cfg(feature = "server")]
+fn retry_pause(attempt: u32) -> Duration {
+    let jitter = RandomState::new().hash_one(attempt) % RETRY_INTERVAL_MS;
+    Duration::from_millis(RETRY_INTERVAL_MS / 2 + jitter)
+}
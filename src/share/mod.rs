@@ -0,0 +1,206 @@
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use anyhow::{anyhow, Context, Result};
+use qrcode::{render::svg, Color, QrCode};
+use std::{fs, path::Path};
+
+use crate::{config::ShareConfig, coupons::Coupon};
+
+/// Card size in pixels, about the aspect of a chat image preview
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 960;
+/// Left and right margin of the text
+const MARGIN: f32 = 48.0;
+/// Longest title, in lines, before it is cut with "…"
+const MAX_TITLE_LINES: usize = 3;
+/// Side of one QR module in pixels
+const QR_MODULE: u32 = 6;
+
+type Rgb = [u8; 3];
+const RED: Rgb = [218, 41, 28];
+const YELLOW: Rgb = [255, 199, 44];
+const WHITE: Rgb = [255, 255, 255];
+const DARK: Rgb = [39, 37, 31];
+const GRAY: Rgb = [110, 110, 110];
+const PAPER: Rgb = [255, 248, 232];
+
+/// Fonts with Chinese glyphs tried when `share.font` is unset
+const SYSTEM_FONTS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/STHeiti Medium.ttc",
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\simhei.ttf",
+];
+
+/// Render `coupon` as a PNG card with its title, price, expiry and a QR code of `share.link`
+pub fn render(coupon: &Coupon, share: &ShareConfig) -> Result<Vec<u8>> {
+    let font = load_font(share)?;
+    let mut canvas = Canvas::new(WIDTH, HEIGHT, PAPER);
+    let text_width = WIDTH as f32 - 2.0 * MARGIN;
+
+    // Header band
+    canvas.fill(0, 0, WIDTH, 150, RED);
+    canvas.text(&font, MARGIN, 95.0, 48.0, "麦当劳优惠券", YELLOW);
+
+    let mut y = 230.0;
+    let title = wrap(&font, 44.0, &coupon.title, text_width, MAX_TITLE_LINES);
+    for line in &title {
+        canvas.text(&font, MARGIN, y, 44.0, line, DARK);
+        y += 60.0;
+    }
+
+    if !coupon.price.is_empty() {
+        y += 20.0;
+        canvas.text(&font, MARGIN, y, 56.0, &coupon.price, RED);
+        y += 40.0;
+    }
+    if !coupon.expiry.is_empty() {
+        y += 20.0;
+        for line in wrap(&font, 26.0, &format!("有效期: {}", coupon.expiry), text_width, 2) {
+            canvas.text(&font, MARGIN, y, 26.0, &line, GRAY);
+            y += 36.0;
+        }
+    }
+
+    // QR code at the bottom, on a white quiet zone
+    let code = QrCode::new(share.link.as_bytes()).map_err(|e| anyhow!("无法生成二维码: {}", e))?;
+    let modules = code.width() as u32;
+    let side = modules * QR_MODULE;
+    let (qr_x, qr_y) = ((WIDTH - side) / 2, HEIGHT - side - 110);
+    canvas.fill(qr_x - 4 * QR_MODULE, qr_y - 4 * QR_MODULE, side + 8 * QR_MODULE, side + 8 * QR_MODULE, WHITE);
+    for (i, color) in code.to_colors().iter().enumerate() {
+        if *color == Color::Dark {
+            let (col, row) = (i as u32 % modules, i as u32 / modules);
+            canvas.fill(qr_x + col * QR_MODULE, qr_y + row * QR_MODULE, QR_MODULE, QR_MODULE, DARK);
+        }
+    }
+    let caption = "扫码打开麦当劳";
+    let caption_x = (WIDTH as f32 - measure(&font, 24.0, caption)) / 2.0;
+    canvas.text(&font, caption_x, HEIGHT as f32 - 50.0, 24.0, caption, GRAY);
+
+    canvas.encode()
+}
+
+/// SVG QR code of a redemption code, sized for a cashier to scan off the screen
+pub fn code_svg(code: &str) -> Result<String> {
+    let code = QrCode::new(code.as_bytes()).map_err(|e| anyhow!("无法生成二维码: {}", e))?;
+    Ok(code.render::<svg::Color>().min_dimensions(320, 320).build())
+}
+
+/// The configured font, or the first system font with Chinese glyphs
+fn load_font(share: &ShareConfig) -> Result<FontVec> {
+    let path = match &share.font {
+        Some(path) => path.as_str(),
+        None => SYSTEM_FONTS.iter()
+            .copied()
+            .find(|p| Path::new(p).exists())
+            .ok_or_else(|| anyhow!("未找到中文字体，请在配置文件的 share.font 中指定字体文件路径"))?,
+    };
+    let data = fs::read(path).context(format!("无法读取字体文件: {}", path))?;
+    // Collections (.ttc) hold several faces, the first is the regular one
+    FontVec::try_from_vec_and_index(data, 0).map_err(|_| anyhow!("无法解析字体文件: {}", path))
+}
+
+/// Width of `text` at `size` pixels
+fn measure(font: &FontVec, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    text.chars().map(|c| scaled.h_advance(scaled.glyph_id(c))).sum()
+}
+
+/// Break `text` into lines no wider than `width`, at any character since Chinese has no spaces.
+/// Text beyond `max_lines` is cut and marked with "…".
+fn wrap(font: &FontVec, size: f32, text: &str, width: f32, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for c in text.chars() {
+        let candidate = format!("{}{}", line, c);
+        if !line.is_empty() && measure(font, size, &candidate) > width {
+            lines.push(std::mem::take(&mut line));
+            line.push(c);
+        } else {
+            line = candidate;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = lines.last_mut().expect("max_lines is at least 1");
+        while !last.is_empty() && measure(font, size, &format!("{}…", last)) > width {
+            last.pop();
+        }
+        last.push('…');
+    }
+    lines
+}
+
+/// RGB pixel buffer
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: background.repeat((width * height) as usize),
+        }
+    }
+
+    /// Blend `color` into a pixel with `alpha` coverage (0.0-1.0); outside pixels are ignored
+    fn blend(&mut self, x: i64, y: i64, color: Rgb, alpha: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let offset = (y as usize * self.width as usize + x as usize) * 3;
+        for (channel, value) in self.pixels[offset..offset + 3].iter_mut().zip(color) {
+            *channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha).round() as u8;
+        }
+    }
+
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgb) {
+        for row in y..y + height {
+            for col in x..x + width {
+                self.blend(col as i64, row as i64, color, 1.0);
+            }
+        }
+    }
+
+    /// Draw one line of text with its baseline at `y`
+    fn text(&mut self, font: &FontVec, x: f32, y: f32, size: f32, text: &str, color: Rgb) {
+        let scale = PxScale::from(size);
+        let scaled = font.as_scaled(scale);
+        let mut caret = x;
+        for c in text.chars() {
+            let id = scaled.glyph_id(c);
+            let glyph = id.with_scale_and_position(scale, point(caret, y));
+            caret += scaled.h_advance(id);
+            let Some(outline) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                self.blend(bounds.min.x as i64 + gx as i64, bounds.min.y as i64 + gy as i64, color, coverage.min(1.0));
+            });
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().context("无法生成图片")?;
+        writer.write_image_data(&self.pixels).context("无法生成图片")?;
+        writer.finish().context("无法生成图片")?;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng}, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{
+    config::{Account, BudgetConfig, Config, ConfigService, DeepLinkConfig, KeyBindings, ThemePreset, TokenHistoryEntry, WatchConfig},
+    coupons::{ClaimRules, HISTORY_FILE, USAGE_FILE},
+    utils::format_current_time,
+};
+
+/// Marker in the `format` field of a sync bundle
+const BUNDLE_FORMAT: &str = "mcd-coupon-sync";
+/// Bundle layout version, bumped when the payload changes incompatibly.
+/// Version 1 carried the whole config; its portable part is still read on import.
+const BUNDLE_VERSION: u32 = 2;
+/// Files in the data directory that are carried in a bundle
+const DATA_FILES: &[&str] = &[USAGE_FILE, HISTORY_FILE];
+const SALT_LEN: usize = 16;
+
+/// Encrypted bundle as written to disk. Only the metadata is readable without the passphrase.
+#[derive(Debug, Serialize, Deserialize)]
+struct Bundle {
+    format: String,
+    version: u32,
+    created_at: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Decrypted content of a bundle
+#[derive(Debug, Serialize, Deserialize)]
+struct Payload {
+    config: Portable,
+    includes_token: bool,
+    /// Data file name to its raw content
+    files: BTreeMap<String, String>,
+}
+
+/// Settings that follow the user to another machine: tokens, accounts and preferences.
+/// Ports, bind addresses, `allowed_ips`, the time zone, paths and the other machine-specific
+/// settings are never carried and stay as they are on the importing machine.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Portable {
+    token: String,
+    accounts: Vec<Account>,
+    active_account: Option<String>,
+    token_history: Vec<TokenHistoryEntry>,
+    show_calories: bool,
+    keybindings: KeyBindings,
+    theme: ThemePreset,
+    rules: ClaimRules,
+    watch: WatchConfig,
+    budget: Option<BudgetConfig>,
+    deep_links: DeepLinkConfig,
+}
+
+impl Portable {
+    fn from_config(config: &Config, include_token: bool) -> Self {
+        let mut accounts = config.accounts.clone();
+        if !include_token {
+            for account in &mut accounts {
+                account.token.clear();
+            }
+        }
+        Self {
+            token: if include_token { config.token.clone() } else { String::new() },
+            accounts,
+            active_account: config.active_account.clone(),
+            token_history: if include_token { config.token_history.clone() } else { Vec::new() },
+            show_calories: config.show_calories,
+            keybindings: config.keybindings.clone(),
+            theme: config.theme,
+            rules: config.rules.clone(),
+            watch: config.watch.clone(),
+            budget: config.budget.clone(),
+            deep_links: config.deep_links.clone(),
+        }
+    }
+
+    /// Merge into the local config. Accounts are matched by name; local ones missing from the
+    /// bundle are kept, and without tokens in the bundle the local tokens stay in place.
+    fn merge_into(self, config: &mut Config, includes_token: bool) {
+        for account in self.accounts {
+            match config.accounts.iter_mut().find(|a| a.name == account.name) {
+                Some(saved) if !includes_token => saved.mcp_server_url = account.mcp_server_url,
+                Some(saved) => *saved = account,
+                None => config.accounts.push(account),
+            }
+        }
+        if includes_token {
+            config.token = self.token;
+            config.active_account = self.active_account;
+            let local = std::mem::replace(&mut config.token_history, self.token_history);
+            for entry in local {
+                if !config.token_history.iter().any(|h| h.token == entry.token) {
+                    config.token_history.push(entry);
+                }
+            }
+        }
+
+        config.show_calories = self.show_calories;
+        config.keybindings = self.keybindings;
+        config.theme = self.theme;
+        config.rules = self.rules;
+        config.watch = self.watch;
+        config.budget = self.budget;
+        config.deep_links = self.deep_links;
+    }
+}
+
+/// What an export or import covered
+#[derive(Debug, Serialize)]
+pub struct SyncSummary {
+    pub created_at: String,
+    pub includes_token: bool,
+    pub files: Vec<String>,
+}
+
+/// Write config and local data to an encrypted bundle at `path`
+pub fn export(path: &Path, passphrase: &str, include_token: bool) -> Result<SyncSummary> {
+    let (text, summary) = seal(passphrase, include_token)?;
+    fs::write(path, text).context(format!("无法写入文件: {}", path.display()))?;
+    Ok(summary)
+}
+
+/// Restore config and local data from the bundle at `path`
+pub fn import(path: &Path, passphrase: &str) -> Result<SyncSummary> {
+    let text = fs::read_to_string(path).context(format!("无法读取文件: {}", path.display()))?;
+    open(&text, passphrase)
+}
+
+/// Encrypt config and local data into bundle text
+pub fn seal(passphrase: &str, include_token: bool) -> Result<(String, SyncSummary)> {
+    let config = Portable::from_config(&ConfigService::global().current(), include_token);
+
+    let mut files = BTreeMap::new();
+    for name in DATA_FILES {
+        let file_path = Config::data_path(name);
+        if file_path.exists() {
+            let text = fs::read_to_string(&file_path)
+                .context(format!("无法读取文件: {}", file_path.display()))?;
+            files.insert(name.to_string(), text);
+        }
+    }
+
+    let payload = Payload {
+        config,
+        includes_token: include_token,
+        files,
+    };
+    let plaintext = serde_json::to_vec(&payload).context("无法序列化同步数据")?;
+
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| anyhow!("加密同步数据失败"))?;
+
+    let bundle = Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        created_at: format_current_time(),
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    let text = serde_json::to_string_pretty(&bundle).context("无法序列化同步包")?;
+
+    let summary = SyncSummary {
+        created_at: bundle.created_at,
+        includes_token: include_token,
+        files: payload.files.into_keys().collect(),
+    };
+    Ok((text, summary))
+}
+
+/// Decrypt bundle text, merge its settings into the local config and restore local data.
+/// A bundle exported without tokens keeps the tokens already saved on this machine.
+pub fn open(text: &str, passphrase: &str) -> Result<SyncSummary> {
+    let bundle: Bundle = serde_json::from_str(text)
+        .ok()
+        .filter(|b: &Bundle| b.format == BUNDLE_FORMAT)
+        .ok_or_else(|| anyhow!("不是有效的同步包"))?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(anyhow!("同步包版本 {} 过新，请先升级本工具", bundle.version));
+    }
+
+    let salt = BASE64.decode(&bundle.salt).context("同步包已损坏")?;
+    let nonce = BASE64.decode(&bundle.nonce).context("同步包已损坏")?;
+    let ciphertext = BASE64.decode(&bundle.ciphertext).context("同步包已损坏")?;
+    if nonce.len() != 24 {
+        return Err(anyhow!("同步包已损坏"));
+    }
+
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow!("解密失败，密码错误或同步包已损坏"))?;
+    let payload: Payload = serde_json::from_slice(&plaintext).context("无法解析同步数据")?;
+
+    let includes_token = payload.includes_token;
+    ConfigService::global().update(|config| payload.config.merge_into(config, includes_token))?;
+
+    for (name, content) in &payload.files {
+        if !DATA_FILES.contains(&name.as_str()) {
+            continue;
+        }
+        let file_path = Config::data_path(name);
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir).context(format!("无法创建目录: {}", dir.display()))?;
+        }
+        fs::write(&file_path, content).context(format!("无法写入文件: {}", file_path.display()))?;
+    }
+
+    Ok(SyncSummary {
+        created_at: bundle.created_at,
+        includes_token: payload.includes_token,
+        files: payload.files.into_keys().collect(),
+    })
+}
+
+/// Stretch the passphrase into a cipher key
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    if passphrase.is_empty() {
+        return Err(anyhow!("密码不能为空"));
+    }
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("密钥派生失败: {}", e))?;
+    Ok(key)
+}
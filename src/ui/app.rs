@@ -1,18 +1,37 @@
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
 use ratatui::{backend::Backend, Frame, Terminal};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use anyhow::Result;
 
-use crate::{mcp::McpClient, ui::screens::{Screen, ScreenType, TokenInputScreen}};
+use crate::{error::McdError, mcp::McpClient, progress::{self, Progress}, ui::{keymap::{Action, KeyMap}, screens::{ErrorScreen, Screen, ScreenType, TokenInputScreen}, theme::Theme, toast::{ToastKind, Toasts}}};
+
+/// Interval between Tick events; also caps the redraw rate when idle
+const TICK_RATE: Duration = Duration::from_millis(100);
+
+/// Spinner frames, advanced once per tick
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
 /// Application state and logic
 pub struct App {
     pub current_screen: ScreenType,
-    pub mcp_client: Option<Arc<Mutex<McpClient>>>,
+    pub mcp_client: Option<Arc<McpClient>>,
     pub logs: Vec<String>,
     pub progress: u16,
     pub is_loading: bool,
+    /// Number of ticks since startup, drives animations
+    pub tick: u64,
+    pub keymap: KeyMap,
+    pub theme: Theme,
+    /// Whether `--color` and `NO_COLOR` allow color; without it every theme is monochrome
+    pub color: bool,
+    /// Set by screens to leave the main loop and restore the terminal
+    pub should_quit: bool,
+    /// Notifications drawn over every screen until they expire
+    pub toasts: Toasts,
+    /// Stage updates of multi-step operations, driving the progress gauge
+    progress_events: broadcast::Receiver<Progress>,
 }
 
 impl App {
@@ -24,35 +43,83 @@ impl App {
             logs: vec!["应用已启动...".to_string()],
             progress: 0,
             is_loading: false,
+            tick: 0,
+            keymap: KeyMap::default(),
+            theme: Theme::default(),
+            color: true,
+            should_quit: false,
+            toasts: Toasts::default(),
+            progress_events: progress::subscribe(),
         }
     }
 
     /// Run the application main loop
     pub async fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let mut last_tick = Instant::now();
         loop {
             // Render current screen
             terminal.draw(|f| self.render(f))?;
 
-            // Handle events
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Char('Q') => {
-                        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            // Handle events, waiting at most until the next tick
+            let timeout = TICK_RATE.saturating_sub(last_tick.elapsed());
+            if event::poll(timeout)? {
+                match event::read()? {
+                    // Windows also reports key releases, which would type every character twice
+                    Event::Key(key) if key.kind == KeyEventKind::Release => {}
+                    Event::Key(key) => {
+                        // A plain-letter quit key must still be typeable into text fields
+                        let typing = self.current_screen.accepts_text() && self.keymap.key(Action::Quit).is_text_key();
+                        if self.keymap.is(Action::Quit, &key) && !typing {
                             break;
                         }
+                        self.current_screen = match self.current_screen.clone() {
+                            ScreenType::Error(error) if ErrorScreen::is_retry(&key) => {
+                                self.dispatch(*error.previous, error.key).await
+                            }
+                            screen => self.dispatch(screen, key).await,
+                        };
                     }
-                    _ => {
-                        self.current_screen = self.current_screen.clone().handle_key(key, self).await?;
-                    }
+                    Event::Paste(text) => self.current_screen.paste(&text),
+                    _ => {}
                 }
             }
+            if self.should_quit {
+                break;
+            }
+
+            // Let screens pick up background results and advance animations
+            if last_tick.elapsed() >= TICK_RATE {
+                self.tick = self.tick.wrapping_add(1);
+                self.toasts.expire();
+                self.apply_progress();
+                self.current_screen = self.current_screen.clone().on_tick(self);
+                last_tick = Instant::now();
+            }
         }
         Ok(())
     }
 
+    /// Send a key to a screen, turning a returned error into the error screen
+    async fn dispatch(&mut self, screen: ScreenType, key: KeyEvent) -> ScreenType {
+        match screen.clone().handle_key(key, self).await {
+            Ok(next) => next,
+            Err(e) => {
+                self.set_loading(false, 0);
+                self.notify(ToastKind::Error, format!("操作失败: {}", e));
+                // Retrying cannot fix a rejected token, so ask for a new one instead
+                if matches!(e.downcast_ref::<McdError>(), Some(McdError::TokenInvalid)) {
+                    self.mcp_client = None;
+                    return ScreenType::TokenInput(TokenInputScreen::new());
+                }
+                ScreenType::Error(Box::new(ErrorScreen::new(&e, screen, key)))
+            }
+        }
+    }
+
     /// Render the current screen
     fn render(&self, f: &mut Frame<'_>) {
         self.current_screen.render(f, self);
+        self.toasts.render(f, &self.theme);
     }
 
     /// Add a log message
@@ -64,16 +131,42 @@ impl App {
         }
     }
 
+    /// Show a toast in the corner, also kept in the log
+    pub fn notify(&mut self, kind: ToastKind, message: String) {
+        self.toasts.push(kind, message.clone());
+        self.add_log(message);
+    }
+
+    /// Current spinner frame for loading indicators
+    pub fn spinner(&self) -> &'static str {
+        SPINNER_FRAMES[(self.tick % SPINNER_FRAMES.len() as u64) as usize]
+    }
+
     /// Set loading state
     pub fn set_loading(&mut self, loading: bool, progress: u16) {
         self.is_loading = loading;
         self.progress = progress;
     }
 
-    /// Initialize MCP client with token
-    pub fn init_mcp_client(&mut self, token: String) -> Result<()> {
-        let client = McpClient::new(token)?;
-        self.mcp_client = Some(Arc::new(Mutex::new(client)));
+    /// Move the gauge to the latest reported stage
+    fn apply_progress(&mut self) {
+        loop {
+            let progress = match self.progress_events.try_recv() {
+                Ok(progress) => progress,
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            };
+            if !progress.finished {
+                self.add_log(progress.message.clone());
+            }
+            self.set_loading(!progress.finished, progress.percent);
+        }
+    }
+
+    /// Initialize MCP client with token, against `url` or the default upstream
+    pub fn init_mcp_client(&mut self, token: String, url: Option<&str>) -> Result<()> {
+        let client = McpClient::with_url(token, url)?;
+        self.mcp_client = Some(Arc::new(client));
         Ok(())
     }
 }
@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+
+use crate::config::KeyBindings;
+
+/// Actions that can be remapped in the `keybindings` config section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Claim,
+    List,
+    Quit,
+    Help,
+    Refresh,
+}
+
+impl Action {
+    const ALL: [Action; 5] = [Action::Claim, Action::List, Action::Quit, Action::Help, Action::Refresh];
+
+    /// Config key of the action
+    fn name(self) -> &'static str {
+        match self {
+            Action::Claim => "claim",
+            Action::List => "list",
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::Refresh => "refresh",
+        }
+    }
+
+    /// Description shown in the help panel
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Claim => "一键领取所有优惠券",
+            Action::List => "切换到优惠券标签页",
+            Action::Quit => "退出",
+            Action::Help => "显示/隐藏按键帮助",
+            Action::Refresh => "刷新当前标签页",
+        }
+    }
+}
+
+/// A single key with modifiers, parsed from strings like "ctrl+q"
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeySpec {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeySpec {
+    /// Parse a key description such as "c", "?", "ctrl+q", "alt+enter" or "f1"
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(anyhow!("按键不能为空"));
+        }
+
+        // "+" is both the separator and a bindable key, as in "+" or "ctrl++"
+        let (modifier_text, key) = if text == "+" {
+            ("", "+")
+        } else if let Some(prefix) = text.strip_suffix("++") {
+            (prefix, "+")
+        } else {
+            text.rsplit_once('+').unwrap_or(("", text))
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for modifier in modifier_text.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                other => return Err(anyhow!("未知的修饰键: {}", other)),
+            };
+        }
+
+        let code = match key.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            f if f.len() > 1 && f.starts_with('f') => match f[1..].parse::<u8>() {
+                Ok(n @ 1..=12) => KeyCode::F(n),
+                _ => return Err(anyhow!("无法识别的按键: {}", key)),
+            },
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c.to_ascii_lowercase()),
+                    _ => return Err(anyhow!("无法识别的按键: {}", key)),
+                }
+            }
+        };
+
+        Ok(Self { code, modifiers })
+    }
+
+    /// Whether the key event triggers this binding. Letters match regardless of case.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let code = match key.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        let required = self.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let pressed = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        code == self.code && required == pressed
+    }
+
+    /// Whether typing this key in a text field would also trigger the binding
+    pub fn is_text_key(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_))
+            && !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Parsed and validated key bindings used by the TUI
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(Action, KeySpec)>,
+}
+
+impl KeyMap {
+    /// Parse the configured bindings, rejecting unknown keys and duplicates
+    pub fn from_config(config: &KeyBindings) -> Result<Self> {
+        let mut bindings: Vec<(Action, KeySpec)> = Vec::new();
+        for action in Action::ALL {
+            let text = match action {
+                Action::Claim => &config.claim,
+                Action::List => &config.list,
+                Action::Quit => &config.quit,
+                Action::Help => &config.help,
+                Action::Refresh => &config.refresh,
+            };
+            let spec = KeySpec::parse(text)
+                .map_err(|e| anyhow!("keybindings.{} 配置无效 (\"{}\"): {}", action.name(), text, e))?;
+            if let Some((other, _)) = bindings.iter().find(|(_, s)| *s == spec) {
+                return Err(anyhow!(
+                    "keybindings.{} 与 keybindings.{} 使用了相同的按键: {}",
+                    action.name(),
+                    other.name(),
+                    spec
+                ));
+            }
+            bindings.push((action, spec));
+        }
+        Ok(Self { bindings })
+    }
+
+    /// Whether the key event triggers the action
+    pub fn is(&self, action: Action, key: &KeyEvent) -> bool {
+        self.key(action).matches(key)
+    }
+
+    /// The key bound to an action
+    pub fn key(&self, action: Action) -> &KeySpec {
+        &self
+            .bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .expect("every action has a binding")
+            .1
+    }
+
+    /// All bindings, in display order
+    pub fn bindings(&self) -> &[(Action, KeySpec)] {
+        &self.bindings
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::from_config(&KeyBindings::default()).expect("default key bindings are valid")
+    }
+}
@@ -0,0 +1,60 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Rows moved by Ctrl-d / Ctrl-u
+const HALF_PAGE: usize = 5;
+
+/// Cursor movement in a list widget, from arrow keys or vim-style keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListNav {
+    Up,
+    Down,
+    Top,
+    Bottom,
+    HalfPageUp,
+    HalfPageDown,
+}
+
+impl ListNav {
+    /// Map a key to a movement: ↑/k, ↓/j, g/Home, G/End, Ctrl-u/PageUp, Ctrl-d/PageDown
+    pub fn from_key(key: &KeyEvent) -> Option<Self> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('u') if ctrl => Some(ListNav::HalfPageUp),
+            KeyCode::Char('d') if ctrl => Some(ListNav::HalfPageDown),
+            _ if ctrl => None,
+            KeyCode::Up | KeyCode::Char('k') => Some(ListNav::Up),
+            KeyCode::Down | KeyCode::Char('j') => Some(ListNav::Down),
+            KeyCode::Home | KeyCode::Char('g') => Some(ListNav::Top),
+            KeyCode::End | KeyCode::Char('G') => Some(ListNav::Bottom),
+            KeyCode::PageUp => Some(ListNav::HalfPageUp),
+            KeyCode::PageDown => Some(ListNav::HalfPageDown),
+            _ => None,
+        }
+    }
+
+    /// New selected index in a list of `len` items
+    pub fn apply(self, index: usize, len: usize) -> usize {
+        let last = len.saturating_sub(1);
+        match self {
+            ListNav::Up => index.saturating_sub(1),
+            ListNav::Down => (index + 1).min(last),
+            ListNav::Top => 0,
+            ListNav::Bottom => last,
+            ListNav::HalfPageUp => index.saturating_sub(HALF_PAGE),
+            ListNav::HalfPageDown => (index + HALF_PAGE).min(last),
+        }
+    }
+}
+
+/// First row of a window `height` rows tall over a list of `len` items that shows
+/// `selected`, moving on from the previous `offset` only as far as needed
+pub fn window_offset(offset: usize, selected: usize, height: usize, len: usize) -> usize {
+    let offset = offset.min(len.saturating_sub(height));
+    if selected < offset {
+        selected
+    } else if selected >= offset + height {
+        (selected + 1).saturating_sub(height)
+    } else {
+        offset
+    }
+}
@@ -1,5 +1,16 @@
 pub mod app;
+pub mod keymap;
+pub mod list_nav;
+pub mod plain;
 pub mod screens;
+pub mod tabs;
+pub mod text_input;
+pub mod theme;
+pub mod toast;
 
 pub use app::App;
+pub use keymap::{Action, KeyMap};
+pub use list_nav::ListNav;
+pub use theme::Theme;
+pub use toast::ToastKind;
 pub use screens::*;
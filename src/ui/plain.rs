@@ -0,0 +1,427 @@
+use anyhow::Result;
+use std::io::{self, Write};
+
+use crate::{audit::{self, Action as AuditAction}, config::{Config, ConfigService}, coupons::{parse_coupons_from_markdown, ClaimHistory, ClaimVerification, Coupon, UsageStore}, error::McdError, mcp::{describe_error, McpClient}, mqtt, progress::{self, ClaimRun}, ui::tabs::settings::Field, utils::mask_token};
+
+/// Audit actor and claim source; the plain mode stands in for the TUI
+const SOURCE: &str = "tui";
+/// Claim history entries listed at once
+const HISTORY_LIMIT: usize = 10;
+
+/// Menu entries, numbered from 1 in this order
+const MENU: [(&str, MenuItem); 7] = [
+    ("一键领取所有优惠券", MenuItem::Claim),
+    ("查看我的优惠券", MenuItem::List),
+    ("标记或取消已使用", MenuItem::MarkUsed),
+    ("查看领取记录", MenuItem::History),
+    ("修改设置", MenuItem::Settings),
+    ("重新设置Token", MenuItem::ResetToken),
+    ("切换账号", MenuItem::SwitchAccount),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum MenuItem {
+    Claim,
+    List,
+    MarkUsed,
+    History,
+    Settings,
+    ResetToken,
+    SwitchAccount,
+}
+
+/// Line-based counterpart of the TUI for screen readers and dumb terminals:
+/// numbered menus and prompts on plain stdout, no alternate screen, colors or box drawing
+pub async fn run() -> Result<()> {
+    println!("麦当劳优惠券自动领取工具（纯文本模式）");
+    let mut session = Session { client: None, coupons: Vec::new() };
+
+    let config = ConfigService::global().current();
+    if config.has_valid_token() {
+        session.client = Some(McpClient::with_url(config.token.clone(), config.upstream_url())?);
+        say("已加载保存的Token");
+    } else if !session.ask_token().await? {
+        return Ok(());
+    }
+
+    print_menu();
+    loop {
+        let Some(input) = prompt("请输入操作序号（? 显示菜单，0 退出）")? else {
+            break;
+        };
+        let item = match input.as_str() {
+            "" => continue,
+            "0" | "q" | "quit" | "exit" => break,
+            "?" | "h" | "help" => {
+                print_menu();
+                continue;
+            },
+            number => match number.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| MENU.get(i)) {
+                Some((_, item)) => *item,
+                None => {
+                    say(format!("无效选项: {}，输入 ? 查看菜单", number));
+                    continue;
+                },
+            },
+        };
+        let result = match item {
+            MenuItem::Claim => session.claim().await,
+            MenuItem::List => session.list().await,
+            MenuItem::MarkUsed => session.mark_used().await,
+            MenuItem::History => {
+                print_history();
+                Ok(())
+            },
+            MenuItem::Settings => edit_settings(),
+            MenuItem::ResetToken => session.reset_token().await,
+            MenuItem::SwitchAccount => session.switch_account(),
+        };
+        match result {
+            Ok(()) => {},
+            // Same as the TUI: a rejected token means asking for a new one
+            Err(e) if matches!(e.downcast_ref::<McdError>(), Some(McdError::TokenInvalid)) => {
+                say(format!("操作失败: {}", McdError::TokenInvalid));
+                session.client = None;
+                if !session.ask_token().await? {
+                    break;
+                }
+            },
+            Err(e) => say(format!("操作失败: {}", e)),
+        }
+    }
+    say("已退出");
+    Ok(())
+}
+
+struct Session {
+    client: Option<McpClient>,
+    /// Coupons from the last listing, numbered for marking as used
+    coupons: Vec<Coupon>,
+}
+
+impl Session {
+    fn client(&self) -> Result<&McpClient> {
+        self.client.as_ref().ok_or_else(|| anyhow::anyhow!("请先设置Token"))
+    }
+
+    /// Ask for a token until one validates. Returns false when the user gives up.
+    async fn ask_token(&mut self) -> Result<bool> {
+        say("请输入 Token，可在浏览器打开获取：https://open.mcd.cn/mcp/login");
+        loop {
+            let input = match prompt("Token（留空退出）")? {
+                Some(input) if !input.is_empty() => input,
+                _ => return Ok(false),
+            };
+            let token = if input.starts_with("Bearer ") {
+                input
+            } else {
+                format!("Bearer {}", input)
+            };
+
+            say("正在验证Token...");
+            let url = ConfigService::global().current().upstream_url().map(str::to_string);
+            let client = McpClient::with_url(token.clone(), url.as_deref())?;
+            match client.validate_token().await {
+                Ok(true) => {
+                    ConfigService::global().update(|config| {
+                        config.set_token(token.clone());
+                        config.remember_token(&token, None);
+                    })?;
+                    audit::record(SOURCE, AuditAction::TokenSet, true, mask_token(&token));
+                    self.client = Some(client);
+                    self.coupons.clear();
+                    say("Token验证成功！");
+                    return Ok(true);
+                },
+                Ok(false) => say("Token无效，请重新输入"),
+                Err(e) => say(format!("验证失败: {}", e)),
+            }
+        }
+    }
+
+    /// Claim everything, reading out each stage as it starts
+    async fn claim(&mut self) -> Result<()> {
+        let mut events = progress::subscribe();
+        let result = {
+            let claim = progress::claim(self.client()?, SOURCE);
+            tokio::pin!(claim);
+            loop {
+                tokio::select! {
+                    result = &mut claim => break result,
+                    Ok(event) = events.recv() => {
+                        if !event.finished {
+                            say(&event.message);
+                        }
+                    },
+                }
+            }
+        };
+
+        let (result, mine, verification) = ClaimRun::split(result);
+        ClaimHistory::record(SOURCE, &result, verification.as_ref());
+        audit::record_claim(SOURCE, &result, verification.as_ref());
+        mqtt::publish_claim(SOURCE, &result, verification.as_ref());
+        let response = match result {
+            Ok(response) => response,
+            Err(McdError::TokenInvalid) => return Err(McdError::TokenInvalid.into()),
+            Err(e) => {
+                say(format!("领取失败: {}", describe_error(&e)));
+                return Ok(());
+            },
+        };
+        // The first non-empty line of the markdown answer is the summary; the coupons follow below
+        let summary = response.lines().map(|l| l.trim().trim_start_matches('#').trim()).find(|l| !l.is_empty()).unwrap_or_default();
+        match &verification {
+            Some(verification) => say(ClaimVerification::message(Some(verification))),
+            None => say(format!("领取成功！{}", summary)),
+        }
+        if let Some(text) = mine {
+            self.show(&text);
+        }
+        Ok(())
+    }
+
+    /// Fetch and read out the held coupons
+    async fn list(&mut self) -> Result<()> {
+        if self.load().await? {
+            self.show_loaded();
+        }
+        Ok(())
+    }
+
+    /// Fetch the held coupons. Returns false when upstream failed, which has been reported.
+    async fn load(&mut self) -> Result<bool> {
+        say("正在加载已领取的优惠券...");
+        match self.client()?.get_my_coupons().await {
+            Ok(text) => {
+                self.set_coupons(&text);
+                Ok(true)
+            },
+            Err(McdError::TokenInvalid) => Err(McdError::TokenInvalid.into()),
+            Err(e) => {
+                say(format!("加载失败: {}", describe_error(&e)));
+                Ok(false)
+            },
+        }
+    }
+
+    fn set_coupons(&mut self, text: &str) {
+        self.coupons = parse_coupons_from_markdown(text);
+        match UsageStore::load() {
+            Ok(store) => store.apply(&mut self.coupons),
+            Err(e) => say(format!("读取使用记录失败: {}", e)),
+        }
+    }
+
+    fn show(&mut self, text: &str) {
+        self.set_coupons(text);
+        self.show_loaded();
+    }
+
+    fn show_loaded(&self) {
+        if self.coupons.is_empty() {
+            say("暂无已领取的优惠券");
+            return;
+        }
+        say(format!("共 {} 张优惠券:", self.coupons.len()));
+        for (i, coupon) in self.coupons.iter().enumerate() {
+            say(format!("{}. {}", i + 1, describe_coupon(coupon)));
+        }
+    }
+
+    /// Toggle the used mark of a coupon from the last listing
+    async fn mark_used(&mut self) -> Result<()> {
+        if self.coupons.is_empty() && !self.load().await? {
+            return Ok(());
+        }
+        if self.coupons.is_empty() {
+            say("暂无已领取的优惠券");
+            return Ok(());
+        }
+        let labels: Vec<String> = self.coupons.iter().map(describe_coupon).collect();
+        let Some(coupon) = choose("优惠券序号（留空取消）", &labels)?.map(|i| self.coupons[i].clone()) else {
+            return Ok(());
+        };
+        let mut store = UsageStore::load()?;
+        let used = store.toggle(&coupon);
+        store.save()?;
+        store.apply(&mut self.coupons);
+
+        let message = if used {
+            format!("已标记为已使用: {}", coupon.title)
+        } else {
+            format!("已取消使用标记: {}", coupon.title)
+        };
+        audit::record(SOURCE, AuditAction::MarkUsed, true, message.clone());
+        say(message);
+        Ok(())
+    }
+
+    /// Clear the saved token and ask for a new one
+    async fn reset_token(&mut self) -> Result<()> {
+        let Some(answer) = prompt("确认清除已保存的Token？(y/N)")? else {
+            return Ok(());
+        };
+        if !answer.eq_ignore_ascii_case("y") {
+            say("已取消");
+            return Ok(());
+        }
+        ConfigService::global().update(|config| config.token = String::new())?;
+        audit::record(SOURCE, AuditAction::TokenReset, true, "");
+        self.client = None;
+        self.coupons.clear();
+        say("Token已重置");
+        if !self.ask_token().await? {
+            say("未设置Token，领取和查询不可用，可在菜单中重新设置");
+        }
+        Ok(())
+    }
+
+    fn switch_account(&mut self) -> Result<()> {
+        let mut config = Config::clone(&ConfigService::global().current());
+        config.ensure_default_account();
+        if config.accounts.is_empty() {
+            say("暂无账号，请先设置Token；添加账号请使用终端界面或网页模式");
+            return Ok(());
+        }
+        let names: Vec<String> = config.accounts.iter()
+            .map(|a| {
+                let active = config.active_account.as_deref() == Some(a.name.as_str());
+                format!("{}{}", a.name, if active { "（当前）" } else { "" })
+            })
+            .collect();
+        let Some(index) = choose("账号序号（留空取消）", &names)? else {
+            return Ok(());
+        };
+        let name = config.accounts[index].name.clone();
+        let config = ConfigService::global().update(|config| {
+            config.ensure_default_account();
+            config.switch_account(&name);
+        })?;
+        audit::record(SOURCE, AuditAction::AccountSwitch, true, name.as_str());
+        self.client = Some(McpClient::with_url(config.token.clone(), config.upstream_url())?);
+        self.coupons.clear();
+        say(format!("已切换到账号: {}", name));
+        Ok(())
+    }
+}
+
+/// One coupon as a sentence, separated by commas rather than columns
+fn describe_coupon(coupon: &Coupon) -> String {
+    let mut parts = vec![format!("{}{}", coupon.title, coupon.count_badge())];
+    if !coupon.price.is_empty() {
+        parts.push(coupon.price.clone());
+    }
+    parts.extend(coupon.calories_label());
+    if !coupon.expiry.is_empty() {
+        parts.push(format!("有效期 {}", coupon.expiry));
+    }
+    if let Some(days) = coupon.days_left().filter(|d| *d >= 0) {
+        parts.push(format!("剩余 {} 天", days));
+    }
+    if coupon.used {
+        parts.push("已使用".to_string());
+    }
+    parts.join("，")
+}
+
+fn print_history() {
+    let history = match ClaimHistory::load() {
+        Ok(history) => history.query(None, None, None),
+        Err(e) => {
+            say(format!("读取领取记录失败: {:#}", e));
+            return;
+        },
+    };
+    if history.is_empty() {
+        say("暂无领取记录");
+        return;
+    }
+    say(format!("最近 {} 条领取记录:", history.len().min(HISTORY_LIMIT)));
+    for entry in history.iter().take(HISTORY_LIMIT) {
+        let account = entry.account.as_deref().map(|a| format!("，账号 {}", a)).unwrap_or_default();
+        let outcome = if entry.success { "成功" } else { "失败" };
+        let coupons = if entry.coupons.is_empty() {
+            String::new()
+        } else {
+            format!("，{}", entry.coupons.join("、"))
+        };
+        say(format!("{}，来源 {}{}，{}: {}{}", entry.at, entry.source, account, outcome, entry.message, coupons));
+    }
+}
+
+/// Pick a field, then toggle it or read a new value, as on the settings tab
+fn edit_settings() -> Result<()> {
+    let config = ConfigService::global().current();
+    let labels: Vec<String> = Field::ALL.iter()
+        .map(|field| {
+            let value = field.value(&config);
+            format!("{}: {}", field.label(), if value.is_empty() { "默认" } else { &value })
+        })
+        .collect();
+    let Some(index) = choose("设置项序号（留空取消）", &labels)? else {
+        return Ok(());
+    };
+    let field = Field::ALL[index];
+
+    let text = if field.is_toggle() {
+        String::new()
+    } else {
+        let hint = match field.hint() {
+            "" => String::new(),
+            hint => format!("，{}", hint),
+        };
+        let Some(text) = prompt(&format!("新的{}（当前 {}{}）", field.label(), field.value(&config), hint))? else {
+            return Ok(());
+        };
+        text
+    };
+
+    let config = ConfigService::global().try_update(|config| field.apply(config, &text))?;
+    let value = field.value(&config);
+    let value = if value.is_empty() { "默认" } else { &value };
+    audit::record(SOURCE, AuditAction::ConfigChange, true, format!("{}: {}", field.label(), value));
+    say(format!("已修改设置 {}: {}，正在运行的网页或 MCP 服务器模式需重启后生效", field.label(), value));
+    Ok(())
+}
+
+fn print_menu() {
+    say("可用操作:");
+    for (i, (label, _)) in MENU.iter().enumerate() {
+        say(format!("{}. {}", i + 1, label));
+    }
+    say("0. 退出");
+}
+
+/// Read out a numbered list and return the index of the chosen line; `None` when cancelled
+fn choose(question: &str, items: &[String]) -> Result<Option<usize>> {
+    for (i, item) in items.iter().enumerate() {
+        say(format!("{}. {}", i + 1, item));
+    }
+    loop {
+        let input = match prompt(question)? {
+            Some(input) if !input.is_empty() => input,
+            _ => return Ok(None),
+        };
+        match input.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).filter(|i| *i < items.len()) {
+            Some(index) => return Ok(Some(index)),
+            None => say(format!("请输入 1-{} 之间的序号", items.len())),
+        }
+    }
+}
+
+/// Ask a question on its own line and read the answer; `None` at end of input
+fn prompt(question: &str) -> Result<Option<String>> {
+    print!("{}: ", question);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        println!();
+        return Ok(None);
+    }
+    Ok(Some(input.trim().to_string()))
+}
+
+fn say(line: impl AsRef<str>) {
+    println!("{}", line.as_ref());
+}
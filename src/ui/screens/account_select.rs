@@ -0,0 +1,391 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{Frame, layout::{Constraint, Direction, Layout}, style::Style, text::{Line, Span}, widgets::{Block, Borders, List, ListItem, Paragraph}};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use crate::{audit::{self, Action as AuditAction}, config::{Config, ConfigService}, mcp::McpClient, ui::{text_input::TextInput, Action, App, ListNav, ScreenType, Theme, ToastKind}, utils::mask_token};
+
+/// Result of the last token check for an account
+#[derive(Clone, Copy, PartialEq)]
+pub enum AccountStatus {
+    Unchecked,
+    Valid,
+    Invalid,
+    CheckFailed,
+}
+
+impl AccountStatus {
+    fn badge(self, theme: &Theme) -> (&'static str, Style) {
+        match self {
+            AccountStatus::Unchecked => ("[未检测]", theme.muted),
+            AccountStatus::Valid => ("[有效]", theme.success),
+            AccountStatus::Invalid => ("[无效]", theme.error),
+            AccountStatus::CheckFailed => ("[检测失败]", theme.warning),
+        }
+    }
+}
+
+/// Step of the inline "add account" form
+#[derive(Clone, PartialEq)]
+enum AddStep {
+    Name,
+    Token { name: String },
+}
+
+/// Account switcher screen for multi-account setups
+#[derive(Clone)]
+pub struct AccountSelectScreen {
+    pub config: Config,
+    pub selected: usize,
+    pub statuses: Vec<AccountStatus>,
+    pub message: Option<String>,
+    add_step: Option<AddStep>,
+    input: TextInput,
+    confirm_delete: bool,
+    /// Results of background token checks, drained on each tick
+    check_results: Arc<Mutex<Vec<(usize, AccountStatus)>>>,
+    pending_checks: usize,
+}
+
+impl AccountSelectScreen {
+    /// Create the screen from the saved configuration
+    pub fn new() -> Self {
+        let mut config = Config::clone(&ConfigService::global().current());
+        config.ensure_default_account();
+        let selected = config
+            .active_account
+            .as_ref()
+            .and_then(|name| config.accounts.iter().position(|a| &a.name == name))
+            .unwrap_or(0);
+        let statuses = vec![AccountStatus::Unchecked; config.accounts.len()];
+        Self {
+            config,
+            selected,
+            statuses,
+            message: None,
+            add_step: None,
+            input: TextInput::default(),
+            confirm_delete: false,
+            check_results: Arc::new(Mutex::new(Vec::new())),
+            pending_checks: 0,
+        }
+    }
+
+    /// Whether the add-account form is open
+    pub fn is_editing(&self) -> bool {
+        self.add_step.is_some()
+    }
+
+    /// Insert pasted text into the add-account form
+    pub fn paste(&mut self, text: &str) {
+        if self.is_editing() {
+            self.input.insert_str(text);
+        }
+    }
+
+    /// Handle keyboard input for the account switcher
+    pub async fn handle_key(mut self, key: KeyEvent, app: &mut App) -> Result<ScreenType> {
+        if self.add_step.is_some() {
+            self.handle_add_key(key, app).await;
+            return Ok(ScreenType::AccountSelect(Box::new(self)));
+        }
+
+        if self.confirm_delete {
+            self.confirm_delete = false;
+            if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                self.delete_selected(app);
+            } else {
+                self.message = Some("已取消删除".to_string());
+            }
+            return Ok(ScreenType::AccountSelect(Box::new(self)));
+        }
+
+        if app.keymap.is(Action::Refresh, &key) {
+            self.check_accounts(app);
+            return Ok(ScreenType::AccountSelect(Box::new(self)));
+        }
+
+        if let Some(nav) = ListNav::from_key(&key) {
+            self.selected = nav.apply(self.selected, self.config.accounts.len());
+            return Ok(ScreenType::AccountSelect(Box::new(self)));
+        }
+
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(screen) = self.switch_to_selected(app) {
+                    return Ok(screen);
+                }
+            },
+            KeyCode::Char('a') | KeyCode::Char('A') => {
+                self.add_step = Some(AddStep::Name);
+                self.input.clear();
+                self.message = Some("请输入账号名称，Enter 确认，Esc 取消".to_string());
+            },
+            KeyCode::Char('d') | KeyCode::Char('D') if self.pending_checks > 0 => {
+                self.message = Some("正在检测账号，请稍候再删除".to_string());
+            },
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                if let Some(account) = self.config.accounts.get(self.selected) {
+                    self.message = Some(format!("确定删除账号「{}」吗？按 y 确认", account.name));
+                    self.confirm_delete = true;
+                }
+            },
+            KeyCode::Esc => {
+                return Ok(if app.mcp_client.is_some() {
+                    ScreenType::Main(Box::new(crate::ui::screens::MainScreen::new()))
+                } else {
+                    ScreenType::TokenInput(crate::ui::screens::TokenInputScreen::new())
+                });
+            },
+            _ => {},
+        }
+        Ok(ScreenType::AccountSelect(Box::new(self)))
+    }
+
+    /// Handle keys while the add-account form is open
+    async fn handle_add_key(&mut self, key: KeyEvent, app: &mut App) {
+        match key.code {
+            KeyCode::Esc => {
+                self.add_step = None;
+                self.input.clear();
+                self.message = Some("已取消添加".to_string());
+            },
+            KeyCode::Enter => {
+                let value = self.input.value().trim().to_string();
+                if value.is_empty() {
+                    self.message = Some("输入不能为空".to_string());
+                    return;
+                }
+                match self.add_step.take() {
+                    Some(AddStep::Name) => {
+                        self.add_step = Some(AddStep::Token { name: value });
+                        self.input.clear();
+                        self.message = Some("请输入该账号的MCP Token".to_string());
+                    },
+                    Some(AddStep::Token { name }) => {
+                        self.input.clear();
+                        self.add_account(name, value, app).await;
+                    },
+                    None => {},
+                }
+            },
+            _ => {
+                self.input.handle_key(&key);
+            },
+        }
+    }
+
+    /// Validate and save a new account
+    async fn add_account(&mut self, name: String, token: String, app: &mut App) {
+        let token = if token.starts_with("Bearer ") {
+            token
+        } else {
+            format!("Bearer {}", token)
+        };
+
+        let url = self.config.mcp_server_url.clone();
+        let status = check_token(token.clone(), url).await;
+        if !self.save(|config| config.add_account(name.clone(), token)) {
+            return;
+        }
+        self.statuses.resize(self.config.accounts.len(), AccountStatus::Unchecked);
+        if let Some(index) = self.config.accounts.iter().position(|a| a.name == name) {
+            self.statuses[index] = status;
+            self.selected = index;
+        }
+
+        app.add_log(format!("已添加账号: {}", name));
+        audit::record("tui", AuditAction::AccountAdd, true, name.as_str());
+        self.message = Some(format!("已添加账号「{}」，按 Enter 切换", name));
+    }
+
+    /// Remove the selected account
+    fn delete_selected(&mut self, app: &mut App) {
+        let Some(account) = self.config.accounts.get(self.selected) else {
+            return;
+        };
+        let name = account.name.clone();
+        let was_active = self.config.active_account.as_deref() == Some(name.as_str());
+
+        if !self.save(|config| config.remove_account(&name)) {
+            return;
+        }
+        if self.selected < self.statuses.len() {
+            self.statuses.remove(self.selected);
+        }
+        self.statuses.resize(self.config.accounts.len(), AccountStatus::Unchecked);
+        if self.selected >= self.config.accounts.len() {
+            self.selected = self.config.accounts.len().saturating_sub(1);
+        }
+        if was_active {
+            app.mcp_client = None;
+        }
+
+        app.add_log(format!("已删除账号: {}", name));
+        audit::record("tui", AuditAction::AccountRemove, true, name.as_str());
+        self.message = Some(format!("已删除账号「{}」", name));
+    }
+
+    /// Apply a change to the shared config and show the saved result. Returns false when saving failed.
+    fn save(&mut self, change: impl FnOnce(&mut Config)) -> bool {
+        let saved = ConfigService::global().update(|config| {
+            config.ensure_default_account();
+            change(config);
+        });
+        match saved {
+            Ok(config) => {
+                self.config = Config::clone(&config);
+                true
+            },
+            Err(e) => {
+                self.message = Some(format!("保存配置失败: {}", e));
+                false
+            },
+        }
+    }
+
+    /// Make the selected account active and reinitialize the MCP client
+    fn switch_to_selected(&mut self, app: &mut App) -> Option<ScreenType> {
+        let name = self.config.accounts.get(self.selected)?.name.clone();
+        if !self.save(|config| {
+            config.switch_account(&name);
+        }) {
+            return None;
+        }
+        audit::record("tui", AuditAction::AccountSwitch, true, name.as_str());
+
+        match app.init_mcp_client(self.config.token.clone(), self.config.upstream_url()) {
+            Ok(_) => {
+                app.notify(ToastKind::Success, format!("已切换到账号: {}", name));
+                Some(ScreenType::Main(Box::new(crate::ui::screens::MainScreen::new())))
+            },
+            Err(e) => {
+                self.message = Some(format!("切换失败: {}", e));
+                None
+            },
+        }
+    }
+
+    /// Validate all account tokens concurrently in the background; results arrive on tick
+    fn check_accounts(&mut self, app: &mut App) {
+        if self.pending_checks > 0 {
+            return;
+        }
+        app.add_log("正在检测账号Token...".to_string());
+        self.pending_checks = self.config.accounts.len();
+        for (index, account) in self.config.accounts.iter().enumerate() {
+            let results = self.check_results.clone();
+            let token = account.token.clone();
+            let url = self.config.account_upstream_url(account).map(str::to_string);
+            tokio::spawn(async move {
+                let status = check_token(token, url).await;
+                if let Ok(mut results) = results.lock() {
+                    results.push((index, status));
+                }
+            });
+        }
+    }
+
+    /// Apply finished token checks
+    pub fn on_tick(mut self, _app: &mut App) -> ScreenType {
+        if self.pending_checks > 0 {
+            let finished: Vec<_> = match self.check_results.lock() {
+                Ok(mut results) => results.drain(..).collect(),
+                Err(_) => Vec::new(),
+            };
+            for (index, status) in finished {
+                if let Some(slot) = self.statuses.get_mut(index) {
+                    *slot = status;
+                }
+                self.pending_checks = self.pending_checks.saturating_sub(1);
+            }
+            if self.pending_checks == 0 {
+                let valid = self.statuses.iter().filter(|s| **s == AccountStatus::Valid).count();
+                self.message = Some(format!("检测完成：{}/{} 个账号有效", valid, self.statuses.len()));
+            }
+        }
+        ScreenType::AccountSelect(Box::new(self))
+    }
+
+    /// Render the account switcher
+    pub fn render(&self, f: &mut Frame<'_>, app: &App) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Min(0),
+                    Constraint::Length(3),
+                    Constraint::Length(3),
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+
+        let title = Paragraph::new("切换账号")
+            .block(Block::default().borders(Borders::ALL))
+            .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(title, layout[0]);
+
+        let items: Vec<ListItem> = if self.config.accounts.is_empty() {
+            vec![ListItem::new("暂无账号，按 'a' 添加")]
+        } else {
+            self.config.accounts.iter()
+                .enumerate()
+                .map(|(i, account)| {
+                    let active = self.config.active_account.as_deref() == Some(account.name.as_str());
+                    let (badge, badge_style) = self.statuses.get(i).copied().unwrap_or(AccountStatus::Unchecked).badge(&app.theme);
+                    let line = Line::from(vec![
+                        Span::raw(if active { "● " } else { "  " }),
+                        Span::raw(format!("{} ", account.name)),
+                        Span::styled(badge, badge_style),
+                        Span::raw(format!("  {}", mask_token(&account.token))),
+                    ]);
+                    let style = if i == self.selected {
+                        app.theme.selected
+                    } else {
+                        Style::default()
+                    };
+                    ListItem::new(line).style(style)
+                })
+                .collect()
+        };
+
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("账号列表 (● 当前账号)"));
+        f.render_widget(list, layout[1]);
+
+        let (input_title, input_text) = match &self.add_step {
+            Some(AddStep::Name) => ("账号名称", self.input.value().to_string()),
+            Some(AddStep::Token { .. }) => ("MCP Token", self.input.value().to_string()),
+            None if self.pending_checks > 0 => ("提示", format!("{} 正在检测账号Token... (剩余 {})", app.spinner(), self.pending_checks)),
+            None => ("提示", self.message.clone().unwrap_or_default()),
+        };
+        // Scrolled so the cursor stays visible while typing
+        let (scroll, cursor_x) = if self.is_editing() { self.input.viewport(layout[2].width.saturating_sub(2)) } else { (0, 0) };
+        let input = Paragraph::new(input_text)
+            .block(Block::default().borders(Borders::ALL).title(input_title))
+            .style(app.theme.accent)
+            .scroll((0, scroll));
+        f.render_widget(input, layout[2]);
+        if self.is_editing() {
+            f.set_cursor(layout[2].x + 1 + cursor_x, layout[2].y + 1);
+        }
+
+        let help = Paragraph::new(format!("Enter 切换 | a 添加 | d 删除 | {} 检测有效性 | Esc 返回", app.keymap.key(Action::Refresh)))
+            .block(Block::default().borders(Borders::ALL));
+        f.render_widget(help, layout[3]);
+    }
+}
+
+/// Check a single token against the upstream
+async fn check_token(token: String, url: Option<String>) -> AccountStatus {
+    let client = match McpClient::with_url(token, url.as_deref()) {
+        Ok(client) => client,
+        Err(_) => return AccountStatus::CheckFailed,
+    };
+    match client.validate_token().await {
+        Ok(true) => AccountStatus::Valid,
+        Ok(false) => AccountStatus::Invalid,
+        Err(_) => AccountStatus::CheckFailed,
+    }
+}
@@ -0,0 +1,70 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{Frame, layout::{Alignment, Constraint, Direction, Layout}, widgets::{Block, Borders, Paragraph, Wrap}};
+use anyhow::Result;
+use crate::{error::McdError, mcp::describe_error, ui::{App, ScreenType}};
+
+/// Shows a recoverable error from a screen, with retry / back choices
+#[derive(Clone)]
+pub struct ErrorScreen {
+    pub message: String,
+    /// Screen that failed, restored on back and used again on retry
+    pub previous: Box<ScreenType>,
+    /// Key that triggered the failure, replayed on retry
+    pub key: KeyEvent,
+}
+
+impl ErrorScreen {
+    /// Create the error screen for a failed key press on `previous`
+    pub fn new(error: &anyhow::Error, previous: ScreenType, key: KeyEvent) -> Self {
+        Self {
+            message: error.downcast_ref::<McdError>().map_or_else(|| error.to_string(), describe_error),
+            previous: Box::new(previous),
+            key,
+        }
+    }
+
+    /// Whether the key asks to retry the failed action
+    pub fn is_retry(key: &KeyEvent) -> bool {
+        matches!(key.code, KeyCode::Enter | KeyCode::Char('r') | KeyCode::Char('R'))
+    }
+
+    /// Handle keyboard input; retry is handled by the app loop
+    pub async fn handle_key(self, key: KeyEvent, _app: &mut App) -> Result<ScreenType> {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('b') | KeyCode::Char('B') => Ok(*self.previous),
+            _ => Ok(ScreenType::Error(Box::new(self))),
+        }
+    }
+
+    /// Render the error screen
+    pub fn render(&self, f: &mut Frame<'_>, app: &App) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Min(0),
+                    Constraint::Length(3),
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+
+        let title = Paragraph::new("操作失败")
+            .block(Block::default().borders(Borders::ALL))
+            .style(app.theme.error)
+            .alignment(Alignment::Center);
+        f.render_widget(title, layout[0]);
+
+        let message = Paragraph::new(self.message.as_str())
+            .block(Block::default().borders(Borders::ALL).title("错误信息").border_style(app.theme.error))
+            .wrap(Wrap { trim: false });
+        f.render_widget(message, layout[1]);
+
+        let help = Paragraph::new("Enter / r 重试 | Esc / b 返回")
+            .block(Block::default().borders(Borders::ALL))
+            .style(app.theme.hint)
+            .alignment(Alignment::Center);
+        f.render_widget(help, layout[2]);
+    }
+}
@@ -1,186 +1,221 @@
 use crossterm::event::{KeyCode, KeyEvent};
-use ratatui::{Frame, layout::{Constraint, Direction, Layout}, widgets::{Block, Borders, Paragraph, List, ListItem, Gauge}};
+use ratatui::{Frame, layout::{Alignment, Constraint, Direction, Layout}, text::Line, widgets::{block::Title, Block, Borders, Gauge, List, ListItem, Paragraph, Tabs}};
 use anyhow::Result;
-use crate::{ui::{App, ScreenType}};
+use std::sync::{Arc, Mutex};
+use crate::{audit, coupons::{ClaimHistory, ClaimVerification}, error::McdError, mcp::{describe_error, metrics}, mqtt, progress::{self, ClaimRun}, ui::{tabs::{CouponsTab, HistoryTab, LogsTab, SettingsTab, Tab}, Action, App, ScreenType, ToastKind, TokenInputScreen}};
 
-/// Main application screen with coupon management features
+/// Upstream success rate (%) below which the status bar shows it in red
+const UPSTREAM_WARN_RATE: f64 = 80.0;
+
+/// Main application screen: a tab bar over the coupon list, logs, claim history and settings
 #[derive(Clone)]
 pub struct MainScreen {
-    pub selected_option: usize,
-    pub show_coupons: bool,
-    pub coupons: Vec<String>,
+    pub tab: Tab,
+    pub show_help: bool,
+    pub coupons: CouponsTab,
+    pub logs: LogsTab,
+    pub history: HistoryTab,
+    pub settings: SettingsTab,
+    /// A claim is running in the background
+    claiming: bool,
+    claim_result: Arc<Mutex<Option<Result<ClaimRun, McdError>>>>,
 }
 
 impl MainScreen {
     /// Create a new main screen instance
     pub fn new() -> Self {
         Self {
-            selected_option: 0,
-            show_coupons: false,
-            coupons: Vec::new(),
+            tab: Tab::Coupons,
+            show_help: false,
+            coupons: CouponsTab::default(),
+            logs: LogsTab::default(),
+            history: HistoryTab::default(),
+            settings: SettingsTab::default(),
+            claiming: false,
+            claim_result: Arc::new(Mutex::new(None)),
         }
     }
 
     /// Handle keyboard input for the main screen
     pub async fn handle_key(mut self, key: KeyEvent, app: &mut App) -> Result<ScreenType> {
-        match key.code {
-            KeyCode::Up => {
-                if self.selected_option > 0 {
-                    self.selected_option -= 1;
-                }
-            },
-            KeyCode::Down => {
-                if self.selected_option < 2 {
-                    self.selected_option += 1;
-                }
-            },
-            KeyCode::Enter => {
-                if let Some(new_screen) = self.handle_option_selection(app).await? {
-                    return Ok(new_screen);
-                }
+        // A settings field being edited takes every key, including the bound ones
+        if self.is_editing() {
+            self.settings.handle_key(&key, app);
+            return Ok(ScreenType::Main(Box::new(self)));
+        }
+        if app.keymap.is(Action::Claim, &key) {
+            self.claim_all_coupons(app);
+            return Ok(ScreenType::Main(Box::new(self)));
+        }
+        if app.keymap.is(Action::List, &key) {
+            self.show_help = false;
+            self.switch_to(Tab::Coupons, app);
+            return Ok(ScreenType::Main(Box::new(self)));
+        }
+        if app.keymap.is(Action::Help, &key) {
+            self.show_help = !self.show_help;
+            return Ok(ScreenType::Main(Box::new(self)));
+        }
+        if app.keymap.is(Action::Refresh, &key) {
+            self.refresh(app);
+            return Ok(ScreenType::Main(Box::new(self)));
+        }
+
+        let tab = match key.code {
+            KeyCode::Tab => Some(self.tab.next()),
+            KeyCode::BackTab => Some(self.tab.previous()),
+            KeyCode::Char(digit) => Tab::from_digit(digit),
+            _ => None,
+        };
+        if let Some(tab) = tab {
+            self.switch_to(tab, app);
+            return Ok(ScreenType::Main(Box::new(self)));
+        }
+        if key.code == KeyCode::Esc && self.show_help {
+            self.show_help = false;
+            return Ok(ScreenType::Main(Box::new(self)));
+        }
+
+        match self.tab {
+            Tab::Coupons => {
+                self.coupons.handle_key(&key, app);
             },
-            KeyCode::Char('1') => {
-                self.selected_option = 0;
-                if let Some(new_screen) = self.handle_option_selection(app).await? {
-                    return Ok(new_screen);
-                }
+            Tab::Logs => {
+                self.logs.handle_key(&key, app);
             },
-            KeyCode::Char('2') => {
-                self.selected_option = 1;
-                if let Some(new_screen) = self.handle_option_selection(app).await? {
-                    return Ok(new_screen);
-                }
+            Tab::History => {
+                self.history.handle_key(&key);
             },
-            KeyCode::Char('3') => {
-                self.selected_option = 2;
-                if let Some(new_screen) = self.handle_option_selection(app).await? {
-                    return Ok(new_screen);
+            Tab::Settings => {
+                if let Some(screen) = self.settings.handle_key(&key, app) {
+                    return Ok(screen);
                 }
             },
-            KeyCode::Char('c') | KeyCode::Char('C') => {
-                self.show_coupons = !self.show_coupons;
-                if self.show_coupons {
-                    self.load_coupons(app).await?;
-                }
-            },
-            _ => {},
         }
-        Ok(ScreenType::Main(self))
+        Ok(ScreenType::Main(Box::new(self)))
     }
 
-    /// Handle option selection
-    async fn handle_option_selection(&mut self, app: &mut App) -> Result<Option<ScreenType>> {
-        match self.selected_option {
-            0 => {
-                self.claim_all_coupons(app).await?;
-                Ok(None)
-            },
-            1 => {
-                self.show_coupons = true;
-                self.load_coupons(app).await?;
-                Ok(None)
-            },
-            2 => {
-                let new_screen = self.reset_token(app);
-                Ok(Some(new_screen))
-            },
-            _ => {
-                Ok(None)
-            },
+    /// Whether a text field has focus
+    pub fn is_editing(&self) -> bool {
+        self.tab == Tab::Settings && self.settings.is_editing()
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        if self.is_editing() {
+            self.settings.paste(text);
         }
     }
 
-    /// Claim all available coupons
-    async fn claim_all_coupons(&mut self, app: &mut App) -> Result<()> {
-        // First, clone the client if it exists
-        if let Some(client) = app.mcp_client.clone() {
-            app.set_loading(true, 0);
-            app.add_log("正在领取所有优惠券...".to_string());
-            
-            let result = client.lock().await.auto_bind_coupons().await;
-            
-            app.set_loading(false, 100);
-            
-            match result {
-                Ok(response) => {
-                    app.add_log("领取成功！".to_string());
-                    // Response is markdown text, show first few lines as summary
-                    for line in response.lines().take(5) {
-                        if !line.trim().is_empty() {
-                            app.add_log(line.to_string());
-                        }
-                    }
-                },
-                Err(e) => {
-                    app.add_log(format!("领取失败: {}", e));
-                },
-            }
+    /// Show a tab, loading its data on first visit
+    fn switch_to(&mut self, tab: Tab, app: &mut App) {
+        self.tab = tab;
+        self.show_help = false;
+        match tab {
+            Tab::Coupons if !self.coupons.loaded => self.coupons.load(app),
+            Tab::History => self.history.reload(),
+            Tab::Settings => self.settings.reload(),
+            _ => {},
         }
-        Ok(())
     }
 
-    /// Load user's coupons
-    async fn load_coupons(&mut self, app: &mut App) -> Result<()> {
-        // First, clone the client if it exists
-        if let Some(client) = app.mcp_client.clone() {
-            app.set_loading(true, 0);
-            app.add_log("正在加载已领取的优惠券...".to_string());
-            
-            let result = client.lock().await.get_my_coupons().await;
-            
-            app.set_loading(false, 100);
-            
-            match result {
-                Ok(coupons_text) => {
-                    self.coupons.clear();
-                    // Response is markdown text, split by lines for display
-                    let lines: Vec<&str> = coupons_text.lines().collect();
-                    let coupon_count = lines.iter().filter(|l| l.starts_with("- ") || l.starts_with("* ")).count();
-                    for line in lines {
-                        if !line.trim().is_empty() {
-                            self.coupons.push(line.to_string());
-                        }
-                    }
-                    app.add_log(format!("已加载优惠券列表 (约 {} 项)", coupon_count));
-                },
-                Err(e) => {
-                    app.add_log(format!("加载失败: {}", e));
-                    self.coupons.push(format!("加载失败: {}", e));
-                },
-            }
+    /// Reload the data of the current tab
+    fn refresh(&mut self, app: &mut App) {
+        match self.tab {
+            Tab::Coupons => self.coupons.load(app),
+            Tab::History => self.history.reload(),
+            Tab::Settings => self.settings.reload(),
+            Tab::Logs => {},
         }
-        Ok(())
     }
 
-    /// Reset the token and return to token input screen
-    fn reset_token(&mut self, app: &mut App) -> ScreenType {
-        // Clear client and config
-        app.mcp_client = None;
-        
-        // Remove token from config
-        if let Ok(mut config) = crate::config::Config::load() {
-            config.token = String::new();
-            config.save().ok();
+    /// Start claiming all available coupons in the background; stages move the gauge
+    /// and the result is picked up on tick
+    fn claim_all_coupons(&mut self, app: &mut App) {
+        let Some(client) = app.mcp_client.clone() else {
+            return;
+        };
+        if self.claiming {
+            app.add_log("正在领取中，请稍候...".to_string());
+            return;
         }
-        
-        app.add_log("Token已重置".to_string());
-        app.add_log("请输入新的MCP Token".to_string());
-        
-        // Return to token input screen
-        ScreenType::TokenInput(crate::ui::screens::TokenInputScreen::new())
+        self.claiming = true;
+        app.set_loading(true, 0);
+        app.add_log("正在领取所有优惠券...".to_string());
+
+        let slot = self.claim_result.clone();
+        tokio::spawn(async move {
+            let result = progress::claim(&client, "tui").await;
+            *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+        });
+    }
+
+    /// Apply a finished coupon load or claim
+    pub fn on_tick(mut self, app: &mut App) -> ScreenType {
+        if let Err(e) = self.coupons.on_tick(app) {
+            // Same as a rejected token anywhere else: ask for a new one
+            app.notify(ToastKind::Error, format!("操作失败: {}", e));
+            app.mcp_client = None;
+            return ScreenType::TokenInput(TokenInputScreen::new());
+        }
+        if !self.claiming {
+            return ScreenType::Main(Box::new(self));
+        }
+        let Some(result) = self.claim_result.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return ScreenType::Main(Box::new(self));
+        };
+        self.claiming = false;
+        app.set_loading(false, 100);
+
+        let (result, mine, verification) = ClaimRun::split(result);
+        ClaimHistory::record("tui", &result, verification.as_ref());
+        audit::record_claim("tui", &result, verification.as_ref());
+        mqtt::publish_claim("tui", &result, verification.as_ref());
+        self.history.reload();
+
+        let succeeded = ClaimVerification::succeeded(&result, verification.as_ref());
+        match result {
+            Ok(response) => {
+                // Response is markdown text, the first non-empty line is the summary
+                let summary = response.lines().map(|l| l.trim().trim_start_matches('#').trim()).find(|l| !l.is_empty()).unwrap_or_default();
+                match &verification {
+                    // Upstream said yes but nothing new is held: worth more than a green toast
+                    Some(v) if !succeeded => app.notify(ToastKind::Error, ClaimVerification::message(Some(v))),
+                    Some(v) => app.notify(ToastKind::Success, ClaimVerification::message(Some(v))),
+                    None => app.notify(ToastKind::Success, format!("领取成功！{}", summary)),
+                }
+                // Show first few lines in the log
+                for line in response.lines().take(5) {
+                    if !line.trim().is_empty() {
+                        app.add_log(line.to_string());
+                    }
+                }
+                if let Some(text) = mine {
+                    self.coupons.show(&text, app);
+                }
+            },
+            Err(McdError::TokenInvalid) => {
+                // Same as a rejected token anywhere else: ask for a new one
+                app.add_log(format!("操作失败: {}", McdError::TokenInvalid));
+                app.mcp_client = None;
+                return ScreenType::TokenInput(TokenInputScreen::new());
+            },
+            Err(e) => {
+                app.notify(ToastKind::Error, format!("领取失败: {}", describe_error(&e)));
+            },
+        }
+        ScreenType::Main(Box::new(self))
     }
 
     /// Render the main screen
     pub fn render(&self, f: &mut Frame<'_>, app: &App) {
         let size = f.size();
-        
+
         // Create vertical layout
         let main_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
                 [
                     Constraint::Length(3),
-                    Constraint::Length(8),
                     Constraint::Min(0),
                     Constraint::Length(3),
                 ]
@@ -188,119 +223,84 @@ impl MainScreen {
             )
             .split(size);
 
-        // Title
-        let title = Paragraph::new("麦当劳优惠券自动领取工具")
-            .block(Block::default().borders(Borders::ALL))
-            .alignment(ratatui::layout::Alignment::Center);
-        f.render_widget(title, main_layout[0]);
-
-        // Main menu options
-        let options = [
-            "[1] 一键领取所有优惠券",
-            "[2] 查看已领取优惠券",
-            "[3] 重新设置Token",
-        ];
-        
-        let items: Vec<ListItem> = options.iter()
+        // Tab bar, numbered for the digit shortcuts
+        let titles: Vec<Line> = Tab::ALL.iter()
             .enumerate()
-            .map(|(i, option)| {
-                let style = if i == self.selected_option {
-                    ratatui::style::Style::default()
-                        .bg(ratatui::style::Color::Green)
-                        .fg(ratatui::style::Color::Black)
-                        .add_modifier(ratatui::style::Modifier::BOLD)
-                } else {
-                    ratatui::style::Style::default()
-                };
-                ListItem::new(*option).style(style)
-            })
+            .map(|(i, tab)| Line::from(format!("{} {}", i + 1, tab.title())))
             .collect();
-        
-        let menu = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("菜单选项"));
-        
-        f.render_widget(menu, main_layout[1]);
+        let tabs = Tabs::new(titles)
+            .block(Block::default().borders(Borders::ALL).title("麦当劳优惠券自动领取工具"))
+            .select(self.tab.index())
+            .highlight_style(app.theme.selected);
+        f.render_widget(tabs, main_layout[0]);
 
-        // Content area - logs or coupons
-        let content_layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(
-                [
-                    Constraint::Percentage(50),
-                    Constraint::Percentage(50),
-                ]
-                .as_ref(),
-            )
-            .split(main_layout[2]);
-        
-        // Logs panel
-        let logs_block = Block::default()
-            .borders(Borders::ALL)
-            .title("操作日志");
-        
-        let log_items: Vec<ListItem> = app.logs.iter()
-            .rev()
-            .take(10)
-            .map(|log| ListItem::new(log.clone()))
-            .collect();
-        
-        let logs_list = List::new(log_items)
-            .block(logs_block);
-        
-        f.render_widget(logs_list, content_layout[0]);
-        
-        // Coupons panel
-        let coupons_block = Block::default()
-            .borders(Borders::ALL)
-            .title("我的优惠券");
-        
-        if self.show_coupons {
-            let coupon_items: Vec<ListItem> = self.coupons.iter()
-                .map(|coupon| ListItem::new(coupon.clone()))
+        if self.show_help {
+            let help_items: Vec<ListItem> = app.keymap.bindings().iter()
+                .map(|(action, key)| ListItem::new(format!("{:<8} {}", key.to_string(), action.description())))
+                .chain([
+                    ListItem::new(format!("{:<8} {}", "Tab", "下一个标签页 (Shift-Tab 上一个)")),
+                    ListItem::new(format!("{:<8} {}", "1-4", "切换到对应标签页")),
+                ])
                 .collect();
-            
-            let coupons_list = List::new(coupon_items)
-                .block(coupons_block);
-            
-            f.render_widget(coupons_list, content_layout[1]);
+
+            let help_list = List::new(help_items)
+                .block(Block::default().borders(Borders::ALL).title("按键帮助 (Esc 关闭)"));
+
+            f.render_widget(help_list, main_layout[1]);
         } else {
-            let hint = Paragraph::new("按 'c' 查看已领取的优惠券")
-                .block(coupons_block)
-                .alignment(ratatui::layout::Alignment::Center);
-            
-            f.render_widget(hint, content_layout[1]);
+            match self.tab {
+                Tab::Coupons => self.coupons.render(f, main_layout[1], app),
+                Tab::Logs => self.logs.render(f, main_layout[1], app),
+                Tab::History => self.history.render(f, main_layout[1], app),
+                Tab::Settings => self.settings.render(f, main_layout[1], app),
+            }
         }
 
-        // Status bar
+        // Status bar, with the latest log line since the log is on its own tab
         let status_text = if app.is_loading {
-            "加载中..."
+            format!("{} 加载中...", app.spinner())
         } else {
-            "按 'q' 退出 | 按方向键选择选项 | 按 Enter 执行"
+            let latest = app.logs.last().map(String::as_str).unwrap_or_default();
+            format!(
+                "{} 领取 | {} 刷新 | {} 帮助 | {} 退出 | {}",
+                app.keymap.key(Action::Claim),
+                app.keymap.key(Action::Refresh),
+                app.keymap.key(Action::Help),
+                app.keymap.key(Action::Quit),
+                latest
+            )
         };
-        
+
+        // Upstream health on the right, to tell a bad local network from an upstream outage
+        let upstream = metrics::snapshot();
+        let upstream_style = if upstream.retry_after_secs.is_some() || (upstream.calls > 0 && upstream.success_rate < UPSTREAM_WARN_RATE) {
+            app.theme.error
+        } else {
+            app.theme.muted
+        };
+        let upstream_title = Title::from(format!(" {} ", upstream.summary()))
+            .alignment(Alignment::Right);
+
         let status = Paragraph::new(status_text)
-            .block(Block::default().borders(Borders::ALL));
-        f.render_widget(status, main_layout[3]);
-        
+            .block(Block::default().borders(Borders::ALL).title(upstream_title).title_style(upstream_style));
+        f.render_widget(status, main_layout[2]);
+
         // Progress bar if loading
         if app.is_loading {
             let progress_block = Block::default()
                 .borders(Borders::NONE)
                 .title("进度");
-            
+
             let gauge = Gauge::default()
                 .block(progress_block)
-                .gauge_style(ratatui::style::Style::default()
-                    .fg(ratatui::style::Color::Green)
-                    .bg(ratatui::style::Color::Black)
-                    .add_modifier(ratatui::style::Modifier::BOLD))
+                .gauge_style(app.theme.gauge)
                 .percent(app.progress.into());
-            
+
             let progress_layout = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Percentage(80), Constraint::Percentage(20)])
-                .split(main_layout[3]);
-            
+                .split(main_layout[2]);
+
             f.render_widget(gauge, progress_layout[0]);
         }
     }
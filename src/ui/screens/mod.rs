@@ -6,6 +6,9 @@ pub trait Screen {
     /// Handle keyboard input
     async fn handle_key(self, key: crossterm::event::KeyEvent, app: &mut App) -> anyhow::Result<ScreenType>;
     
+    /// Handle a periodic Tick event from the render loop
+    fn on_tick(self, app: &mut App) -> ScreenType;
+
     /// Render the screen
     fn render(&self, f: &mut Frame<'_>, app: &App);
 }
@@ -13,29 +16,76 @@ pub trait Screen {
 /// Enum representing all possible screen types
 #[derive(Clone)]
 pub enum ScreenType {
+    /// Shown while the saved token loads, before any other screen
+    Splash(SplashScreen),
     TokenInput(TokenInputScreen),
-    Main(MainScreen),
+    Main(Box<MainScreen>),
+    AccountSelect(Box<AccountSelectScreen>),
+    Error(Box<ErrorScreen>),
+}
+
+impl ScreenType {
+    /// Whether the screen currently has a focused text field
+    pub fn accepts_text(&self) -> bool {
+        match self {
+            ScreenType::TokenInput(_) => true,
+            ScreenType::Main(screen) => screen.is_editing(),
+            ScreenType::AccountSelect(screen) => screen.is_editing(),
+            ScreenType::Splash(_) | ScreenType::Error(_) => false,
+        }
+    }
+
+    /// Insert pasted text into the focused text field, if there is one
+    pub fn paste(&mut self, text: &str) {
+        match self {
+            ScreenType::TokenInput(screen) => screen.paste(text),
+            ScreenType::Main(screen) => screen.paste(text),
+            ScreenType::AccountSelect(screen) => screen.paste(text),
+            ScreenType::Splash(_) | ScreenType::Error(_) => {}
+        }
+    }
 }
 
 /// Implement Screen trait for ScreenType
 impl Screen for ScreenType {
     async fn handle_key(self, key: crossterm::event::KeyEvent, app: &mut App) -> anyhow::Result<ScreenType> {
         match self {
+            ScreenType::Splash(screen) => screen.handle_key(key, app).await,
             ScreenType::TokenInput(screen) => screen.handle_key(key, app).await,
             ScreenType::Main(screen) => screen.handle_key(key, app).await,
+            ScreenType::AccountSelect(screen) => screen.handle_key(key, app).await,
+            ScreenType::Error(screen) => screen.handle_key(key, app).await,
         }
     }
     
+    fn on_tick(self, app: &mut App) -> ScreenType {
+        match self {
+            ScreenType::Splash(screen) => screen.on_tick(app),
+            ScreenType::Main(screen) => screen.on_tick(app),
+            ScreenType::AccountSelect(screen) => screen.on_tick(app),
+            screen => screen,
+        }
+    }
+
     fn render(&self, f: &mut Frame<'_>, app: &App) {
         match self {
+            ScreenType::Splash(screen) => screen.render(f, app),
             ScreenType::TokenInput(screen) => screen.render(f, app),
             ScreenType::Main(screen) => screen.render(f, app),
+            ScreenType::AccountSelect(screen) => screen.render(f, app),
+            ScreenType::Error(screen) => screen.render(f, app),
         }
     }
 }
 
+pub mod account_select;
+pub mod error;
 pub mod main_screen;
+pub mod splash;
 pub mod token_input;
 
+pub use account_select::AccountSelectScreen;
+pub use error::ErrorScreen;
 pub use main_screen::MainScreen;
+pub use splash::SplashScreen;
 pub use token_input::TokenInputScreen;
\ No newline at end of file
@@ -0,0 +1,121 @@
+use crossterm::event::KeyEvent;
+use ratatui::{Frame, layout::{Alignment, Constraint, Direction, Layout}, widgets::{Block, Borders, Paragraph}};
+use anyhow::Result;
+use std::{sync::{Arc, Mutex}, time::Duration};
+use crate::{mcp::{describe_error, McpClient}, ui::{Action, App, MainScreen, ScreenType, ToastKind, TokenInputScreen}};
+
+/// How long the startup token check may take before the TUI opens without it
+const VALIDATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Drawn while the saved token is loaded and validated in the background,
+/// so the first frame does not wait for the network
+#[derive(Clone)]
+pub struct SplashScreen {
+    startup: Arc<Mutex<Startup>>,
+}
+
+/// Progress of the background startup, shared with its task
+#[derive(Default)]
+struct Startup {
+    /// Stage being worked on, shown under the spinner
+    status: String,
+    outcome: Option<Outcome>,
+}
+
+enum Outcome {
+    /// Token accepted; with a warning when it could not be checked
+    Ready(McpClient, Option<String>),
+    /// The client could not be built or upstream rejected the token
+    NeedsToken(String),
+}
+
+impl SplashScreen {
+    /// Start loading the saved token against `url`. Must be called inside a tokio runtime.
+    pub fn start(token: String, url: Option<String>) -> Self {
+        let startup = Arc::new(Mutex::new(Startup::default()));
+        let shared = startup.clone();
+        let report = move |status: &str, outcome: Option<Outcome>| {
+            let mut startup = shared.lock().unwrap_or_else(|e| e.into_inner());
+            startup.status = status.to_string();
+            startup.outcome = outcome;
+        };
+        tokio::spawn(async move {
+            report("正在初始化客户端...", None);
+            let client = match McpClient::with_url(token, url.as_deref()) {
+                Ok(client) => client,
+                Err(e) => return report("", Some(Outcome::NeedsToken(format!("加载Token失败: {}", e)))),
+            };
+            report("正在验证 Token...", None);
+            let outcome = match tokio::time::timeout(VALIDATE_TIMEOUT, client.validate_token()).await {
+                Ok(Ok(true)) => Outcome::Ready(client, None),
+                Ok(Ok(false)) => Outcome::NeedsToken("已保存的 Token 已失效，请重新输入".to_string()),
+                Ok(Err(e)) => Outcome::Ready(client, Some(format!("暂时无法验证 Token: {}", describe_error(&e)))),
+                Err(_) => Outcome::Ready(client, Some(format!("{} 秒内未能验证 Token，可稍后刷新重试", VALIDATE_TIMEOUT.as_secs()))),
+            };
+            report("", Some(outcome));
+        });
+        Self { startup }
+    }
+
+    /// Keys wait until startup is done; quitting is handled by the app loop
+    pub async fn handle_key(self, _key: KeyEvent, _app: &mut App) -> Result<ScreenType> {
+        Ok(ScreenType::Splash(self))
+    }
+
+    /// Move on once the background startup has finished
+    pub fn on_tick(self, app: &mut App) -> ScreenType {
+        let outcome = self.startup.lock().unwrap_or_else(|e| e.into_inner()).outcome.take();
+        match outcome {
+            None => ScreenType::Splash(self),
+            Some(Outcome::Ready(client, warning)) => {
+                app.mcp_client = Some(Arc::new(client));
+                app.add_log("已加载保存的Token".to_string());
+                if let Some(warning) = warning {
+                    app.notify(ToastKind::Error, warning);
+                }
+                ScreenType::Main(Box::new(MainScreen::new()))
+            }
+            Some(Outcome::NeedsToken(message)) => {
+                app.add_log(message.clone());
+                let mut screen = TokenInputScreen::new();
+                screen.error_message = Some(message);
+                ScreenType::TokenInput(screen)
+            }
+        }
+    }
+
+    /// Render the splash screen
+    pub fn render(&self, f: &mut Frame<'_>, app: &App) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Percentage(40),
+                    Constraint::Length(3),
+                    Constraint::Length(1),
+                    Constraint::Min(0),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+
+        let title = Paragraph::new("麦当劳优惠券自动领取工具")
+            .block(Block::default().borders(Borders::ALL))
+            .style(app.theme.accent)
+            .alignment(Alignment::Center);
+        f.render_widget(title, layout[1]);
+
+        let status = self.startup.lock().unwrap_or_else(|e| e.into_inner()).status.clone();
+        let status = if status.is_empty() { "正在启动...".to_string() } else { status };
+        let status = Paragraph::new(format!("{} {}", app.spinner(), status))
+            .style(app.theme.text)
+            .alignment(Alignment::Center);
+        f.render_widget(status, layout[2]);
+
+        let help = Paragraph::new(format!("按 {} 退出", app.keymap.key(Action::Quit)))
+            .style(app.theme.hint)
+            .alignment(Alignment::Center);
+        f.render_widget(help, layout[4]);
+    }
+}
@@ -1,54 +1,80 @@
-use ratatui::{widgets::*, style::*, layout::*};
+use ratatui::{widgets::*, layout::*};
 use ratatui::{Frame, backend::Backend};
-use crate::ui::{app::App, screens::ScreenType};
+use crate::ui::{app::App, screens::ScreenType, text_input::TextInput, ToastKind};
 use anyhow::Result;
-use crate::config::Config;
+use crate::{audit::{self, Action as AuditAction}, config::{ConfigService, TokenHistoryEntry}, utils::mask_token};
 
 /// Token input screen
 #[derive(Clone)]
 pub struct TokenInputScreen {
-    pub input: String,
+    pub input: TextInput,
     pub error_message: Option<String>,
+    /// Recently validated tokens, selectable with ↑/↓ instead of pasting again
+    history: Vec<TokenHistoryEntry>,
+    selected: Option<usize>,
 }
 
 impl TokenInputScreen {
     /// Create a new token input screen
     pub fn new() -> Self {
         Self {
-            input: String::new(),
+            input: TextInput::default(),
             error_message: None,
+            history: ConfigService::global().current().token_history.clone(),
+            selected: None,
         }
     }
     
+    /// Insert a pasted token, leaving any selected history entry
+    pub fn paste(&mut self, text: &str) {
+        self.input.insert_str(text.trim());
+        self.selected = None;
+    }
+
     /// Handle keyboard input
     pub async fn handle_key(mut self, key: crossterm::event::KeyEvent, app: &mut App) -> Result<ScreenType> {
         match key.code {
-            crossterm::event::KeyCode::Char(c) => {
-                self.input.push(c);
+            crossterm::event::KeyCode::Up | crossterm::event::KeyCode::Down if !self.history.is_empty() => {
+                let last = self.history.len() - 1;
+                self.selected = match (key.code, self.selected) {
+                    (crossterm::event::KeyCode::Up, None | Some(0)) => None,
+                    (crossterm::event::KeyCode::Up, Some(i)) => Some(i - 1),
+                    (_, None) => Some(0),
+                    (_, Some(i)) => Some((i + 1).min(last)),
+                };
                 Ok(ScreenType::TokenInput(self))
             },
-            crossterm::event::KeyCode::Backspace => {
-                self.input.pop();
+            _ if self.input.handle_key(&key) => {
+                // Typing switches back from a history entry to the input field
+                self.selected = None;
                 Ok(ScreenType::TokenInput(self))
             },
+            crossterm::event::KeyCode::Tab => {
+                Ok(ScreenType::AccountSelect(Box::new(crate::ui::screens::AccountSelectScreen::new())))
+            },
             crossterm::event::KeyCode::Enter => {
-                // Validate input
-                if self.input.is_empty() {
+                // A selected history entry takes the place of typed input
+                let input = match self.selected.and_then(|i| self.history.get(i)) {
+                    Some(entry) => entry.token.as_str(),
+                    None => self.input.value(),
+                };
+                if input.is_empty() {
                     self.error_message = Some("Token不能为空".to_string());
                     return Ok(ScreenType::TokenInput(self));
                 }
                 
                 // Format token with Bearer prefix if needed
-                let formatted_token = if self.input.starts_with("Bearer ") {
-                    self.input.to_string()
+                let formatted_token = if input.starts_with("Bearer ") {
+                    input.to_string()
                 } else {
-                    format!("Bearer {}", self.input)
+                    format!("Bearer {}", input)
                 };
                 
                 // Validate token
                 app.set_loading(true, 50);
                 
-                let client = crate::mcp::McpClient::new(formatted_token.clone())?;
+                let url = ConfigService::global().current().upstream_url().map(str::to_string);
+                let client = crate::mcp::McpClient::with_url(formatted_token.clone(), url.as_deref())?;
                 let validation_result = client.validate_token().await;
                 
                 app.set_loading(false, 0);
@@ -56,17 +82,19 @@ impl TokenInputScreen {
                 match validation_result {
                     Ok(true) => {
                         // Save token to config
-                        let mut config = Config::load()?;
-                        config.token = formatted_token.clone();
-                        config.save()?;
+                        ConfigService::global().update(|config| {
+                            config.set_token(formatted_token.clone());
+                            config.remember_token(&formatted_token, None);
+                        })?;
+                        audit::record("tui", AuditAction::TokenSet, true, mask_token(&formatted_token));
 
                         // Initialize MCP client
-                        app.init_mcp_client(formatted_token)?;
-                        app.add_log("Token验证成功！".to_string());
+                        app.init_mcp_client(formatted_token, url.as_deref())?;
+                        app.notify(ToastKind::Success, "Token验证成功！".to_string());
                         app.add_log("配置已保存到当前目录".to_string());
 
                         // Switch to main screen
-                        Ok(ScreenType::Main(crate::ui::screens::MainScreen::new()))
+                        Ok(ScreenType::Main(Box::new(crate::ui::screens::MainScreen::new())))
                     }
                     Ok(false) => {
                         self.error_message = Some("Token无效，请重新输入".to_string());
@@ -79,9 +107,9 @@ impl TokenInputScreen {
                 }
             },
             crossterm::event::KeyCode::Esc => {
-                // Exit application
-                // app.running is not available, we'll exit through the main loop
-                std::process::exit(0);
+                // Exit through the main loop so the terminal is restored
+                app.should_quit = true;
+                Ok(ScreenType::TokenInput(self))
             },
             _ => Ok(ScreenType::TokenInput(self)),
         }
@@ -90,6 +118,7 @@ impl TokenInputScreen {
     /// Render the token input screen
     pub fn render(&self, f: &mut Frame<'_>, app: &App) {
         let size = f.size();
+        let history_height = if self.history.is_empty() { 0 } else { self.history.len() as u16 + 2 };
         
         // Create vertical layout
         let layout = Layout::default()
@@ -99,6 +128,7 @@ impl TokenInputScreen {
                     Constraint::Length(3),
                     Constraint::Length(1),
                     Constraint::Length(3),
+                    Constraint::Length(history_height),
                     Constraint::Length(1),
                     Constraint::Length(3),
                     Constraint::Min(0),
@@ -118,36 +148,61 @@ impl TokenInputScreen {
             .alignment(ratatui::layout::Alignment::Center);
         f.render_widget(subtitle, layout[1]);
         
-        // Token input field
-        let input_field = Paragraph::new(self.input.as_str())
+        // Token input field, scrolled so the cursor stays visible
+        let (scroll, cursor_x) = self.input.viewport(layout[2].width.saturating_sub(2));
+        let input_field = Paragraph::new(self.input.value())
             .block(Block::default()
                 .borders(Borders::ALL)
                 .title("MCP Token")
-                .style(Style::default().fg(Color::Cyan)))
-            .style(Style::default().fg(Color::White));
+                .style(app.theme.accent))
+            .style(app.theme.text)
+            .scroll((0, scroll));
         f.render_widget(input_field, layout[2]);
+        if self.selected.is_none() {
+            f.set_cursor(layout[2].x + 1 + cursor_x, layout[2].y + 1);
+        }
+
+        // Recently used tokens
+        if !self.history.is_empty() {
+            let items: Vec<ListItem> = self.history.iter()
+                .map(|entry| ListItem::new(format!(
+                    "{}  {}  上次有效: {}",
+                    entry.label(),
+                    mask_token(&entry.token),
+                    entry.last_valid
+                )))
+                .collect();
+            let history = List::new(items)
+                .block(Block::default()
+                    .borders(Borders::ALL)
+                    .title("最近使用的 Token (↑/↓ 选择)"))
+                .highlight_style(app.theme.highlight);
+            let mut list_state = ListState::default();
+            list_state.select(self.selected);
+            f.render_stateful_widget(history, layout[3], &mut list_state);
+        }
         
         // Error message
         if let Some(ref error) = self.error_message {
             let error_widget = Paragraph::new(error.as_str())
                 .block(Block::default().borders(Borders::NONE))
-                .style(Style::default().fg(Color::Red))
+                .style(app.theme.error)
                 .alignment(ratatui::layout::Alignment::Center);
-            f.render_widget(error_widget, layout[3]);
+            f.render_widget(error_widget, layout[4]);
         }
         
         // Help text
-        let help_text = Paragraph::new("按 Enter 确认，Esc 退出")
+        let help_text = Paragraph::new("按 Enter 确认，Tab 选择已保存账号，Esc 退出 | ←/→ Home/End 移动光标，Ctrl+←/→ 按词跳转，Ctrl+U 清空")
             .block(Block::default().borders(Borders::NONE))
-            .style(Style::default().fg(Color::Yellow))
+            .style(app.theme.hint)
             .alignment(ratatui::layout::Alignment::Center);
-        f.render_widget(help_text, layout[4]);
+        f.render_widget(help_text, layout[5]);
         
         // Logs area
         let logs_title = Paragraph::new("日志信息")
             .block(Block::default().borders(Borders::ALL))
-            .style(Style::default().fg(Color::Green))
+            .style(app.theme.success)
             .alignment(ratatui::layout::Alignment::Center);
-        f.render_widget(logs_title, layout[5]);
+        f.render_widget(logs_title, layout[6]);
     }
 }
\ No newline at end of file
@@ -0,0 +1,264 @@
+use anyhow::Result;
+use std::{cell::Cell, sync::{Arc, Mutex}};
+use crossterm::event::{KeyCode, KeyEvent};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use ratatui::{Frame, layout::{Alignment, Constraint, Direction, Layout, Rect}, style::{Color, Style}, widgets::{List, ListItem, ListState, Paragraph, Wrap}};
+
+use crate::{audit::{self, Action as AuditAction}, coupons::{parse_coupons_from_markdown, Coupon, UsageStore}, error::McdError, images, mcp::describe_error, ui::{list_nav::window_offset, Action, App, ListNav}, utils::truncate_string};
+
+use super::tab_block;
+
+/// Claimed coupons, with used marks
+#[derive(Clone, Default)]
+pub struct CouponsTab {
+    pub coupons: Vec<Coupon>,
+    /// Error from the last load, shown in place of the list
+    pub error: Option<String>,
+    /// Whether a load has been attempted, so the first visit fetches the list
+    pub loaded: bool,
+    pub hide_used: bool,
+    /// Whether the detail pane of the selected coupon is shown below the list
+    pub show_detail: bool,
+    pub selected: usize,
+    /// First row drawn last frame; only the rows from here that fit are built
+    offset: Cell<usize>,
+    /// A load is running in the background
+    fetching: bool,
+    /// Answer of the background load, picked up on tick
+    fetched: Arc<Mutex<Option<Result<String, McdError>>>>,
+}
+
+impl CouponsTab {
+    /// Start loading the user's coupons in the background; the answer is applied on tick
+    pub fn load(&mut self, app: &mut App) {
+        let Some(client) = app.mcp_client.clone() else {
+            return;
+        };
+        self.loaded = true;
+        if self.fetching {
+            return;
+        }
+        self.fetching = true;
+        app.set_loading(true, 0);
+        app.add_log("正在加载已领取的优惠券...".to_string());
+
+        let slot = self.fetched.clone();
+        tokio::spawn(async move {
+            let result = client.get_my_coupons().await;
+            *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+        });
+    }
+
+    /// Apply a finished load. A rejected token is returned as an error so the app can ask for a new one.
+    pub fn on_tick(&mut self, app: &mut App) -> Result<(), McdError> {
+        if !self.fetching {
+            return Ok(());
+        }
+        let Some(result) = self.fetched.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return Ok(());
+        };
+        self.fetching = false;
+        app.set_loading(false, 100);
+
+        match result {
+            Ok(coupons_text) => self.show(&coupons_text, app),
+            Err(McdError::TokenInvalid) => return Err(McdError::TokenInvalid),
+            Err(e) => {
+                let message = format!("加载失败: {}", describe_error(&e));
+                app.add_log(message.clone());
+                self.coupons.clear();
+                self.error = Some(message);
+            },
+        }
+        Ok(())
+    }
+
+    /// Replace the list with the coupons in an upstream answer
+    pub fn show(&mut self, coupons_text: &str, app: &mut App) {
+        self.coupons = parse_coupons_from_markdown(coupons_text);
+        images::prefetch(&self.coupons);
+        self.error = None;
+        self.loaded = true;
+        self.selected = 0;
+        match UsageStore::load() {
+            Ok(store) => store.apply(&mut self.coupons),
+            Err(e) => app.add_log(format!("读取使用记录失败: {}", e)),
+        }
+        app.add_log(format!("已加载优惠券列表 (共 {} 张)", self.coupons.len()));
+    }
+
+    /// Handle a key; returns false when the key is not for this tab
+    pub fn handle_key(&mut self, key: &KeyEvent, app: &mut App) -> bool {
+        if let Some(nav) = ListNav::from_key(key) {
+            self.selected = nav.apply(self.selected, self.visible().len());
+            return true;
+        }
+        match key.code {
+            KeyCode::Char('u') | KeyCode::Char('U') => self.toggle_selected_used(app),
+            KeyCode::Char('h') | KeyCode::Char('H') => {
+                self.hide_used = !self.hide_used;
+                self.selected = 0;
+            },
+            KeyCode::Enter => self.show_detail = !self.show_detail,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Coupons shown in the list, honoring the hide-used filter
+    fn visible(&self) -> Vec<&Coupon> {
+        self.coupons.iter()
+            .filter(|c| !(self.hide_used && c.used))
+            .collect()
+    }
+
+    /// Toggle the persisted used flag of the selected coupon
+    fn toggle_selected_used(&mut self, app: &mut App) {
+        let Some(coupon) = self.visible().get(self.selected).map(|c| (*c).clone()) else {
+            return;
+        };
+
+        let mut store = match UsageStore::load() {
+            Ok(store) => store,
+            Err(e) => {
+                app.add_log(format!("读取使用记录失败: {}", e));
+                return;
+            },
+        };
+        let used = store.toggle(&coupon);
+        if let Err(e) = store.save() {
+            app.add_log(format!("保存使用记录失败: {}", e));
+            return;
+        }
+        store.apply(&mut self.coupons);
+
+        let message = if used {
+            format!("已标记为已使用: {}", coupon.title)
+        } else {
+            format!("已取消使用标记: {}", coupon.title)
+        };
+        audit::record("tui", AuditAction::MarkUsed, true, message.clone());
+        app.add_log(message);
+        let visible = self.visible().len();
+        if self.selected >= visible {
+            self.selected = visible.saturating_sub(1);
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame<'_>, area: Rect, app: &App) {
+        if let Some(error) = &self.error {
+            let error = Paragraph::new(error.as_str())
+                .block(tab_block("我的优惠券", &app.theme))
+                .style(app.theme.error);
+            f.render_widget(error, area);
+            return;
+        }
+        if !self.loaded {
+            let hint = Paragraph::new(format!("按 '{}' 加载已领取的优惠券", app.keymap.key(Action::Refresh)))
+                .block(tab_block("我的优惠券", &app.theme))
+                .alignment(Alignment::Center);
+            f.render_widget(hint, area);
+            return;
+        }
+
+        let visible = self.visible();
+        let area = match visible.get(self.selected).filter(|_| self.show_detail) {
+            Some(coupon) => {
+                let lines = detail_lines(coupon);
+                let qr = coupon.code.as_deref().and_then(code_qr);
+                // Text, the QR code and the borders; the list keeps at least three rows
+                let height = lines.len() + qr.as_ref().map_or(0, |qr| qr.lines().count()) + 2;
+                let layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(3), Constraint::Length(height as u16)])
+                    .split(area);
+                render_detail(f, layout[1], &lines, qr.as_deref(), app);
+                layout[0]
+            },
+            None => area,
+        };
+
+        // Inside the borders, so long titles end in "…" instead of being clipped mid-column
+        let width = area.width.saturating_sub(2) as usize;
+        let height = area.height.saturating_sub(2).max(1) as usize;
+        let offset = window_offset(self.offset.get(), self.selected, height, visible.len());
+        self.offset.set(offset);
+        let items: Vec<ListItem> = visible.iter()
+            .skip(offset)
+            .take(height)
+            .map(|coupon| {
+                let mark = if coupon.used { "✔" } else { " " };
+                let mut line = format!("{} {}{} | {} | {}", mark, coupon.title, coupon.count_badge(), coupon.price, coupon.expiry);
+                if let Some(calories) = coupon.calories_label() {
+                    line.push_str(&format!(" | {}", calories));
+                }
+                let item = ListItem::new(truncate_string(&line, width));
+                if coupon.used {
+                    item.style(app.theme.muted)
+                } else {
+                    item
+                }
+            })
+            .collect();
+
+        let title = if self.hide_used {
+            "我的优惠券 - 已隐藏已使用 (u 标记已使用, h 显示全部, Enter 详情)"
+        } else {
+            "我的优惠券 (u 标记已使用, h 隐藏已使用, Enter 详情)"
+        };
+        let list = List::new(items)
+            .block(tab_block(title, &app.theme))
+            .highlight_style(app.theme.highlight);
+        let mut state = ListState::default().with_selected(Some(self.selected - offset));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// Full fields of one coupon, with its redemption code and the links that open it in the McDonald's app
+fn detail_lines(coupon: &Coupon) -> Vec<String> {
+    let mut lines = vec![
+        format!("{}{}", coupon.title, coupon.count_badge()),
+        format!("价格: {}    有效期: {}", coupon.price, coupon.expiry),
+    ];
+    if !coupon.tags.is_empty() {
+        lines.push(format!("标签: {}", coupon.tags));
+    }
+    if let Some(calories) = coupon.calories_label() {
+        lines.push(format!("热量: {}", calories));
+    }
+    if let Some(code) = &coupon.code {
+        lines.push(format!("券码: {}", code));
+    }
+    match (&coupon.app_link, &coupon.universal_link) {
+        (None, None) => lines.push("未配置 App 跳转链接 (deep_links)".to_string()),
+        (app_link, universal_link) => {
+            lines.extend(app_link.iter().map(|link| format!("App 链接: {}", link)));
+            lines.extend(universal_link.iter().map(|link| format!("通用链接: {}", link)));
+        },
+    }
+    lines
+}
+
+/// Redemption code as a QR code of half-height block characters, two modules per row
+fn code_qr(code: &str) -> Option<String> {
+    let qr = QrCode::new(code.as_bytes()).ok()?;
+    Some(qr.render::<Dense1x2>().build())
+}
+
+fn render_detail(f: &mut Frame<'_>, area: Rect, lines: &[String], qr: Option<&str>, app: &App) {
+    let block = tab_block("优惠券详情 (Enter 关闭)", &app.theme);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(lines.len() as u16), Constraint::Min(0)])
+        .split(inner);
+    f.render_widget(Paragraph::new(lines.join("\n")).wrap(Wrap { trim: false }), layout[0]);
+
+    if let Some(qr) = qr {
+        // Black on white whatever the terminal colors, so scanners read it the right way round
+        let width = qr.lines().next().map_or(0, |line| line.chars().count()) as u16;
+        let area = Rect { width: width.min(layout[1].width), ..layout[1] };
+        f.render_widget(Paragraph::new(qr).style(Style::default().fg(Color::Black).bg(Color::White)), area);
+    }
+}
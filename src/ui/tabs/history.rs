@@ -0,0 +1,78 @@
+use crossterm::event::KeyEvent;
+use ratatui::{Frame, layout::Rect, widgets::{List, ListItem, ListState, Paragraph}};
+
+use crate::{coupons::{ClaimEntry, ClaimHistory}, ui::{App, ListNav}};
+
+use super::tab_block;
+
+/// Past claim attempts from `claim-history.json`, newest first
+#[derive(Clone, Default)]
+pub struct HistoryTab {
+    entries: Vec<ClaimEntry>,
+    error: Option<String>,
+    selected: usize,
+}
+
+impl HistoryTab {
+    /// Re-read the history file, which other modes and the scheduler also write to
+    pub fn reload(&mut self) {
+        match ClaimHistory::load() {
+            Ok(history) => {
+                self.entries = history.query(None, None, None);
+                self.error = None;
+            },
+            Err(e) => {
+                self.entries.clear();
+                self.error = Some(format!("读取领取记录失败: {:#}", e));
+            },
+        }
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    /// Handle a key; returns false when the key is not for this tab
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        match ListNav::from_key(key) {
+            Some(nav) => {
+                self.selected = nav.apply(self.selected, self.entries.len());
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame<'_>, area: Rect, app: &App) {
+        if let Some(error) = &self.error {
+            let error = Paragraph::new(error.as_str())
+                .block(tab_block("领取记录", &app.theme))
+                .style(app.theme.error);
+            f.render_widget(error, area);
+            return;
+        }
+        if self.entries.is_empty() {
+            f.render_widget(Paragraph::new("暂无领取记录").block(tab_block("领取记录", &app.theme)), area);
+            return;
+        }
+
+        let items: Vec<ListItem> = self.entries.iter()
+            .map(|entry| {
+                let account = entry.account.as_deref().map(|a| format!(" [{}]", a)).unwrap_or_default();
+                let detail = if entry.coupons.is_empty() {
+                    entry.message.clone()
+                } else {
+                    format!("{} ({})", entry.message, entry.coupons.join("、"))
+                };
+                let item = ListItem::new(format!("{} {:<8}{} {}", entry.at, entry.source, account, detail));
+                if entry.success {
+                    item
+                } else {
+                    item.style(app.theme.error)
+                }
+            })
+            .collect();
+        let list = List::new(items)
+            .block(tab_block("领取记录", &app.theme))
+            .highlight_style(app.theme.highlight);
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
@@ -0,0 +1,40 @@
+use crossterm::event::KeyEvent;
+use ratatui::{Frame, layout::Rect, widgets::{List, ListItem, ListState}};
+
+use crate::{ui::{App, ListNav}, utils::truncate_string};
+
+use super::tab_block;
+
+/// Operation log, newest first
+#[derive(Clone, Default)]
+pub struct LogsTab {
+    /// Selected line, counted from the newest
+    pub selected: usize,
+}
+
+impl LogsTab {
+    /// Handle a key; returns false when the key is not for this tab
+    pub fn handle_key(&mut self, key: &KeyEvent, app: &App) -> bool {
+        match ListNav::from_key(key) {
+            Some(nav) => {
+                self.selected = nav.apply(self.selected, app.logs.len());
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame<'_>, area: Rect, app: &App) {
+        let width = area.width.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = app.logs.iter()
+            .rev()
+            .map(|log| ListItem::new(truncate_string(log, width)))
+            .collect();
+        let list = List::new(items)
+            .block(tab_block("操作日志", &app.theme))
+            .highlight_style(app.theme.highlight);
+        let mut state = ListState::default()
+            .with_selected(Some(self.selected.min(app.logs.len().saturating_sub(1))));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
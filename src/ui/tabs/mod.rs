@@ -0,0 +1,63 @@
+use ratatui::widgets::{Block, Borders};
+
+use crate::ui::Theme;
+
+pub mod coupons;
+pub mod history;
+pub mod logs;
+pub mod settings;
+
+pub use coupons::CouponsTab;
+pub use history::HistoryTab;
+pub use logs::LogsTab;
+pub use settings::SettingsTab;
+
+/// Tabs of the main screen, switched with Tab/Shift-Tab or the number keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Coupons,
+    Logs,
+    History,
+    Settings,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 4] = [Tab::Coupons, Tab::Logs, Tab::History, Tab::Settings];
+
+    /// Label in the tab bar
+    pub fn title(self) -> &'static str {
+        match self {
+            Tab::Coupons => "优惠券",
+            Tab::Logs => "操作日志",
+            Tab::History => "领取记录",
+            Tab::Settings => "设置",
+        }
+    }
+
+    /// Position in the tab bar
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|t| *t == self).unwrap_or(0)
+    }
+
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    /// Tab for the number key `digit`, counting from 1
+    pub fn from_digit(digit: char) -> Option<Self> {
+        let index = digit.to_digit(10)?.checked_sub(1)?;
+        Self::ALL.get(index as usize).copied()
+    }
+}
+
+/// Bordered block around a tab's content
+fn tab_block<'a>(title: &'a str, theme: &Theme) -> Block<'a> {
+    Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(theme.frame)
+}
@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{Frame, layout::{Constraint, Direction, Layout, Rect}, widgets::{Block, Borders, List, ListItem, ListState, Paragraph}};
+use std::sync::Arc;
+
+use crate::{audit::{self, Action as AuditAction}, config::{Config, ConfigService, ScheduleConfig}, scheduler, ui::{App, ListNav, screens::{AccountSelectScreen, ScreenType, TokenInputScreen}, text_input::TextInput, Theme, ToastKind}};
+
+use super::tab_block;
+
+/// Editable config option
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    ReleaseTimes,
+    DelaySeconds,
+    WatchInterval,
+    WebPort,
+    McpServerPort,
+    UpstreamUrl,
+    AccessLog,
+    KeepDuplicates,
+    ShowCalories,
+    Theme,
+}
+
+/// Row of the settings list
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Item {
+    Field(Field),
+    ResetToken,
+    SwitchAccount,
+}
+
+const ITEMS: [Item; 12] = [
+    Item::Field(Field::ReleaseTimes),
+    Item::Field(Field::DelaySeconds),
+    Item::Field(Field::WatchInterval),
+    Item::Field(Field::WebPort),
+    Item::Field(Field::McpServerPort),
+    Item::Field(Field::UpstreamUrl),
+    Item::Field(Field::AccessLog),
+    Item::Field(Field::KeepDuplicates),
+    Item::Field(Field::ShowCalories),
+    Item::Field(Field::Theme),
+    Item::ResetToken,
+    Item::SwitchAccount,
+];
+
+impl Field {
+    pub const ALL: [Field; 10] = [
+        Field::ReleaseTimes,
+        Field::DelaySeconds,
+        Field::WatchInterval,
+        Field::WebPort,
+        Field::McpServerPort,
+        Field::UpstreamUrl,
+        Field::AccessLog,
+        Field::KeepDuplicates,
+        Field::ShowCalories,
+        Field::Theme,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::ReleaseTimes => "定时领取时间",
+            Field::DelaySeconds => "开抢后延迟(秒)",
+            Field::WatchInterval => "监控间隔(分钟)",
+            Field::WebPort => "网页端口",
+            Field::McpServerPort => "MCP 服务器端口",
+            Field::UpstreamUrl => "上游地址",
+            Field::AccessLog => "访问日志",
+            Field::KeepDuplicates => "显示重复优惠券",
+            Field::ShowCalories => "估算热量",
+            Field::Theme => "配色",
+        }
+    }
+
+    /// Input hint shown while editing
+    pub fn hint(self) -> &'static str {
+        match self {
+            Field::ReleaseTimes => "服务器时间 HH:MM，多个用逗号分隔，留空关闭",
+            Field::WebPort => "留空使用默认 8080",
+            Field::McpServerPort => "留空使用默认 8080",
+            Field::UpstreamUrl => "留空使用默认地址",
+            _ => "",
+        }
+    }
+
+    pub fn is_toggle(self) -> bool {
+        matches!(self, Field::AccessLog | Field::KeepDuplicates | Field::ShowCalories | Field::Theme)
+    }
+
+    /// Current value as edited in the text field
+    pub fn value(self, config: &Config) -> String {
+        let schedule = config.schedule.clone().unwrap_or_default();
+        let on_off = |on: bool| if on { "开启" } else { "关闭" }.to_string();
+        match self {
+            Field::ReleaseTimes => schedule.release_times.join(","),
+            Field::DelaySeconds => schedule.delay_seconds.to_string(),
+            Field::WatchInterval => config.watch.interval_minutes.to_string(),
+            Field::WebPort => config.web_port.map(|p| p.to_string()).unwrap_or_default(),
+            Field::McpServerPort => config.mcp_server_port.map(|p| p.to_string()).unwrap_or_default(),
+            Field::UpstreamUrl => config.mcp_server_url.clone().unwrap_or_default(),
+            Field::AccessLog => on_off(config.access_log),
+            Field::KeepDuplicates => on_off(config.keep_duplicate_coupons),
+            Field::ShowCalories => on_off(config.show_calories),
+            Field::Theme => config.theme.label().to_string(),
+        }
+    }
+
+    /// Store `text` in the config, rejecting values the modes would refuse at startup
+    pub fn apply(self, config: &mut Config, text: &str) -> Result<()> {
+        let text = text.trim();
+        let number = |text: &str| text.parse::<u64>().map_err(|_| anyhow!("请输入非负整数"));
+        let port = |text: &str| match text {
+            "" => Ok(None),
+            text => text.parse::<u16>().ok().filter(|p| *p > 0).map(Some).ok_or_else(|| anyhow!("端口应为 1-65535")),
+        };
+        match self {
+            Field::ReleaseTimes => {
+                let times: Vec<String> = text.split([',', '，', ' '])
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                config.schedule.get_or_insert_with(ScheduleConfig::default).release_times = times;
+                scheduler::check(config)?;
+            },
+            Field::DelaySeconds => config.schedule.get_or_insert_with(ScheduleConfig::default).delay_seconds = number(text)?,
+            Field::WatchInterval => match number(text)? {
+                0 => return Err(anyhow!("监控间隔至少为 1 分钟")),
+                minutes => config.watch.interval_minutes = minutes,
+            },
+            Field::WebPort => config.web_port = port(text)?,
+            Field::McpServerPort => config.mcp_server_port = port(text)?,
+            Field::UpstreamUrl => {
+                config.mcp_server_url = Some(text.to_string()).filter(|t| !t.is_empty());
+                config.check_upstream_urls()?;
+            },
+            Field::AccessLog => config.access_log = !config.access_log,
+            Field::KeepDuplicates => config.keep_duplicate_coupons = !config.keep_duplicate_coupons,
+            Field::ShowCalories => config.show_calories = !config.show_calories,
+            Field::Theme => config.theme = config.theme.next(),
+        }
+        // An emptied schedule is dropped rather than saved as a stub
+        if config.schedule.as_ref() == Some(&ScheduleConfig::default()) {
+            config.schedule = None;
+        }
+        Ok(())
+    }
+}
+
+/// Config editor and account actions
+#[derive(Clone, Default)]
+pub struct SettingsTab {
+    config: Arc<Config>,
+    selected: usize,
+    /// Field being edited and its text
+    editing: Option<(Field, TextInput)>,
+    /// Result of the last save, and whether it failed
+    message: Option<(String, bool)>,
+}
+
+impl SettingsTab {
+    /// Re-read the config so changes from other modes show up
+    pub fn reload(&mut self) {
+        self.config = ConfigService::global().current();
+        self.editing = None;
+    }
+
+    /// Whether a text field has focus, so all keys go to it
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    /// Insert pasted text into the field being edited
+    pub fn paste(&mut self, text: &str) {
+        if let Some((_, input)) = &mut self.editing {
+            input.insert_str(text);
+        }
+    }
+
+    /// Handle a key, returning the screen to switch to when an account action is chosen
+    pub fn handle_key(&mut self, key: &KeyEvent, app: &mut App) -> Option<ScreenType> {
+        if let Some((field, input)) = &mut self.editing {
+            match key.code {
+                KeyCode::Esc => self.editing = None,
+                KeyCode::Enter => {
+                    let (field, text) = (*field, input.value().to_string());
+                    if self.save(field, &text, app) {
+                        self.editing = None;
+                    }
+                },
+                _ => {
+                    input.handle_key(key);
+                },
+            }
+            return None;
+        }
+
+        if let Some(nav) = ListNav::from_key(key) {
+            self.selected = nav.apply(self.selected, ITEMS.len());
+            return None;
+        }
+        if key.code != KeyCode::Enter {
+            return None;
+        }
+        match ITEMS[self.selected] {
+            Item::Field(field) if field.is_toggle() => {
+                self.save(field, "", app);
+                None
+            },
+            Item::Field(field) => {
+                self.editing = Some((field, TextInput::with_value(&field.value(&self.config))));
+                self.message = None;
+                None
+            },
+            Item::ResetToken => Some(reset_token(app)),
+            Item::SwitchAccount => Some(ScreenType::AccountSelect(Box::new(AccountSelectScreen::new()))),
+        }
+    }
+
+    /// Apply a value to the current config and save it. Returns false when the value was rejected.
+    fn save(&mut self, field: Field, text: &str, app: &mut App) -> bool {
+        match ConfigService::global().try_update(|config| field.apply(config, text)) {
+            Ok(config) => {
+                let value = field.value(&config);
+                audit::record("tui", AuditAction::ConfigChange, true, format!("{}: {}", field.label(), if value.is_empty() { "默认" } else { &value }));
+                app.notify(ToastKind::Info, format!("已修改设置 {}: {}", field.label(), if value.is_empty() { "默认" } else { &value }));
+                self.message = Some(("已保存，正在运行的网页或 MCP 服务器模式需重启后生效".to_string(), false));
+                if field == Field::Theme {
+                    app.theme = Theme::new(config.theme, app.color);
+                }
+                self.config = config;
+                true
+            },
+            Err(e) => {
+                self.message = Some((format!("{:#}", e), true));
+                false
+            },
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame<'_>, area: Rect, app: &App) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(4), Constraint::Min(0), Constraint::Length(3)])
+            .split(area);
+
+        let summary = Paragraph::new(format!(
+            "配置文件: {}\n当前账号: {}（共 {} 个）",
+            Config::get_config_path().display(),
+            self.config.active_account.as_deref().unwrap_or("未设置"),
+            self.config.accounts.len(),
+        ))
+        .block(tab_block("设置", &app.theme));
+        f.render_widget(summary, layout[0]);
+
+        let items: Vec<ListItem> = ITEMS.iter()
+            .map(|item| match item {
+                Item::Field(field) => {
+                    let value = field.value(&self.config);
+                    ListItem::new(format!("{:<16} {}", field.label(), if value.is_empty() { "默认" } else { &value }))
+                },
+                Item::ResetToken => ListItem::new("重新设置Token"),
+                Item::SwitchAccount => ListItem::new("切换账号"),
+            })
+            .collect();
+        let list = List::new(items)
+            .block(tab_block("Enter 修改或执行", &app.theme))
+            .highlight_style(app.theme.selected);
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        f.render_stateful_widget(list, layout[1], &mut state);
+
+        // Text field while editing, otherwise the outcome of the last change
+        if let Some((field, input)) = &self.editing {
+            let (scroll, cursor_x) = input.viewport(layout[2].width.saturating_sub(2));
+            let title = match &self.message {
+                Some((error, true)) => format!("{}: {}", field.label(), error),
+                _ => format!("{} (Enter 保存，Esc 取消) {}", field.label(), field.hint()),
+            };
+            let field_widget = Paragraph::new(input.value())
+                .block(Block::default().borders(Borders::ALL).title(title).style(app.theme.accent))
+                .style(app.theme.text)
+                .scroll((0, scroll));
+            f.render_widget(field_widget, layout[2]);
+            f.set_cursor(layout[2].x + 1 + cursor_x, layout[2].y + 1);
+        } else if let Some((message, failed)) = &self.message {
+            let style = if *failed { app.theme.error } else { app.theme.success };
+            let message = Paragraph::new(message.as_str())
+                .block(Block::default().borders(Borders::ALL))
+                .style(style);
+            f.render_widget(message, layout[2]);
+        }
+    }
+}
+
+/// Clear the saved token and return to token input screen
+fn reset_token(app: &mut App) -> ScreenType {
+    app.mcp_client = None;
+
+    if ConfigService::global().update(|config| config.token = String::new()).is_ok() {
+        audit::record("tui", AuditAction::TokenReset, true, "");
+    }
+
+    app.add_log("Token已重置".to_string());
+    app.add_log("请输入新的MCP Token".to_string());
+
+    ScreenType::TokenInput(TokenInputScreen::new())
+}
@@ -0,0 +1,130 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Single-line text field with a cursor, edited with the usual terminal keys
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    value: String,
+    /// Cursor position in characters, 0..=len
+    cursor: usize,
+}
+
+impl TextInput {
+    /// Field pre-filled with `value`, cursor at the end
+    pub fn with_value(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+            cursor: value.chars().count(),
+        }
+    }
+
+    /// Current text
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn clear(&mut self) {
+        self.value.clear();
+        self.cursor = 0;
+    }
+
+    /// Insert text at the cursor, as sent by a bracketed paste or committed at once by an IME.
+    /// Line breaks and other control characters are dropped, since the field is single-line.
+    pub fn insert_str(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        let at = self.byte_index(self.cursor);
+        self.value.insert_str(at, &text);
+        self.cursor += text.chars().count();
+    }
+
+    /// Apply an editing key: characters, Backspace/Delete, ←/→/Home/End,
+    /// Ctrl+←/→ to jump by word and Ctrl+U to clear. Returns false for keys it ignores.
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let len = self.len();
+        match key.code {
+            KeyCode::Char('u') if ctrl => self.clear(),
+            KeyCode::Left if ctrl => self.cursor = self.prev_word(),
+            KeyCode::Right if ctrl => self.cursor = self.next_word(),
+            _ if ctrl || key.modifiers.contains(KeyModifiers::ALT) => return false,
+            KeyCode::Char(c) if !c.is_control() => {
+                let at = self.byte_index(self.cursor);
+                self.value.insert(at, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.value.remove(self.byte_index(self.cursor));
+            }
+            KeyCode::Delete if self.cursor < len => {
+                self.value.remove(self.byte_index(self.cursor));
+            }
+            KeyCode::Backspace | KeyCode::Delete => {}
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Horizontal scroll and cursor column for a field `width` cells wide,
+    /// keeping the cursor inside the visible part of long values. Both are in
+    /// terminal columns, so wide characters such as Chinese count twice, and the
+    /// scroll never starts halfway through one.
+    pub fn viewport(&self, width: u16) -> (u16, u16) {
+        let width = width.max(1) as usize;
+        let before = &self.value[..self.byte_index(self.cursor)];
+        // The character under the cursor must fit too; past the end the cursor takes one column
+        let under = self.value[before.len()..].chars().next().and_then(|c| c.width()).unwrap_or(1);
+        let needed = (before.width() + under).saturating_sub(width);
+        let mut scroll = 0;
+        for c in before.chars() {
+            if scroll >= needed {
+                break;
+            }
+            scroll += c.width().unwrap_or(0);
+        }
+        (scroll as u16, (before.width() - scroll) as u16)
+    }
+
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.value.char_indices().nth(cursor).map_or(self.value.len(), |(i, _)| i)
+    }
+
+    /// Start of the word before the cursor
+    fn prev_word(&self) -> usize {
+        let chars: Vec<char> = self.value.chars().collect();
+        let mut i = self.cursor;
+        while i > 0 && !is_word_char(chars[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && is_word_char(chars[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    /// End of the word after the cursor
+    fn next_word(&self) -> usize {
+        let chars: Vec<char> = self.value.chars().collect();
+        let mut i = self.cursor;
+        while i < chars.len() && !is_word_char(chars[i]) {
+            i += 1;
+        }
+        while i < chars.len() && is_word_char(chars[i]) {
+            i += 1;
+        }
+        i
+    }
+}
+
+/// Tokens are split into words at punctuation such as '.', '-' and spaces
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
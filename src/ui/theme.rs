@@ -0,0 +1,99 @@
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::config::ThemePreset;
+
+/// Styles the TUI draws with, named by meaning so a preset can change all of them at once
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    /// Borders and titles of input boxes, info toasts
+    pub accent: Style,
+    /// Text typed into input boxes
+    pub text: Style,
+    /// Border of the tab content
+    pub frame: Style,
+    /// Key hints
+    pub hint: Style,
+    pub success: Style,
+    pub error: Style,
+    pub warning: Style,
+    /// Used coupons, unchecked accounts and other inactive items
+    pub muted: Style,
+    /// Cursor row of a list
+    pub highlight: Style,
+    /// Active tab and cursor row of a menu
+    pub selected: Style,
+    /// Filled part of the progress gauge
+    pub gauge: Style,
+}
+
+impl Theme {
+    /// Theme for `preset`, or the monochrome one when color is off
+    pub fn new(preset: ThemePreset, color: bool) -> Self {
+        if !color {
+            return Self::monochrome();
+        }
+        match preset {
+            ThemePreset::Default => Self::default(),
+            ThemePreset::ColorBlind => Self::color_blind(),
+            ThemePreset::Monochrome => Self::monochrome(),
+        }
+    }
+
+    /// Okabe-Ito colors: success and failure differ in hue and brightness,
+    /// so they stay apart with red-green color blindness
+    fn color_blind() -> Self {
+        const BLUE: Color = Color::Rgb(0, 114, 178);
+        const SKY_BLUE: Color = Color::Rgb(86, 180, 233);
+        const ORANGE: Color = Color::Rgb(230, 159, 0);
+        const VERMILLION: Color = Color::Rgb(213, 94, 0);
+        const YELLOW: Color = Color::Rgb(240, 228, 66);
+        Self {
+            accent: Style::default().fg(SKY_BLUE),
+            text: Style::default().fg(Color::White),
+            frame: Style::default().fg(ORANGE),
+            hint: Style::default().fg(ORANGE),
+            success: Style::default().fg(BLUE),
+            error: Style::default().fg(VERMILLION).add_modifier(Modifier::BOLD),
+            warning: Style::default().fg(YELLOW),
+            muted: Style::default().fg(Color::DarkGray),
+            highlight: Style::default().fg(Color::Black).bg(Color::Gray),
+            selected: Style::default().fg(Color::Black).bg(SKY_BLUE).add_modifier(Modifier::BOLD),
+            gauge: Style::default().fg(BLUE).bg(Color::Black).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// No colors at all; emphasis and selection use text attributes instead
+    fn monochrome() -> Self {
+        Self {
+            accent: Style::default(),
+            text: Style::default(),
+            frame: Style::default(),
+            hint: Style::default(),
+            success: Style::default().add_modifier(Modifier::BOLD),
+            error: Style::default().add_modifier(Modifier::BOLD),
+            warning: Style::default().add_modifier(Modifier::BOLD),
+            muted: Style::default().add_modifier(Modifier::DIM),
+            highlight: Style::default().add_modifier(Modifier::REVERSED),
+            selected: Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+            gauge: Style::default().add_modifier(Modifier::BOLD),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            accent: Style::default().fg(Color::Cyan),
+            text: Style::default().fg(Color::White),
+            frame: Style::default().fg(Color::Yellow),
+            hint: Style::default().fg(Color::Yellow),
+            success: Style::default().fg(Color::Green),
+            error: Style::default().fg(Color::Red),
+            warning: Style::default().fg(Color::Yellow),
+            muted: Style::default().fg(Color::DarkGray),
+            highlight: Style::default().fg(Color::Black).bg(Color::Gray),
+            selected: Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD),
+            gauge: Style::default().fg(Color::Green).bg(Color::Black).add_modifier(Modifier::BOLD),
+        }
+    }
+}
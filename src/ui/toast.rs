@@ -0,0 +1,71 @@
+use ratatui::{Frame, layout::Rect, widgets::{Block, Borders, Clear, Paragraph, Wrap}};
+use std::time::{Duration, Instant};
+
+use crate::ui::Theme;
+
+/// How long a toast stays on screen
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+/// Older toasts are dropped beyond this many
+const MAX_TOASTS: usize = 4;
+/// Widest a toast gets, in cells
+const MAX_WIDTH: u16 = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToastKind {
+    Success,
+    Info,
+    Error,
+}
+
+/// Short-lived notification drawn over the current screen
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub kind: ToastKind,
+    pub message: String,
+    shown_at: Instant,
+}
+
+/// Toasts stacked in the top-right corner, newest at the top
+#[derive(Debug, Clone, Default)]
+pub struct Toasts {
+    items: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, kind: ToastKind, message: String) {
+        self.items.insert(0, Toast { kind, message, shown_at: Instant::now() });
+        self.items.truncate(MAX_TOASTS);
+    }
+
+    /// Drop toasts that have been shown long enough
+    pub fn expire(&mut self) {
+        self.items.retain(|t| t.shown_at.elapsed() < TOAST_DURATION);
+    }
+
+    pub fn render(&self, f: &mut Frame<'_>, theme: &Theme) {
+        let area = f.size();
+        let width = MAX_WIDTH.min(area.width / 2).max(20).min(area.width);
+        let mut y = area.y + 1;
+        for toast in &self.items {
+            // Wrapped text height plus the border, counting CJK characters as two cells
+            let cells: usize = toast.message.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum();
+            let lines = cells as u16 / width.saturating_sub(4).max(1) + 1;
+            let height = (lines + 2).min(area.height.saturating_sub(y));
+            if height < 3 {
+                break;
+            }
+            let rect = Rect::new(area.right().saturating_sub(width + 1), y, width, height);
+            let (title, style) = match toast.kind {
+                ToastKind::Success => ("成功", theme.success),
+                ToastKind::Info => ("提示", theme.accent),
+                ToastKind::Error => ("错误", theme.error),
+            };
+            let widget = Paragraph::new(toast.message.as_str())
+                .wrap(Wrap { trim: true })
+                .block(Block::default().borders(Borders::ALL).title(title).border_style(style));
+            f.render_widget(Clear, rect);
+            f.render_widget(widget, rect);
+            y += height;
+        }
+    }
+}
@@ -1,8 +1,123 @@
-use chrono::Local;
+use anyhow::anyhow;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+#[cfg(feature = "server")]
+use chrono::{FixedOffset, TimeZone};
+use chrono_tz::Tz;
+use std::sync::Mutex;
+#[cfg(any(feature = "tui", feature = "web"))]
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-/// Format current time as string
+/// UTC offset of the times used by upstream (China Standard Time)
+#[cfg(feature = "server")]
+const SERVER_UTC_OFFSET_SECONDS: i32 = 8 * 3600;
+
+/// Formats the upstream time text may use
+#[cfg(feature = "server")]
+const SERVER_TIME_FORMATS: [&str; 3] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y/%m/%d %H:%M:%S"];
+
+/// Time zone of schedules and displayed times unless the `timezone` config option says
+/// otherwise, as coupons are released on Beijing time
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Asia::Shanghai;
+
+/// Where local dates and times are read
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Named(Tz),
+    /// The system time zone, for `"timezone": "local"`
+    System,
+}
+
+static TIMEZONE: Mutex<Zone> = Mutex::new(Zone::Named(DEFAULT_TIMEZONE));
+
+/// Set the time zone of schedules, countdowns and timestamps from the `timezone` config option:
+/// an IANA name such as "Asia/Shanghai", or "local" for the system's own. An unknown name
+/// leaves the default in place and is reported.
+pub fn set_timezone(name: Option<&str>) -> anyhow::Result<()> {
+    let zone = parse_timezone(name);
+    *TIMEZONE.lock().unwrap_or_else(|e| e.into_inner()) = *zone.as_ref().unwrap_or(&Zone::Named(DEFAULT_TIMEZONE));
+    zone.map(drop)
+}
+
+/// Whether the `timezone` config option names a known time zone
+pub fn check_timezone(name: Option<&str>) -> anyhow::Result<()> {
+    parse_timezone(name).map(drop)
+}
+
+fn parse_timezone(name: Option<&str>) -> anyhow::Result<Zone> {
+    match name.map(str::trim) {
+        None | Some("") => Ok(Zone::Named(DEFAULT_TIMEZONE)),
+        Some(name) if name.eq_ignore_ascii_case("local") => Ok(Zone::System),
+        Some(name) => name.parse::<Tz>()
+            .map(Zone::Named)
+            .map_err(|_| anyhow!("timezone 配置无效: {}，应为 Asia/Shanghai 这样的时区名称或 local", name)),
+    }
+}
+
+fn zone() -> Zone {
+    *TIMEZONE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Wall-clock time of an instant in the configured time zone
+pub fn to_local(time: DateTime<Utc>) -> NaiveDateTime {
+    match zone() {
+        Zone::Named(tz) => time.with_timezone(&tz).naive_local(),
+        Zone::System => time.with_timezone(&Local).naive_local(),
+    }
+}
+
+/// Instant of a wall-clock time in the configured time zone: the first one when the clocks
+/// go back, None when they skip it
+#[cfg(feature = "server")]
+pub fn from_local(time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    match zone() {
+        Zone::Named(tz) => tz.from_local_datetime(&time).earliest().map(|t| t.with_timezone(&Utc)),
+        Zone::System => Local.from_local_datetime(&time).earliest().map(|t| t.with_timezone(&Utc)),
+    }
+}
+
+/// Current wall-clock time in the configured time zone
+pub fn local_now() -> NaiveDateTime {
+    to_local(Utc::now())
+}
+
+/// Today's date in the configured time zone
+pub fn local_today() -> NaiveDate {
+    local_now().date()
+}
+
+/// Format current time as string, in the configured time zone
 pub fn format_current_time() -> String {
-    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+    local_now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Format an instant in the configured time zone
+#[cfg(feature = "server")]
+pub fn format_local_time(time: DateTime<Utc>) -> String {
+    to_local(time).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Time zone of upstream times such as coupon release times
+#[cfg(feature = "server")]
+pub fn server_offset() -> FixedOffset {
+    FixedOffset::east_opt(SERVER_UTC_OFFSET_SECONDS).expect("valid offset")
+}
+
+/// Format an instant in server time
+#[cfg(feature = "web")]
+pub fn format_server_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&server_offset()).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// First `YYYY-MM-DD HH:MM:SS`-style timestamp in `now-time-info` output, read as server time
+#[cfg(feature = "server")]
+pub fn parse_server_time(text: &str) -> Option<DateTime<Utc>> {
+    text.char_indices().find_map(|(i, _)| {
+        let candidate = text.get(i..i + 19)?;
+        SERVER_TIME_FORMATS.iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(candidate, format).ok())
+            .and_then(|time| time.and_local_timezone(server_offset()).single())
+            .map(|time| time.with_timezone(&Utc))
+    })
 }
 
 /// Format a log message with timestamp
@@ -10,16 +125,53 @@ pub fn format_log_message(message: &str) -> String {
     format!("[{}] {}", format_current_time(), message)
 }
 
-/// Truncate string to fit within a certain width
+/// Truncate a string to at most `width` terminal columns, ending it with "…" when cut.
+/// Chinese characters take two columns and are never split.
+#[cfg(any(feature = "tui", feature = "web"))]
 pub fn truncate_string(s: &str, width: usize) -> String {
-    if s.len() <= width {
-        s.to_string()
-    } else {
-        format!("{:.width$}...", s, width = width - 3)
+    if s.width() <= width {
+        return s.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut used = 0;
+    let mut truncated = String::new();
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w >= width {
+            break;
+        }
+        used += w;
+        truncated.push(c);
     }
+    truncated.push('…');
+    truncated
+}
+
+/// Mask a token for display, keeping only a few characters at each end
+#[cfg(any(feature = "tui", feature = "web", feature = "mcp-server"))]
+pub fn mask_token(token: &str) -> String {
+    let token = token.trim_start_matches("Bearer ");
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+/// Random 128-bit hex id that other clients cannot guess, e.g. for MCP sessions
+#[cfg(feature = "mcp-server")]
+pub fn random_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("系统随机数不可用");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 /// Open MCP login page to help user obtain token
+#[cfg(any(feature = "tui", feature = "web", feature = "mcp-server"))]
 pub fn open_mcp_login_page(url: &str) {
     #[cfg(target_os = "macos")]
     {
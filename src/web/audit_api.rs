@@ -0,0 +1,66 @@
+use axum::{extract::Query, response::{IntoResponse, Json}};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{audit::{self, AuditEntry}, web::paging::{PageInfo, PageQuery}};
+
+/// Filters for the audit trail
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// Actor prefix, e.g. "web", "mcp:" or "scheduler"
+    pub actor: Option<String>,
+    /// Action name, e.g. "claim" or "token_set"
+    pub action: Option<String>,
+}
+
+/// One page of the audit trail, newest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditResponse {
+    pub success: bool,
+    pub message: String,
+    pub entries: Vec<AuditEntry>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub page: Option<PageInfo>,
+}
+
+fn audit_error(message: String) -> Json<AuditResponse> {
+    Json(AuditResponse {
+        success: false,
+        message,
+        entries: Vec::new(),
+        page: None,
+    })
+}
+
+/// API handler for the audit trail of mutating actions
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    tag = "stats",
+    params(AuditQuery, PageQuery),
+    responses((status = 200, description = "审计日志（分页，最新的在前）", body = AuditResponse))
+)]
+pub async fn api_audit_handler(
+    Query(filter): Query<AuditQuery>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let action = match filter.action.as_deref().filter(|a| !a.is_empty()) {
+        Some(name) => match audit::parse_action(name) {
+            Some(action) => Some(action),
+            None => return audit_error(format!("未知的操作: {}", name)),
+        },
+        None => None,
+    };
+    let entries = match audit::query(filter.actor.as_deref().filter(|a| !a.is_empty()), action) {
+        Ok(entries) => entries,
+        Err(e) => return audit_error(format!("读取审计日志失败: {:#}", e)),
+    };
+    let (entries, page) = query.apply(entries);
+    Json(AuditResponse {
+        success: true,
+        message: format!("共 {} 条审计记录", page.total),
+        entries,
+        page: Some(page),
+    })
+}
@@ -0,0 +1,204 @@
+use axum::{extract::{ConnectInfo, State}, http::HeaderMap, response::{IntoResponse, Json, Response}};
+use serde::Serialize;
+use std::{collections::{HashMap, HashSet}, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{audit, coupons::{match_key, parse_coupons_from_markdown, Coupon, UsageStore}, error::McdError, mcp::describe_error, web::{begin_claim, claim_all, conflict_response, idempotency_key, token_invalid, WebAppState}};
+
+/// Upstream can take a moment to list freshly bound coupons
+const REFRESH_DELAY: Duration = Duration::from_secs(2);
+
+/// What the claim did for one coupon
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimOutcome {
+    /// Held now and not (or fewer copies) before
+    Claimed,
+    /// Already held before the claim
+    AlreadyHeld,
+    /// Offered, but not held after the claim
+    NotClaimed,
+}
+
+impl ClaimOutcome {
+    fn label(self) -> &'static str {
+        match self {
+            ClaimOutcome::Claimed => "已领取",
+            ClaimOutcome::AlreadyHeld => "已持有",
+            ClaimOutcome::NotClaimed => "未领取",
+        }
+    }
+}
+
+/// Claim result of one coupon
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CouponClaimResult {
+    pub title: String,
+    pub expiry: String,
+    pub outcome: ClaimOutcome,
+    /// Chinese label of the outcome
+    pub label: &'static str,
+}
+
+/// Response of the combined claim and refresh endpoint
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClaimRefreshResponse {
+    pub success: bool,
+    pub message: String,
+    /// Held coupons after the claim
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coupons: Option<Vec<Coupon>>,
+    /// One entry per offered coupon, plus any other coupon that appeared
+    pub results: Vec<CouponClaimResult>,
+}
+
+impl ClaimRefreshResponse {
+    fn failed(message: String) -> Response {
+        Json(Self {
+            success: false,
+            message,
+            coupons: None,
+            results: Vec::new(),
+        }).into_response()
+    }
+}
+
+/// API handler for claiming all coupons and returning the refreshed list in one go
+#[utoipa::path(
+    post,
+    path = "/api/claim-and-refresh",
+    tag = "coupons",
+    params(("Idempotency-Key" = Option<String>, Header, description = "重试时带上同一个值，返回第一次领取的结果而不再重复领取")),
+    responses(
+        (status = 200, description = "一键领取并刷新：领取后的优惠券列表与每张优惠券的领取结果", body = ClaimRefreshResponse),
+        (status = 401, description = "Token 已失效，需要重新设置", body = super::ApiResponse),
+        (status = 409, description = "已有领取正在进行，响应头 Operation-Id 为其操作 id", body = ClaimRefreshResponse),
+    )
+)]
+pub async fn api_claim_and_refresh_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let (client, ticket) = match begin_claim(&state, idempotency_key(&headers)).await {
+        Ok(started) => started,
+        // A retry gets the outcome without the per-coupon results, which are not kept
+        Err(existing) => return match existing.resolve().await {
+            Ok(outcome) => Json(ClaimRefreshResponse {
+                success: outcome.success,
+                message: outcome.message,
+                coupons: None,
+                results: Vec::new(),
+            }).into_response(),
+            Err(conflict) => conflict_response(&conflict.id, ClaimRefreshResponse {
+                success: false,
+                message: conflict.to_string(),
+                coupons: None,
+                results: Vec::new(),
+            }),
+        },
+    };
+
+    let Some(client) = client else {
+        return ClaimRefreshResponse::failed("请先设置Token".to_string());
+    };
+
+    // The state is only locked between the upstream calls, so the page stays responsive
+
+    // Snapshot what is offered and held, to tell what the claim added
+    let (available, before) = tokio::join!(client.get_available_coupons(), client.get_my_coupons());
+    let (available, before) = match (available, before) {
+        (Ok(available), Ok(before)) => (parse_coupons_from_markdown(&available), parse_coupons_from_markdown(&before)),
+        (Err(McdError::TokenInvalid), _) | (_, Err(McdError::TokenInvalid)) => return token_invalid(&mut *state.lock().await),
+        (Err(e), _) | (_, Err(e)) => {
+            let message = format!("领取前加载优惠券失败: {}", describe_error(&e));
+            state.lock().await.add_log(message.clone());
+            return ClaimRefreshResponse::failed(message);
+        },
+    };
+
+    match claim_all(&state, &client, &audit::web_actor(peer.ip()), ticket).await {
+        Ok(_) => {},
+        Err(McdError::TokenInvalid) => return token_invalid(&mut *state.lock().await),
+        Err(e) => return ClaimRefreshResponse::failed(format!("领取失败: {}", describe_error(&e))),
+    }
+
+    // The refresh inside the claim can miss coupons upstream has not listed yet
+    let mut after = state.lock().await.coupons.clone();
+    if after.is_empty() || missing(&available, &before, &after) {
+        tokio::time::sleep(REFRESH_DELAY).await;
+        match client.get_my_coupons().await {
+            Ok(text) => {
+                after = parse_coupons_from_markdown(&text);
+                UsageStore::load().unwrap_or_default().apply(&mut after);
+                state.lock().await.coupons = after.clone();
+            },
+            Err(e) => tracing::warn!("领取后再次刷新优惠券失败: {}", e),
+        }
+    }
+
+    let results = outcomes(&available, &before, &after);
+    let claimed = results.iter().filter(|r| r.outcome == ClaimOutcome::Claimed).count();
+    let not_claimed = results.iter().filter(|r| r.outcome == ClaimOutcome::NotClaimed).count();
+    let message = format!("领取成功！新增 {} 张，未领取 {} 张，当前持有 {} 张", claimed, not_claimed, after.len());
+    state.lock().await.add_log(message.clone());
+
+    Json(ClaimRefreshResponse {
+        success: true,
+        message,
+        coupons: Some(after),
+        results,
+    }).into_response()
+}
+
+/// Coupons counted by normalized title and validity, see `match_key`
+fn counts(coupons: &[Coupon]) -> HashMap<(String, String), usize> {
+    let mut counts = HashMap::new();
+    for coupon in coupons {
+        *counts.entry((match_key(&coupon.title), coupon.expiry.clone())).or_default() += coupon.count;
+    }
+    counts
+}
+
+/// Whether an offered coupon that was not held before is still not held
+fn missing(available: &[Coupon], before: &[Coupon], after: &[Coupon]) -> bool {
+    let (before, after) = (counts(before), counts(after));
+    counts(available).keys().any(|key| !before.contains_key(key) && !after.contains_key(key))
+}
+
+/// Outcome of every offered coupon, then of the other coupons that appeared
+fn outcomes(available: &[Coupon], before: &[Coupon], after: &[Coupon]) -> Vec<CouponClaimResult> {
+    let (held_before, held_after) = (counts(before), counts(after));
+    let outcome = |key: &(String, String)| {
+        let was = held_before.get(key).copied().unwrap_or_default();
+        let now = held_after.get(key).copied().unwrap_or_default();
+        if now > was {
+            ClaimOutcome::Claimed
+        } else if was > 0 {
+            ClaimOutcome::AlreadyHeld
+        } else {
+            ClaimOutcome::NotClaimed
+        }
+    };
+    let result = |coupon: &Coupon, outcome: ClaimOutcome| CouponClaimResult {
+        title: coupon.title.clone(),
+        expiry: coupon.expiry.clone(),
+        outcome,
+        label: outcome.label(),
+    };
+
+    let offered = counts(available);
+    let mut seen = HashSet::new();
+    let mut results: Vec<CouponClaimResult> = available.iter()
+        .filter(|c| seen.insert((match_key(&c.title), c.expiry.clone())))
+        .map(|c| result(c, outcome(&(match_key(&c.title), c.expiry.clone()))))
+        .collect();
+    results.extend(after.iter()
+        .filter(|c| {
+            let key = (match_key(&c.title), c.expiry.clone());
+            !offered.contains_key(&key) && outcome(&key) == ClaimOutcome::Claimed && seen.insert(key)
+        })
+        .map(|c| result(c, ClaimOutcome::Claimed)));
+    results
+}
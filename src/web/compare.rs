@@ -0,0 +1,60 @@
+use axum::{extract::Query, response::{IntoResponse, Json}};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{config::ConfigService, coupons::{compare_accounts, pick_accounts, AccountComparison}, mcp::describe_error};
+
+/// Accounts to compare; without them only the account names are returned
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CompareQuery {
+    pub a: Option<String>,
+    pub b: Option<String>,
+}
+
+/// Saved account names and, when two were given, their coupons side by side
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompareResponse {
+    pub success: bool,
+    pub message: String,
+    pub accounts: Vec<String>,
+    pub comparison: Option<AccountComparison>,
+}
+
+/// API handler comparing the coupons held by two saved accounts.
+/// Reads the current config on each call, so accounts added from the terminal mode are seen.
+#[utoipa::path(
+    get,
+    path = "/api/accounts/compare",
+    tag = "coupons",
+    params(CompareQuery),
+    responses((status = 200, description = "两个账号的优惠券对比：仅一方持有的和双方都有的", body = CompareResponse))
+)]
+pub async fn api_compare_accounts_handler(Query(query): Query<CompareQuery>) -> impl IntoResponse {
+    let config = ConfigService::global().current();
+    let accounts: Vec<String> = config.accounts.iter().map(|a| a.name.clone()).collect();
+    let respond = |success: bool, message: String, comparison: Option<AccountComparison>| Json(CompareResponse {
+        success,
+        message,
+        accounts: accounts.clone(),
+        comparison,
+    });
+
+    let (Some(a), Some(b)) = (query.a, query.b) else {
+        return respond(true, format!("共 {} 个账号", accounts.len()), None);
+    };
+    let (a, b) = match pick_accounts(&config, &a, &b) {
+        Ok(pair) => pair,
+        Err(message) => return respond(false, message, None),
+    };
+    match compare_accounts(&config, a, b).await {
+        Ok(comparison) => {
+            let message = format!(
+                "仅「{}」持有 {} 张，仅「{}」持有 {} 张，两个账号都有 {} 张",
+                comparison.a, comparison.only_a.len(), comparison.b, comparison.only_b.len(), comparison.both.len()
+            );
+            respond(true, message, Some(comparison))
+        },
+        Err(e) => respond(false, format!("优惠券加载失败: {}", describe_error(&e)), None),
+    }
+}
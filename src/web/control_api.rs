@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::{config::ConfigService, control::{self, Command, InstanceStatus, Reply}, error::McdError, mcp::describe_error, web::{begin_claim, claim_all, forget_token, WebAppState}};
+
+/// Answer commands from the local control socket with the web state
+pub fn spawn(state: Arc<Mutex<WebAppState>>) {
+    let Some(mut commands) = control::spawn("web") else {
+        return;
+    };
+    tokio::spawn(async move {
+        while let Some(pending) = commands.recv().await {
+            let reply = match pending.command {
+                Command::Claim => claim(&state).await,
+                Command::Status => {
+                    let state = state.lock().await;
+                    InstanceStatus::reply("web", state.mcp_client.is_some(), state.coupons.len())
+                },
+                Command::ReloadConfig => reload(&mut *state.lock().await).await,
+            };
+            pending.reply(reply);
+        }
+    });
+}
+
+async fn claim(state: &Mutex<WebAppState>) -> Reply {
+    let (client, ticket) = match begin_claim(state, None).await {
+        Ok(started) => started,
+        Err(existing) => return match existing.resolve().await {
+            Ok(outcome) if outcome.success => Reply::ok(outcome.message),
+            Ok(outcome) => Reply::failed(outcome.message),
+            Err(conflict) => Reply::failed(conflict.to_string()),
+        },
+    };
+    let Some(client) = client else {
+        return Reply::failed("请先设置Token");
+    };
+    match claim_all(state, &client, control::ACTOR, ticket).await {
+        Ok(outcome) if outcome.success => Reply::ok(outcome.message),
+        Ok(outcome) => Reply::failed(outcome.message),
+        Err(McdError::TokenInvalid) => Reply::failed(forget_token(&mut *state.lock().await)),
+        Err(e) => Reply::failed(format!("领取失败: {}", describe_error(&e))),
+    }
+}
+
+/// Re-read the config file and rebuild the client from its token.
+/// The port and IP allowlist are only read at startup.
+async fn reload(state: &mut WebAppState) -> Reply {
+    let config = match ConfigService::global().reload() {
+        Ok(config) => config,
+        Err(e) => return Reply::failed(format!("读取配置失败: {}", e)),
+    };
+    let token = config.token.clone();
+    let has_token = config.has_valid_token();
+    state.config = config;
+    state.coupons.clear();
+    state.mcp_client = None;
+    if has_token {
+        if let Err(e) = state.init_mcp_client(token).await {
+            return Reply::failed(format!("创建客户端失败: {}", e));
+        }
+    }
+    state.add_log("已重新加载配置".to_string());
+    Reply::ok("已重新加载配置（端口和 allowed_ips 需重启后生效）")
+}
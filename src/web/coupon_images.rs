@@ -0,0 +1,29 @@
+use axum::{extract::Query, http::{header, StatusCode}, response::{IntoResponse, Response}};
+use serde::Deserialize;
+
+use crate::images;
+
+/// Image to serve, by its original upstream URL
+#[derive(Debug, Deserialize)]
+pub struct ImageQuery {
+    pub url: String,
+}
+
+/// Coupon image from the disk cache, downloading it on a miss.
+/// Only URLs from coupon lists are served, so this cannot be used as an open proxy.
+pub async fn image_handler(Query(query): Query<ImageQuery>) -> Response {
+    if !images::is_known(&query.url) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match images::get(&query.url).await {
+        Ok((bytes, content_type)) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "public, max-age=604800"),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("{:#}", e)).into_response(),
+    }
+}
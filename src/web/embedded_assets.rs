@@ -0,0 +1,40 @@
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::{hash::{DefaultHasher, Hash, Hasher}, sync::OnceLock};
+
+/// Stylesheet and script of the web UI, split out of the page so browsers can cache them
+const ASSETS: &[(&str, &str, &str)] = &[
+    ("app.css", "text/css; charset=utf-8", include_str!("static/app.css")),
+    ("app.js", "text/javascript; charset=utf-8", include_str!("static/app.js")),
+];
+
+/// Short hash of all assets, appended to their URLs so a new binary busts browser caches
+pub fn version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(|| {
+        let mut hasher = DefaultHasher::new();
+        for (name, _, body) in ASSETS {
+            name.hash(&mut hasher);
+            body.hash(&mut hasher);
+        }
+        format!("{:08x}", hasher.finish() as u32)
+    })
+}
+
+/// Serve an embedded asset under `/assets/`. URLs carry the version, so responses never go stale.
+pub async fn asset_handler(Path(file): Path<String>) -> Response {
+    match ASSETS.iter().find(|(name, _, _)| *name == file) {
+        Some((_, content_type, body)) => (
+            [
+                (header::CONTENT_TYPE, *content_type),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            *body,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
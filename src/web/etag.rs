@@ -0,0 +1,52 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Tag JSON responses with an ETag of their body and answer a matching
+/// `If-None-Match` with 304, so polling clients skip unchanged payloads
+pub async fn etag(request: Request, next: Next) -> Response {
+    let is_get = request.method() == Method::GET;
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let response = next.run(request).await;
+    if !is_get || response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    // Weak, since the compression layer may re-encode the same body
+    let tag = format!("W/\"{:016x}\"", hasher.finish());
+
+    let value = HeaderValue::from_str(&tag).expect("hex etag is a valid header value");
+    parts.headers.insert(header::ETAG, value.clone());
+    // Let browsers keep the body but check back every time
+    parts.headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    if if_none_match.is_some_and(|v| matches(&v, &tag)) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, value);
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Whether an If-None-Match header lists the tag, using weak comparison
+fn matches(header: &HeaderValue, tag: &str) -> bool {
+    let tag = tag.trim_start_matches("W/");
+    header.to_str().is_ok_and(|value| {
+        value.split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == "*" || t == tag)
+    })
+}
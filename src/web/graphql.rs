@@ -0,0 +1,169 @@
+use async_graphql::{http::GraphiQLSource, Context, EmptySubscription, Error, Object, Request, Response as GraphQLResponse, Schema, SimpleObject};
+use axum::{extract::{ConnectInfo, State}, response::{Html, IntoResponse, Json}};
+use chrono::NaiveDate;
+use std::{net::SocketAddr, sync::{Arc, OnceLock}};
+use tokio::sync::Mutex;
+
+use crate::{audit, coupons::{ClaimEntry, ClaimHistory, Coupon, TagStats, UsageStore}, error::McdError, mcp::{describe_error, metrics::{self, UpstreamStats}}, scheduler::{self, SchedulerStatus}, web::{begin_claim, claim_all, forget_token, history::date_range, reset_token, stats::{coupon_stats, held_coupons, savings_stats, ClaimRecord, CouponStats, SavingsStats}, WebAppState}};
+
+/// Schema served at `/graphql`
+type ApiSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// Audit actor of the HTTP request the operation came in on
+struct Actor(String);
+
+/// Built once; per-request state is attached as request data
+fn schema() -> &'static ApiSchema {
+    static SCHEMA: OnceLock<ApiSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| Schema::build(QueryRoot, MutationRoot, EmptySubscription).finish())
+}
+
+fn web_state<'a>(ctx: &Context<'a>) -> &'a Arc<Mutex<WebAppState>> {
+    ctx.data_unchecked::<Arc<Mutex<WebAppState>>>()
+}
+
+/// Dashboard figures; each field is only computed when selected, so asking for
+/// `savings` alone does not reach upstream
+pub struct Stats;
+
+#[Object]
+impl Stats {
+    /// Held and soon-expiring coupon counts
+    async fn coupons(&self, ctx: &Context<'_>) -> Result<CouponStats, Error> {
+        let coupons = held_coupons(web_state(ctx)).await?;
+        Ok(coupon_stats(&coupons))
+    }
+
+    /// Savings from coupons marked as used this month
+    async fn savings(&self) -> SavingsStats {
+        savings_stats()
+    }
+
+    /// Most recent claim in this session
+    async fn last_claim(&self, ctx: &Context<'_>) -> Option<ClaimRecord> {
+        web_state(ctx).lock().await.last_claim.clone()
+    }
+
+    async fn scheduler(&self) -> SchedulerStatus {
+        scheduler::status()
+    }
+
+    /// Rolling upstream success rate and latency
+    async fn upstream(&self) -> UpstreamStats {
+        metrics::snapshot()
+    }
+
+    /// Claims and savings per tag, most claimed first. Dates are YYYY-MM-DD.
+    async fn tags(&self, from: Option<String>, to: Option<String>) -> Result<Vec<TagStats>, Error> {
+        let (from, to) = date_range(&from, &to).map_err(Error::new)?;
+        let history = ClaimHistory::load().map_err(|e| Error::new(format!("读取领取记录失败: {}", e)))?;
+        Ok(history.tag_stats(&UsageStore::load().unwrap_or_default(), from, to))
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Held coupons, from the cache unless `refresh` is set
+    async fn coupons(&self, ctx: &Context<'_>, #[graphql(default)] refresh: bool) -> Result<Vec<Coupon>, Error> {
+        let state = web_state(ctx);
+        if refresh {
+            state.lock().await.coupons.clear();
+        }
+        Ok(held_coupons(state).await?)
+    }
+
+    /// Local claim history, newest first. Dates are YYYY-MM-DD.
+    async fn history(
+        &self,
+        from: Option<String>,
+        to: Option<String>,
+        account: Option<String>,
+        #[graphql(default = 50)] limit: usize,
+    ) -> Result<Vec<ClaimEntry>, Error> {
+        let date = |name: &str, value: Option<String>| match value.filter(|v| !v.is_empty()) {
+            Some(text) => NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| Error::new(format!("{} 应为 YYYY-MM-DD 格式", name))),
+            None => Ok(None),
+        };
+        let (from, to) = (date("from", from)?, date("to", to)?);
+        let history = ClaimHistory::load().map_err(|e| Error::new(format!("读取领取记录失败: {}", e)))?;
+        let mut entries = history.query(from, to, account.as_deref().filter(|a| !a.is_empty()));
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    async fn stats(&self) -> Stats {
+        Stats
+    }
+
+    /// Operation log shown in the web UI, newest first
+    async fn logs(&self, ctx: &Context<'_>, #[graphql(default = 50)] limit: usize) -> Vec<String> {
+        web_state(ctx).lock().await.logs.iter().rev().take(limit).cloned().collect()
+    }
+}
+
+/// Outcome of a mutation
+#[derive(SimpleObject)]
+pub struct MutationResult {
+    pub success: bool,
+    pub message: String,
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Claim all available coupons, same as `POST /api/claim`
+    async fn claim(&self, ctx: &Context<'_>) -> Result<MutationResult, Error> {
+        let state = web_state(ctx);
+        let (client, ticket) = match begin_claim(state, None).await {
+            Ok(started) => started,
+            Err(existing) => return match existing.resolve().await {
+                Ok(outcome) => Ok(MutationResult { success: outcome.success, message: outcome.message }),
+                Err(conflict) => Err(Error::new(conflict.to_string())),
+            },
+        };
+        let Some(client) = client else {
+            return Err(Error::new("请先设置Token"));
+        };
+        match claim_all(state, &client, &ctx.data_unchecked::<Actor>().0, ticket).await {
+            Ok(outcome) => Ok(MutationResult {
+                success: outcome.success,
+                message: outcome.message,
+            }),
+            Err(McdError::TokenInvalid) => Err(Error::new(forget_token(&mut *state.lock().await))),
+            Err(e) => Ok(MutationResult {
+                success: false,
+                message: format!("领取失败: {}", describe_error(&e)),
+            }),
+        }
+    }
+
+    /// Forget the saved token, same as `POST /api/reset`
+    async fn reset(&self, ctx: &Context<'_>) -> MutationResult {
+        let mut state = web_state(ctx).lock().await;
+        reset_token(&mut state, &ctx.data_unchecked::<Actor>().0);
+        MutationResult {
+            success: true,
+            message: "Token已重置".to_string(),
+        }
+    }
+}
+
+/// GraphQL endpoint for queries and mutations
+pub async fn graphql_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(request): Json<Request>,
+) -> Json<GraphQLResponse> {
+    let request = request.data(state).data(Actor(audit::web_actor(peer.ip())));
+    Json(schema().execute(request).await)
+}
+
+/// GraphiQL page for trying queries in the browser
+pub async fn graphiql_handler() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").title("麦当劳优惠券 GraphQL").finish())
+}
@@ -0,0 +1,150 @@
+// tonic's API takes and returns `Status` by value
+#![allow(clippy::result_large_err)]
+
+use futures_util::{stream, Stream, StreamExt};
+use std::{net::SocketAddr, pin::Pin, sync::Arc};
+use tokio::sync::{broadcast, Mutex};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{allowlist::Allowlist, coupons, error::McdError, mcp::{describe_error, metrics}, scheduler, web::{begin_claim, claim_all, forget_token, stats::{coupon_stats, held_coupons, savings_stats}, WebAppState}};
+
+mod proto {
+    tonic::include_proto!("mcdcoupon");
+}
+
+use proto::coupon_service_server::{CouponService, CouponServiceServer};
+
+/// Serve the gRPC API on `port` in the background, next to the web pages
+pub fn spawn(port: u16, state: Arc<Mutex<WebAppState>>, allowlist: Arc<Allowlist>) {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let service = CouponServiceServer::with_interceptor(GrpcService { state }, move |request: Request<()>| {
+        match request.remote_addr() {
+            Some(peer) if !allowlist.allows(peer.ip()) => {
+                tracing::warn!("已拒绝不在 allowed_ips 中的 gRPC 客户端: {}", peer.ip());
+                Err(Status::permission_denied("该地址不在允许访问的范围内"))
+            },
+            _ => Ok(request),
+        }
+    });
+    tokio::spawn(async move {
+        println!("gRPC 服务已启动: {}", addr);
+        if let Err(e) = Server::builder().add_service(service).serve(addr).await {
+            tracing::error!("gRPC 服务启动失败 ({}): {}", addr, e);
+        }
+    });
+}
+
+struct GrpcService {
+    state: Arc<Mutex<WebAppState>>,
+}
+
+/// Audit actor of a gRPC call, e.g. "grpc:127.0.0.1"
+fn actor<T>(request: &Request<T>) -> String {
+    match request.remote_addr() {
+        Some(peer) => format!("grpc:{}", peer.ip()),
+        None => "grpc".to_string(),
+    }
+}
+
+impl From<coupons::Coupon> for proto::Coupon {
+    fn from(c: coupons::Coupon) -> Self {
+        Self {
+            title: c.title,
+            price: c.price,
+            expiry: c.expiry,
+            receive_time: c.receive_time,
+            tags: c.tags,
+            image_url: c.image_url,
+            used: c.used,
+            count: c.count as u32,
+        }
+    }
+}
+
+type LogStream = Pin<Box<dyn Stream<Item = Result<proto::LogLine, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl CouponService for GrpcService {
+    async fn list_coupons(&self, request: Request<proto::ListCouponsRequest>) -> Result<Response<proto::ListCouponsResponse>, Status> {
+        {
+            let mut state = self.state.lock().await;
+            if state.mcp_client.is_none() {
+                return Err(Status::failed_precondition("请先设置Token"));
+            }
+            if request.get_ref().refresh {
+                state.coupons.clear();
+            }
+        }
+        let coupons = held_coupons(&self.state).await.map_err(Status::unavailable)?;
+        Ok(Response::new(proto::ListCouponsResponse {
+            coupons: coupons.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn claim(&self, request: Request<proto::ClaimRequest>) -> Result<Response<proto::ClaimResponse>, Status> {
+        let (client, ticket) = match begin_claim(&self.state, None).await {
+            Ok(started) => started,
+            Err(existing) => return match existing.resolve().await {
+                Ok(outcome) => Ok(Response::new(proto::ClaimResponse { success: outcome.success, message: outcome.message })),
+                Err(conflict) => Err(Status::aborted(conflict.to_string())),
+            },
+        };
+        let Some(client) = client else {
+            return Err(Status::failed_precondition("请先设置Token"));
+        };
+        let (success, message) = match claim_all(&self.state, &client, &actor(&request), ticket).await {
+            Ok(outcome) => (outcome.success, outcome.message),
+            Err(McdError::TokenInvalid) => return Err(Status::unauthenticated(forget_token(&mut *self.state.lock().await))),
+            Err(e) => (false, format!("领取失败: {}", describe_error(&e))),
+        };
+        Ok(Response::new(proto::ClaimResponse { success, message }))
+    }
+
+    async fn get_stats(&self, _request: Request<proto::GetStatsRequest>) -> Result<Response<proto::Stats>, Status> {
+        if self.state.lock().await.mcp_client.is_none() {
+            return Err(Status::failed_precondition("请先设置Token"));
+        }
+        let coupons = coupon_stats(&held_coupons(&self.state).await.map_err(Status::unavailable)?);
+        let savings = savings_stats();
+        let last_claim = self.state.lock().await.last_claim.clone();
+        let scheduler = scheduler::status();
+        let upstream = metrics::snapshot();
+        Ok(Response::new(proto::Stats {
+            held: coupons.held as u32,
+            expiring_soon: coupons.expiring_soon as u32,
+            estimated_savings: savings.estimated_savings,
+            month: savings.month,
+            last_claim_at: last_claim.as_ref().map(|c| c.at.clone()).unwrap_or_default(),
+            last_claim_success: last_claim.as_ref().is_some_and(|c| c.success),
+            last_claim_message: last_claim.map(|c| c.message).unwrap_or_default(),
+            scheduler_enabled: scheduler.enabled,
+            scheduler_next_run: scheduler.next_run.unwrap_or_default(),
+            upstream_success_rate: upstream.success_rate,
+            upstream_avg_latency_ms: upstream.avg_latency_ms,
+        }))
+    }
+
+    type StreamLogsStream = LogStream;
+
+    async fn stream_logs(&self, request: Request<proto::StreamLogsRequest>) -> Result<Response<LogStream>, Status> {
+        let state = self.state.lock().await;
+        let backlog = request.get_ref().backlog as usize;
+        let recent: Vec<String> = state.logs.iter().rev().take(backlog).rev().cloned().collect();
+        // Subscribed under the same lock as the backlog, so no line is missed or sent twice
+        let events = state.log_events.subscribe();
+        drop(state);
+
+        let follow = stream::unfold(events, |mut events| async move {
+            loop {
+                match events.recv().await {
+                    Ok(line) => return Some((line, events)),
+                    // A slow follower skips what it missed rather than ending the stream
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        let lines = stream::iter(recent).chain(follow).map(|line| Ok(proto::LogLine { line }));
+        Ok(Response::new(Box::pin(lines)))
+    }
+}
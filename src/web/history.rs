@@ -0,0 +1,112 @@
+use axum::{extract::{Query, State}, response::{IntoResponse, Json}};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{coupons::{ClaimEntry, ClaimHistory}, web::{paging::{PageInfo, PageQuery}, WebAppState}};
+
+/// Filters for the claim history
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// Earliest date, YYYY-MM-DD
+    pub from: Option<String>,
+    /// Latest date, YYYY-MM-DD
+    pub to: Option<String>,
+    /// Only claims made with this account
+    pub account: Option<String>,
+}
+
+/// One page of the claim history, newest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryResponse {
+    pub success: bool,
+    pub message: String,
+    pub entries: Vec<ClaimEntry>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub page: Option<PageInfo>,
+}
+
+/// One page of the in-memory operation log, newest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogsResponse {
+    pub success: bool,
+    pub logs: Vec<String>,
+    #[serde(flatten)]
+    pub page: PageInfo,
+}
+
+/// `from` and `to` query parameters as dates; empty ones are left open
+pub(super) fn date_range(from: &Option<String>, to: &Option<String>) -> Result<(Option<NaiveDate>, Option<NaiveDate>), String> {
+    let mut dates = [None, None];
+    for (slot, (name, value)) in dates.iter_mut().zip([("from", from), ("to", to)]) {
+        if let Some(text) = value.as_deref().filter(|v| !v.is_empty()) {
+            let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| format!("{} 应为 YYYY-MM-DD 格式", name))?;
+            *slot = Some(date);
+        }
+    }
+    Ok((dates[0], dates[1]))
+}
+
+fn history_error(message: String) -> Json<HistoryResponse> {
+    Json(HistoryResponse {
+        success: false,
+        message,
+        entries: Vec::new(),
+        page: None,
+    })
+}
+
+/// API handler for the local claim history
+#[utoipa::path(
+    get,
+    path = "/api/history",
+    tag = "coupons",
+    params(HistoryQuery, PageQuery),
+    responses((status = 200, description = "领取历史（分页，最新的在前）", body = HistoryResponse))
+)]
+pub async fn api_history_handler(
+    Query(filter): Query<HistoryQuery>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let (from, to) = match date_range(&filter.from, &filter.to) {
+        Ok(range) => range,
+        Err(message) => return history_error(message),
+    };
+
+    let history = match ClaimHistory::load() {
+        Ok(history) => history,
+        Err(e) => return history_error(format!("读取领取记录失败: {}", e)),
+    };
+    let entries = history.query(from, to, filter.account.as_deref().filter(|a| !a.is_empty()));
+    let (entries, page) = query.apply(entries);
+    Json(HistoryResponse {
+        success: true,
+        message: format!("共 {} 条领取记录", page.total),
+        entries,
+        page: Some(page),
+    })
+}
+
+/// API handler for the operation log shown in the web UI
+#[utoipa::path(
+    get,
+    path = "/api/logs",
+    tag = "stats",
+    params(PageQuery),
+    responses((status = 200, description = "操作日志（分页，最新的在前）", body = LogsResponse))
+)]
+pub async fn api_logs_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let logs: Vec<String> = state.lock().await.logs.iter().rev().cloned().collect();
+    let (logs, page) = query.apply(logs);
+    Json(LogsResponse {
+        success: true,
+        logs,
+        page,
+    })
+}
@@ -0,0 +1,177 @@
+use axum::{extract::{ConnectInfo, Path, State}, http::{header, HeaderMap, StatusCode}, response::{IntoResponse, Json, Response}};
+use serde::Serialize;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{audit, coupons::{parse_coupons_from_markdown, UsageStore}, error::McdError, images, inflight::{self, Existing, Operation, Outcome}, jobs::{self, Job, JobKind}, mcp::describe_error, web::{begin_claim, claim_all, conflict_response, forget_token, idempotency_key, versioning, WebAppState}};
+
+/// Reply about one job
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<Job>,
+}
+
+/// Jobs kept since startup, newest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobsResponse {
+    pub jobs: Vec<Job>,
+}
+
+fn reply(status: StatusCode, success: bool, message: impl Into<String>, job: Option<Job>) -> Response {
+    (status, Json(JobResponse { success, message: message.into(), job })).into_response()
+}
+
+/// 202 for a job that has just started, pointing at where to poll it
+fn accepted(job: Job) -> Response {
+    let location = format!("{}/jobs/{}", versioning::PREFIX, job.id);
+    let body = JobResponse { success: true, message: format!("已开始，请通过 {} 查询结果", location), job: Some(job) };
+    (StatusCode::ACCEPTED, [(header::LOCATION, location)], Json(body)).into_response()
+}
+
+/// Answer for an operation that is running or was retried by its idempotency key,
+/// with its job when it was started as one
+fn existing_job(existing: Existing) -> Response {
+    let job = jobs::get(existing.id());
+    match existing {
+        Existing::Running { same_key: true, .. } => reply(StatusCode::ACCEPTED, true, "任务仍在进行", job),
+        Existing::Running { operation, id, .. } => {
+            let message = inflight::Conflict { operation, id: id.clone() }.to_string();
+            conflict_response(&id, JobResponse { success: false, message, job })
+        },
+        Existing::Done { outcome, .. } => reply(StatusCode::OK, outcome.success, outcome.message, job),
+    }
+}
+
+/// API handler starting a claim in the background
+#[utoipa::path(
+    post,
+    path = "/api/jobs/claim",
+    tag = "jobs",
+    params(("Idempotency-Key" = Option<String>, Header, description = "重试时带上同一个值，返回第一次创建的任务而不再重复领取")),
+    responses(
+        (status = 202, description = "已开始领取，响应头 Location 为任务地址", body = JobResponse),
+        (status = 409, description = "已有领取正在进行，响应头 Operation-Id 为其操作 id", body = JobResponse),
+    )
+)]
+pub async fn api_claim_job_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let (client, ticket) = match begin_claim(&state, idempotency_key(&headers)).await {
+        Ok(started) => started,
+        Err(existing) => return existing_job(existing),
+    };
+    let Some(client) = client else {
+        return reply(StatusCode::OK, false, "请先设置Token", None);
+    };
+
+    let job = jobs::start(ticket.id(), JobKind::Claim);
+    let actor = audit::web_actor(peer.ip());
+    let id = job.id.clone();
+    tokio::spawn(async move {
+        match claim_all(&state, &client, &actor, ticket).await {
+            Ok(outcome) => {
+                let coupons = Some(state.lock().await.coupons.clone()).filter(|c| !c.is_empty());
+                jobs::finish(&id, outcome.success, outcome.message, coupons);
+            },
+            Err(McdError::TokenInvalid) => jobs::finish(&id, false, forget_token(&mut *state.lock().await), None),
+            Err(e) => jobs::finish(&id, false, format!("领取失败: {}", describe_error(&e)), None),
+        }
+    });
+    accepted(job)
+}
+
+/// API handler reloading the held coupons in the background
+#[utoipa::path(
+    post,
+    path = "/api/jobs/refresh",
+    tag = "jobs",
+    params(("Idempotency-Key" = Option<String>, Header, description = "重试时带上同一个值，返回第一次创建的任务")),
+    responses(
+        (status = 202, description = "已开始刷新，响应头 Location 为任务地址", body = JobResponse),
+        (status = 409, description = "已有刷新正在进行，响应头 Operation-Id 为其操作 id", body = JobResponse),
+    )
+)]
+pub async fn api_refresh_job_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    headers: HeaderMap,
+) -> Response {
+    let key = idempotency_key(&headers);
+    if let Some(existing) = inflight::existing(Operation::Refresh, key) {
+        return existing_job(existing);
+    }
+    let (client, ticket) = {
+        let mut guard = state.lock().await;
+        let Some(client) = guard.mcp_client.clone() else {
+            return reply(StatusCode::OK, false, "请先设置Token", None);
+        };
+        match inflight::begin(Operation::Refresh, &inflight::account(&guard.config.token), key) {
+            Ok(ticket) => {
+                guard.add_log("正在加载已领取的优惠券...".to_string());
+                (client, ticket)
+            },
+            Err(existing) => return existing_job(existing),
+        }
+    };
+
+    let job = jobs::start(ticket.id(), JobKind::Refresh);
+    let id = job.id.clone();
+    tokio::spawn(async move {
+        let result = client.get_my_coupons().await;
+        let mut state = state.lock().await;
+        let (success, message, coupons) = match result {
+            Ok(text) => {
+                let mut coupons = parse_coupons_from_markdown(&text);
+                UsageStore::load().unwrap_or_default().apply(&mut coupons);
+                images::prefetch(&coupons);
+                let message = format!("共找到 {} 张优惠券", coupons.len());
+                state.add_log(format!("优惠券加载成功！{}", message));
+                state.coupons = coupons.clone();
+                (true, message, Some(coupons))
+            },
+            Err(McdError::TokenInvalid) => (false, forget_token(&mut state), None),
+            Err(e) => {
+                let message = format!("优惠券加载失败: {}", describe_error(&e));
+                state.add_log(message.clone());
+                (false, message, None)
+            },
+        };
+        jobs::finish(&id, success, message.clone(), coupons);
+        ticket.finish(Outcome { success, message });
+    });
+    accepted(job)
+}
+
+/// API handler for the status, progress and result of one job. Does not take the state lock.
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    tag = "jobs",
+    params(("id" = String, Path, description = "任务 id，如 claim-3")),
+    responses(
+        (status = 200, description = "任务的状态、进度和结果", body = JobResponse),
+        (status = 404, description = "任务不存在或已过期", body = JobResponse),
+    )
+)]
+pub async fn api_job_handler(Path(id): Path<String>) -> Response {
+    match jobs::get(&id) {
+        Some(job) => reply(StatusCode::OK, true, job.message.clone().unwrap_or_else(|| "任务仍在进行".to_string()), Some(job)),
+        None => reply(StatusCode::NOT_FOUND, false, format!("任务 {} 不存在或已过期", id), None),
+    }
+}
+
+/// API handler listing the jobs kept since startup
+#[utoipa::path(
+    get,
+    path = "/api/jobs",
+    tag = "jobs",
+    responses((status = 200, description = "最近的后台任务，最新的在前", body = JobsResponse))
+)]
+pub async fn api_jobs_handler() -> impl IntoResponse {
+    Json(JobsResponse { jobs: jobs::list() })
+}
@@ -0,0 +1,69 @@
+use axum::{extract::State, response::{IntoResponse, Json}};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{coupons::{pick_by_title, MealPlan}, web::{stats::held_coupons, WebAppState}};
+
+/// Titles of the held coupons picked for the meal
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MealPayload {
+    pub titles: Vec<String>,
+}
+
+/// Meal plan built from the picked coupons
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MealResponse {
+    pub success: bool,
+    pub message: String,
+    pub plan: Option<MealPlan>,
+}
+
+/// API handler for building a store checklist from picked coupons
+#[utoipa::path(
+    post,
+    path = "/api/meal-plan",
+    tag = "coupons",
+    request_body = MealPayload,
+    responses((status = 200, description = "点餐清单：合计金额、冲突提示与清单文本", body = MealResponse))
+)]
+pub async fn api_meal_plan_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Json(payload): Json<MealPayload>,
+) -> impl IntoResponse {
+    if payload.titles.is_empty() {
+        return Json(MealResponse {
+            success: false,
+            message: "请至少选择一张优惠券".to_string(),
+            plan: None,
+        });
+    }
+
+    let coupons = match held_coupons(&state).await {
+        Ok(coupons) => coupons,
+        Err(message) => {
+            return Json(MealResponse {
+                success: false,
+                message,
+                plan: None,
+            })
+        }
+    };
+
+    let (picked, missing) = pick_by_title(&coupons, &payload.titles);
+    if !missing.is_empty() {
+        return Json(MealResponse {
+            success: false,
+            message: format!("未找到已领取的优惠券: {}", missing.join("、")),
+            plan: None,
+        });
+    }
+
+    let plan = MealPlan::build(&picked);
+    Json(MealResponse {
+        success: true,
+        message: format!("共 {} 张优惠券，合计 ¥{:.2}", plan.items.len(), plan.total),
+        plan: Some(plan),
+    })
+}
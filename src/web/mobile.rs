@@ -0,0 +1,106 @@
+use axum::{extract::{Query, State}, http::{header, HeaderMap}, response::{IntoResponse, Json}};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{utils::truncate_string, web::{paging::{PageInfo, PageQuery}, stats::held_coupons, WebAppState}};
+
+/// User-agent fragments of phone browsers, which get the compact layout by default
+const MOBILE_AGENTS: &[&str] = &["Mobi", "Android", "iPhone", "iPod", "HarmonyOS"];
+/// Columns of a compact title, about one line on a phone (a Chinese character takes two)
+const COMPACT_TITLE_WIDTH: usize = 28;
+
+/// `?layout=compact|full` on the page URL, overriding user-agent detection
+#[derive(Debug, Deserialize)]
+pub struct LayoutQuery {
+    pub layout: Option<String>,
+}
+
+/// Whether to render the compact small-screen layout
+pub fn wants_compact(query: &LayoutQuery, headers: &HeaderMap) -> bool {
+    match query.layout.as_deref() {
+        Some("compact") => true,
+        Some("full") => false,
+        _ => headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|agent| MOBILE_AGENTS.iter().any(|m| agent.contains(m))),
+    }
+}
+
+/// Held coupon with only the fields shown on a phone
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompactCoupon {
+    /// Shortened with "…" when longer than one line
+    pub title: String,
+    pub price: String,
+    /// Full expiry text, also the key for `/api/coupons/used`
+    pub expiry: String,
+    pub days_left: Option<i64>,
+    pub used: bool,
+    /// Copies upstream listed, shown as an "x2" badge
+    pub count: usize,
+    /// Estimated kcal, when `show_calories` is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calories: Option<u32>,
+    /// Opens the coupon in the McDonald's app, see `Coupon::redeem_link`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redeem_link: Option<String>,
+    /// Redemption code, shown as a QR code from `/api/coupons/code-qr`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+/// Compact coupon list for small screens
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompactResponse {
+    pub success: bool,
+    pub message: String,
+    pub coupons: Vec<CompactCoupon>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub page: Option<PageInfo>,
+}
+
+/// API handler for the compact coupon list
+#[utoipa::path(
+    get,
+    path = "/api/coupons/compact",
+    tag = "coupons",
+    params(PageQuery),
+    responses((status = 200, description = "精简字段的已领取优惠券列表（分页），适合手机端", body = CompactResponse))
+)]
+pub async fn api_compact_coupons_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    match held_coupons(&state).await {
+        Ok(coupons) => {
+            let (coupons, page) = query.apply(coupons);
+            Json(CompactResponse {
+                success: true,
+                message: format!("共找到 {} 张优惠券", page.total),
+                coupons: coupons.iter()
+                    .map(|c| CompactCoupon {
+                        title: truncate_string(&c.title, COMPACT_TITLE_WIDTH),
+                        price: c.price.clone(),
+                        expiry: c.expiry.clone(),
+                        days_left: c.days_left(),
+                        used: c.used,
+                        count: c.count,
+                        calories: c.calories,
+                        redeem_link: c.redeem_link().map(str::to_string),
+                        code: c.code.clone(),
+                    })
+                    .collect(),
+                page: Some(page),
+            })
+        }
+        Err(message) => Json(CompactResponse {
+            success: false,
+            message,
+            coupons: Vec::new(),
+            page: None,
+        }),
+    }
+}
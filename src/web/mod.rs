@@ -1,67 +1,121 @@
-use axum::{extract::State, response::{Html, IntoResponse, Json}, routing::{get, post}, Router};
+use axum::{extract::{ConnectInfo, Query, State}, http::{HeaderMap, StatusCode}, middleware, response::{Html, IntoResponse, Json, Response}, routing::{get, post}, Router};
 use handlebars::Handlebars;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::{broadcast, Mutex};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use crate::{mcp::McpClient, config::Config, utils::open_mcp_login_page};
-
-/// Coupon structure for template rendering
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Coupon {
-    pub title: String,
-    pub price: String,
-    pub expiry: String,
-    pub receive_time: String,
-    pub tags: String,
-    pub image_url: String,
-}
+use utoipa::ToSchema;
+use tower_http::compression::CompressionLayer;
+use utoipa_swagger_ui::SwaggerUi;
+use crate::{allowlist::{self, Allowlist}, audit::{self, Action}, backup, control, error::McdError, images, inflight::{self, Existing, Operation, Outcome, Ticket}, logging, mcp::{describe_error, McpClient}, mqtt, progress::{self, ClaimRun}, retention, scheduler, config::{Config, ConfigService}, coupons::{parse_coupons_from_markdown, ClaimHistory, ClaimVerification, Coupon, UsageStore}, utils::{mask_token, open_mcp_login_page}};
+
+mod audit_api;
+mod claim_refresh;
+mod compare;
+mod control_api;
+mod coupon_images;
+mod embedded_assets;
+mod etag;
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod history;
+mod jobs;
+mod meal;
+mod mobile;
+mod openapi;
+mod paging;
+mod plan;
+mod progress_api;
+mod pwa;
+mod requests;
+mod server_time;
+mod share_api;
+mod stats;
+mod template_data;
+mod usage;
+mod versioning;
+
+use mobile::LayoutQuery;
+use paging::{PageInfo, PageQuery};
+use stats::ClaimRecord;
+use template_data::CouponData;
 
 /// Serializable view of the application state for templates
 #[derive(Debug, Serialize)]
 pub struct AppStateView {
     pub has_token: bool,
+    /// Tab shown first once a token is set: "dashboard" or "claim"
+    pub page: &'static str,
+    /// Small-screen layout with fewer fields and larger tap targets
+    pub compact: bool,
+    /// Cache-busting suffix of the stylesheet and script URLs
+    pub asset_version: &'static str,
+    /// Last loaded coupons, for the first paint before the script fetches them
+    pub coupons: Vec<CouponData>,
+    /// Warning about other machines being able to use the page
+    pub bind_warning: Option<String>,
 }
 
 impl AppStateView {
-    fn from_state(state: &WebAppState) -> Self {
+    fn from_state(state: &WebAppState, page: &'static str, compact: bool) -> Self {
         Self {
             has_token: state.mcp_client.is_some(),
+            page,
+            compact,
+            asset_version: embedded_assets::version(),
+            coupons: state.coupons.iter().map(CouponData::from).collect(),
+            bind_warning: state.bind_warning.clone(),
         }
     }
 }
 
 /// API Response structure
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ApiResponse {
     pub success: bool,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub coupons: Option<Vec<Coupon>>,
+    /// Page position, on paginated list responses
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub page: Option<PageInfo>,
 }
 
-/// Web application state
+/// Web application state. Handlers lock it only for short reads and writes: the client is
+/// cloned out before upstream calls and results are stored after, so the page and the
+/// read-only endpoints stay responsive while a claim runs.
 pub struct WebAppState {
-    pub mcp_client: Option<Arc<Mutex<McpClient>>>,
-    pub config: Config,
+    pub mcp_client: Option<Arc<McpClient>>,
+    pub config: Arc<Config>,
     pub logs: Vec<String>,
     pub coupons: Vec<Coupon>,
+    pub last_claim: Option<ClaimRecord>,
     pub handlebars: Handlebars<'static>,
+    /// Every log line as it is added, for followers such as the gRPC `StreamLogs`
+    pub log_events: broadcast::Sender<String>,
+    /// Shown on every page while listening beyond loopback with no `allowed_ips`
+    pub bind_warning: Option<String>,
 }
 
 impl WebAppState {
-    pub fn new(config: Config, handlebars: Handlebars<'static>) -> Self {
+    pub fn new(config: Arc<Config>, handlebars: Handlebars<'static>) -> Self {
         Self {
             mcp_client: None,
             config,
             logs: vec!["应用已启动...".to_string()],
             coupons: Vec::new(),
+            last_claim: None,
             handlebars,
+            log_events: broadcast::channel(100).0,
+            bind_warning: None,
         }
     }
 
     pub fn add_log(&mut self, message: String) {
-        println!("[LOG] {}", message);
+        tracing::info!("{}", message);
+        // No followers is the common case
+        let _ = self.log_events.send(message.clone());
         self.logs.push(message);
         // Keep only the last 100 logs
         if self.logs.len() > 100 {
@@ -70,30 +124,51 @@ impl WebAppState {
     }
 
     pub async fn init_mcp_client(&mut self, token: String) -> Result<()> {
-        let client = McpClient::new(token)?;
-        self.mcp_client = Some(Arc::new(Mutex::new(client)));
+        let client = McpClient::with_url(token, self.config.upstream_url())?;
+        self.mcp_client = Some(Arc::new(client));
         Ok(())
     }
 }
 
-/// Initialize the web application
-pub async fn run() -> Result<()> {
+/// Ports the web mode tries, in order: `web_port` (default 8080) and the 920 after it
+pub fn ports(config: &Config) -> std::ops::RangeInclusive<u16> {
+    let first = config.web_port.unwrap_or(8080);
+    first..=first.saturating_add(920)
+}
+
+/// Initialize the web application. `open_browser` is off when running unattended, e.g. under `daemon`.
+pub async fn run(open_browser: bool) -> Result<()> {
     // Load configuration
-    let config = Config::load()?;
+    let config = ConfigService::global().current();
+    let allowlist = Arc::new(Allowlist::from_config(&config.allowed_ips, "allowed_ips")?);
+    let bind = allowlist::bind_address(config.web_bind.as_deref(), "web_bind")?;
+    let bind_warning = allowlist::check_bind(&config, bind, "web_bind")?;
 
     // Set up Handlebars template engine
     let mut handlebars = Handlebars::new();
+    template_data::register_helpers(&mut handlebars);
 
     // Embed template into binary (no external file dependency)
     const INDEX_TEMPLATE: &str = include_str!("templates/index.html");
     handlebars.register_template_string("index", INDEX_TEMPLATE)?;
 
+    backup::spawn_scheduler(&config);
+    retention::spawn(&config);
+    scheduler::spawn(&config)?;
+    mqtt::spawn(&config);
+
     // Create application state
+    let ports = ports(&config);
+    let grpc_port = config.grpc_port;
     let app_state = Arc::new(Mutex::new(WebAppState::new(config, handlebars)));
 
     // Check if token exists and initialize MCP client
     {
         let mut state = app_state.lock().await;
+        if let Some(warning) = bind_warning {
+            tracing::warn!("{}", warning);
+            state.bind_warning = Some(warning);
+        }
         if state.config.has_valid_token() {
             let token = state.config.token.clone();
             match state.init_mcp_client(token).await {
@@ -106,45 +181,119 @@ pub async fn run() -> Result<()> {
             }
         } else {
             println!("未检测到已保存的 Token，请在浏览器打开获取：https://open.mcd.cn/mcp/login");
-            open_mcp_login_page("https://open.mcd.cn/mcp/login");
+            if open_browser {
+                open_mcp_login_page("https://open.mcd.cn/mcp/login");
+            }
         }
     }
 
+    // gRPC server sharing the web state, when configured
+    match grpc_port {
+        #[cfg(feature = "grpc")]
+        Some(port) => grpc::spawn(port, app_state.clone(), allowlist.clone()),
+        #[cfg(not(feature = "grpc"))]
+        Some(_) => tracing::warn!("已配置 grpc_port，但当前版本未包含 gRPC 支持，请使用 --features grpc 重新编译"),
+        None => {},
+    }
+
+    // Local control socket for scripts, answered with the same state
+    control_api::spawn(app_state.clone());
+
+    // JSON API, served under /api/v1 and, deprecated, under /api for one release
+    let api = Router::new()
+        .route("/token", post(api_token_handler))
+        .route("/token/history", get(api_token_history_handler))
+        .route("/coupons", get(api_coupons_handler).layer(middleware::from_fn(etag::etag)))
+        .route("/coupons/used", post(usage::api_toggle_used_handler))
+        .route("/coupons/share-card", get(share_api::api_share_card_handler))
+        .route("/coupons/code-qr", get(share_api::api_code_qr_handler))
+        .route("/coupons/compact", get(mobile::api_compact_coupons_handler).layer(middleware::from_fn(etag::etag)))
+        .route("/claim", post(api_claim_handler))
+        .route("/claim-and-refresh", post(claim_refresh::api_claim_and_refresh_handler))
+        .route("/reset", post(api_reset_handler))
+        .route("/plan", get(plan::api_plan_handler))
+        .route("/history", get(history::api_history_handler))
+        .route("/logs", get(history::api_logs_handler))
+        .route("/audit", get(audit_api::api_audit_handler))
+        .route("/requests", get(requests::api_requests_handler).post(requests::api_submit_request_handler))
+        .route("/requests/coupons", get(requests::api_requestable_coupons_handler))
+        .route("/requests/:id/approve", post(requests::api_approve_request_handler))
+        .route("/requests/:id/reject", post(requests::api_reject_request_handler))
+        .route("/accounts/compare", get(compare::api_compare_accounts_handler))
+        .route("/meal-plan", post(meal::api_meal_plan_handler))
+        .route("/server-time", get(server_time::api_server_time_handler))
+        .route("/progress", get(progress_api::api_progress_handler))
+        // Background jobs, polled by id
+        .route("/jobs", get(jobs::api_jobs_handler))
+        .route("/jobs/claim", post(jobs::api_claim_job_handler))
+        .route("/jobs/refresh", post(jobs::api_refresh_job_handler))
+        .route("/jobs/:id", get(jobs::api_job_handler))
+        // Dashboard aggregate routes
+        .route("/stats", get(stats::api_stats_handler).layer(middleware::from_fn(etag::etag)))
+        .route("/stats/coupons", get(stats::api_coupon_stats_handler))
+        .route("/stats/savings", get(stats::api_savings_stats_handler))
+        .route("/stats/last-claim", get(stats::api_last_claim_handler))
+        .route("/stats/scheduler", get(stats::api_scheduler_status_handler))
+        .route("/stats/upstream", get(stats::api_upstream_stats_handler))
+        .route("/stats/tags", get(stats::api_tag_stats_handler))
+        .layer(middleware::from_fn(versioning::stamp));
+
     // Build the router
     let app = Router::new()
         // Main page
         .route("/", get(index_handler))
+        .route("/dashboard", get(index_handler))
+        .route("/claim", get(claim_page_handler))
+        .route("/assets/:file", get(embedded_assets::asset_handler))
+        .route("/images", get(coupon_images::image_handler))
+        // Installable app (PWA) assets
+        .route("/manifest.json", get(pwa::manifest_handler))
+        .route("/sw.js", get(pwa::service_worker_handler))
+        .route("/icons/icon-192.png", get(pwa::icon_192_handler))
+        .route("/icons/icon-512.png", get(pwa::icon_512_handler))
         // API routes
-        .route("/api/token", post(api_token_handler))
-        .route("/api/coupons", get(api_coupons_handler))
-        .route("/api/claim", post(api_claim_handler))
-        .route("/api/reset", post(api_reset_handler))
+        .nest(versioning::PREFIX, api.clone())
+        .nest("/api", api.layer(middleware::from_fn(versioning::deprecated)))
+        // GraphQL endpoint and GraphiQL page
+        .route("/graphql", get(graphql::graphiql_handler).post(graphql::graphql_handler))
+        // OpenAPI spec and Swagger UI
+        .merge(SwaggerUi::new("/api/docs").url("/api/openapi.json", openapi::document()))
         // Add state
-        .with_state(app_state);
-
-    // Try to bind to a port, starting from 8080
-    let mut port = 8080u16;
+        .with_state(app_state)
+        .layer(middleware::from_fn_with_state(allowlist, allowlist::enforce))
+        // gzip / brotli for clients that accept it; small responses are left as is
+        .layer(CompressionLayer::new())
+        .layer(logging::access::layer());
+
+    // Try to bind to a port, starting from the configured one
+    let mut port = *ports.start();
     let listener = loop {
-        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        let addr = std::net::SocketAddr::new(bind, port);
         match tokio::net::TcpListener::bind(addr).await {
             Ok(listener) => break listener,
             Err(_) => {
                 port += 1;
-                if port > 9000 {
-                    return Err(anyhow::anyhow!("无法找到可用端口 (8080-9000)"));
+                if port > *ports.end() {
+                    return Err(anyhow::anyhow!("无法找到可用端口 ({}-{})", ports.start(), ports.end()));
                 }
             }
         }
     };
 
-    let url = format!("http://127.0.0.1:{}", port);
+    // A wildcard address is opened on this machine through loopback
+    let local = if bind.is_unspecified() { std::net::Ipv4Addr::LOCALHOST.into() } else { bind };
+    let url = format!("http://{}", std::net::SocketAddr::new(local, port));
     println!("HTML模式已启动，访问地址: {}", url);
 
     // Open browser in incognito/private mode
-    open_browser_incognito(&url);
+    if open_browser {
+        open_browser_incognito(&url);
+    }
 
     // Serve the app
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .with_graceful_shutdown(control::stopped())
+        .await?;
 
     Ok(())
 }
@@ -272,12 +421,30 @@ fn open_browser_incognito(url: &str) {
     }
 }
 
-/// Handler for the index page
-async fn index_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
+/// Handler for the index page, opening on the dashboard tab
+async fn index_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<LayoutQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    render_index(state, "dashboard", mobile::wants_compact(&query, &headers)).await
+}
+
+/// Handler for the claim page, opening on the claim tab
+async fn claim_page_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<LayoutQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    render_index(state, "claim", mobile::wants_compact(&query, &headers)).await
+}
+
+/// Render the single-page UI with the given tab active
+async fn render_index(state: Arc<Mutex<WebAppState>>, page: &'static str, compact: bool) -> axum::response::Response {
     let state = state.lock().await;
 
     // Render main page with has_token flag
-    let view = AppStateView::from_state(&state);
+    let view = AppStateView::from_state(&state, page, compact);
     match state.handlebars.render("index", &view) {
         Ok(html) => Html(html).into_response(),
         Err(e) => {
@@ -288,30 +455,67 @@ async fn index_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl Int
 }
 
 /// API handler for token submission
+#[utoipa::path(
+    post,
+    path = "/api/token",
+    tag = "token",
+    request_body = TokenPayload,
+    responses((status = 200, description = "验证并保存Token的结果", body = ApiResponse))
+)]
 async fn api_token_handler(
     State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     Json(payload): Json<TokenPayload>,
 ) -> impl IntoResponse {
-    let mut state = state.lock().await;
+    let (history_token, upstream_url) = {
+        let state = state.lock().await;
+        let entry = payload.history.map(|index| state.config.token_history.get(index).map(|entry| entry.token.clone()));
+        (entry, state.config.upstream_url().map(str::to_string))
+    };
+
+    // A history entry, when selected, stands in for a pasted token
+    let token = match history_token {
+        Some(entry) => match entry {
+            Some(token) => token,
+            None => {
+                return Json(ApiResponse {
+                    success: false,
+                    message: "所选的历史Token不存在，请刷新页面后重试".to_string(),
+                    coupons: None,
+                    page: None,
+                });
+            }
+        },
+        None => payload.token,
+    };
 
     // Format token if needed
-    let formatted_token = if payload.token.starts_with("Bearer ") {
-        payload.token.clone()
+    let formatted_token = if token.starts_with("Bearer ") {
+        token
     } else {
-        format!("Bearer {}", payload.token)
+        format!("Bearer {}", token)
     };
 
-    // Validate token
-    match McpClient::new(formatted_token.clone()) {
+    // Validate token, without holding the state during the upstream call
+    match McpClient::with_url(formatted_token.clone(), upstream_url.as_deref()) {
         Ok(client) => {
-            match client.validate_token().await {
+            let valid = client.validate_token().await;
+            let mut state = state.lock().await;
+            match valid {
                 Ok(true) => {
                     // Save token
-                    state.config.token = formatted_token.clone();
-                    state.config.save().ok();
+                    let saved = ConfigService::global().update(|config| {
+                        config.set_token(formatted_token.clone());
+                        config.remember_token(&formatted_token, payload.nickname);
+                    });
+                    match saved {
+                        Ok(config) => state.config = config,
+                        Err(e) => state.add_log(format!("保存配置失败: {}", e)),
+                    }
+                    audit::record(&audit::web_actor(peer.ip()), Action::TokenSet, true, mask_token(&formatted_token));
 
                     // Initialize MCP client
-                    state.mcp_client = Some(Arc::new(Mutex::new(client)));
+                    state.mcp_client = Some(Arc::new(client));
 
                     // Add logs
                     state.add_log("Token验证成功！".to_string());
@@ -321,6 +525,7 @@ async fn api_token_handler(
                         success: true,
                         message: "Token验证成功！".to_string(),
                         coupons: None,
+                        page: None,
                     })
                 },
                 Ok(false) => {
@@ -329,6 +534,7 @@ async fn api_token_handler(
                         success: false,
                         message: "Token无效，请重新输入".to_string(),
                         coupons: None,
+                        page: None,
                     })
                 },
                 Err(e) => {
@@ -337,220 +543,297 @@ async fn api_token_handler(
                         success: false,
                         message: format!("验证失败: {}", e),
                         coupons: None,
+                        page: None,
                     })
                 }
             }
         },
         Err(e) => {
-            state.add_log(format!("创建客户端失败: {}", e));
+            state.lock().await.add_log(format!("创建客户端失败: {}", e));
             Json(ApiResponse {
                 success: false,
                 message: format!("创建客户端失败: {}", e),
                 coupons: None,
+                page: None,
             })
         }
     }
 }
 
-/// Parse markdown text to extract coupons
-fn parse_coupons_from_markdown(text: &str) -> Vec<Coupon> {
-    let mut coupons = Vec::new();
-    let mut current_title = String::new();
-    let mut current_price = String::new();
-    let mut current_expiry = String::new();
-    let mut current_receive_time = String::new();
-    let mut current_tags = String::new();
-    let mut current_image_url = String::new();
-
-    for line in text.lines() {
-        let line = line.trim();
-
-        // Skip empty lines and header
-        if line.is_empty() || line.starts_with("# ") || line.starts_with("共 ") {
-            continue;
-        }
-
-        // New coupon title (## 标题)
-        if line.starts_with("## ") {
-            // Save previous coupon if exists
-            if !current_title.is_empty() {
-                coupons.push(Coupon {
-                    title: current_title.clone(),
-                    price: current_price.clone(),
-                    expiry: current_expiry.clone(),
-                    receive_time: current_receive_time.clone(),
-                    tags: current_tags.clone(),
-                    image_url: current_image_url.clone(),
-                });
-            }
-            // Start new coupon
-            current_title = line.trim_start_matches("## ").to_string();
-            current_price.clear();
-            current_expiry.clear();
-            current_receive_time.clear();
-            current_tags.clear();
-            current_image_url.clear();
-        }
-        // Parse coupon details
-        else if line.starts_with("- **优惠**:") {
-            current_price = line.trim_start_matches("- **优惠**:").trim().to_string();
-        }
-        else if line.starts_with("- **有效期**:") {
-            current_expiry = line.trim_start_matches("- **有效期**:").trim().to_string();
-        }
-        else if line.starts_with("- **领取时间**:") {
-            current_receive_time = line.trim_start_matches("- **领取时间**:").trim().to_string();
-        }
-        else if line.starts_with("- **标签**:") {
-            current_tags = line.trim_start_matches("- **标签**:").trim().to_string();
-        }
-        // Parse image URL
-        else if line.starts_with("<img") {
-            // Extract src from <img src="..." ...>
-            if let Some(start) = line.find("src=\"") {
-                let rest = &line[start + 5..];
-                if let Some(end) = rest.find('"') {
-                    current_image_url = rest[..end].to_string();
-                }
-            }
-        }
-    }
-
-    // Don't forget the last coupon
-    if !current_title.is_empty() {
-        coupons.push(Coupon {
-            title: current_title,
-            price: current_price,
-            expiry: current_expiry,
-            receive_time: current_receive_time,
-            tags: current_tags,
-            image_url: current_image_url,
-        });
-    }
+/// Drop the client after upstream rejected its token, so the page asks for a new one
+pub(super) fn forget_token(state: &mut WebAppState) -> String {
+    let message = McdError::TokenInvalid.to_string();
+    state.add_log(message.clone());
+    state.mcp_client = None;
+    message
+}
 
-    coupons
+/// 401 reply for a token upstream rejected
+pub(super) fn token_invalid(state: &mut WebAppState) -> Response {
+    let message = forget_token(state);
+    (StatusCode::UNAUTHORIZED, Json(ApiResponse {
+        success: false,
+        message,
+        coupons: None,
+        page: None,
+    }))
+    .into_response()
 }
 
 /// API handler for getting coupons
-async fn api_coupons_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
-    let mut state = state.lock().await;
+#[utoipa::path(
+    get,
+    path = "/api/coupons",
+    tag = "coupons",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "已领取的优惠券列表（分页）", body = ApiResponse),
+        (status = 401, description = "Token 已失效，需要重新设置", body = ApiResponse),
+    )
+)]
+async fn api_coupons_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<PageQuery>,
+) -> Response {
+    let client = {
+        let mut state = state.lock().await;
+        let client = state.mcp_client.clone();
+        if client.is_some() {
+            state.add_log("正在加载已领取的优惠券...".to_string());
+        }
+        client
+    };
 
     // If no token, return error
-    if state.mcp_client.is_none() {
+    let Some(client) = client else {
         return Json(ApiResponse {
             success: false,
             message: "请先设置Token".to_string(),
             coupons: None,
-        });
-    }
+            page: None,
+        }).into_response();
+    };
 
-    // Load coupons
-    state.add_log("正在加载已领取的优惠券...".to_string());
-    if let Some(client) = state.mcp_client.clone() {
-        match client.lock().await.get_my_coupons().await {
-            Ok(coupons_text) => {
-                state.add_log(format!("原始数据: {}", coupons_text));
-
-                // Parse markdown text to extract coupons
-                let coupons = parse_coupons_from_markdown(&coupons_text);
-                let coupon_count = coupons.len();
-
-                if coupon_count > 0 {
-                    state.add_log(format!("优惠券加载成功！共找到 {} 张优惠券", coupon_count));
-                    state.coupons = coupons.clone();
-                    return Json(ApiResponse {
-                        success: true,
-                        message: format!("共找到 {} 张优惠券", coupon_count),
-                        coupons: Some(coupons),
-                    });
-                } else {
-                    state.add_log("未解析到优惠券数据".to_string());
-                    return Json(ApiResponse {
-                        success: true,
-                        message: "暂无优惠券".to_string(),
-                        coupons: Some(vec![]),
-                    });
-                }
-            },
-            Err(e) => {
-                state.add_log(format!("优惠券加载失败: {}", e));
-                return Json(ApiResponse {
-                    success: false,
-                    message: format!("优惠券加载失败: {}", e),
-                    coupons: None,
-                });
+    // Load coupons, locking the state again only once they are in
+    let result = client.get_my_coupons().await;
+    let mut state = state.lock().await;
+    match result {
+        Ok(coupons_text) => {
+            state.add_log(format!("原始数据: {}", coupons_text));
+
+            // Parse markdown text to extract coupons
+            let mut coupons = parse_coupons_from_markdown(&coupons_text);
+            UsageStore::load().unwrap_or_default().apply(&mut coupons);
+            images::prefetch(&coupons);
+            let coupon_count = coupons.len();
+
+            if coupon_count > 0 {
+                state.add_log(format!("优惠券加载成功！共找到 {} 张优惠券", coupon_count));
+                state.coupons = coupons.clone();
+                let (coupons, page) = query.apply(coupons);
+                Json(ApiResponse {
+                    success: true,
+                    message: format!("共找到 {} 张优惠券", coupon_count),
+                    coupons: Some(coupons),
+                    page: Some(page),
+                }).into_response()
+            } else {
+                state.add_log("未解析到优惠券数据".to_string());
+                Json(ApiResponse {
+                    success: true,
+                    message: "暂无优惠券".to_string(),
+                    coupons: Some(vec![]),
+                    page: Some(query.apply(Vec::<Coupon>::new()).1),
+                }).into_response()
             }
+        },
+        Err(McdError::TokenInvalid) => token_invalid(&mut state),
+        Err(e) => {
+            let message = format!("优惠券加载失败: {}", describe_error(&e));
+            state.add_log(message.clone());
+            Json(ApiResponse {
+                success: false,
+                message,
+                coupons: None,
+                page: None,
+            }).into_response()
         }
     }
-
-    Json(ApiResponse {
-        success: false,
-        message: "未知错误".to_string(),
-        coupons: None,
-    })
 }
 
 /// API handler for claiming all coupons
-async fn api_claim_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
-    let mut state = state.lock().await;
+#[utoipa::path(
+    post,
+    path = "/api/claim",
+    tag = "coupons",
+    params(("Idempotency-Key" = Option<String>, Header, description = "重试时带上同一个值，返回第一次领取的结果而不再重复领取")),
+    responses(
+        (status = 200, description = "一键领取的结果", body = ApiResponse),
+        (status = 401, description = "Token 已失效，需要重新设置", body = ApiResponse),
+        (status = 409, description = "已有领取正在进行，响应头 Operation-Id 为其操作 id", body = ApiResponse),
+    )
+)]
+async fn api_claim_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let (client, ticket) = match begin_claim(&state, idempotency_key(&headers)).await {
+        Ok(started) => started,
+        Err(existing) => return match existing.resolve().await {
+            Ok(outcome) => Json(ApiResponse {
+                success: outcome.success,
+                message: outcome.message,
+                coupons: None,
+                page: None,
+            }).into_response(),
+            Err(conflict) => conflict_response(&conflict.id, ApiResponse {
+                success: false,
+                message: conflict.to_string(),
+                coupons: None,
+                page: None,
+            }),
+        },
+    };
 
     // If no token, return error
-    if state.mcp_client.is_none() {
+    let Some(client) = client else {
         return Json(ApiResponse {
             success: false,
             message: "请先设置Token".to_string(),
             coupons: None,
-        });
+            page: None,
+        }).into_response();
+    };
+
+    match claim_all(&state, &client, &audit::web_actor(peer.ip()), ticket).await {
+        Ok(outcome) => Json(ApiResponse {
+            success: outcome.success,
+            message: outcome.message,
+            coupons: None,
+            page: None,
+        }).into_response(),
+        Err(McdError::TokenInvalid) => token_invalid(&mut *state.lock().await),
+        Err(e) => Json(ApiResponse {
+            success: false,
+            message: format!("领取失败: {}", describe_error(&e)),
+            coupons: None,
+            page: None,
+        }).into_response(),
     }
+}
 
-    // Claim all coupons
-    state.add_log("正在领取所有优惠券...".to_string());
-    if let Some(client) = state.mcp_client.clone() {
-        match client.lock().await.auto_bind_coupons().await {
-            Ok(result) => {
-                state.add_log("领取成功！".to_string());
-                // Add result to logs
-                for line in result.lines().take(5) {
-                    if !line.trim().is_empty() {
-                        state.add_log(line.to_string());
-                    }
+/// `Idempotency-Key` header of a request
+pub(super) fn idempotency_key(headers: &HeaderMap) -> Option<&str> {
+    headers.get("idempotency-key")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+/// 409 for a request arriving while a claim runs, naming it in `Operation-Id`
+pub(super) fn conflict_response(id: &str, body: impl Serialize) -> Response {
+    (StatusCode::CONFLICT, [("operation-id", id.to_string())], Json(body)).into_response()
+}
+
+/// Start a claim with the current client (None without a token), unless a claim is
+/// already running or `key` retries one
+pub(super) async fn begin_claim(state: &Mutex<WebAppState>, key: Option<&str>) -> std::result::Result<(Option<Arc<McpClient>>, Ticket), Existing> {
+    let state = state.lock().await;
+    let ticket = inflight::begin(Operation::Claim, &inflight::account(&state.config.token), key)?;
+    Ok((state.mcp_client.clone(), ticket))
+}
+
+/// Claim all coupons with `client`, recording the run and caching the refreshed list.
+/// The state is only locked before and after the upstream calls. Failures are logged
+/// here; the caller only shapes the reply. Returns the outcome of a claim upstream accepted,
+/// with the coupons it was verified to add; one that bound nothing is not a success.
+/// The outcome also goes to `ticket`, for requests that arrived meanwhile.
+pub(super) async fn claim_all(state: &Mutex<WebAppState>, client: &McpClient, actor: &str, ticket: Ticket) -> std::result::Result<Outcome, McdError> {
+    state.lock().await.add_log("正在领取所有优惠券...".to_string());
+    let run = progress::claim(client, "web").await;
+    settle_claim(&mut *state.lock().await, run, actor, ticket)
+}
+
+/// Record a finished claim run: history, audit, MQTT, the ticket and the cached list
+fn settle_claim(state: &mut WebAppState, run: std::result::Result<ClaimRun, McdError>, actor: &str, ticket: Ticket) -> std::result::Result<Outcome, McdError> {
+    let (result, mine, verification) = ClaimRun::split(run);
+    let success = ClaimVerification::succeeded(&result, verification.as_ref());
+    ClaimHistory::record("web", &result, verification.as_ref());
+    audit::record_claim(actor, &result, verification.as_ref());
+    mqtt::publish_claim("web", &result, verification.as_ref());
+    ticket.finish(match &result {
+        Ok(_) => Outcome { success, message: ClaimVerification::message(verification.as_ref()) },
+        Err(e) => Outcome { success: false, message: format!("领取失败: {}", describe_error(e)) },
+    });
+    match result {
+        Ok(result) => {
+            let message = ClaimVerification::message(verification.as_ref());
+            state.add_log(message.clone());
+            state.last_claim = Some(ClaimRecord::now(success, message.clone()));
+            // Add result to logs
+            for line in result.lines().take(5) {
+                if !line.trim().is_empty() {
+                    state.add_log(line.to_string());
                 }
-                // Clear cached coupons so they will be reloaded
-                state.coupons.clear();
-                return Json(ApiResponse {
-                    success: true,
-                    message: "领取成功！".to_string(),
-                    coupons: None,
-                });
-            },
-            Err(e) => {
-                state.add_log(format!("领取失败: {}", e));
-                return Json(ApiResponse {
-                    success: false,
-                    message: format!("领取失败: {}", e),
-                    coupons: None,
-                });
             }
+            // Cache the refreshed list, or clear it so it will be reloaded
+            state.coupons = match mine {
+                Some(text) => {
+                    let mut coupons = parse_coupons_from_markdown(&text);
+                    UsageStore::load().unwrap_or_default().apply(&mut coupons);
+                    coupons
+                },
+                None => Vec::new(),
+            };
+            Ok(Outcome { success, message })
+        },
+        Err(McdError::TokenInvalid) => {
+            state.last_claim = Some(ClaimRecord::now(false, McdError::TokenInvalid.to_string()));
+            Err(McdError::TokenInvalid)
+        },
+        Err(e) => {
+            let message = format!("领取失败: {}", describe_error(&e));
+            state.add_log(message.clone());
+            state.last_claim = Some(ClaimRecord::now(false, message));
+            Err(e)
         }
     }
+}
+
+/// API handler for resetting token
+#[utoipa::path(
+    post,
+    path = "/api/reset",
+    tag = "token",
+    responses((status = 200, description = "重置Token的结果", body = ApiResponse))
+)]
+async fn api_reset_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let mut state = state.lock().await;
+    reset_token(&mut state, &audit::web_actor(peer.ip()));
 
     Json(ApiResponse {
-        success: false,
-        message: "未知错误".to_string(),
+        success: true,
+        message: "Token已重置".to_string(),
         coupons: None,
+        page: None,
     })
 }
 
-/// API handler for resetting token
-async fn api_reset_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
-    let mut state = state.lock().await;
-
+/// Forget the saved token and the coupons loaded with it
+pub(super) fn reset_token(state: &mut WebAppState, actor: &str) {
     // Clear token
     state.mcp_client = None;
 
     // Remove token from config
-    state.config.token = String::new();
-    state.config.save().ok();
+    match ConfigService::global().update(|config| config.token = String::new()) {
+        Ok(config) => state.config = config,
+        Err(e) => state.add_log(format!("保存配置失败: {}", e)),
+    }
+    audit::record(actor, Action::TokenReset, true, "");
 
     // Clear coupons
     state.coupons.clear();
@@ -558,16 +841,47 @@ async fn api_reset_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl
     // Add logs
     state.add_log("Token已重置".to_string());
     state.add_log("请输入新的MCP Token".to_string());
-
-    Json(ApiResponse {
-        success: true,
-        message: "Token已重置".to_string(),
-        coupons: None,
-    })
 }
 
 /// Payload for token API
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TokenPayload {
+    #[serde(default)]
     pub token: String,
+    /// Nickname saved with the token in the history
+    #[serde(default)]
+    pub nickname: Option<String>,
+    /// Index into `/api/token/history` to reuse a previous token instead of `token`
+    #[serde(default)]
+    pub history: Option<usize>,
+}
+
+/// A previously validated token, masked for display
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenHistoryItem {
+    pub index: usize,
+    pub nickname: Option<String>,
+    pub masked: String,
+    pub last_valid: String,
+}
+
+/// API handler listing recently validated tokens
+#[utoipa::path(
+    get,
+    path = "/api/token/history",
+    tag = "token",
+    responses((status = 200, description = "最近验证通过的Token（已打码），最新的在前", body = Vec<TokenHistoryItem>))
+)]
+async fn api_token_history_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
+    let state = state.lock().await;
+    let items: Vec<TokenHistoryItem> = state.config.token_history.iter()
+        .enumerate()
+        .map(|(index, entry)| TokenHistoryItem {
+            index,
+            nickname: entry.nickname.clone(),
+            masked: mask_token(&entry.token),
+            last_valid: entry.last_valid.clone(),
+        })
+        .collect();
+    Json(items)
 }
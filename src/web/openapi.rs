@@ -0,0 +1,80 @@
+use utoipa::OpenApi;
+
+use crate::{audit::{Action as AuditAction, AuditEntry}, jobs::{Job, JobKind, JobStatus}, mcp::metrics::UpstreamStats, progress::{Progress, Stage}, queue::{ClaimRequest, RequestStatus}, scheduler::SchedulerStatus, coupons::{AccountComparison, ClaimEntry, ClaimPlan, MealItem, MealPlan, PlanConflict, TagStats}, web::{audit_api::AuditResponse, claim_refresh::{ClaimOutcome, ClaimRefreshResponse, CouponClaimResult}, compare::CompareResponse, history::{HistoryResponse, LogsResponse}, jobs::{JobResponse, JobsResponse}, meal::{MealPayload, MealResponse}, paging::PageInfo, mobile::{CompactCoupon, CompactResponse}, plan::PlanResponse, progress_api::ProgressResponse, requests::{RejectPayload, RequestResponse, RequestableResponse, RequestsResponse, SubmitPayload}, server_time::ServerTimeResponse, stats::*, usage::{UsedPayload, UsedResponse}, versioning, ApiResponse, Coupon, TokenHistoryItem, TokenPayload}};
+
+/// OpenAPI document for the web API, served at `/api/openapi.json`.
+/// Handlers declare their unversioned paths; `document` moves them under `versioning::PREFIX`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "麦当劳优惠券自动领取工具 API",
+        description = "网页模式提供的 HTTP 接口，可供快捷指令、n8n、Tasker 等自动化工具调用。\n\n接口位于 `/api/v1` 下，JSON 对象响应都带有 `api_version` 字段。同一版本内只会新增字段；重命名、删除字段或改变含义时会启用新版本路径。不带版本的 `/api/...` 旧路径暂时保留一个版本，响应头带有 `Deprecation: true` 和指向新路径的 `Link`。",
+        version = "1",
+    ),
+    paths(
+        super::api_token_handler,
+        super::api_token_history_handler,
+        super::api_coupons_handler,
+        super::usage::api_toggle_used_handler,
+        super::share_api::api_share_card_handler,
+        super::share_api::api_code_qr_handler,
+        super::mobile::api_compact_coupons_handler,
+        super::api_claim_handler,
+        super::claim_refresh::api_claim_and_refresh_handler,
+        super::api_reset_handler,
+        super::plan::api_plan_handler,
+        super::meal::api_meal_plan_handler,
+        super::compare::api_compare_accounts_handler,
+        super::requests::api_requestable_coupons_handler,
+        super::requests::api_requests_handler,
+        super::requests::api_submit_request_handler,
+        super::requests::api_approve_request_handler,
+        super::requests::api_reject_request_handler,
+        super::history::api_history_handler,
+        super::history::api_logs_handler,
+        super::audit_api::api_audit_handler,
+        super::stats::api_stats_handler,
+        super::stats::api_coupon_stats_handler,
+        super::stats::api_savings_stats_handler,
+        super::stats::api_last_claim_handler,
+        super::stats::api_scheduler_status_handler,
+        super::stats::api_upstream_stats_handler,
+        super::stats::api_tag_stats_handler,
+        super::server_time::api_server_time_handler,
+        super::progress_api::api_progress_handler,
+        super::jobs::api_claim_job_handler,
+        super::jobs::api_refresh_job_handler,
+        super::jobs::api_job_handler,
+        super::jobs::api_jobs_handler,
+    ),
+    components(schemas(
+        ApiResponse, Coupon, TokenPayload, TokenHistoryItem, PlanResponse, ClaimPlan, PlanConflict, UsedPayload, UsedResponse,
+        MealPayload, MealResponse, MealPlan, MealItem, CompareResponse, AccountComparison, CompactResponse, CompactCoupon,
+        PageInfo, HistoryResponse, LogsResponse, ClaimEntry, AuditResponse, AuditEntry, AuditAction,
+        DashboardStats, CouponStats, ExpiringCoupon, SavingsStats, ClaimRecord, SchedulerStatus, UpstreamStats, ServerTimeResponse,
+        ProgressResponse, Progress, Stage, RequestableResponse, RequestsResponse, SubmitPayload, RejectPayload, RequestResponse,
+        ClaimRequest, RequestStatus, ClaimRefreshResponse, CouponClaimResult, ClaimOutcome, JobResponse, JobsResponse, Job, JobKind,
+        JobStatus, TagStats, TagStatsResponse,
+    )),
+    tags(
+        (name = "token", description = "Token 管理"),
+        (name = "coupons", description = "优惠券查询与领取"),
+        (name = "requests", description = "家人代领请求"),
+        (name = "stats", description = "仪表盘汇总数据"),
+        (name = "jobs", description = "后台任务：立即返回任务 id，再按 id 查询进度和结果"),
+    )
+)]
+pub struct ApiDoc;
+
+/// The API document with every path under the current version prefix
+pub fn document() -> utoipa::openapi::OpenApi {
+    let mut doc = ApiDoc::openapi();
+    doc.paths.paths = std::mem::take(&mut doc.paths.paths)
+        .into_iter()
+        .map(|(path, item)| match path.strip_prefix("/api") {
+            Some(rest) => (format!("{}{}", versioning::PREFIX, rest), item),
+            None => (path, item),
+        })
+        .collect();
+    doc
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Page size when `per_page` is not given
+const DEFAULT_PER_PAGE: usize = 50;
+/// Largest accepted `per_page`
+const MAX_PER_PAGE: usize = 200;
+
+/// `page` / `per_page` query parameters shared by list endpoints
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// Page number, starting at 1
+    pub page: Option<usize>,
+    /// Items per page, default 50, at most 200
+    pub per_page: Option<usize>,
+}
+
+/// Position of the returned items in the full list
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PageInfo {
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub pages: usize,
+}
+
+impl PageQuery {
+    /// The requested page of `items`, with counts for the response envelope
+    pub fn apply<T>(&self, items: Vec<T>) -> (Vec<T>, PageInfo) {
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+        let page = self.page.unwrap_or(1).max(1);
+        let total = items.len();
+        let items = items.into_iter().skip(page.saturating_sub(1).saturating_mul(per_page)).take(per_page).collect();
+        (items, PageInfo { total, page, per_page, pages: total.div_ceil(per_page) })
+    }
+}
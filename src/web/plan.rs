@@ -0,0 +1,71 @@
+use axum::{extract::State, response::{IntoResponse, Json}};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{coupons::{parse_coupons_from_markdown, ClaimPlan}, mcp::describe_error, web::WebAppState};
+
+/// Response of the claim simulation endpoint
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlanResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<ClaimPlan>,
+}
+
+impl PlanResponse {
+    fn failed(message: String) -> Json<Self> {
+        Json(Self {
+            success: false,
+            message,
+            plan: None,
+        })
+    }
+}
+
+/// API handler for previewing what a claim would do
+#[utoipa::path(
+    get,
+    path = "/api/plan",
+    tag = "coupons",
+    responses((status = 200, description = "一键领取预览：将新增、重复与冲突的优惠券", body = PlanResponse))
+)]
+pub async fn api_plan_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
+    let Some(client) = state.lock().await.mcp_client.clone() else {
+        return PlanResponse::failed("请先设置Token".to_string());
+    };
+
+    state.lock().await.add_log("正在生成领取预览...".to_string());
+    let (available, held) = tokio::join!(client.get_available_coupons(), client.get_my_coupons());
+
+    let mut state = state.lock().await;
+
+    let available = match available {
+        Ok(text) => parse_coupons_from_markdown(&text),
+        Err(e) => {
+            let message = format!("可领取优惠券加载失败: {}", describe_error(&e));
+            state.add_log(message.clone());
+            return PlanResponse::failed(message);
+        }
+    };
+    let held = match held {
+        Ok(text) => parse_coupons_from_markdown(&text),
+        Err(e) => {
+            let message = format!("已领取优惠券加载失败: {}", describe_error(&e));
+            state.add_log(message.clone());
+            return PlanResponse::failed(message);
+        }
+    };
+    state.coupons = held.clone();
+
+    let plan = ClaimPlan::build(&available, &held);
+    let message = plan.summary();
+    state.add_log(format!("领取预览: {}", message));
+    Json(PlanResponse {
+        success: true,
+        message,
+        plan: Some(plan),
+    })
+}
@@ -0,0 +1,29 @@
+use axum::response::{IntoResponse, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::progress::{self, Progress};
+
+/// Stage of the running or last multi-step operation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProgressResponse {
+    /// An operation is in progress
+    pub running: bool,
+    /// Latest update; absent before the first operation since startup
+    pub progress: Option<Progress>,
+}
+
+/// API handler reporting claim progress. Does not take the state lock, so it answers while a claim runs.
+#[utoipa::path(
+    get,
+    path = "/api/progress",
+    tag = "coupons",
+    responses((status = 200, description = "一键领取的当前阶段与进度", body = ProgressResponse))
+)]
+pub async fn api_progress_handler() -> impl IntoResponse {
+    let progress = progress::latest();
+    Json(ProgressResponse {
+        running: progress.as_ref().is_some_and(|p| !p.finished),
+        progress,
+    })
+}
@@ -0,0 +1,35 @@
+use axum::{http::header, response::IntoResponse};
+
+// Embedded so the binary still serves a complete installable app without external files
+const MANIFEST: &str = include_str!("static/manifest.json");
+const SERVICE_WORKER: &str = include_str!("static/sw.js");
+const ICON_192: &[u8] = include_bytes!("static/icon-192.png");
+const ICON_512: &[u8] = include_bytes!("static/icon-512.png");
+
+/// Web app manifest, letting phones add the UI to the home screen
+pub async fn manifest_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/manifest+json")], MANIFEST)
+}
+
+/// Service worker caching the page and last coupon list for offline use.
+/// Served from the root so its scope covers the whole UI.
+pub async fn service_worker_handler() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            // Browsers must pick up a new worker after upgrading the binary
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        SERVICE_WORKER,
+    )
+}
+
+/// 192x192 app icon
+pub async fn icon_192_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "image/png")], ICON_192)
+}
+
+/// 512x512 app icon
+pub async fn icon_512_handler() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "image/png")], ICON_512)
+}
@@ -0,0 +1,332 @@
+use axum::{extract::{ConnectInfo, Path, Query, State}, http::StatusCode, response::{IntoResponse, Json, Response}};
+use serde::{Deserialize, Serialize};
+use std::{net::{IpAddr, SocketAddr}, sync::Arc};
+use tokio::sync::Mutex;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{allowlist::Allowlist, audit::{self, Action}, config::ConfigService, coupons::{parse_coupons_from_markdown, Coupon}, error::McdError, mcp::describe_error, mqtt, queue::{ClaimRequest, RequestQueue, RequestStatus}, web::{begin_claim, claim_all, token_invalid, ApiResponse, WebAppState}};
+
+/// Requests to list; without a requester every request is returned, for the account owner
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RequestsQuery {
+    pub requester: Option<String>,
+}
+
+/// Claim requests, newest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RequestsResponse {
+    pub success: bool,
+    pub message: String,
+    /// Requests waiting for the account owner, across all requesters
+    pub pending: usize,
+    pub requests: Vec<ClaimRequest>,
+}
+
+/// Coupons a family member can pick from
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RequestableResponse {
+    pub success: bool,
+    pub message: String,
+    pub coupons: Vec<Coupon>,
+}
+
+/// A family member asking for coupons to be claimed for them
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SubmitPayload {
+    pub requester: String,
+    pub titles: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Why the account owner turned a request down
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RejectPayload {
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Result of acting on one request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RequestResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<ClaimRequest>,
+}
+
+impl RequestResponse {
+    fn failed(message: String) -> Response {
+        Json(Self {
+            success: false,
+            message,
+            request: None,
+        })
+        .into_response()
+    }
+
+    /// 403 for a client that is not the account owner, see `is_owner`
+    fn forbidden(ip: IpAddr) -> Response {
+        tracing::warn!("已拒绝不在 request_owner_ips 中的客户端处理代领请求: {}", ip);
+        (StatusCode::FORBIDDEN, Json(Self {
+            success: false,
+            message: "只有账号主人可以处理代领请求".to_string(),
+            request: None,
+        }))
+        .into_response()
+    }
+
+    fn done(message: String, request: ClaimRequest) -> Response {
+        Json(Self {
+            success: true,
+            message,
+            request: Some(request),
+        })
+        .into_response()
+    }
+}
+
+/// Whether `ip` belongs to the account owner: this machine, or an address in `request_owner_ips`.
+/// Family members reach the same page, so only the owner may approve or reject.
+fn is_owner(ip: IpAddr) -> bool {
+    let config = ConfigService::global().current();
+    Allowlist::from_config(&config.request_owner_ips, "request_owner_ips")
+        .unwrap_or_default()
+        .lists(ip)
+}
+
+/// API handler listing the coupons that can currently be claimed, for family members to pick from
+#[utoipa::path(
+    get,
+    path = "/api/requests/coupons",
+    tag = "requests",
+    responses((status = 200, description = "当前可领取的优惠券，供家人勾选", body = RequestableResponse))
+)]
+pub async fn api_requestable_coupons_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
+    let Some(client) = state.lock().await.mcp_client.clone() else {
+        return Json(RequestableResponse {
+            success: false,
+            message: "账号主人尚未设置Token".to_string(),
+            coupons: Vec::new(),
+        });
+    };
+    let available = client.get_available_coupons().await;
+    match available {
+        Ok(text) => {
+            let coupons = parse_coupons_from_markdown(&text);
+            Json(RequestableResponse {
+                success: true,
+                message: format!("共 {} 张可领取的优惠券", coupons.len()),
+                coupons,
+            })
+        },
+        Err(e) => Json(RequestableResponse {
+            success: false,
+            message: format!("可领取优惠券加载失败: {}", describe_error(&e)),
+            coupons: Vec::new(),
+        }),
+    }
+}
+
+/// API handler listing claim requests
+#[utoipa::path(
+    get,
+    path = "/api/requests",
+    tag = "requests",
+    params(RequestsQuery),
+    responses((status = 200, description = "代领请求列表（最新的在前）", body = RequestsResponse))
+)]
+pub async fn api_requests_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<RequestsQuery>,
+) -> impl IntoResponse {
+    // Held so the file is not read halfway through a write by another handler
+    let _state = state.lock().await;
+    let queue = match RequestQueue::load() {
+        Ok(queue) => queue,
+        Err(e) => {
+            return Json(RequestsResponse {
+                success: false,
+                message: format!("读取代领请求失败: {}", e),
+                pending: 0,
+                requests: Vec::new(),
+            })
+        }
+    };
+    let requester = query.requester.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    let requests: Vec<ClaimRequest> = queue.requests.iter()
+        .rev()
+        .filter(|r| requester.is_none_or(|name| r.requester == name))
+        .cloned()
+        .collect();
+    Json(RequestsResponse {
+        success: true,
+        message: format!("共 {} 条代领请求", requests.len()),
+        pending: queue.pending(),
+        requests,
+    })
+}
+
+/// API handler queuing a claim request from a family member
+#[utoipa::path(
+    post,
+    path = "/api/requests",
+    tag = "requests",
+    request_body = SubmitPayload,
+    responses((status = 200, description = "提交的代领请求", body = RequestResponse))
+)]
+pub async fn api_submit_request_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(payload): Json<SubmitPayload>,
+) -> Response {
+    let mut state = state.lock().await;
+    let actor = audit::web_actor(peer.ip());
+
+    let mut queue = match RequestQueue::load() {
+        Ok(queue) => queue,
+        Err(e) => return RequestResponse::failed(format!("读取代领请求失败: {}", e)),
+    };
+    let request = match queue.submit(&payload.requester, payload.titles, &payload.note) {
+        Ok(request) => request,
+        Err(e) => return RequestResponse::failed(e.to_string()),
+    };
+    if let Err(e) = queue.save() {
+        return RequestResponse::failed(format!("保存代领请求失败: {}", e));
+    }
+
+    let message = format!("「{}」请求代领 {} 张优惠券: {}", request.requester, request.titles.len(), request.titles.join("、"));
+    state.add_log(format!("代领请求 #{} {}", request.id, message));
+    audit::record(&actor, Action::RequestSubmit, true, format!("#{} {}", request.id, message));
+    mqtt::publish_request("submit", &request);
+    RequestResponse::done("已提交，等待账号主人处理".to_string(), request)
+}
+
+/// API handler claiming coupons for a request, for the account owner only. Claiming always takes
+/// every available coupon; the request is then settled by which of the wanted ones are now held.
+#[utoipa::path(
+    post,
+    path = "/api/requests/{id}/approve",
+    tag = "requests",
+    params(("id" = u64, Path, description = "代领请求编号")),
+    responses(
+        (status = 200, description = "领取后的代领请求", body = RequestResponse),
+        (status = 401, description = "Token 已失效，需要重新设置", body = ApiResponse),
+        (status = 403, description = "不是账号主人（本机或 request_owner_ips 中的地址）", body = RequestResponse),
+    )
+)]
+pub async fn api_approve_request_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+) -> Response {
+    if !is_owner(peer.ip()) {
+        return RequestResponse::forbidden(peer.ip());
+    }
+    let (client, ticket) = match begin_claim(&state, None).await {
+        Ok(started) => started,
+        Err(existing) => return RequestResponse::failed(existing.resolve().await.map_or_else(|c| c.to_string(), |o| o.message)),
+    };
+    let actor = audit::web_actor(peer.ip());
+
+    let Some(client) = client else {
+        return RequestResponse::failed("请先设置Token".to_string());
+    };
+    let open = RequestQueue::load()
+        .map_err(|e| format!("读取代领请求失败: {}", e))
+        .and_then(|mut queue| queue.open_mut(id).map(drop).map_err(|e| e.to_string()));
+    if let Err(message) = open {
+        return RequestResponse::failed(message);
+    }
+
+    let claimed = claim_all(&state, &client, &actor, ticket).await;
+    // The refreshed list is empty when it could not be fetched after claiming
+    let cached = state.lock().await.coupons.clone();
+    let held = match claimed {
+        Ok(_) if cached.is_empty() => client.get_my_coupons().await
+            .map(|text| parse_coupons_from_markdown(&text)),
+        Ok(_) => Ok(cached),
+        Err(e) => Err(e),
+    };
+
+    // Read again, as the queue may have changed while claiming
+    let mut queue = match RequestQueue::load() {
+        Ok(queue) => queue,
+        Err(e) => return RequestResponse::failed(format!("读取代领请求失败: {}", e)),
+    };
+    let request = match queue.open_mut(id) {
+        Ok(request) => request,
+        Err(e) => return RequestResponse::failed(e.to_string()),
+    };
+    let token_rejected = matches!(held, Err(McdError::TokenInvalid));
+    match &held {
+        Ok(held) => request.settle(held),
+        Err(e) => request.close(RequestStatus::Failed, format!("领取失败: {}", describe_error(e))),
+    }
+    let request = request.clone();
+    if let Err(e) = queue.save() {
+        return RequestResponse::failed(format!("保存代领请求失败: {}", e));
+    }
+
+    let message = format!("代领请求 #{}「{}」{}: {}", request.id, request.requester, request.status.label(), request.reply);
+    let mut state = state.lock().await;
+    state.add_log(message.clone());
+    audit::record(&actor, Action::RequestApprove, held.is_ok(), message.clone());
+    mqtt::publish_request("approve", &request);
+    if token_rejected {
+        return token_invalid(&mut state);
+    }
+    Json(RequestResponse {
+        success: held.is_ok(),
+        message,
+        request: Some(request),
+    })
+    .into_response()
+}
+
+/// API handler turning a request down, for the account owner only
+#[utoipa::path(
+    post,
+    path = "/api/requests/{id}/reject",
+    tag = "requests",
+    params(("id" = u64, Path, description = "代领请求编号")),
+    request_body = RejectPayload,
+    responses(
+        (status = 200, description = "拒绝后的代领请求", body = RequestResponse),
+        (status = 403, description = "不是账号主人（本机或 request_owner_ips 中的地址）", body = RequestResponse),
+    )
+)]
+pub async fn api_reject_request_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    payload: Option<Json<RejectPayload>>,
+) -> Response {
+    if !is_owner(peer.ip()) {
+        return RequestResponse::forbidden(peer.ip());
+    }
+    let mut state = state.lock().await;
+    let reason = payload.map(|Json(p)| p.reason.trim().to_string()).unwrap_or_default();
+
+    let mut queue = match RequestQueue::load() {
+        Ok(queue) => queue,
+        Err(e) => return RequestResponse::failed(format!("读取代领请求失败: {}", e)),
+    };
+    let request = match queue.open_mut(id) {
+        Ok(request) => request,
+        Err(e) => return RequestResponse::failed(e.to_string()),
+    };
+    let reply = if reason.is_empty() { "账号主人拒绝了这次请求".to_string() } else { reason };
+    request.close(RequestStatus::Rejected, reply);
+    let request = request.clone();
+    if let Err(e) = queue.save() {
+        return RequestResponse::failed(format!("保存代领请求失败: {}", e));
+    }
+
+    let message = format!("已拒绝代领请求 #{}「{}」: {}", request.id, request.requester, request.reply);
+    state.add_log(message.clone());
+    audit::record(&audit::web_actor(peer.ip()), Action::RequestReject, true, message.clone());
+    mqtt::publish_request("reject", &request);
+    RequestResponse::done(message, request)
+}
@@ -0,0 +1,78 @@
+use axum::{extract::State, response::{IntoResponse, Json}};
+use chrono::Utc;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{mcp::describe_error, utils::format_server_time, web::WebAppState};
+
+/// Clock drift beyond this many seconds is flagged in the UI
+const DRIFT_WARN_SECONDS: i64 = 30;
+
+/// Upstream server time compared with the local clock
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ServerTimeResponse {
+    pub success: bool,
+    pub message: String,
+    /// Server time in China Standard Time, as reported by `now-time-info`
+    pub server_time: Option<String>,
+    /// Local clock at the same moment, in China Standard Time
+    pub local_time: String,
+    /// Local minus server time; positive when the local clock is ahead
+    pub drift_seconds: Option<i64>,
+    pub drift_threshold_seconds: i64,
+    /// Drift exceeds the threshold, so local countdowns to release times are off
+    pub drifted: bool,
+}
+
+/// API handler comparing the upstream clock with the local one
+#[utoipa::path(
+    get,
+    path = "/api/server-time",
+    tag = "stats",
+    responses((status = 200, description = "麦当劳服务器时间与本机时间的偏差", body = ServerTimeResponse))
+)]
+pub async fn api_server_time_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
+    let client = state.lock().await.mcp_client.clone();
+    let failed = |message: String| Json(ServerTimeResponse {
+        success: false,
+        message,
+        server_time: None,
+        local_time: format_server_time(Utc::now()),
+        drift_seconds: None,
+        drift_threshold_seconds: DRIFT_WARN_SECONDS,
+        drifted: false,
+    });
+    let Some(client) = client else {
+        return failed("请先设置Token".to_string());
+    };
+
+    let sent = Utc::now();
+    let result = client.get_server_time().await;
+    let received = Utc::now();
+    let server = match result {
+        Ok(server) => server,
+        Err(e) => return failed(format!("获取服务器时间失败: {}", describe_error(&e))),
+    };
+
+    // The server read its clock somewhere during the round trip; assume the middle
+    let local = sent + (received - sent) / 2;
+    let drift = (local - server).num_seconds();
+    let drifted = drift.abs() > DRIFT_WARN_SECONDS;
+    let message = match drift {
+        d if !drifted => format!("本机时间与服务器相差 {} 秒", d.abs()),
+        d if d > 0 => format!("本机时间比麦当劳服务器快 {} 秒，开抢时间请以服务器时间为准", d),
+        d => format!("本机时间比麦当劳服务器慢 {} 秒，开抢时间请以服务器时间为准", -d),
+    };
+
+    Json(ServerTimeResponse {
+        success: true,
+        message,
+        server_time: Some(format_server_time(server)),
+        local_time: format_server_time(local),
+        drift_seconds: Some(drift),
+        drift_threshold_seconds: DRIFT_WARN_SECONDS,
+        drifted,
+    })
+}
@@ -0,0 +1,91 @@
+use axum::{extract::{Query, State}, http::{header, StatusCode}, response::{IntoResponse, Response}};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::IntoParams;
+
+use crate::{share, web::WebAppState};
+
+/// Held coupon to render, identified like in `/api/coupons/used`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ShareCardQuery {
+    pub title: String,
+    pub expiry: String,
+}
+
+/// API handler rendering a held coupon as a PNG card for sharing in group chats
+#[utoipa::path(
+    get,
+    path = "/api/coupons/share-card",
+    tag = "coupons",
+    params(ShareCardQuery),
+    responses(
+        (status = 200, description = "优惠券分享卡片", content_type = "image/png", body = Vec<u8>),
+        (status = 404, description = "当前列表中没有这张优惠券"),
+        (status = 500, description = "缺少中文字体或生成图片失败"),
+    )
+)]
+pub async fn api_share_card_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<ShareCardQuery>,
+) -> Response {
+    let (coupon, config) = {
+        let state = state.lock().await;
+        let coupon = state.coupons.iter()
+            .find(|c| c.title == query.title && c.expiry == query.expiry)
+            .cloned();
+        (coupon, state.config.share.clone())
+    };
+    let Some(coupon) = coupon else {
+        return (StatusCode::NOT_FOUND, "当前列表中没有这张优惠券，请先刷新优惠券列表").into_response();
+    };
+
+    // Font loading and rasterizing take a while, keep them off the async workers
+    match tokio::task::spawn_blocking(move || share::render(&coupon, &config)).await {
+        Ok(Ok(png)) => (
+            [
+                (header::CONTENT_TYPE, "image/png"),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            png,
+        )
+            .into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// API handler rendering the redemption code of a held coupon as a QR code for the cashier
+#[utoipa::path(
+    get,
+    path = "/api/coupons/code-qr",
+    tag = "coupons",
+    params(ShareCardQuery),
+    responses(
+        (status = 200, description = "券码二维码", content_type = "image/svg+xml", body = String),
+        (status = 404, description = "当前列表中没有这张优惠券，或上游未提供券码"),
+    )
+)]
+pub async fn api_code_qr_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    Query(query): Query<ShareCardQuery>,
+) -> Response {
+    let code = state.lock().await.coupons.iter()
+        .find(|c| c.title == query.title && c.expiry == query.expiry)
+        .and_then(|c| c.code.clone());
+    let Some(code) = code else {
+        return (StatusCode::NOT_FOUND, "当前列表中没有这张优惠券的券码，请先刷新优惠券列表").into_response();
+    };
+    match share::code_svg(&code) {
+        Ok(svg) => (
+            [
+                (header::CONTENT_TYPE, "image/svg+xml"),
+                (header::CACHE_CONTROL, "no-store"),
+            ],
+            svg,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response(),
+    }
+}
@@ -0,0 +1,790 @@
+// Page navigation
+function showPage(pageId) {
+    document.querySelectorAll('.page').forEach(p => p.classList.remove('active'));
+    document.getElementById(pageId).classList.add('active');
+    document.querySelectorAll('.tab').forEach(t => t.classList.toggle('active', t.dataset.page === pageId));
+    document.getElementById('tabs').classList.toggle('show', pageId !== 'token-page');
+    if (pageId === 'dashboard-page') {
+        loadDashboard();
+    } else if (pageId === 'meal-page') {
+        loadMealOptions();
+    } else if (pageId === 'compare-page') {
+        loadCompareAccounts();
+    } else if (pageId === 'requests-page') {
+        loadRequestOptions();
+        pollRequests();
+    }
+}
+
+document.querySelectorAll('.tab').forEach(tab => {
+    tab.addEventListener('click', () => showPage(tab.dataset.page));
+});
+
+// Load dashboard cards
+async function loadDashboard() {
+    const error = document.getElementById('dashboard-error');
+    error.classList.remove('show');
+    // From local history only, so it shows even when upstream cannot be reached
+    loadTagChart();
+    try {
+        const response = await fetch('/api/v1/stats');
+        const data = await response.json();
+        if (!data.success) {
+            error.textContent = data.message || '加载失败';
+            error.classList.add('show');
+            return;
+        }
+
+        document.getElementById('stat-held').textContent = data.coupons.held;
+        document.getElementById('stat-expiring').textContent = data.coupons.expiring_soon;
+        document.getElementById('stat-expiring-detail').textContent = data.coupons.expiring.length > 0
+            ? data.coupons.expiring.map(c => `${c.title} (${c.days_left}天)`).join('、')
+            : `${data.coupons.expiring_within_days} 天内无过期优惠券`;
+
+        if (data.last_claim) {
+            document.getElementById('stat-last-claim').textContent = data.last_claim.success ? '成功' : '失败';
+            document.getElementById('stat-last-claim-detail').textContent = `${data.last_claim.at} ${data.last_claim.message}`;
+        } else {
+            document.getElementById('stat-last-claim').textContent = '暂无';
+            document.getElementById('stat-last-claim-detail').textContent = '本次启动后尚未领取';
+        }
+
+        document.getElementById('stat-scheduler').textContent = data.scheduler.enabled ? '已启用' : '未启用';
+        document.getElementById('stat-scheduler-detail').textContent = data.scheduler.next_run
+            ? `下次运行: ${data.scheduler.next_run}`
+            : data.scheduler.message;
+
+        document.getElementById('stat-savings').textContent = `¥${data.savings.estimated_savings}`;
+        document.getElementById('stat-savings-detail').textContent = `${data.savings.month} 已使用 ${data.savings.counted} 张优惠券`;
+    } catch (err) {
+        error.textContent = '网络错误，请重试';
+        error.classList.add('show');
+    }
+}
+
+// Bar per tag of the coupons claimed in the chosen range, drawn from /api/v1/stats/tags
+async function loadTagChart() {
+    const bars = document.getElementById('tag-bars');
+    const days = document.getElementById('tag-range').value;
+    let url = '/api/v1/stats/tags';
+    if (days) {
+        const from = new Date(Date.now() - days * 86400000);
+        url += `?from=${from.getFullYear()}-${String(from.getMonth() + 1).padStart(2, '0')}-${String(from.getDate()).padStart(2, '0')}`;
+    }
+    try {
+        const response = await fetch(url);
+        const data = await response.json();
+        if (!data.success || data.tags.length === 0) {
+            bars.innerHTML = `<p class="tag-empty">${escapeHtml(data.success ? '这段时间没有带标签的领取记录' : data.message)}</p>`;
+            return;
+        }
+        const max = Math.max(...data.tags.map(t => t.claimed), 1);
+        bars.innerHTML = data.tags.map(t => `
+            <div class="tag-bar-row">
+                <span class="tag-bar-label">${escapeHtml(t.tag)}</span>
+                <span class="tag-bar-track"><span class="tag-bar" style="width: ${t.claimed / max * 100}%"></span></span>
+                <span class="tag-bar-value">${t.claimed} 张${t.used ? ` · 已用 ${t.used} · 省 ¥${t.savings}` : ''}</span>
+            </div>
+        `).join('');
+    } catch (err) {
+        bars.innerHTML = '<p class="tag-empty">网络错误，请重试</p>';
+    }
+}
+
+document.getElementById('tag-range').addEventListener('change', loadTagChart);
+
+// Upstream success rate and latency in the footer
+let upstreamRetryTimer = null;
+async function loadUpstreamStats() {
+    const el = document.getElementById('upstream-stats');
+    try {
+        const response = await fetch('/api/v1/stats/upstream');
+        const data = await response.json();
+        if (data.retry_after_secs) {
+            el.textContent = `| 上游: 被限流，将在 ${data.retry_after_secs} 秒后重试`;
+        } else {
+            el.textContent = data.calls === 0
+                ? '| 上游: 暂无数据'
+                : `| 上游: ${Math.round(data.success_rate)}% / ${data.avg_latency_ms}ms`;
+        }
+        el.classList.toggle('degraded', !!data.retry_after_secs || (data.calls > 0 && data.success_rate < 80));
+        // Poll again soon so the countdown and its end show up
+        clearTimeout(upstreamRetryTimer);
+        if (data.retry_after_secs) {
+            upstreamRetryTimer = setTimeout(loadUpstreamStats, 5000);
+        }
+    } catch (err) {
+        el.textContent = '';
+    }
+}
+loadUpstreamStats();
+setInterval(loadUpstreamStats, 30000);
+
+// Warn when the local clock drifts from the server, whose time decides coupon release windows
+async function checkServerTime() {
+    const el = document.getElementById('clock-warning');
+    try {
+        const response = await fetch('/api/v1/server-time');
+        const data = await response.json();
+        el.textContent = data.drifted ? `⏰ ${data.message}（服务器时间 ${data.server_time}）` : '';
+        el.classList.toggle('show', data.success && data.drifted);
+    } catch (err) {
+        el.classList.remove('show');
+    }
+}
+
+// Upstream rejected the saved token: go back to the token form with the reason
+function askForNewToken(message) {
+    const error = document.getElementById('token-error');
+    error.textContent = message || 'Token 无效或已过期，请重新设置';
+    error.classList.add('show');
+    document.getElementById('clock-warning').classList.remove('show');
+    loadTokenHistory();
+    showPage('token-page');
+}
+
+// Show message
+function showMessage(elementId, message, isError = false) {
+    const el = document.getElementById(elementId);
+    el.textContent = message;
+    el.classList.add('show');
+    setTimeout(() => el.classList.remove('show'), 5000);
+}
+
+// Offer previously validated tokens instead of pasting again
+async function loadTokenHistory() {
+    const select = document.getElementById('token-history');
+    try {
+        const response = await fetch('/api/v1/token/history');
+        const items = await response.json();
+        select.length = 1;
+        items.forEach(item => {
+            const option = document.createElement('option');
+            option.value = item.index;
+            option.textContent = `${item.nickname || '未命名'}  ${item.masked}  (上次有效: ${item.last_valid})`;
+            select.appendChild(option);
+        });
+        document.getElementById('token-history-group').style.display = items.length ? '' : 'none';
+        select.value = items.length ? '0' : '';
+    } catch (err) {
+        select.value = '';
+    }
+    select.dispatchEvent(new Event('change'));
+}
+
+document.getElementById('token-history').addEventListener('change', function() {
+    const manual = this.value === '';
+    document.getElementById('token-input-group').style.display = manual ? '' : 'none';
+    document.getElementById('token-nickname-group').style.display = manual ? '' : 'none';
+    document.getElementById('token').required = manual;
+});
+
+// Token form submission
+document.getElementById('token-form').addEventListener('submit', async function(e) {
+    e.preventDefault();
+    const selected = document.getElementById('token-history').value;
+    const payload = selected === ''
+        ? { token: document.getElementById('token').value, nickname: document.getElementById('token-nickname').value || null }
+        : { history: Number(selected) };
+    const btn = this.querySelector('button');
+    btn.classList.add('loading');
+    btn.disabled = true;
+
+    try {
+        const response = await fetch('/api/v1/token', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(payload)
+        });
+        const data = await response.json();
+
+        if (data.success) {
+            showPage('dashboard-page');
+            checkServerTime();
+        } else {
+            document.getElementById('token-error').textContent = data.message || 'Token验证失败';
+            document.getElementById('token-error').classList.add('show');
+        }
+    } catch (err) {
+        document.getElementById('token-error').textContent = '网络错误，请重试';
+        document.getElementById('token-error').classList.add('show');
+    } finally {
+        btn.classList.remove('loading');
+        btn.disabled = false;
+    }
+});
+
+// Preview what a claim would do
+document.getElementById('plan-btn').addEventListener('click', async function() {
+    const result = document.getElementById('plan-result');
+    this.classList.add('loading');
+    this.disabled = true;
+    document.getElementById('main-error').classList.remove('show');
+    result.classList.remove('show');
+
+    try {
+        const response = await fetch('/api/v1/plan');
+        const data = await response.json();
+
+        if (!data.success) {
+            showMessage('main-error', data.message || '预览失败');
+            return;
+        }
+
+        const list = (items, cls, text) => items.length === 0 ? '' :
+            `<ul class="${cls}">${items.map(item => `<li>${escapeHtml(text(item))}</li>`).join('')}</ul>`;
+        result.innerHTML = `
+            <strong>${escapeHtml(data.message)}</strong>
+            ${list(data.plan.would_add, 'plan-add', c => `新增：${c.title}（${c.expiry}）`)}
+            ${list(data.plan.duplicates, 'plan-duplicate', c => `重复：${c.title}`)}
+            ${list(data.plan.conflicts, 'plan-conflict', c => `冲突：${c.coupon.title} - ${c.reason}`)}
+        `;
+        result.classList.add('show');
+    } catch (err) {
+        showMessage('main-error', '网络错误，请重试');
+    } finally {
+        this.classList.remove('loading');
+        this.disabled = false;
+    }
+});
+
+// Claim all coupons
+document.getElementById('claim-btn').addEventListener('click', async function() {
+    this.classList.add('loading');
+    this.disabled = true;
+    document.getElementById('main-error').classList.remove('show');
+    document.getElementById('main-success').classList.remove('show');
+    document.getElementById('plan-result').classList.remove('show');
+
+    // Show the stage being run on the button while the claim is in flight
+    const label = this.textContent;
+    const progressTimer = setInterval(async () => {
+        try {
+            const data = await (await fetch('/api/v1/progress')).json();
+            if (data.running) {
+                this.textContent = `${data.progress.message} ${data.progress.percent}%`;
+            }
+        } catch (err) {
+            // Progress is cosmetic; the claim result still arrives
+        }
+    }, 500);
+
+    try {
+        const response = await fetch('/api/v1/claim-and-refresh', { method: 'POST' });
+        const data = await response.json();
+
+        if (response.status === 401) {
+            askForNewToken(data.message);
+        } else if (data.success) {
+            showMessage('main-success', data.message || '领取成功！');
+            document.getElementById('main-success').classList.add('show');
+
+            // Show what happened to each coupon, and keep the list in step without a reload
+            const result = document.getElementById('plan-result');
+            const cls = { claimed: 'plan-add', already_held: 'plan-duplicate', not_claimed: 'plan-conflict' };
+            if (data.results.length > 0) {
+                result.innerHTML = `<ul>${data.results.map(r =>
+                    `<li class="${cls[r.outcome]}">${escapeHtml(r.label)}：${escapeHtml(r.title)}（${escapeHtml(r.expiry)}）</li>`).join('')}</ul>`;
+                result.classList.add('show');
+            }
+            if (!COMPACT) {
+                loadedCoupons = data.coupons || [];
+                renderCoupons();
+            }
+        } else {
+            showMessage('main-error', data.message || '领取失败');
+            document.getElementById('main-error').classList.add('show');
+        }
+    } catch (err) {
+        showMessage('main-error', '网络错误，请重试');
+        document.getElementById('main-error').classList.add('show');
+    } finally {
+        clearInterval(progressTimer);
+        this.textContent = label;
+        this.classList.remove('loading');
+        this.disabled = false;
+    }
+});
+
+// Meal planner: unused held coupons offered as options
+let mealCoupons = [];
+
+// Meal planner: list held coupons to pick from
+async function loadMealOptions() {
+    const options = document.getElementById('meal-options');
+    document.getElementById('meal-error').classList.remove('show');
+    options.innerHTML = '<p>正在加载优惠券...</p>';
+
+    try {
+        const response = await fetch('/api/v1/coupons?per_page=200');
+        const data = await response.json();
+        if (!data.success) {
+            options.innerHTML = '';
+            showMessage('meal-error', data.message || '加载失败');
+            return;
+        }
+
+        mealCoupons = (data.coupons || []).filter(c => !c.used);
+        if (mealCoupons.length === 0) {
+            options.innerHTML = '<p>暂无可用的优惠券</p>';
+            return;
+        }
+        options.innerHTML = mealCoupons.map((c, i) => `
+            <label class="meal-option">
+                <input type="checkbox" value="${i}">
+                <span>${escapeHtml(c.title)}</span>
+                <span class="meal-price">${escapeHtml(c.price)}${c.calories ? ` · 约 ${c.calories} 千卡` : ''} · ${escapeHtml(c.expiry)}</span>
+            </label>
+        `).join('');
+    } catch (err) {
+        options.innerHTML = '';
+        showMessage('meal-error', '网络错误，请重试');
+    }
+}
+
+// Meal planner: build the checklist from the picked coupons
+document.getElementById('meal-btn').addEventListener('click', async function() {
+    const result = document.getElementById('meal-result');
+    const copyBtn = document.getElementById('meal-copy-btn');
+    const titles = Array.from(document.querySelectorAll('#meal-options input:checked')).map(i => mealCoupons[i.value].title);
+    result.classList.remove('show');
+    copyBtn.style.display = 'none';
+
+    this.classList.add('loading');
+    this.disabled = true;
+    try {
+        const response = await fetch('/api/v1/meal-plan', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ titles })
+        });
+        const data = await response.json();
+        if (!data.success) {
+            showMessage('meal-error', data.message || '生成失败');
+            return;
+        }
+
+        const warnings = data.plan.warnings.length === 0 ? '' :
+            `<ul class="plan-conflict">${data.plan.warnings.map(w => `<li>${escapeHtml(w)}</li>`).join('')}</ul>`;
+        result.innerHTML = `
+            <strong>${escapeHtml(data.message)}</strong>
+            ${warnings}
+            <div class="meal-checklist">${escapeHtml(data.plan.checklist)}</div>
+        `;
+        result.classList.add('show');
+        copyBtn.dataset.checklist = data.plan.checklist;
+        copyBtn.style.display = 'block';
+    } catch (err) {
+        showMessage('meal-error', '网络错误，请重试');
+    } finally {
+        this.classList.remove('loading');
+        this.disabled = false;
+    }
+});
+
+document.getElementById('meal-copy-btn').addEventListener('click', async function() {
+    try {
+        await navigator.clipboard.writeText(this.dataset.checklist);
+        this.textContent = '已复制';
+    } catch (err) {
+        this.textContent = '复制失败，请手动选择文本';
+    }
+    setTimeout(() => this.textContent = '复制清单', 2000);
+});
+
+// Account comparison: fill both pickers with the saved accounts
+async function loadCompareAccounts() {
+    document.getElementById('compare-error').classList.remove('show');
+    try {
+        const response = await fetch('/api/v1/accounts/compare');
+        const data = await response.json();
+        if (data.accounts.length < 2) {
+            showMessage('compare-error', '至少需要保存两个账号才能对比，可在终端模式的设置中添加账号');
+        }
+        ['compare-a', 'compare-b'].forEach((id, i) => {
+            const select = document.getElementById(id);
+            select.innerHTML = data.accounts.map(name => `<option>${escapeHtml(name)}</option>`).join('');
+            select.selectedIndex = Math.min(i, data.accounts.length - 1);
+        });
+    } catch (err) {
+        showMessage('compare-error', '网络错误，请重试');
+    }
+}
+
+// Account comparison: coupons only one account holds are the ones that decide the account
+document.getElementById('compare-btn').addEventListener('click', async function() {
+    const result = document.getElementById('compare-result');
+    const a = document.getElementById('compare-a').value;
+    const b = document.getElementById('compare-b').value;
+    result.classList.remove('show');
+
+    this.classList.add('loading');
+    this.disabled = true;
+    try {
+        const response = await fetch(`/api/v1/accounts/compare?a=${encodeURIComponent(a)}&b=${encodeURIComponent(b)}`);
+        const data = await response.json();
+        if (!data.success) {
+            showMessage('compare-error', data.message || '对比失败');
+            return;
+        }
+
+        const c = data.comparison;
+        const list = (coupons) => coupons.length === 0 ? '<p>无</p>' :
+            `<ul class="plan-add">${coupons.map(x => `<li>${escapeHtml(x.title)} · ${escapeHtml(x.price)}${x.used ? '（已使用）' : ''}</li>`).join('')}</ul>`;
+        result.innerHTML = `
+            <strong>${escapeHtml(data.message)}</strong>
+            <h3>仅「${escapeHtml(c.a)}」持有</h3>${list(c.only_a)}
+            <h3>仅「${escapeHtml(c.b)}」持有</h3>${list(c.only_b)}
+            <h3>两个账号都有</h3>
+            ${c.both.length === 0 ? '<p>无</p>' : `<ul class="plan-duplicate">${c.both.map(t => `<li>${escapeHtml(t)}</li>`).join('')}</ul>`}
+        `;
+        result.classList.add('show');
+    } catch (err) {
+        showMessage('compare-error', '网络错误，请重试');
+    } finally {
+        this.classList.remove('loading');
+        this.disabled = false;
+    }
+});
+
+// Claim requests: coupons offered to family members
+let requestCoupons = [];
+// Claim requests: pending count and own request statuses seen by the last poll, to notify on changes
+let seenPending = null;
+const requesterInput = document.getElementById('requester');
+requesterInput.value = localStorage.getItem('requester') || '';
+
+// Claim requests: list the coupons that can be claimed right now
+async function loadRequestOptions() {
+    const options = document.getElementById('request-options');
+    document.getElementById('requests-error').classList.remove('show');
+    options.innerHTML = '<p>正在加载优惠券...</p>';
+    try {
+        const response = await fetch('/api/v1/requests/coupons');
+        const data = await response.json();
+        if (!data.success) {
+            options.innerHTML = '';
+            showMessage('requests-error', data.message || '加载失败');
+            return;
+        }
+        requestCoupons = data.coupons;
+        if (requestCoupons.length === 0) {
+            options.innerHTML = '<p>暂无可领取的优惠券</p>';
+            return;
+        }
+        options.innerHTML = requestCoupons.map((c, i) => `
+            <label class="meal-option">
+                <input type="checkbox" value="${i}">
+                <span>${escapeHtml(c.title)}</span>
+                <span class="meal-price">${escapeHtml(c.price)} · ${escapeHtml(c.expiry)}</span>
+            </label>
+        `).join('');
+    } catch (err) {
+        options.innerHTML = '';
+        showMessage('requests-error', '网络错误，请重试');
+    }
+}
+
+// Claim requests: browser notification when allowed, with the in-page message as well
+function notifyRequests(message) {
+    showMessage('requests-success', message);
+    if ('Notification' in window && Notification.permission === 'granted') {
+        new Notification('麦当劳优惠券代领', { body: message, icon: '/icons/icon-192.png' });
+    }
+}
+
+function askNotificationPermission() {
+    if ('Notification' in window && Notification.permission === 'default') {
+        Notification.requestPermission();
+    }
+}
+
+const requestStatusText = { pending: '待处理', claimed: '已领取', partial: '部分领取', failed: '领取失败', rejected: '已拒绝' };
+
+function renderRequest(r, owner) {
+    const actions = !owner || !['pending', 'failed'].includes(r.status) ? '' : `
+        <div class="request-actions">
+            <button class="btn btn-primary" data-approve="${escapeHtml(r.id)}">${r.status === 'failed' ? '重新领取' : '确认领取'}</button>
+            <button class="btn btn-outline" data-reject="${escapeHtml(r.id)}">拒绝</button>
+        </div>`;
+    return `
+        <div class="request-item request-${r.status}">
+            <strong>#${r.id} ${escapeHtml(r.requester)}</strong>
+            <span class="request-status">${escapeHtml(requestStatusText[r.status])}</span>
+            <div>${r.titles.map(escapeHtml).join('、')}</div>
+            ${r.note ? `<div class="request-note">留言：${escapeHtml(r.note)}</div>` : ''}
+            ${r.reply ? `<div class="request-note">回复：${escapeHtml(r.reply)}</div>` : ''}
+            <div class="request-note">${escapeHtml(r.updated_at)}</div>
+            ${actions}
+        </div>`;
+}
+
+// Claim requests: refresh both lists and the tab badge, notifying about anything new
+async function pollRequests() {
+    try {
+        const data = await (await fetch('/api/v1/requests')).json();
+        if (!data.success) {
+            return;
+        }
+        const badge = document.getElementById('requests-badge');
+        badge.textContent = data.pending > 0 ? data.pending : '';
+        if (seenPending !== null && data.pending > seenPending) {
+            notifyRequests(`有 ${data.pending} 条代领请求等待处理`);
+        }
+        seenPending = data.pending;
+
+        const name = requesterInput.value.trim();
+        const mine = name ? data.requests.filter(r => r.requester === name) : [];
+        const seen = JSON.parse(localStorage.getItem('requestStatus') || '{}');
+        mine.filter(r => seen[r.id] && seen[r.id] !== r.status)
+            .forEach(r => notifyRequests(`你的请求 #${r.id} ${requestStatusText[r.status]}${r.reply ? '：' + r.reply : ''}`));
+        localStorage.setItem('requestStatus', JSON.stringify(Object.fromEntries(mine.map(r => [r.id, r.status]))));
+
+        document.getElementById('my-requests').innerHTML = mine.length === 0 ? '<p>暂无请求</p>' :
+            mine.map(r => renderRequest(r, false)).join('');
+        const open = data.requests.filter(r => ['pending', 'failed'].includes(r.status));
+        document.getElementById('owner-requests').innerHTML = open.length === 0 ? '<p>暂无待处理的请求</p>' :
+            open.map(r => renderRequest(r, true)).join('');
+    } catch (err) {
+        // Polling again shortly; the lists keep their last content
+    }
+}
+pollRequests();
+setInterval(pollRequests, 30000);
+
+document.getElementById('request-submit-btn').addEventListener('click', async function() {
+    const requester = requesterInput.value.trim();
+    const titles = Array.from(document.querySelectorAll('#request-options input:checked')).map(i => requestCoupons[i.value].title);
+    localStorage.setItem('requester', requester);
+    askNotificationPermission();
+
+    this.classList.add('loading');
+    this.disabled = true;
+    try {
+        const response = await fetch('/api/v1/requests', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ requester, titles, note: document.getElementById('request-note').value })
+        });
+        const data = await response.json();
+        if (!data.success) {
+            showMessage('requests-error', data.message || '提交失败');
+            return;
+        }
+        showMessage('requests-success', data.message);
+        document.querySelectorAll('#request-options input:checked').forEach(i => i.checked = false);
+        document.getElementById('request-note').value = '';
+        pollRequests();
+    } catch (err) {
+        showMessage('requests-error', '网络错误，请重试');
+    } finally {
+        this.classList.remove('loading');
+        this.disabled = false;
+    }
+});
+
+// Claim requests: the owner approves or rejects from the pending list
+document.getElementById('owner-requests').addEventListener('click', async function(event) {
+    const button = event.target.closest('button');
+    if (!button) {
+        return;
+    }
+    askNotificationPermission();
+    const approve = button.dataset.approve;
+    let url = `/api/v1/requests/${approve}/approve`;
+    let body;
+    if (!approve) {
+        const reason = prompt('拒绝原因（可选）:');
+        if (reason === null) {
+            return;
+        }
+        url = `/api/v1/requests/${button.dataset.reject}/reject`;
+        body = JSON.stringify({ reason });
+    }
+
+    button.classList.add('loading');
+    button.disabled = true;
+    try {
+        const response = await fetch(url, { method: 'POST', headers: { 'Content-Type': 'application/json' }, body });
+        const data = await response.json();
+        if (response.status === 401) {
+            askForNewToken(data.message);
+            return;
+        }
+        showMessage(data.success ? 'requests-success' : 'requests-error', data.message);
+        pollRequests();
+    } catch (err) {
+        showMessage('requests-error', '网络错误，请重试');
+    } finally {
+        button.classList.remove('loading');
+        button.disabled = false;
+    }
+});
+
+// View coupons
+document.getElementById('view-coupons-btn').addEventListener('click', async function() {
+    showPage('coupons-page');
+    loadCoupons();
+});
+
+// Coupons from the last load, re-rendered when a used flag or the filter changes
+let loadedCoupons = [];
+
+// Load coupons
+async function loadCoupons() {
+    const container = document.getElementById('coupons-container');
+    const empty = document.getElementById('coupons-empty');
+    const spinner = document.getElementById('loading-spinner');
+
+    container.innerHTML = '';
+    empty.style.display = 'none';
+    spinner.classList.add('show');
+
+    try {
+        const response = await fetch((COMPACT ? '/api/v1/coupons/compact' : '/api/v1/coupons') + '?per_page=200');
+        const data = await response.json();
+
+        spinner.classList.remove('show');
+
+        if (response.status === 401) {
+            askForNewToken(data.message);
+        } else if (data.success && data.coupons && data.coupons.length > 0) {
+            loadedCoupons = data.coupons;
+            renderCoupons();
+        } else {
+            loadedCoupons = [];
+            empty.style.display = 'block';
+        }
+    } catch (err) {
+        spinner.classList.remove('show');
+        empty.innerHTML = '<h3>加载失败</h3><p>无法加载优惠券，请稍后重试</p>';
+        empty.style.display = 'block';
+    }
+}
+
+function renderCoupons() {
+    const container = document.getElementById('coupons-container');
+    const hideUsed = document.getElementById('hide-used').checked;
+    container.innerHTML = '';
+
+    loadedCoupons.filter(coupon => !(hideUsed && coupon.used)).forEach(coupon => {
+        const card = document.createElement('div');
+        card.className = coupon.used ? 'coupon-card used' : 'coupon-card';
+        card.innerHTML = COMPACT ? `
+            <div class="coupon-content">
+                <div class="coupon-title">${escapeHtml(coupon.title)}${coupon.count > 1 ? `<span class="coupon-count">x${coupon.count}</span>` : ''}</div>
+                <div class="coupon-price">${escapeHtml(coupon.price)}${caloriesText(coupon)}</div>
+                <div class="coupon-expiry">${daysLeftText(coupon.days_left)}</div>
+                <button class="used-toggle">${coupon.used ? '取消已使用' : '标记为已使用'}</button>
+                ${coupon.code ? '<button class="show-code">出示券码</button>' : ''}
+                ${redeemLink(coupon.redeem_link)}
+            </div>
+        ` : `
+            ${coupon.image_url ? `<img class="coupon-image" src="/images?url=${encodeURIComponent(coupon.image_url)}" alt="${escapeHtml(coupon.title)}" loading="lazy">` : ''}
+            <div class="coupon-content">
+                <div class="coupon-title">${escapeHtml(coupon.title)}${coupon.count > 1 ? `<span class="coupon-count">x${coupon.count}</span>` : ''}</div>
+                <div class="coupon-price">${escapeHtml(coupon.price)}${caloriesText(coupon)}</div>
+                <div class="coupon-expiry">${escapeHtml(coupon.expiry)}</div>
+                ${coupon.tags ? `<div class="coupon-tags">${escapeHtml(coupon.tags)}</div>` : ''}
+                <button class="used-toggle">${coupon.used ? '取消已使用' : '标记为已使用'}</button>
+                ${coupon.code ? '<button class="show-code">出示券码</button>' : ''}
+                ${redeemLink(coupon.app_link || coupon.universal_link)}
+                <a class="share-card" href="/api/v1/coupons/share-card?title=${encodeURIComponent(coupon.title)}&expiry=${encodeURIComponent(coupon.expiry)}" target="_blank" rel="noopener">分享卡片</a>
+            </div>
+        `;
+        card.querySelector('.used-toggle').addEventListener('click', () => toggleUsed(coupon));
+        card.querySelector('.show-code')?.addEventListener('click', () => showCode(coupon));
+        container.appendChild(card);
+    });
+}
+
+// Calorie estimate next to the price, present when show_calories is on
+function caloriesText(coupon) {
+    return coupon.calories ? `<span class="coupon-calories">约 ${coupon.calories} 千卡</span>` : '';
+}
+
+// Redemption code as a large QR code over the list, for the cashier to scan off the screen
+function showCode(coupon) {
+    document.getElementById('code-title').textContent = coupon.title;
+    document.getElementById('code-qr').src = `/api/v1/coupons/code-qr?title=${encodeURIComponent(coupon.title)}&expiry=${encodeURIComponent(coupon.expiry)}`;
+    document.getElementById('code-text').textContent = coupon.code;
+    document.getElementById('code-overlay').hidden = false;
+}
+
+document.getElementById('code-close').addEventListener('click', function() {
+    document.getElementById('code-overlay').hidden = true;
+});
+
+// Button opening the coupon in the McDonald's app, present when a deep link template is set
+function redeemLink(link) {
+    return link ? `<a class="redeem-link" href="${escapeHtml(link)}">在 App 中使用</a>` : '';
+}
+
+// Expiry countdown shown instead of the full validity text in the compact layout
+function daysLeftText(days) {
+    if (days === null || days === undefined) return '';
+    if (days < 0) return '已过期';
+    if (days === 0) return '今天到期';
+    return `剩 ${days} 天`;
+}
+
+// Mark a coupon as used / unused
+async function toggleUsed(coupon) {
+    try {
+        const response = await fetch('/api/v1/coupons/used', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ title: coupon.title, expiry: coupon.expiry })
+        });
+        const data = await response.json();
+        if (data.success) {
+            coupon.used = data.used;
+            renderCoupons();
+        }
+    } catch (err) {
+        // Keep the current state; the next load shows the saved flag
+    }
+}
+
+document.getElementById('hide-used').addEventListener('change', renderCoupons);
+
+// Back to main
+document.getElementById('back-btn').addEventListener('click', function() {
+    showPage('main-page');
+});
+
+// Reset token
+document.getElementById('reset-btn').addEventListener('click', async function() {
+    if (!confirm('确定要重新设置Token吗？')) return;
+
+    try {
+        await fetch('/api/v1/reset', { method: 'POST' });
+        document.getElementById('token').value = '';
+        document.getElementById('clock-warning').classList.remove('show');
+        loadTokenHistory();
+        showPage('token-page');
+    } catch (err) {
+        showMessage('main-error', '重置失败，请重试');
+        document.getElementById('main-error').classList.add('show');
+    }
+});
+
+if (HAS_TOKEN) {
+    showPage(INITIAL_PAGE);
+    checkServerTime();
+} else {
+    loadTokenHistory();
+}
+
+// Installable app: cache the page and last coupon list for offline viewing
+if ('serviceWorker' in navigator) {
+    navigator.serviceWorker.register('/sw.js').catch(err => console.warn('Service worker 注册失败', err));
+}
+
+// Escape HTML
+// Escapes quotes too, so the result is safe inside attribute values as well as text
+const HTML_ESCAPES = { '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;', "'": '&#39;' };
+
+function escapeHtml(text) {
+    return String(text ?? '').replace(/[&<>"']/g, ch => HTML_ESCAPES[ch]);
+}
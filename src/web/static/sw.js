@@ -0,0 +1,68 @@
+// Service worker: keeps the page shell and the last coupon / dashboard responses
+// so the installed app can still show coupons without a connection.
+const CACHE = 'mcd-coupon-v3';
+const SHELL = ['/', '/manifest.json', '/icons/icon-192.png', '/icons/icon-512.png'];
+// GET APIs whose last response is shown when offline
+const CACHED_APIS = ['/api/v1/coupons', '/api/v1/stats'];
+
+self.addEventListener('install', event => {
+    event.waitUntil(caches.open(CACHE).then(cache => cache.addAll(SHELL)));
+    self.skipWaiting();
+});
+
+self.addEventListener('activate', event => {
+    event.waitUntil(
+        caches.keys()
+            .then(keys => Promise.all(keys.filter(key => key !== CACHE).map(key => caches.delete(key))))
+            .then(() => self.clients.claim())
+    );
+});
+
+// Versioned /assets/ URLs never change, so serve them from the cache once fetched
+async function cacheFirst(request) {
+    const cache = await caches.open(CACHE);
+    const cached = await cache.match(request);
+    if (cached) {
+        return cached;
+    }
+    const response = await fetch(request);
+    if (response.ok) {
+        cache.put(request, response.clone());
+    }
+    return response;
+}
+
+// Network first, falling back to the cached copy
+async function networkFirst(request, fallback) {
+    const cache = await caches.open(CACHE);
+    try {
+        const response = await fetch(request);
+        if (response.ok) {
+            cache.put(fallback || request, response.clone());
+        }
+        return response;
+    } catch (err) {
+        const cached = await cache.match(fallback || request);
+        if (cached) {
+            return cached;
+        }
+        throw err;
+    }
+}
+
+self.addEventListener('fetch', event => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (request.method !== 'GET' || url.origin !== self.location.origin) {
+        return;
+    }
+    if (request.mode === 'navigate') {
+        event.respondWith(networkFirst(request, '/'));
+    } else if (CACHED_APIS.some(path => url.pathname === path || url.pathname.startsWith(path + '/'))) {
+        event.respondWith(networkFirst(request));
+    } else if (url.pathname.startsWith('/assets/')) {
+        event.respondWith(cacheFirst(request));
+    } else if (SHELL.includes(url.pathname)) {
+        event.respondWith(caches.match(request).then(cached => cached || fetch(request)));
+    }
+});
@@ -0,0 +1,254 @@
+use async_graphql::SimpleObject;
+use axum::{extract::{Query, State}, response::{IntoResponse, Json, Response}};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{coupons::{parse_coupons_from_markdown, ClaimHistory, Coupon, TagStats, UsageStore}, images, mcp::{describe_error, metrics::{self, UpstreamStats}}, scheduler::{self, SchedulerStatus}, utils::{format_current_time, local_today}, web::{history::date_range, ApiResponse, WebAppState}};
+
+/// Coupons expiring within this many days are flagged on the dashboard
+const EXPIRING_SOON_DAYS: i64 = 3;
+
+/// Held coupon counts for the dashboard
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct CouponStats {
+    pub held: usize,
+    pub expiring_soon: usize,
+    pub expiring_within_days: i64,
+    pub expiring: Vec<ExpiringCoupon>,
+}
+
+/// A held coupon that expires soon
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct ExpiringCoupon {
+    pub title: String,
+    pub expiry: String,
+    pub days_left: i64,
+}
+
+/// Savings from coupons marked as used during the current month
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct SavingsStats {
+    pub month: String,
+    pub estimated_savings: f64,
+    /// Number of redeemed coupons with a parseable amount
+    pub counted: usize,
+}
+
+/// Date range of the tag statistics
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TagStatsQuery {
+    /// Earliest date, YYYY-MM-DD
+    pub from: Option<String>,
+    /// Latest date, YYYY-MM-DD
+    pub to: Option<String>,
+}
+
+/// Claims and savings per tag, one entry per bar of a chart
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TagStatsResponse {
+    pub success: bool,
+    pub message: String,
+    pub tags: Vec<TagStats>,
+}
+
+/// Outcome of the most recent claim in this session
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct ClaimRecord {
+    pub at: String,
+    pub success: bool,
+    pub message: String,
+}
+
+impl ClaimRecord {
+    /// Record a claim outcome at the current time
+    pub fn now(success: bool, message: String) -> Self {
+        Self {
+            at: format_current_time(),
+            success,
+            message,
+        }
+    }
+}
+
+/// All dashboard cards in one response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DashboardStats {
+    pub success: bool,
+    pub coupons: CouponStats,
+    pub savings: SavingsStats,
+    pub last_claim: Option<ClaimRecord>,
+    pub scheduler: SchedulerStatus,
+    pub upstream: UpstreamStats,
+}
+
+/// Held coupons, from the cache or freshly fetched from upstream.
+/// The state is not locked while fetching.
+pub(super) async fn held_coupons(state: &Mutex<WebAppState>) -> Result<Vec<Coupon>, String> {
+    let client = {
+        let state = state.lock().await;
+        if !state.coupons.is_empty() {
+            return Ok(state.coupons.clone());
+        }
+        state.mcp_client.clone().ok_or_else(|| "请先设置Token".to_string())?
+    };
+    let text = client.get_my_coupons().await
+        .map_err(|e| format!("优惠券加载失败: {}", describe_error(&e)))?;
+    let mut coupons = parse_coupons_from_markdown(&text);
+    UsageStore::load().unwrap_or_default().apply(&mut coupons);
+    images::prefetch(&coupons);
+    state.lock().await.coupons = coupons.clone();
+    Ok(coupons)
+}
+
+pub(super) fn coupon_stats(coupons: &[Coupon]) -> CouponStats {
+    let mut expiring: Vec<ExpiringCoupon> = coupons.iter()
+        .filter(|c| !c.used)
+        .filter_map(|c| {
+            let days_left = c.days_left()?;
+            (0..=EXPIRING_SOON_DAYS).contains(&days_left).then(|| ExpiringCoupon {
+                title: c.title.clone(),
+                expiry: c.expiry.clone(),
+                days_left,
+            })
+        })
+        .collect();
+    expiring.sort_by_key(|c| c.days_left);
+
+    CouponStats {
+        held: coupons.len(),
+        expiring_soon: expiring.len(),
+        expiring_within_days: EXPIRING_SOON_DAYS,
+        expiring,
+    }
+}
+
+pub(super) fn savings_stats() -> SavingsStats {
+    let (savings, counted) = UsageStore::load().unwrap_or_default().savings_this_month();
+    SavingsStats {
+        month: local_today().format("%Y-%m").to_string(),
+        estimated_savings: savings,
+        counted,
+    }
+}
+
+fn unavailable(message: String) -> Response {
+    Json(ApiResponse {
+        success: false,
+        message,
+        coupons: None,
+        page: None,
+    })
+    .into_response()
+}
+
+/// API handler for all dashboard cards
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    tag = "stats",
+    responses((status = 200, description = "仪表盘汇总数据", body = DashboardStats))
+)]
+pub async fn api_stats_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> Response {
+    let coupons = match held_coupons(&state).await {
+        Ok(coupons) => coupons,
+        Err(message) => return unavailable(message),
+    };
+
+    Json(DashboardStats {
+        success: true,
+        coupons: coupon_stats(&coupons),
+        savings: savings_stats(),
+        last_claim: state.lock().await.last_claim.clone(),
+        scheduler: scheduler::status(),
+        upstream: metrics::snapshot(),
+    })
+    .into_response()
+}
+
+/// API handler for held / expiring coupon counts
+#[utoipa::path(
+    get,
+    path = "/api/stats/coupons",
+    tag = "stats",
+    responses((status = 200, description = "已领取与即将过期的优惠券数量", body = CouponStats))
+)]
+pub async fn api_coupon_stats_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> Response {
+    match held_coupons(&state).await {
+        Ok(coupons) => Json(coupon_stats(&coupons)).into_response(),
+        Err(message) => unavailable(message),
+    }
+}
+
+/// API handler for this month's savings from used coupons
+#[utoipa::path(
+    get,
+    path = "/api/stats/savings",
+    tag = "stats",
+    responses((status = 200, description = "本月已使用优惠券节省的金额", body = SavingsStats))
+)]
+pub async fn api_savings_stats_handler() -> impl IntoResponse {
+    Json(savings_stats())
+}
+
+/// API handler for the last claim result
+#[utoipa::path(
+    get,
+    path = "/api/stats/last-claim",
+    tag = "stats",
+    responses((status = 200, description = "最近一次领取结果，未领取过时为 null", body = Option<ClaimRecord>))
+)]
+pub async fn api_last_claim_handler(State(state): State<Arc<Mutex<WebAppState>>>) -> impl IntoResponse {
+    let state = state.lock().await;
+    Json(state.last_claim.clone())
+}
+
+/// API handler for the scheduler status
+#[utoipa::path(
+    get,
+    path = "/api/stats/scheduler",
+    tag = "stats",
+    responses((status = 200, description = "定时领取状态", body = SchedulerStatus))
+)]
+pub async fn api_scheduler_status_handler() -> impl IntoResponse {
+    Json(scheduler::status())
+}
+
+/// API handler for the rolling upstream success rate and latency
+#[utoipa::path(
+    get,
+    path = "/api/stats/upstream",
+    tag = "stats",
+    responses((status = 200, description = "最近 50 次上游调用的成功率与平均耗时", body = UpstreamStats))
+)]
+pub async fn api_upstream_stats_handler() -> impl IntoResponse {
+    Json(metrics::snapshot())
+}
+
+/// API handler for claims and savings grouped by tag
+#[utoipa::path(
+    get,
+    path = "/api/stats/tags",
+    tag = "stats",
+    params(TagStatsQuery),
+    responses((status = 200, description = "按标签统计的领取数量、使用数量和节省金额，领取最多的在前", body = TagStatsResponse))
+)]
+pub async fn api_tag_stats_handler(Query(query): Query<TagStatsQuery>) -> impl IntoResponse {
+    let failed = |message: String| Json(TagStatsResponse { success: false, message, tags: Vec::new() });
+    let (from, to) = match date_range(&query.from, &query.to) {
+        Ok(range) => range,
+        Err(message) => return failed(message),
+    };
+    let history = match ClaimHistory::load() {
+        Ok(history) => history,
+        Err(e) => return failed(format!("读取领取记录失败: {}", e)),
+    };
+    let tags = history.tag_stats(&UsageStore::load().unwrap_or_default(), from, to);
+    Json(TagStatsResponse {
+        success: true,
+        message: format!("共 {} 个标签", tags.len()),
+        tags,
+    })
+}
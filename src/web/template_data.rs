@@ -0,0 +1,75 @@
+use chrono::{DateTime, NaiveDate};
+use handlebars::{handlebars_helper, Handlebars};
+use serde::Serialize;
+
+use crate::{coupons::{parse_amount, Coupon}, utils::{from_local, local_today, to_local}};
+
+/// Coupon as the page template sees it: dates and prices as values to compute with,
+/// next to the upstream text for display
+#[derive(Debug, Serialize)]
+pub struct CouponData {
+    pub title: String,
+    /// Price parsed from the text, None when it has no number
+    pub price: Option<f64>,
+    pub price_raw: String,
+    /// Unix timestamp of the start of the last valid day
+    pub expires_at: Option<i64>,
+    /// Unix timestamp of the start of the first valid day, when the text gives a range
+    pub valid_from: Option<i64>,
+    pub expiry_raw: String,
+    pub tags: Vec<String>,
+    pub image_url: String,
+    pub used: bool,
+    pub count: usize,
+    /// Link opening the coupon in the app, the universal link when no app link is set
+    pub redeem_link: Option<String>,
+}
+
+impl From<&Coupon> for CouponData {
+    fn from(coupon: &Coupon) -> Self {
+        Self {
+            title: coupon.title.clone(),
+            price: parse_amount(&coupon.price),
+            price_raw: coupon.price.clone(),
+            expires_at: coupon.expiry_date().and_then(timestamp),
+            valid_from: coupon.valid_from().and_then(timestamp),
+            expiry_raw: coupon.expiry.clone(),
+            tags: coupon.tag_list(),
+            image_url: coupon.image_url.clone(),
+            used: coupon.used,
+            count: coupon.count,
+            redeem_link: coupon.redeem_link().map(str::to_string),
+        }
+    }
+}
+
+/// Midnight of `date` in the configured time zone
+fn timestamp(date: NaiveDate) -> Option<i64> {
+    from_local(date.and_hms_opt(0, 0, 0)?).map(|t| t.timestamp())
+}
+
+fn date(at: i64) -> Option<NaiveDate> {
+    DateTime::from_timestamp(at, 0).map(|t| to_local(t).date())
+}
+
+// `{{format_date expires_at}}` or `{{format_date expires_at format="%m月%d日"}}`; empty for null
+handlebars_helper!(format_date: |at: Option<i64>, {format: str = "%Y-%m-%d"}| {
+    at.and_then(date).map(|d| d.format(format).to_string()).unwrap_or_default()
+});
+
+// `{{days_left expires_at}}`: days until the date, negative once past; null for null
+handlebars_helper!(days_left: |at: Option<i64>| {
+    at.and_then(date).map(|d| (d - local_today()).num_days())
+});
+
+// `{{currency price}}`: "¥9.90"; empty for null
+handlebars_helper!(currency: |amount: Option<f64>| {
+    amount.map(|a| format!("¥{:.2}", a)).unwrap_or_default()
+});
+
+/// Register the helpers the page templates use with `CouponData`
+pub fn register_helpers(handlebars: &mut Handlebars) {
+    handlebars.register_helper("format_date", Box::new(format_date));
+    handlebars.register_helper("days_left", Box::new(days_left));
+    handlebars.register_helper("currency", Box::new(currency));
+}
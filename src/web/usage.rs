@@ -0,0 +1,92 @@
+use axum::{extract::{ConnectInfo, State}, response::{IntoResponse, Json}};
+use serde::{Deserialize, Serialize};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::{audit::{self, Action}, coupons::{Coupon, UsageStore}, web::WebAppState};
+
+/// Identifies a held coupon to mark as used or unused
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UsedPayload {
+    pub title: String,
+    pub expiry: String,
+}
+
+/// Result of toggling the used flag
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsedResponse {
+    pub success: bool,
+    pub message: String,
+    pub used: bool,
+}
+
+/// API handler for toggling whether a coupon has been used
+#[utoipa::path(
+    post,
+    path = "/api/coupons/used",
+    tag = "coupons",
+    request_body = UsedPayload,
+    responses((status = 200, description = "切换后的使用状态", body = UsedResponse))
+)]
+pub async fn api_toggle_used_handler(
+    State(state): State<Arc<Mutex<WebAppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(payload): Json<UsedPayload>,
+) -> impl IntoResponse {
+    let mut state = state.lock().await;
+
+    let coupon = state.coupons.iter()
+        .find(|c| c.title == payload.title && c.expiry == payload.expiry)
+        .cloned()
+        .unwrap_or_else(|| Coupon {
+            title: payload.title.clone(),
+            price: String::new(),
+            expiry: payload.expiry.clone(),
+            receive_time: String::new(),
+            tags: String::new(),
+            image_url: String::new(),
+            raw_title: payload.title.clone(),
+            raw_tags: String::new(),
+            used: false,
+            count: 1,
+            calories: None,
+            app_link: None,
+            universal_link: None,
+            code: None,
+        });
+
+    let mut store = match UsageStore::load() {
+        Ok(store) => store,
+        Err(e) => {
+            return Json(UsedResponse {
+                success: false,
+                message: format!("读取使用记录失败: {}", e),
+                used: coupon.used,
+            })
+        }
+    };
+    let used = store.toggle(&coupon);
+    if let Err(e) = store.save() {
+        return Json(UsedResponse {
+            success: false,
+            message: format!("保存使用记录失败: {}", e),
+            used: !used,
+        });
+    }
+
+    store.apply(&mut state.coupons);
+    let message = if used {
+        format!("已标记「{}」为已使用", coupon.title)
+    } else {
+        format!("已取消「{}」的使用标记", coupon.title)
+    };
+    state.add_log(message.clone());
+    audit::record(&audit::web_actor(peer.ip()), Action::MarkUsed, true, message.clone());
+
+    Json(UsedResponse {
+        success: true,
+        message,
+        used,
+    })
+}
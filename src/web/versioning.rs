@@ -0,0 +1,56 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Version of the response schemas served under `PREFIX`. Within a version fields are
+/// only ever added; renaming or removing one, or changing what it means, starts the next.
+pub const API_VERSION: u32 = 1;
+
+/// Where the current version of the API is served
+pub const PREFIX: &str = "/api/v1";
+
+/// Add `api_version` to JSON object responses, so scripts can check which schema they got
+pub async fn stamp(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let is_json = response.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    // Spliced in first rather than re-serialized, keeping the order of the other fields
+    let body = match bytes.strip_prefix(b"{") {
+        Some(rest) => {
+            let separator = if rest.trim_ascii_start().starts_with(b"}") { "" } else { "," };
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from([format!("{{\"api_version\":{}{}", API_VERSION, separator).as_bytes(), rest].concat())
+        },
+        None => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
+
+/// Mark a response of an unversioned `/api/...` alias as deprecated, pointing at the
+/// same route under `PREFIX`. The aliases are kept for one release after versioning.
+pub async fn deprecated(request: Request, next: Next) -> Response {
+    // Paths inside the nested router come without the `/api` prefix
+    let successor = format!("<{}{}>; rel=\"successor-version\"", PREFIX, request.uri().path());
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, link);
+    }
+    response
+}